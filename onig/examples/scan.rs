@@ -1,6 +1,6 @@
 use onig::*;
 
-fn scan_callback<'t>(n: i32, caps: Captures<'t>) -> bool {
+fn scan_callback(n: i32, caps: Captures) -> bool {
     println!("scan: {}", n);
     println!("match at {}", caps.offset());
 
@@ -0,0 +1,3526 @@
+//! Pattern Syntax Trees
+//!
+//! This module parses a pattern into a typed syntax tree, without
+//! compiling it, so tools can inspect, lint or rewrite patterns. The
+//! parser follows the rules of a [`Syntax`]: its operators, behaviours,
+//! options and meta characters decide what each part of the pattern
+//! means, just as they do for Oniguruma's own parser. Every node
+//! carries the byte range of the pattern it was parsed from.
+//!
+//! A pattern which parses should compile with
+//! [`Regex::with_options`](crate::Regex::with_options) and the same
+//! syntax and options, and the other way around. The checks Oniguruma
+//! makes after parsing are not repeated though: look-behinds which
+//! aren't fixed width, never ending recursion and the arguments of
+//! built-in callouts are accepted, and callout names aren't checked
+//! against the registered callouts. Code points which aren't Unicode
+//! scalar values, e.g. `\x{D800}`, are rejected, and `(?R)` and
+//! `(?&name)` are checked like `\g<0>` and `\g<name>`, where Oniguruma
+//! accepts any name.
+//!
+//! # Examples
+//!
+//! ```
+//! use onig::ast::{self, GroupKind, NodeKind};
+//! use onig::{RegexOptions, Syntax};
+//!
+//! let ast = ast::parse(r"(?<year>\d{4})-\d\d", RegexOptions::REGEX_OPTION_NONE, Syntax::ruby())
+//!     .unwrap();
+//! let NodeKind::Concat(ref nodes) = ast.root.kind else { panic!() };
+//! let NodeKind::Group(ref group) = nodes[0].kind else { panic!() };
+//! assert_eq!(nodes[0].span, 0..14);
+//! assert_eq!(
+//!     group.kind,
+//!     GroupKind::Capture {
+//!         index: Some(1),
+//!         name: Some("year".into()),
+//!         history: false
+//!     }
+//! );
+//! ```
+
+use std::ops::Range;
+use std::os::raw::c_int;
+
+use super::{Encoding, Error, RegexOptions, Syntax, SyntaxBehavior, SyntaxOperator};
+
+/// A byte range of the pattern.
+pub type Span = Range<usize>;
+
+/// The largest count Oniguruma accepts in an interval.
+const MAX_REPEAT: u32 = 100_000;
+
+/// The largest number which is read as a back-reference.
+const MAX_BACKREF: usize = 1000;
+
+/// Parse a Pattern
+///
+/// Parses `pattern` into a syntax tree according to `syntax`, with
+/// `options` added to the syntax's own options.
+///
+/// # Errors
+///
+/// Returns the error Oniguruma reports for the pattern where it can,
+/// with [`Error::pattern_span`] set to the part of the pattern at
+/// fault.
+///
+/// # Examples
+///
+/// ```
+/// use onig::ast;
+/// use onig::{RegexOptions, Syntax};
+///
+/// let error = ast::parse("a(b", RegexOptions::REGEX_OPTION_NONE, Syntax::ruby()).unwrap_err();
+/// assert_eq!(error.description(), "end pattern with unmatched parenthesis");
+/// assert_eq!(error.pattern_span(), Some(1..3));
+/// ```
+pub fn parse(pattern: &str, options: RegexOptions, syntax: &Syntax) -> Result<Ast, Error> {
+    Parser::new(pattern, options, syntax).parse()
+}
+
+/// A Parsed Pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ast {
+    /// The top level expression.
+    pub root: Node,
+    /// The number of capture groups.
+    pub captures: usize,
+    /// The group names in order of first definition, each with the
+    /// numbers of the groups defining it.
+    pub names: Vec<(String, Vec<usize>)>,
+}
+
+/// A Node of the Tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// What the node is.
+    pub kind: NodeKind,
+    /// The part of the pattern the node was parsed from.
+    pub span: Span,
+}
+
+/// The Kinds of Node
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeKind {
+    /// Matches the empty string, e.g. an empty alternative.
+    Empty,
+    /// A character, written as itself or as an escape.
+    Literal(char),
+    /// A byte which isn't part of a UTF-8 sequence, e.g. `\xFF`.
+    Byte(u8),
+    /// `.`, or the syntax's any character meta character.
+    AnyChar,
+    /// An assertion which doesn't consume text.
+    Anchor(Anchor),
+    /// One of the Oniguruma escapes with special behaviour.
+    Special(Special),
+    /// A bracket expression.
+    Class(Class),
+    /// A class escape such as `\d`.
+    ClassEscape(ClassEscape),
+    /// A character property, `\p{...}` or `\P{...}`.
+    Property(Property),
+    /// A back-reference to a group.
+    Backref(Backref),
+    /// A subexpression call.
+    Call(Call),
+    /// A callout.
+    Callout(Callout),
+    /// Options set until the end of the enclosing group, e.g. `(?i)`,
+    /// including any alternatives after them. In syntaxes where isolated
+    /// options don't continue into the following alternatives, such as
+    /// Ruby's, they're parsed as a [`GroupKind::Options`] group around
+    /// the rest of the enclosing group instead.
+    Options(Options),
+    /// A group.
+    Group(Group),
+    /// A conditional, `(?(cond)yes|no)`.
+    Conditional(Conditional),
+    /// An absent group or absent range, `(?~...)`.
+    Absent(Absent),
+    /// A repeated expression.
+    Repeat(Repeat),
+    /// A sequence of expressions.
+    Concat(Vec<Node>),
+    /// Alternative expressions.
+    Alternation(Vec<Node>),
+}
+
+/// Anchors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    /// `^`
+    LineStart,
+    /// `$`
+    LineEnd,
+    /// `\A`, or `` \` `` in GNU syntaxes.
+    StartOfString,
+    /// `\z`, or `\'` in GNU syntaxes.
+    EndOfString,
+    /// `\Z`, the end of the string or before a final newline.
+    EndOfStringOrNewline,
+    /// `\G`, where the search started.
+    SearchStart,
+    /// `\b`
+    WordBoundary,
+    /// `\B`
+    NotWordBoundary,
+    /// `\<`
+    WordStart,
+    /// `\>`
+    WordEnd,
+    /// `\y`
+    TextSegmentBoundary,
+    /// `\Y`
+    NotTextSegmentBoundary,
+}
+
+/// Special Escapes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Special {
+    /// `\K`, keep the text matched so far out of the match.
+    Keep,
+    /// `\R`, a general newline.
+    GeneralNewline,
+    /// `\N`, any character but newline.
+    NotNewline,
+    /// `\O`, any character.
+    TrueAnyChar,
+    /// `\X`, a text segment.
+    TextSegment,
+}
+
+/// The Classes of Class Escapes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClassEscapeKind {
+    /// `\w`
+    Word,
+    /// `\d`
+    Digit,
+    /// `\s`
+    Space,
+    /// `\h`
+    HexDigit,
+}
+
+/// A Class Escape
+///
+/// The upper case forms, e.g. `\W`, are negated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClassEscape {
+    /// The class.
+    pub kind: ClassEscapeKind,
+    /// Does the escape match characters outside the class?
+    pub negated: bool,
+}
+
+/// A Character Property
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Property {
+    /// The name as written, e.g. `Greek`.
+    pub name: String,
+    /// Does the property match characters without it? `\P{...}` and
+    /// `\p{^...}` are negated, `\P{^...}` isn't.
+    pub negated: bool,
+}
+
+/// A POSIX Bracket, e.g. `[:alpha:]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Posix {
+    /// The class name.
+    pub name: String,
+    /// Is it written `[:^name:]`?
+    pub negated: bool,
+}
+
+/// A Bracket Expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class {
+    /// Is it written `[^...]`?
+    pub negated: bool,
+    /// The operands of the `&&` intersection. A class without `&&` has
+    /// a single operand.
+    pub operands: Vec<Vec<ClassItem>>,
+}
+
+/// An Item in a Bracket Expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassItem {
+    /// What the item is.
+    pub kind: ClassItemKind,
+    /// The part of the pattern the item was parsed from.
+    pub span: Span,
+}
+
+/// The Kinds of Bracket Expression Item
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClassItemKind {
+    /// A character.
+    Literal(char),
+    /// A byte which isn't part of a UTF-8 sequence.
+    Byte(u8),
+    /// An inclusive range of characters.
+    Range(char, char),
+    /// A class escape such as `\d`.
+    Escape(ClassEscape),
+    /// A character property.
+    Property(Property),
+    /// A POSIX bracket.
+    Posix(Posix),
+    /// A nested bracket expression.
+    Class(Class),
+}
+
+/// How a Group is Referred To
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Reference {
+    /// By number, e.g. `\1` or `\g<2>`. Calls to group 0 recurse into
+    /// the whole pattern.
+    Number(usize),
+    /// Relative to the reference, e.g. `\k<-1>`.
+    Relative(isize),
+    /// By name.
+    Name(String),
+}
+
+/// A Back-reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backref {
+    /// The group as written.
+    pub target: Reference,
+    /// The nest level, e.g. `+1` in `\k<name+1>`.
+    pub level: Option<i32>,
+    /// The numbers of the groups referred to. A name defined more than
+    /// once refers to all of its groups.
+    pub groups: Vec<usize>,
+}
+
+/// A Subexpression Call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// The group as written.
+    pub target: Reference,
+    /// The number of the group called, or 0 for the whole pattern.
+    pub group: usize,
+}
+
+/// The Direction a Callout Runs In
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CalloutDirection {
+    /// When the matcher progresses, the default.
+    Progress,
+    /// When the matcher backtracks, `<`.
+    Retraction,
+    /// Both, `X`.
+    Both,
+}
+
+/// A Callout
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Callout {
+    /// `(?{contents}[tag]D)`
+    Contents {
+        /// The text between the braces.
+        contents: String,
+        /// The tag, if any.
+        tag: Option<String>,
+        /// When the callout runs.
+        direction: CalloutDirection,
+    },
+    /// `(*NAME[tag]{args})`
+    Name {
+        /// The callout name.
+        name: String,
+        /// The tag, if any.
+        tag: Option<String>,
+        /// The arguments, as written.
+        args: Vec<String>,
+    },
+}
+
+/// A Group Option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flag {
+    /// `i`
+    IgnoreCase,
+    /// `x`
+    Extend,
+    /// `.` matches newlines: `m` in Ruby syntaxes, `s` in Perl ones.
+    DotAll,
+    /// `^` and `$` match at newlines: `m` in Perl syntaxes.
+    MultiLine,
+    /// `a`, ASCII only word, digit, space and POSIX classes.
+    Ascii,
+    /// `W`, ASCII only word classes.
+    WordAscii,
+    /// `D`, ASCII only digit classes.
+    DigitAscii,
+    /// `S`, ASCII only space classes.
+    SpaceAscii,
+    /// `P`, ASCII only POSIX classes.
+    PosixAscii,
+    /// `y{g}`, extended grapheme cluster text segments.
+    GraphemeCluster,
+    /// `y{w}`, word text segments.
+    WordSegment,
+    /// `I`, ASCII only case folding, for the whole pattern.
+    IgnoreCaseAscii,
+    /// `L`, find the longest match, for the whole pattern.
+    FindLongest,
+    /// `C`, ignore empty matches, for the whole pattern.
+    FindNotEmpty,
+}
+
+/// Options Set by a Group
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// The options turned on.
+    pub on: Vec<Flag>,
+    /// The options turned off, written after `-`.
+    pub off: Vec<Flag>,
+}
+
+/// The Kinds of Group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupKind {
+    /// A capture group. Unnamed groups don't capture when the pattern
+    /// has named groups and the syntax captures only named groups, in
+    /// which case `index` is `None`.
+    Capture {
+        /// The group number.
+        index: Option<usize>,
+        /// The group name.
+        name: Option<String>,
+        /// Is it a capture history group, `(?@...)`?
+        history: bool,
+    },
+    /// `(?:...)`
+    NonCapture,
+    /// `(?>...)`
+    Atomic,
+    /// `(?=...)`
+    LookAhead,
+    /// `(?!...)`
+    NegativeLookAhead,
+    /// `(?<=...)`
+    LookBehind,
+    /// `(?<!...)`
+    NegativeLookBehind,
+    /// `(?i-m:...)`
+    Options(Options),
+}
+
+/// A Group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// What kind of group it is.
+    pub kind: GroupKind,
+    /// The expression in the group.
+    pub body: Box<Node>,
+}
+
+/// The Condition of a Conditional
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// Did the group match? E.g. `(?(1)...)` or `(?(<name>)...)`.
+    Backref(Backref),
+    /// Does the expression match here? E.g. `(?(a|b)...)`.
+    Expression(Box<Node>),
+}
+
+/// A Conditional
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conditional {
+    /// What to check.
+    pub condition: Condition,
+    /// The expression to match if the condition holds.
+    pub yes: Box<Node>,
+    /// The expression to match if it doesn't. When the conditional has
+    /// more than two branches this is an alternation of the rest.
+    pub no: Option<Box<Node>>,
+}
+
+/// Absent Groups and Ranges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Absent {
+    /// `(?~absent)`, any text which doesn't contain `absent`.
+    Repeater(Box<Node>),
+    /// `(?~|absent|expr)`, `expr` as long as it doesn't contain
+    /// `absent`.
+    Expression {
+        /// The expression which mustn't occur.
+        absent: Box<Node>,
+        /// The expression to match.
+        expr: Box<Node>,
+    },
+    /// `(?~|absent)`, limit the rest of the match to text which
+    /// doesn't contain `absent`.
+    Range(Box<Node>),
+    /// `(?~|)`, clear an absent range.
+    Clear,
+}
+
+/// How a Repetition Matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Greed {
+    /// As many times as possible, backtracking to fewer.
+    Greedy,
+    /// As few times as possible, e.g. `*?`.
+    Lazy,
+    /// As many times as possible without backtracking, e.g. `*+`.
+    Possessive,
+}
+
+/// A Repetition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeat {
+    /// The least number of repetitions.
+    pub min: u32,
+    /// The most, or `None` if unbounded.
+    pub max: Option<u32>,
+    /// How the repetition matches.
+    pub greed: Greed,
+    /// The expression repeated.
+    pub body: Box<Node>,
+}
+
+/// A capture group seen while parsing.
+struct Capture {
+    name: Option<String>,
+}
+
+/// A repeat operator.
+struct Quantifier {
+    min: u32,
+    max: Option<u32>,
+    greed: Greed,
+    span: Span,
+}
+
+/// A character or byte given by an escape.
+enum Value {
+    Char(char),
+    Byte(u8),
+    /// A code point list, e.g. `\x{41 42}`, marking the code points
+    /// which end a range, as in `[\x{41-43}]`.
+    List(Vec<(char, Span, bool)>),
+}
+
+/// The state of a bracket expression, after Oniguruma's `prs_cc`.
+#[derive(Clone, Copy)]
+enum ClassState {
+    /// At the start, or after `&&`.
+    Start,
+    /// After a character, which may start a range.
+    Char(char),
+    /// After a class, which can't start a range.
+    Set,
+    /// After `x-`.
+    Range(char),
+    /// After a range or a nested class.
+    Complete,
+}
+
+struct Parser<'p> {
+    pattern: &'p str,
+    pos: usize,
+    ops: SyntaxOperator,
+    behavior: SyntaxBehavior,
+    options: RegexOptions,
+    escape: Option<char>,
+    /// The variable meta characters: any char, zero or more, zero or
+    /// one, one or more and any char zero or more times.
+    meta: [Option<char>; 5],
+    extended: bool,
+    /// Whether `(?I)`, `(?L)` or `(?C)` opened the pattern.
+    whole_options: bool,
+    depth: usize,
+    captures: Vec<Capture>,
+}
+
+impl<'p> Parser<'p> {
+    fn new(pattern: &'p str, options: RegexOptions, syntax: &Syntax) -> Self {
+        let ops = syntax.operators();
+        let options = options | syntax.options();
+        let table = syntax.meta_chars();
+        let meta_char = |c: u32| char::from_u32(c).filter(|&c| c != '\0');
+        let escape = if ops.contains(SyntaxOperator::SYNTAX_OPERATOR_INEFFECTIVE_ESCAPE) {
+            None
+        } else {
+            meta_char(table.esc)
+        };
+        let meta = if ops.contains(SyntaxOperator::SYNTAX_OPERATOR_VARIABLE_META_CHARACTERS) {
+            [
+                meta_char(table.anychar),
+                meta_char(table.anytime),
+                meta_char(table.zero_or_one_time),
+                meta_char(table.one_or_more_time),
+                meta_char(table.anychar_anytime),
+            ]
+        } else {
+            [None; 5]
+        };
+        Parser {
+            pattern,
+            pos: 0,
+            ops,
+            behavior: syntax.behavior(),
+            options,
+            escape,
+            meta,
+            extended: options.contains(RegexOptions::REGEX_OPTION_EXTEND),
+            whole_options: false,
+            depth: 0,
+            captures: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> Result<Ast, Error> {
+        let mut root = self.parse_alternation()?;
+        let named = self.captures.iter().any(|c| c.name.is_some());
+        let only_named = named
+            && self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_CAPTURE_ONLY_NAMED_GROUP)
+            && !self
+                .options
+                .contains(RegexOptions::REGEX_OPTION_CAPTURE_GROUP);
+        let mut numbers = Vec::with_capacity(self.captures.len());
+        let mut captures = 0;
+        for capture in &self.captures {
+            if only_named && capture.name.is_none() {
+                numbers.push(None);
+            } else {
+                captures += 1;
+                numbers.push(Some(captures));
+            }
+        }
+        self.resolve(&mut root, &numbers, only_named)?;
+
+        let mut names: Vec<(String, Vec<usize>)> = Vec::new();
+        for (capture, number) in self.captures.iter().zip(&numbers) {
+            if let (Some(name), Some(number)) = (&capture.name, number) {
+                match names.iter_mut().find(|(n, _)| n == name) {
+                    Some((_, groups)) => groups.push(*number),
+                    None => names.push((name.clone(), vec![*number])),
+                }
+            }
+        }
+        Ok(Ast {
+            root,
+            captures,
+            names,
+        })
+    }
+
+    // Errors and lookahead
+
+    /// Build the error Oniguruma reports for `code`, pointing at `span`.
+    fn error(&self, code: i32, span: Span) -> Error {
+        let base = self.pattern.as_ptr() as *mut u8;
+        let info = onig_sys::OnigErrorInfo {
+            enc: Encoding::UTF8.as_raw(),
+            par: base.wrapping_add(span.start),
+            par_end: base.wrapping_add(span.end),
+        };
+        Error::from_code_and_info(code as c_int, &info, self.pattern.as_bytes())
+    }
+
+    fn has(&self, op: SyntaxOperator) -> bool {
+        self.ops.contains(op)
+    }
+
+    fn allows(&self, behavior: SyntaxBehavior) -> bool {
+        self.behavior.contains(behavior)
+    }
+
+    fn rest(&self) -> &'p str {
+        &self.pattern[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.rest().chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_escape(&self, c: char) -> bool {
+        self.escape == Some(c)
+    }
+
+    /// The length of `c`, or of `c` preceded by the escape character
+    /// when `escaped`, if the rest of the pattern starts with it.
+    fn starts_with(&self, c: char, escaped: bool) -> Option<usize> {
+        let rest = self.rest();
+        if !escaped {
+            return rest.starts_with(c).then_some(c.len_utf8());
+        }
+        let escape = self.escape?;
+        let rest = rest.strip_prefix(escape)?;
+        rest.starts_with(c)
+            .then_some(escape.len_utf8() + c.len_utf8())
+    }
+
+    /// The length of the alternation operator, if it's next.
+    fn at_alternation(&self) -> Option<usize> {
+        self.starts_with('|', false)
+            .filter(|_| self.has(SyntaxOperator::SYNTAX_OPERATOR_VBAR_ALT))
+            .or_else(|| {
+                self.starts_with('|', true)
+                    .filter(|_| self.has(SyntaxOperator::SYNTAX_OPERATOR_ESC_VBAR_ALT))
+            })
+    }
+
+    /// The length of the group close operator, if it's next.
+    fn at_close(&self) -> Option<usize> {
+        self.starts_with(')', false)
+            .filter(|_| self.has(SyntaxOperator::SYNTAX_OPERATOR_LPAREN_SUBEXP))
+            .or_else(|| {
+                self.starts_with(')', true)
+                    .filter(|_| self.has(SyntaxOperator::SYNTAX_OPERATOR_ESC_LPAREN_SUBEXP))
+            })
+    }
+
+    /// Skip `(?#...)` comments, and whitespace and `#` comments in
+    /// extended mode.
+    fn skip_trivia(&mut self) -> Result<(), Error> {
+        loop {
+            if self.extended {
+                match self.peek() {
+                    Some(' ' | '\t' | '\n' | '\r' | '\x0c') => {
+                        self.pos += 1;
+                        continue;
+                    }
+                    Some('#') if !self.meta.contains(&Some('#')) => {
+                        match self.rest().find('\n') {
+                            Some(end) => self.pos += end + 1,
+                            None => self.pos = self.pattern.len(),
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            if self.has(SyntaxOperator::SYNTAX_OPERATOR_LPAREN_SUBEXP)
+                && self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT)
+                && self.rest().starts_with("(?#")
+            {
+                let start = self.pos;
+                self.pos += 3;
+                loop {
+                    match self.bump() {
+                        None => {
+                            return Err(
+                                self.error(onig_sys::ONIGERR_END_PATTERN_IN_GROUP, start..self.pos)
+                            )
+                        }
+                        Some(')') => break,
+                        Some(c) if self.is_escape(c) => {
+                            self.bump();
+                        }
+                        Some(_) => {}
+                    }
+                }
+                continue;
+            }
+            return Ok(());
+        }
+    }
+
+    // Expressions
+
+    fn parse_alternation(&mut self) -> Result<Node, Error> {
+        let start = self.pos;
+        let mut branches = vec![self.parse_concat()?];
+        while let Some(len) = self.at_alternation() {
+            self.pos += len;
+            branches.push(self.parse_concat()?);
+        }
+        if branches.len() == 1 {
+            return Ok(branches.pop().unwrap());
+        }
+        Ok(Node {
+            kind: NodeKind::Alternation(branches),
+            span: start..self.pos,
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, Error> {
+        let start = self.pos;
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            if self.pos == self.pattern.len() || self.at_alternation().is_some() {
+                break;
+            }
+            let node = match self.at_close() {
+                Some(_) if self.depth > 0 => break,
+                Some(len) => {
+                    let span = self.pos..self.pos + len;
+                    if !self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_ALLOW_UNMATCHED_CLOSE_SUBEXP) {
+                        return Err(self.error(onig_sys::ONIGERR_UNMATCHED_CLOSE_PARENTHESIS, span));
+                    }
+                    self.pos += len;
+                    Node {
+                        kind: NodeKind::Literal(')'),
+                        span,
+                    }
+                }
+                None => self.parse_atom()?,
+            };
+            // Isolated options apply to the rest of the group, so a
+            // repeat operator after them has nothing to repeat.
+            let node = match node.kind {
+                NodeKind::Options(options)
+                    if !self.allows(
+                        SyntaxBehavior::SYNTAX_BEHAVIOR_ISOLATED_OPTION_CONTINUE_BRANCH,
+                    ) =>
+                {
+                    // Unless the syntax carries them into the following
+                    // alternatives, they open a group running to the end
+                    // of the enclosing one, taking those in.
+                    let body = self.parse_alternation()?;
+                    nodes.push(Node {
+                        kind: NodeKind::Group(Group {
+                            kind: GroupKind::Options(options),
+                            body: Box::new(body),
+                        }),
+                        span: node.span.start..self.pos,
+                    });
+                    break;
+                }
+                NodeKind::Options(_) => node,
+                _ => self.parse_repeats(node)?,
+            };
+            match node.kind {
+                NodeKind::Concat(quoted) => nodes.extend(quoted),
+                _ => nodes.push(node),
+            }
+        }
+        let span = start..self.pos;
+        Ok(match nodes.len() {
+            0 => Node {
+                kind: NodeKind::Empty,
+                span,
+            },
+            1 => nodes.pop().unwrap(),
+            _ => Node {
+                kind: NodeKind::Concat(nodes),
+                span,
+            },
+        })
+    }
+
+    /// Apply any repeat operators following `node` to it.
+    fn parse_repeats(&mut self, mut node: Node) -> Result<Node, Error> {
+        loop {
+            self.skip_trivia()?;
+            let quantifier = match self.parse_quantifier()? {
+                Some(quantifier) => quantifier,
+                None => return Ok(node),
+            };
+            // The operator binds to the last character of a quote or
+            // code point list.
+            if let NodeKind::Concat(ref mut nodes) = node.kind {
+                let last = nodes.pop().unwrap();
+                nodes.push(repeat(last, quantifier));
+                node.span.end = self.pos;
+                continue;
+            }
+            if matches!(node.kind, NodeKind::Options(_)) || is_invalid_repeat_target(&node) {
+                return Err(self.error(
+                    onig_sys::ONIGERR_TARGET_OF_REPEAT_OPERATOR_INVALID,
+                    quantifier.span,
+                ));
+            }
+            node = repeat(node, quantifier);
+        }
+    }
+
+    /// Parse a repeat operator, if one is next.
+    fn parse_quantifier(&mut self) -> Result<Option<Quantifier>, Error> {
+        use SyntaxOperator as Op;
+        let start = self.pos;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let (min, max, interval) = if self.is_escape(c) {
+            let (min, max) = match self.peek_second() {
+                Some('*') if self.has(Op::SYNTAX_OPERATOR_ESC_ASTERISK_ZERO_INF) => (0, None),
+                Some('+') if self.has(Op::SYNTAX_OPERATOR_ESC_PLUS_ONE_INF) => (1, None),
+                Some('?') if self.has(Op::SYNTAX_OPERATOR_ESC_QMARK_ZERO_ONE) => (0, Some(1)),
+                Some('{') if self.has(Op::SYNTAX_OPERATOR_ESC_BRACE_INTERVAL) => {
+                    self.pos += c.len_utf8() + 1;
+                    return self.parse_interval(start);
+                }
+                _ => return Ok(None),
+            };
+            self.pos += c.len_utf8() + 1;
+            (min, max, false)
+        } else if Some(c) == self.meta[1] {
+            self.bump();
+            (0, None, false)
+        } else if Some(c) == self.meta[2] {
+            self.bump();
+            (0, Some(1), false)
+        } else if Some(c) == self.meta[3] {
+            self.bump();
+            (1, None, false)
+        } else {
+            match c {
+                '*' if self.has(Op::SYNTAX_OPERATOR_ASTERISK_ZERO_INF) => {
+                    self.pos += 1;
+                    (0, None, false)
+                }
+                '+' if self.has(Op::SYNTAX_OPERATOR_PLUS_ONE_INF) => {
+                    self.pos += 1;
+                    (1, None, false)
+                }
+                '?' if self.has(Op::SYNTAX_OPERATOR_QMARK_ZERO_ONE) => {
+                    self.pos += 1;
+                    (0, Some(1), false)
+                }
+                '{' if self.has(Op::SYNTAX_OPERATOR_BRACE_INTERVAL) => {
+                    self.pos += 1;
+                    return self.parse_interval(start);
+                }
+                _ => return Ok(None),
+            }
+        };
+        let greed = self.parse_greed(interval, false);
+        Ok(Some(Quantifier {
+            min,
+            max,
+            greed,
+            span: start..self.pos,
+        }))
+    }
+
+    /// Parse the rest of an interval, after the `{`. Invalid intervals
+    /// are literals if the syntax allows them.
+    fn parse_interval(&mut self, start: usize) -> Result<Option<Quantifier>, Error> {
+        let allow_invalid = self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_ALLOW_INVALID_INTERVAL);
+        let invalid = |parser: &mut Self| {
+            if allow_invalid {
+                parser.pos = start;
+                Ok(None)
+            } else {
+                Err(parser.error(
+                    onig_sys::ONIGERR_INVALID_REPEAT_RANGE_PATTERN,
+                    start..parser.pos,
+                ))
+            }
+        };
+        if self.pos == self.pattern.len() {
+            return if allow_invalid {
+                self.pos = start;
+                Ok(None)
+            } else {
+                Err(self.error(onig_sys::ONIGERR_END_PATTERN_AT_LEFT_BRACE, start..self.pos))
+            };
+        }
+        if !allow_invalid && matches!(self.peek(), Some('(' | ')' | '|')) {
+            return Err(self.error(onig_sys::ONIGERR_END_PATTERN_AT_LEFT_BRACE, start..self.pos));
+        }
+
+        let (min, max, fixed);
+        match self.scan_repeat_count(start)? {
+            Some(low) => {
+                if self.eat(',') {
+                    min = low;
+                    max = self.scan_repeat_count(start)?;
+                    fixed = false;
+                } else {
+                    min = low;
+                    max = Some(low);
+                    fixed = true;
+                }
+            }
+            None if self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_ALLOW_INTERVAL_LOW_ABBREV) => {
+                if !self.eat(',') {
+                    return invalid(self);
+                }
+                match self.scan_repeat_count(start)? {
+                    Some(up) => {
+                        min = 0;
+                        max = Some(up);
+                        fixed = false;
+                    }
+                    None => return invalid(self),
+                }
+            }
+            None => return invalid(self),
+        }
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_ESC_BRACE_INTERVAL) {
+            match self.bump() {
+                Some(c) if self.is_escape(c) => {}
+                _ => return invalid(self),
+            }
+        }
+        if !self.eat('}') {
+            return invalid(self);
+        }
+
+        let (min, max, greed) = match max {
+            Some(up) if min > up => {
+                // `{n,m}` with n > m is a possessive `{m,n}`, unless
+                // `{n,m}+` is.
+                if self.has(SyntaxOperator::SYNTAX_OPERATOR_PLUS_POSSESSIVE_INTERVAL) {
+                    return Err(self.error(
+                        onig_sys::ONIGERR_UPPER_SMALLER_THAN_LOWER_IN_REPEAT_RANGE,
+                        start..self.pos,
+                    ));
+                }
+                let greed = self.parse_greed(true, true);
+                (up, Some(min), greed)
+            }
+            _ => {
+                let greedy_only = fixed
+                    && self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_FIXED_INTERVAL_IS_GREEDY_ONLY);
+                (min, max, self.parse_greed(true, greedy_only))
+            }
+        };
+        Ok(Some(Quantifier {
+            min,
+            max,
+            greed,
+            span: start..self.pos,
+        }))
+    }
+
+    /// Scan the decimal count of an interval.
+    fn scan_repeat_count(&mut self, start: usize) -> Result<Option<u32>, Error> {
+        let digits = self.rest().bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return Ok(None);
+        }
+        let count = self.rest()[..digits].parse::<u32>().ok();
+        self.pos += digits;
+        match count {
+            Some(count) if count <= MAX_REPEAT => Ok(Some(count)),
+            _ => Err(self.error(
+                onig_sys::ONIGERR_TOO_BIG_NUMBER_FOR_REPEAT_RANGE,
+                start..self.pos,
+            )),
+        }
+    }
+
+    /// Parse a lazy or possessive suffix. `no_lazy` is set for
+    /// operators which can't be lazy.
+    fn parse_greed(&mut self, interval: bool, no_lazy: bool) -> Greed {
+        use SyntaxOperator as Op;
+        if !no_lazy && self.has(Op::SYNTAX_OPERATOR_QMARK_NON_GREEDY) && self.eat('?') {
+            return Greed::Lazy;
+        }
+        let possessive = if interval {
+            self.has(Op::SYNTAX_OPERATOR_PLUS_POSSESSIVE_INTERVAL)
+        } else {
+            self.has(Op::SYNTAX_OPERATOR_PLUS_POSSESSIVE_REPEAT)
+        };
+        if possessive && self.eat('+') {
+            return Greed::Possessive;
+        }
+        Greed::Greedy
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, Error> {
+        use SyntaxOperator as Op;
+        let start = self.pos;
+        if let Some(quantifier) = self.parse_quantifier()? {
+            return self.parse_leading_quantifier(quantifier);
+        }
+        let c = self.bump().unwrap();
+        if self.is_escape(c) {
+            return self.parse_escape(start);
+        }
+        let kind = if Some(c) == self.meta[0] {
+            NodeKind::AnyChar
+        } else if Some(c) == self.meta[4] {
+            NodeKind::Repeat(Repeat {
+                min: 0,
+                max: None,
+                greed: Greed::Greedy,
+                body: Box::new(Node {
+                    kind: NodeKind::AnyChar,
+                    span: start..self.pos,
+                }),
+            })
+        } else {
+            match c {
+                '(' if self.has(Op::SYNTAX_OPERATOR_LPAREN_SUBEXP) => {
+                    return self.parse_group(start)
+                }
+                '[' if self.has(Op::SYNTAX_OPERATOR_BRACKET_CC) => {
+                    NodeKind::Class(self.parse_class(start)?)
+                }
+                '.' if self.has(Op::SYNTAX_OPERATOR_DOT_ANYCHAR) => NodeKind::AnyChar,
+                '^' if self.has(Op::SYNTAX_OPERATOR_LINE_ANCHOR) => {
+                    NodeKind::Anchor(Anchor::LineStart)
+                }
+                '$' if self.has(Op::SYNTAX_OPERATOR_LINE_ANCHOR) => {
+                    NodeKind::Anchor(Anchor::LineEnd)
+                }
+                c => NodeKind::Literal(c),
+            }
+        };
+        Ok(Node {
+            kind,
+            span: start..self.pos,
+        })
+    }
+
+    /// Handle a repeat operator with nothing before it to repeat.
+    fn parse_leading_quantifier(&mut self, quantifier: Quantifier) -> Result<Node, Error> {
+        let span = quantifier.span;
+        if !self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_CONTEXT_INDEP_REPEAT_OPS) {
+            // The operator is literal text.
+            let nodes = self.pattern[span.clone()]
+                .char_indices()
+                .map(|(i, c)| Node {
+                    kind: NodeKind::Literal(c),
+                    span: span.start + i..span.start + i + c.len_utf8(),
+                })
+                .collect();
+            return Ok(Node {
+                kind: NodeKind::Concat(nodes),
+                span,
+            });
+        }
+        if self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_CONTEXT_INVALID_REPEAT_OPS) {
+            return Err(self.error(
+                onig_sys::ONIGERR_TARGET_OF_REPEAT_OPERATOR_NOT_SPECIFIED,
+                span,
+            ));
+        }
+        // The operator matches the empty string.
+        Ok(Node {
+            kind: NodeKind::Empty,
+            span,
+        })
+    }
+
+    // Escapes
+
+    /// Parse an escape outside a bracket expression, after the escape
+    /// character.
+    fn parse_escape(&mut self, start: usize) -> Result<Node, Error> {
+        use SyntaxOperator as Op;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => {
+                return Err(self.error(onig_sys::ONIGERR_END_PATTERN_AT_ESCAPE, start..self.pos))
+            }
+        };
+        let kind = match c {
+            '(' if self.has(Op::SYNTAX_OPERATOR_ESC_LPAREN_SUBEXP) => {
+                return self.parse_group(start)
+            }
+            '1'..='9' => match self.parse_numbered_backref(start, c)? {
+                Some(kind) => kind,
+                None => return self.parse_literal_escape(start, c),
+            },
+            'Q' if self.has(Op::SYNTAX_OPERATOR_ESC_CAPITAL_Q_QUOTE) => {
+                return Ok(self.parse_quote(start))
+            }
+            // Python spells the end of the string `\Z`.
+            'z' if self.has(Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR)
+                && self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_PYTHON) =>
+            {
+                return Err(self.error(onig_sys::ONIGERR_UNDEFINED_OPERATOR, start..self.pos))
+            }
+            'p' | 'P'
+                if self.has(Op::SYNTAX_OPERATOR_ESC_P_BRACE_CHAR_PROPERTY)
+                    && self.peek() == Some('{') =>
+            {
+                NodeKind::Property(self.parse_property(c == 'P')?)
+            }
+            'k' if self.has(Op::SYNTAX_OPERATOR_ESC_K_NAMED_BACKREF)
+                && matches!(self.peek(), Some('<' | '\'')) =>
+            {
+                let close = if self.bump() == Some('<') { '>' } else { '\'' };
+                NodeKind::Backref(self.parse_backref_name(close)?)
+            }
+            'g' if self.has(Op::SYNTAX_OPERATOR_ESC_G_SUBEXP_CALL)
+                && matches!(self.peek(), Some('<' | '\'')) =>
+            {
+                let close = if self.bump() == Some('<') { '>' } else { '\'' };
+                NodeKind::Call(self.parse_call_name(close)?)
+            }
+            c => match self.escape_kind(c) {
+                Some(kind) => kind,
+                None => return self.parse_literal_escape(start, c),
+            },
+        };
+        Ok(Node {
+            kind,
+            span: start..self.pos,
+        })
+    }
+
+    /// The anchor, class or special escape `c` stands for, if any.
+    fn escape_kind(&self, c: char) -> Option<NodeKind> {
+        use SyntaxOperator as Op;
+        if let Some(escape) = self.class_escape(c) {
+            return Some(NodeKind::ClassEscape(escape));
+        }
+        let (op, kind) = match c {
+            'b' => (
+                Op::SYNTAX_OPERATOR_ESC_B_WORD_BOUND,
+                NodeKind::Anchor(Anchor::WordBoundary),
+            ),
+            'B' => (
+                Op::SYNTAX_OPERATOR_ESC_B_WORD_BOUND,
+                NodeKind::Anchor(Anchor::NotWordBoundary),
+            ),
+            'A' => (
+                Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR,
+                NodeKind::Anchor(Anchor::StartOfString),
+            ),
+            'z' => (
+                Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR,
+                NodeKind::Anchor(Anchor::EndOfString),
+            ),
+            'Z' if self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_PYTHON) => (
+                Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR,
+                NodeKind::Anchor(Anchor::EndOfString),
+            ),
+            'Z' => (
+                Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR,
+                NodeKind::Anchor(Anchor::EndOfStringOrNewline),
+            ),
+            'G' => (
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_G_BEGIN_ANCHOR,
+                NodeKind::Anchor(Anchor::SearchStart),
+            ),
+            '<' => (
+                Op::SYNTAX_OPERATOR_ESC_LTGT_WORD_BEGIN_END,
+                NodeKind::Anchor(Anchor::WordStart),
+            ),
+            '>' => (
+                Op::SYNTAX_OPERATOR_ESC_LTGT_WORD_BEGIN_END,
+                NodeKind::Anchor(Anchor::WordEnd),
+            ),
+            '`' => (
+                Op::SYNTAX_OPERATOR_ESC_GNU_BUF_ANCHOR,
+                NodeKind::Anchor(Anchor::StartOfString),
+            ),
+            '\'' => (
+                Op::SYNTAX_OPERATOR_ESC_GNU_BUF_ANCHOR,
+                NodeKind::Anchor(Anchor::EndOfString),
+            ),
+            // Oniguruma tests the bit of `\y` and `\Y` against the
+            // wrong set of operators, where it means control character
+            // escapes.
+            'y' => (
+                Op::SYNTAX_OPERATOR_ESC_CONTROL_CHARS,
+                NodeKind::Anchor(Anchor::TextSegmentBoundary),
+            ),
+            'Y' => (
+                Op::SYNTAX_OPERATOR_ESC_CONTROL_CHARS,
+                NodeKind::Anchor(Anchor::NotTextSegmentBoundary),
+            ),
+            'X' => (
+                Op::SYNTAX_OPERATOR_ESC_X_Y_TEXT_SEGMENT,
+                NodeKind::Special(Special::TextSegment),
+            ),
+            'K' => (
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_K_KEEP,
+                NodeKind::Special(Special::Keep),
+            ),
+            'R' => (
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_R_GENERAL_NEWLINE,
+                NodeKind::Special(Special::GeneralNewline),
+            ),
+            'N' => (
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_N_O_SUPER_DOT,
+                NodeKind::Special(Special::NotNewline),
+            ),
+            'O' => (
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_N_O_SUPER_DOT,
+                NodeKind::Special(Special::TrueAnyChar),
+            ),
+            _ => return None,
+        };
+        self.has(op).then_some(kind)
+    }
+
+    /// The class escape `c` stands for, if any.
+    fn class_escape(&self, c: char) -> Option<ClassEscape> {
+        use SyntaxOperator as Op;
+        let (op, kind) = match c.to_ascii_lowercase() {
+            'w' => (Op::SYNTAX_OPERATOR_ESC_W_WORD, ClassEscapeKind::Word),
+            'd' => (Op::SYNTAX_OPERATOR_ESC_D_DIGIT, ClassEscapeKind::Digit),
+            's' => (
+                Op::SYNTAX_OPERATOR_ESC_S_WHITE_SPACE,
+                ClassEscapeKind::Space,
+            ),
+            'h' => (Op::SYNTAX_OPERATOR_ESC_H_XDIGIT, ClassEscapeKind::HexDigit),
+            _ => return None,
+        };
+        self.has(op).then_some(ClassEscape {
+            kind,
+            negated: c.is_ascii_uppercase(),
+        })
+    }
+
+    /// Parse `\n` as a back-reference, if it is one. Numbers which
+    /// aren't back-references are octal escapes or literal digits.
+    fn parse_numbered_backref(
+        &mut self,
+        start: usize,
+        first: char,
+    ) -> Result<Option<NodeKind>, Error> {
+        let digits_start = self.pos - 1;
+        let digits = self.pattern[digits_start..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        let number = self.pattern[digits_start..digits_start + digits]
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n <= MAX_BACKREF);
+        if let Some(number) = number {
+            let defined = self.captures.len();
+            if self.has(SyntaxOperator::SYNTAX_OPERATOR_DECIMAL_BACKREF)
+                && (number <= defined || number <= 9)
+            {
+                self.pos = digits_start + digits;
+                if self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_STRICT_CHECK_BACKREF)
+                    && number > defined
+                {
+                    return Err(self.error(onig_sys::ONIGERR_INVALID_BACKREF, start..self.pos));
+                }
+                return Ok(Some(NodeKind::Backref(Backref {
+                    target: Reference::Number(number),
+                    level: None,
+                    groups: vec![number],
+                })));
+            }
+        }
+        if first == '8' || first == '9' {
+            return Ok(Some(NodeKind::Literal(first)));
+        }
+        Ok(None)
+    }
+
+    /// Parse an escape which stands for a character.
+    fn parse_literal_escape(&mut self, start: usize, c: char) -> Result<Node, Error> {
+        let kind = match self.parse_value(start, c, false)? {
+            Value::Char(c) => NodeKind::Literal(c),
+            Value::Byte(b) => NodeKind::Byte(b),
+            Value::List(chars) => {
+                let mut nodes: Vec<_> = chars
+                    .into_iter()
+                    .map(|(c, span, _)| Node {
+                        kind: NodeKind::Literal(c),
+                        span,
+                    })
+                    .collect();
+                if nodes.len() == 1 {
+                    return Ok(nodes.pop().unwrap());
+                }
+                NodeKind::Concat(nodes)
+            }
+        };
+        Ok(Node {
+            kind,
+            span: start..self.pos,
+        })
+    }
+
+    /// Parse the value of an escape, after the escape character and
+    /// `c`. Bytes which start a UTF-8 sequence are combined with the
+    /// escaped bytes following them.
+    fn parse_value(&mut self, start: usize, c: char, in_class: bool) -> Result<Value, Error> {
+        use SyntaxOperator as Op;
+        let value = match c {
+            'x' if self.has(Op::SYNTAX_OPERATOR_ESC_X_BRACE_HEX8) && self.peek() == Some('{') => {
+                match self.parse_code_point_list(start, 16, 8, in_class)? {
+                    Some(value) => return Ok(value),
+                    None => Value::Char(c),
+                }
+            }
+            'o' if self.has(Op::SYNTAX_OPERATOR_ESC_O_BRACE_OCTAL) && self.peek() == Some('{') => {
+                match self.parse_code_point_list(start, 8, 11, in_class)? {
+                    Some(value) => return Ok(value),
+                    None => Value::Char(c),
+                }
+            }
+            'x' if self.has(Op::SYNTAX_OPERATOR_ESC_X_HEX2) => {
+                Value::Byte(self.scan_number(16, 2).unwrap_or(0) as u8)
+            }
+            'u' if self.has(Op::SYNTAX_OPERATOR_ESC_U_HEX4) => {
+                let digits = self
+                    .rest()
+                    .bytes()
+                    .take(4)
+                    .take_while(u8::is_ascii_hexdigit)
+                    .count();
+                if digits < 4 {
+                    self.pos += digits;
+                    return Err(
+                        self.error(onig_sys::ONIGERR_INVALID_CODE_POINT_VALUE, start..self.pos)
+                    );
+                }
+                let code = self.scan_number(16, 4).unwrap();
+                Value::Char(self.code_point(start, code)?)
+            }
+            '0'..='7' if self.has(Op::SYNTAX_OPERATOR_ESC_OCTAL3) => {
+                // `\0` takes up to two more digits, `\1` to `\7` are
+                // read again as the first of three.
+                let digits = if c == '0' {
+                    2
+                } else {
+                    self.pos -= 1;
+                    3
+                };
+                // Larger values are cut down to a byte, except in a
+                // class.
+                let code = self.scan_octal(digits);
+                if code > 0xff && in_class {
+                    return Err(self.error(onig_sys::ONIGERR_TOO_BIG_NUMBER, start..self.pos));
+                }
+                Value::Byte(code as u8)
+            }
+            'c' if self.has(Op::SYNTAX_OPERATOR_ESC_C_CONTROL) => {
+                Value::Char(self.parse_control(start, c)?)
+            }
+            'C' if self.has(Op::SYNTAX_OPERATOR_ESC_CAPITAL_C_BAR_CONTROL) => {
+                Value::Char(self.parse_control(start, c)?)
+            }
+            'M' if self.has(Op::SYNTAX_OPERATOR_ESC_CAPITAL_M_BAR_META) => {
+                Value::Char(self.parse_control(start, c)?)
+            }
+            c if self.has(Op::SYNTAX_OPERATOR_ESC_CONTROL_CHARS) => Value::Char(match c {
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                'f' => '\x0c',
+                'a' => '\x07',
+                'e' => '\x1b',
+                'v' if self.has(Op::SYNTAX_OPERATOR_ESC_V_VTAB) => '\x0b',
+                'b' if in_class => '\x08',
+                c => c,
+            }),
+            c => Value::Char(c),
+        };
+        match value {
+            // A stray continuation byte is only allowed in a class.
+            Value::Byte(0x80..=0xbf) if !in_class => {
+                Err(self.error(onig_sys::ONIGERR_INVALID_CODE_POINT_VALUE, start..self.pos))
+            }
+            Value::Byte(b) if b >= 0x80 => self.parse_multibyte(start, b, in_class),
+            Value::Byte(b) => Ok(Value::Char(b as char)),
+            value => Ok(value),
+        }
+    }
+
+    /// Parse the rest of a character, given as escaped bytes, after
+    /// its first byte.
+    fn parse_multibyte(&mut self, start: usize, lead: u8, in_class: bool) -> Result<Value, Error> {
+        let len = match lead {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => return Ok(Value::Byte(lead)),
+        };
+        let mut bytes = vec![lead];
+        while bytes.len() < len {
+            let at = self.pos;
+            let byte = match (self.bump(), self.bump()) {
+                (Some(e), Some('x'))
+                    if self.is_escape(e)
+                        && self.has(SyntaxOperator::SYNTAX_OPERATOR_ESC_X_HEX2) =>
+                {
+                    match self.peek() {
+                        Some('{') => None,
+                        _ => self.scan_number(16, 2).map(|b| b as u8),
+                    }
+                }
+                // Classes only take hexadecimal bytes.
+                (Some(e), Some(c @ '0'..='7'))
+                    if self.is_escape(e)
+                        && !in_class
+                        && self.has(SyntaxOperator::SYNTAX_OPERATOR_ESC_OCTAL3) =>
+                {
+                    let digits = if c == '0' {
+                        2
+                    } else {
+                        self.pos -= 1;
+                        3
+                    };
+                    u8::try_from(self.scan_octal(digits)).ok()
+                }
+                _ => None,
+            };
+            match byte {
+                Some(byte) => bytes.push(byte),
+                None => {
+                    self.pos = at;
+                    return Err(self.error(
+                        onig_sys::ONIGERR_TOO_SHORT_MULTI_BYTE_STRING,
+                        start..self.pos,
+                    ));
+                }
+            }
+        }
+        match std::str::from_utf8(&bytes) {
+            Ok(s) => Ok(Value::Char(s.chars().next().unwrap())),
+            // A class takes the bits of the bytes as they are.
+            Err(_) if in_class => {
+                let code = bytes[1..]
+                    .iter()
+                    .fold(u32::from(lead) & (0x7f >> len), |code, &b| {
+                        code << 6 | u32::from(b) & 0x3f
+                    });
+                Ok(Value::Char(self.code_point(start, code)?))
+            }
+            Err(_) => Err(self.error(onig_sys::ONIGERR_INVALID_CODE_POINT_VALUE, start..self.pos)),
+        }
+    }
+
+    /// Parse `{...}` after `\x` or `\o`: one or more code points
+    /// separated by spaces. Returns `None`, with nothing consumed, if
+    /// no number follows the brace.
+    fn parse_code_point_list(
+        &mut self,
+        start: usize,
+        radix: u32,
+        max_digits: usize,
+        in_class: bool,
+    ) -> Result<Option<Value>, Error> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut chars = Vec::new();
+        let mut range_end = false;
+        loop {
+            let at = self.pos;
+            let code = match self.scan_number(radix, max_digits) {
+                Some(code) => code,
+                // Without a number the brace is a literal.
+                None if chars.is_empty() => {
+                    self.pos = open;
+                    return Ok(None);
+                }
+                None => {
+                    return Err(
+                        self.error(onig_sys::ONIGERR_INVALID_CODE_POINT_VALUE, start..self.pos)
+                    )
+                }
+            };
+            if matches!(self.peek(), Some(c) if c.is_digit(radix)) {
+                return Err(self.error(onig_sys::ONIGERR_TOO_LONG_WIDE_CHAR_VALUE, at..self.pos));
+            }
+            chars.push((self.code_point(at, code)?, at..self.pos, range_end));
+            if self.eat('}') {
+                break;
+            }
+            // Code points are divided by spaces or newlines, and in a
+            // class `-` makes a range of two of them.
+            let divider = self.pos;
+            self.skip_code_point_dividers();
+            range_end = in_class && !range_end && self.eat('-');
+            if range_end {
+                self.skip_code_point_dividers();
+            }
+            if self.pos == divider {
+                return Err(self.error(onig_sys::ONIGERR_INVALID_CODE_POINT_VALUE, start..self.pos));
+            }
+        }
+        Ok(Some(match chars.len() {
+            1 => Value::Char(chars[0].0),
+            _ => {
+                chars[0].1.start = start;
+                chars.last_mut().unwrap().1.end = self.pos;
+                Value::List(chars)
+            }
+        }))
+    }
+
+    fn skip_code_point_dividers(&mut self) {
+        while self.eat(' ') || self.eat('\n') {}
+    }
+
+    /// Scan up to `max_digits` digits in `radix`.
+    fn scan_number(&mut self, radix: u32, max_digits: usize) -> Option<u32> {
+        let digits = self
+            .rest()
+            .chars()
+            .take(max_digits)
+            .take_while(|c| c.is_digit(radix))
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        let number = u32::from_str_radix(&self.rest()[..digits], radix).ok();
+        self.pos += digits;
+        number
+    }
+
+    /// Scan up to `max_digits` octal digits.
+    fn scan_octal(&mut self, max_digits: usize) -> u32 {
+        let mut code = 0;
+        for _ in 0..max_digits {
+            match self.peek().and_then(|c| c.to_digit(8)) {
+                Some(digit) => {
+                    code = code * 8 + digit;
+                    self.pos += 1;
+                }
+                _ => break,
+            }
+        }
+        code
+    }
+
+    fn code_point(&self, start: usize, code: u32) -> Result<char, Error> {
+        char::from_u32(code)
+            .ok_or_else(|| self.error(onig_sys::ONIGERR_INVALID_CODE_POINT_VALUE, start..self.pos))
+    }
+
+    /// Parse a control or meta character, `\cX`, `\C-X` or `\M-X`,
+    /// after the `c`, `C` or `M`.
+    fn parse_control(&mut self, start: usize, c: char) -> Result<char, Error> {
+        let code = self.control_code(start, c)?;
+        self.code_point(start, code)
+    }
+
+    /// The code of a control or meta character. An escape in its
+    /// operand can only be another control or meta character or a
+    /// control character escape like `\n`, as in Oniguruma.
+    fn control_code(&mut self, start: usize, c: char) -> Result<u32, Error> {
+        if c == 'M' {
+            if self.bump() != Some('-') {
+                return Err(self.meta_error(start));
+            }
+            let code = match self.bump() {
+                Some(c) if self.is_escape(c) => self.escaped_code(start)?,
+                Some(c) => c as u32,
+                None => return Err(self.meta_error(start)),
+            };
+            return Ok((code & 0xff) | 0x80);
+        }
+        if c == 'C' && self.bump() != Some('-') {
+            return Err(self.control_error(start));
+        }
+        match self.bump() {
+            Some('?') => Ok(0x7f),
+            Some(c) if self.is_escape(c) => Ok(self.escaped_code(start)? & 0x9f),
+            Some(c) => Ok(c as u32 & 0x9f),
+            None => Err(self.control_error(start)),
+        }
+    }
+
+    /// The code of an escape in the operand of a control or meta
+    /// character, after the escape character.
+    fn escaped_code(&mut self, start: usize) -> Result<u32, Error> {
+        use SyntaxOperator as Op;
+        match self.bump() {
+            Some(c @ 'c') if self.has(Op::SYNTAX_OPERATOR_ESC_C_CONTROL) => {
+                self.control_code(start, c)
+            }
+            Some(c @ 'C') if self.has(Op::SYNTAX_OPERATOR_ESC_CAPITAL_C_BAR_CONTROL) => {
+                self.control_code(start, c)
+            }
+            Some(c @ 'M') if self.has(Op::SYNTAX_OPERATOR_ESC_CAPITAL_M_BAR_META) => {
+                self.control_code(start, c)
+            }
+            Some(c) if self.has(Op::SYNTAX_OPERATOR_ESC_CONTROL_CHARS) => Ok(match c {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'f' => '\x0c',
+                'a' => '\x07',
+                'b' => '\x08',
+                'e' => '\x1b',
+                'v' if self.has(Op::SYNTAX_OPERATOR_ESC_V_VTAB) => '\x0b',
+                c => c,
+            } as u32),
+            Some(c) => Ok(c as u32),
+            None => Err(self.error(onig_sys::ONIGERR_END_PATTERN_AT_ESCAPE, start..self.pos)),
+        }
+    }
+
+    fn control_error(&self, start: usize) -> Error {
+        let code = if self.pos == self.pattern.len() {
+            onig_sys::ONIGERR_END_PATTERN_AT_CONTROL
+        } else {
+            onig_sys::ONIGERR_CONTROL_CODE_SYNTAX
+        };
+        self.error(code, start..self.pos)
+    }
+
+    fn meta_error(&self, start: usize) -> Error {
+        let code = if self.pos == self.pattern.len() {
+            onig_sys::ONIGERR_END_PATTERN_AT_META
+        } else {
+            onig_sys::ONIGERR_META_CODE_SYNTAX
+        };
+        self.error(code, start..self.pos)
+    }
+
+    /// Parse the rest of a `\Q...\E` quote.
+    fn parse_quote(&mut self, start: usize) -> Node {
+        let mut nodes = Vec::new();
+        loop {
+            if let Some(len) = self.starts_with('E', true) {
+                self.pos += len;
+                break;
+            }
+            let at = self.pos;
+            match self.bump() {
+                Some(c) => nodes.push(Node {
+                    kind: NodeKind::Literal(c),
+                    span: at..self.pos,
+                }),
+                None => break,
+            }
+        }
+        let span = start..self.pos;
+        match nodes.len() {
+            0 => Node {
+                kind: NodeKind::Empty,
+                span,
+            },
+            _ => Node {
+                kind: NodeKind::Concat(nodes),
+                span,
+            },
+        }
+    }
+
+    /// Parse the rest of `\p{...}`, after the `p`.
+    fn parse_property(&mut self, negated: bool) -> Result<Property, Error> {
+        self.pos += 1;
+        let mut negated = negated;
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_ESC_P_BRACE_CIRCUMFLEX_NOT) && self.eat('^') {
+            negated = !negated;
+        }
+        let name_start = self.pos;
+        loop {
+            match self.bump() {
+                Some('}') => break,
+                Some('(' | ')' | '{' | '|') | None => {
+                    return Err(self.error(
+                        onig_sys::ONIGERR_INVALID_CHAR_PROPERTY_NAME,
+                        name_start..self.pos,
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        let name_span = name_start..self.pos - 1;
+        let name = &self.pattern[name_span.clone()];
+        if !property_exists(name) {
+            return Err(self.error(onig_sys::ONIGERR_INVALID_CHAR_PROPERTY_NAME, name_span));
+        }
+        Ok(Property {
+            name: name.to_owned(),
+            negated,
+        })
+    }
+
+    // References
+
+    /// Read a name up to `close`. Returns the name and its span.
+    fn read_name(&mut self, close: char) -> Result<(&'p str, Span), Error> {
+        let start = self.pos;
+        loop {
+            match self.bump() {
+                Some(c) if c == close => break,
+                Some(')') => {
+                    return Err(
+                        self.error(onig_sys::ONIGERR_INVALID_GROUP_NAME, start..self.pos - 1)
+                    )
+                }
+                Some(_) => {}
+                None => {
+                    return Err(self.error(onig_sys::ONIGERR_INVALID_GROUP_NAME, start..self.pos))
+                }
+            }
+        }
+        let span = start..self.pos - close.len_utf8();
+        if span.is_empty() {
+            return Err(self.error(onig_sys::ONIGERR_EMPTY_GROUP_NAME, span));
+        }
+        Ok((&self.pattern[span.clone()], span))
+    }
+
+    /// Read the name of a group definition up to `close`.
+    fn read_group_name(&mut self, close: char) -> Result<(String, Span), Error> {
+        let (name, span) = self.read_name(close)?;
+        if name.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') {
+            return Err(self.error(onig_sys::ONIGERR_INVALID_GROUP_NAME, span));
+        }
+        Ok((name.to_owned(), span))
+    }
+
+    /// Parse the group of a back-reference, up to `close`. A nest level
+    /// may follow the group.
+    fn parse_backref_name(&mut self, close: char) -> Result<Backref, Error> {
+        let start = self.pos;
+        let (text, span) = self.read_name(close)?;
+        let (target, level) = match split_reference(text, true) {
+            Some(reference) => reference,
+            None => return Err(self.error(onig_sys::ONIGERR_INVALID_GROUP_NAME, span)),
+        };
+        self.backref(target, level, start - 1..self.pos, span)
+    }
+
+    /// Resolve a back-reference to the groups defined so far.
+    fn backref(
+        &self,
+        target: Reference,
+        level: Option<i32>,
+        span: Span,
+        name_span: Span,
+    ) -> Result<Backref, Error> {
+        let groups = match target {
+            Reference::Number(number) => vec![number],
+            Reference::Relative(offset) => {
+                let number = self.captures.len() as isize + 1 + offset;
+                if offset >= 0 || number <= 0 {
+                    return Err(self.error(onig_sys::ONIGERR_INVALID_BACKREF, span));
+                }
+                vec![number as usize]
+            }
+            Reference::Name(ref name) => {
+                let groups: Vec<_> = self
+                    .captures
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, capture)| capture.name.as_ref() == Some(name))
+                    .map(|(i, _)| i + 1)
+                    .collect();
+                if groups.is_empty() {
+                    return Err(self.error(onig_sys::ONIGERR_UNDEFINED_NAME_REFERENCE, name_span));
+                }
+                groups
+            }
+        };
+        Ok(Backref {
+            target,
+            level,
+            groups,
+        })
+    }
+
+    /// Parse the group of a subexpression call, up to `close`.
+    fn parse_call_name(&mut self, close: char) -> Result<Call, Error> {
+        let start = self.pos;
+        let (text, _) = self.read_name(close)?;
+        let target = match split_reference(text, false) {
+            Some((target, _)) => target,
+            None => Reference::Name(text.to_owned()),
+        };
+        self.call(target, start - 1..self.pos)
+    }
+
+    /// Resolve a numbered subexpression call. Named calls are resolved
+    /// once every group is known.
+    fn call(&self, target: Reference, span: Span) -> Result<Call, Error> {
+        let group = match target {
+            Reference::Number(number) => number,
+            Reference::Relative(offset) => {
+                let defined = self.captures.len() as isize;
+                let number = if offset < 0 {
+                    defined + 1 + offset
+                } else {
+                    defined + offset
+                };
+                // One before the first group is the whole pattern.
+                if number < 0 {
+                    return Err(self.error(onig_sys::ONIGERR_UNDEFINED_GROUP_REFERENCE, span));
+                }
+                number as usize
+            }
+            Reference::Name(_) => return Ok(Call { target, group: 0 }),
+        };
+        // Oniguruma can't apply whole pattern options to a call of the
+        // whole pattern.
+        if group == 0 && self.whole_options {
+            return Err(self.error(onig_sys::ONIGERR_INVALID_GROUP_OPTION, span));
+        }
+        Ok(Call { target, group })
+    }
+
+    // Groups
+
+    /// Parse a group, after the opening parenthesis.
+    fn parse_group(&mut self, start: usize) -> Result<Node, Error> {
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT) && self.eat('?') {
+            return self.parse_extension(start);
+        }
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_ASTERISK_CALLOUT_NAME) && self.eat('*') {
+            return self.parse_callout_name(start);
+        }
+        let kind = if self
+            .options
+            .contains(RegexOptions::REGEX_OPTION_DONT_CAPTURE_GROUP)
+        {
+            GroupKind::NonCapture
+        } else {
+            self.open_capture(None, false, start..start)?
+        };
+        self.parse_group_body(start, kind, self.extended)
+    }
+
+    /// Parse the body of a group, up to and including the closing
+    /// parenthesis, with extended mode set to `extended` inside it.
+    fn parse_nested(&mut self, start: usize, extended: bool) -> Result<Node, Error> {
+        let outer = self.extended;
+        self.extended = extended;
+        self.depth += 1;
+        let body = self.parse_alternation();
+        self.depth -= 1;
+        self.extended = outer;
+        let body = body?;
+        match self.at_close() {
+            Some(len) => self.pos += len,
+            None => {
+                return Err(self.error(
+                    onig_sys::ONIGERR_END_PATTERN_WITH_UNMATCHED_PARENTHESIS,
+                    start..self.pos,
+                ))
+            }
+        }
+        Ok(body)
+    }
+
+    fn parse_group_body(
+        &mut self,
+        start: usize,
+        kind: GroupKind,
+        extended: bool,
+    ) -> Result<Node, Error> {
+        let body = self.parse_nested(start, extended)?;
+        Ok(Node {
+            kind: NodeKind::Group(Group {
+                kind,
+                body: Box::new(body),
+            }),
+            span: start..self.pos,
+        })
+    }
+
+    /// Number a new capture group.
+    fn open_capture(
+        &mut self,
+        name: Option<String>,
+        history: bool,
+        name_span: Span,
+    ) -> Result<GroupKind, Error> {
+        if let Some(ref name) = name {
+            if !self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_ALLOW_MULTIPLEX_DEFINITION_NAME)
+                && self
+                    .captures
+                    .iter()
+                    .any(|capture| capture.name.as_ref() == Some(name))
+            {
+                return Err(self.error(onig_sys::ONIGERR_MULTIPLEX_DEFINED_NAME, name_span));
+            }
+        }
+        self.captures.push(Capture { name: name.clone() });
+        Ok(GroupKind::Capture {
+            index: Some(self.captures.len()),
+            name,
+            history,
+        })
+    }
+
+    /// Parse a `(?...)` group, after the question mark.
+    fn parse_extension(&mut self, start: usize) -> Result<Node, Error> {
+        use SyntaxOperator as Op;
+        let extended = self.extended;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Err(self.error(onig_sys::ONIGERR_END_PATTERN_IN_GROUP, start..self.pos)),
+        };
+        let kind = match c {
+            ':' => GroupKind::NonCapture,
+            '=' => GroupKind::LookAhead,
+            '!' => GroupKind::NegativeLookAhead,
+            '>' => GroupKind::Atomic,
+            '<' if self.eat('=') => GroupKind::LookBehind,
+            '<' if self.eat('!') => GroupKind::NegativeLookBehind,
+            '<' | '\'' if self.has(Op::SYNTAX_OPERATOR_QMARK_LT_NAMED_GROUP) => {
+                let close = if c == '<' { '>' } else { '\'' };
+                let (name, span) = self.read_group_name(close)?;
+                self.open_capture(Some(name), false, span)?
+            }
+            '@' if self.has(Op::SYNTAX_OPERATOR_ATMARK_CAPTURE_HISTORY) => {
+                let name = match self.peek() {
+                    Some(c @ ('<' | '\''))
+                        if self.has(Op::SYNTAX_OPERATOR_QMARK_LT_NAMED_GROUP) =>
+                    {
+                        self.bump();
+                        Some(self.read_group_name(if c == '<' { '>' } else { '\'' })?)
+                    }
+                    _ => None,
+                };
+                match name {
+                    Some((name, span)) => self.open_capture(Some(name), true, span)?,
+                    None => self.open_capture(None, true, start..start)?,
+                }
+            }
+            'P' if self.has(Op::SYNTAX_OPERATOR_QMARK_CAPITAL_P_NAME) => match self.bump() {
+                Some('<') => {
+                    let (name, span) = self.read_group_name('>')?;
+                    self.open_capture(Some(name), false, span)?
+                }
+                Some('=') => {
+                    let (name, span) = self.read_name(')')?;
+                    let backref = self.backref(
+                        Reference::Name(name.to_owned()),
+                        None,
+                        start..self.pos,
+                        span,
+                    )?;
+                    return Ok(Node {
+                        kind: NodeKind::Backref(backref),
+                        span: start..self.pos,
+                    });
+                }
+                Some('>') => {
+                    let (name, _) = self.read_name(')')?;
+                    let call = self.call(Reference::Name(name.to_owned()), start..self.pos)?;
+                    return Ok(Node {
+                        kind: NodeKind::Call(call),
+                        span: start..self.pos,
+                    });
+                }
+                _ => return Err(self.undefined_option(start)),
+            },
+            '&' if self.has(Op::SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL) => {
+                let (name, _) = self.read_name(')')?;
+                let call = self.call(Reference::Name(name.to_owned()), start..self.pos)?;
+                return Ok(Node {
+                    kind: NodeKind::Call(call),
+                    span: start..self.pos,
+                });
+            }
+            'R' if self.has(Op::SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL) => {
+                if !self.eat(')') {
+                    return Err(self.undefined_option(start));
+                }
+                return Ok(Node {
+                    kind: NodeKind::Call(Call {
+                        target: Reference::Number(0),
+                        group: 0,
+                    }),
+                    span: start..self.pos,
+                });
+            }
+            '-' | '+' | '0'..='9' if self.has(Op::SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL) => {
+                // Oniguruma reads these as numbered calls, which it
+                // doesn't support.
+                let code = if c == '-' {
+                    onig_sys::ONIGERR_INVALID_GROUP_NAME
+                } else {
+                    onig_sys::ONIGERR_UNDEFINED_GROUP_OPTION
+                };
+                return Err(self.error(code, start..self.pos));
+            }
+            '(' if self.has(Op::SYNTAX_OPERATOR_QMARK_LPAREN_IF_ELSE) => {
+                return self.parse_conditional(start)
+            }
+            '~' if self.has(Op::SYNTAX_OPERATOR_QMARK_TILDE_ABSENT_GROUP) => {
+                return self.parse_absent(start)
+            }
+            '{' if self.has(Op::SYNTAX_OPERATOR_QMARK_BRACE_CALLOUT_CONTENTS) => {
+                return self.parse_callout_contents(start)
+            }
+            _ => {
+                self.pos -= c.len_utf8();
+                return self.parse_options(start);
+            }
+        };
+        self.parse_group_body(start, kind, extended)
+    }
+
+    /// Is there nothing but comments and white space before `at`?
+    fn only_trivia_before(&mut self, at: usize) -> bool {
+        let pos = self.pos;
+        self.pos = 0;
+        let first = self.skip_trivia().ok().map(|_| self.pos);
+        self.pos = pos;
+        first == Some(at)
+    }
+
+    fn undefined_option(&self, start: usize) -> Error {
+        self.error(onig_sys::ONIGERR_UNDEFINED_GROUP_OPTION, start..self.pos)
+    }
+
+    /// Parse `(?imx-imx)` or `(?imx-imx:...)`, after the question mark.
+    fn parse_options(&mut self, start: usize) -> Result<Node, Error> {
+        use SyntaxOperator as Op;
+        let perl = self.has(Op::SYNTAX_OPERATOR_OPTION_PERL);
+        let ruby = self.has(Op::SYNTAX_OPERATOR_OPTION_RUBY);
+        let oniguruma = self.has(Op::SYNTAX_OPERATOR_OPTION_ONIGURUMA);
+        let mut options = Options::default();
+        let mut negative = false;
+        let first = self.pos;
+        let isolated = loop {
+            let at = self.pos;
+            let flag = match self.bump() {
+                None => {
+                    return Err(self.error(onig_sys::ONIGERR_END_PATTERN_IN_GROUP, start..self.pos))
+                }
+                Some(')') if at > first => break true,
+                Some(':') if at > first => break false,
+                Some('-') => {
+                    negative = true;
+                    continue;
+                }
+                Some('i') => Flag::IgnoreCase,
+                Some('x') => Flag::Extend,
+                Some('s') if perl => Flag::DotAll,
+                Some('m') if perl => Flag::MultiLine,
+                Some('m') if ruby || oniguruma => Flag::DotAll,
+                Some('a') if self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_PYTHON) => Flag::Ascii,
+                Some('W') if oniguruma => Flag::WordAscii,
+                Some('D') if oniguruma => Flag::DigitAscii,
+                Some('S') if oniguruma => Flag::SpaceAscii,
+                Some('P') if oniguruma => Flag::PosixAscii,
+                Some('y') if oniguruma => {
+                    let flag = match (self.bump(), self.bump(), self.bump()) {
+                        (Some('{'), Some('g'), Some('}')) => Flag::GraphemeCluster,
+                        (Some('{'), Some('w'), Some('}')) => Flag::WordSegment,
+                        _ => {
+                            return Err(
+                                self.error(onig_sys::ONIGERR_INVALID_GROUP_OPTION, at..self.pos)
+                            )
+                        }
+                    };
+                    if negative {
+                        return Err(
+                            self.error(onig_sys::ONIGERR_INVALID_GROUP_OPTION, at..self.pos)
+                        );
+                    }
+                    flag
+                }
+                Some(c @ ('I' | 'L' | 'C'))
+                    if self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_WHOLE_OPTIONS) =>
+                {
+                    // Whole pattern options must open the pattern.
+                    if negative || !self.only_trivia_before(start) {
+                        return Err(
+                            self.error(onig_sys::ONIGERR_INVALID_GROUP_OPTION, at..self.pos)
+                        );
+                    }
+                    self.whole_options = true;
+                    match c {
+                        'I' => Flag::IgnoreCaseAscii,
+                        'L' => Flag::FindLongest,
+                        _ => Flag::FindNotEmpty,
+                    }
+                }
+                Some(_) => return Err(self.undefined_option(start)),
+            };
+            if negative {
+                options.off.push(flag);
+            } else {
+                options.on.push(flag);
+            }
+        };
+        let mut extended = self.extended;
+        if options.on.contains(&Flag::Extend) {
+            extended = true;
+        }
+        if options.off.contains(&Flag::Extend) {
+            extended = false;
+        }
+        if isolated {
+            self.extended = extended;
+            return Ok(Node {
+                kind: NodeKind::Options(options),
+                span: start..self.pos,
+            });
+        }
+        self.parse_group_body(start, GroupKind::Options(options), extended)
+    }
+
+    /// Parse `(?(cond)yes|no)`, after the condition's parenthesis.
+    fn parse_conditional(&mut self, start: usize) -> Result<Node, Error> {
+        let extended = self.extended;
+        let condition_start = self.pos - 1;
+        let condition = match self.parse_condition_reference(condition_start)? {
+            Some(backref) => Condition::Backref(backref),
+            None => {
+                let expression = self.parse_nested(condition_start, extended)?;
+                // An expression needs something to choose between.
+                if self.at_close().is_some() {
+                    return Err(
+                        self.error(onig_sys::ONIGERR_INVALID_IF_ELSE_SYNTAX, start..self.pos)
+                    );
+                }
+                Condition::Expression(Box::new(expression))
+            }
+        };
+        let body = self.parse_nested(start, extended)?;
+        let (yes, no) = match body.kind {
+            NodeKind::Alternation(mut branches) => {
+                let yes = branches.remove(0);
+                let no = match branches.len() {
+                    1 => branches.pop().unwrap(),
+                    _ => Node {
+                        span: branches[0].span.start..body.span.end,
+                        kind: NodeKind::Alternation(branches),
+                    },
+                };
+                (yes, Some(Box::new(no)))
+            }
+            _ => (body, None),
+        };
+        Ok(Node {
+            kind: NodeKind::Conditional(Conditional {
+                condition,
+                yes: Box::new(yes),
+                no,
+            }),
+            span: start..self.pos,
+        })
+    }
+
+    /// Parse a group number or name followed by `)` as the condition of
+    /// a conditional. Returns `None`, with nothing consumed, if the
+    /// condition is an expression.
+    fn parse_condition_reference(&mut self, start: usize) -> Result<Option<Backref>, Error> {
+        let rest = self.rest();
+        let (target, len, name_span) = match rest.chars().next() {
+            Some(c @ ('<' | '\'')) => {
+                let close = if c == '<' { '>' } else { '\'' };
+                let end = match rest[1..].find([close, ')']) {
+                    Some(end) if rest[1 + end..].starts_with(close) => end + 1,
+                    _ => return Ok(None),
+                };
+                if !rest[end + 1..].starts_with(')') {
+                    return Ok(None);
+                }
+                let text = &rest[1..end];
+                let span = self.pos + 1..self.pos + end;
+                match split_reference(text, false) {
+                    Some((target, _)) if !text.is_empty() => (target, end + 2, span),
+                    _ => return Ok(None),
+                }
+            }
+            Some(_) => {
+                let end = match rest.find(')') {
+                    Some(end) => end,
+                    None => return Ok(None),
+                };
+                let text = &rest[..end];
+                let span = self.pos..self.pos + end;
+                match split_reference(text, false) {
+                    Some((target @ (Reference::Number(_) | Reference::Relative(_)), _)) => {
+                        (target, end + 1, span)
+                    }
+                    // A bare name is a reference only to a defined group.
+                    Some((Reference::Name(name), _))
+                        if self
+                            .captures
+                            .iter()
+                            .any(|c| c.name.as_deref() == Some(&name)) =>
+                    {
+                        (Reference::Name(name), end + 1, span)
+                    }
+                    _ => return Ok(None),
+                }
+            }
+            None => return Ok(None),
+        };
+        self.pos += len;
+        self.backref(target, None, start..self.pos, name_span)
+            .map(Some)
+    }
+
+    /// Parse `(?~...)`, after the tilde.
+    fn parse_absent(&mut self, start: usize) -> Result<Node, Error> {
+        let extended = self.extended;
+        let absent = if self.eat('|') {
+            if let Some(len) = self.at_close() {
+                self.pos += len;
+                Absent::Clear
+            } else {
+                let body = self.parse_nested(start, extended)?;
+                match body.kind {
+                    // Any further branches are alternatives of the
+                    // expression.
+                    NodeKind::Alternation(mut branches) => {
+                        let absent = branches.remove(0);
+                        let expr = if branches.len() == 1 {
+                            branches.pop().unwrap()
+                        } else {
+                            Node {
+                                span: branches[0].span.start..branches[branches.len() - 1].span.end,
+                                kind: NodeKind::Alternation(branches),
+                            }
+                        };
+                        Absent::Expression {
+                            absent: Box::new(absent),
+                            expr: Box::new(expr),
+                        }
+                    }
+                    _ => Absent::Range(Box::new(body)),
+                }
+            }
+        } else {
+            Absent::Repeater(Box::new(self.parse_nested(start, extended)?))
+        };
+        Ok(Node {
+            kind: NodeKind::Absent(absent),
+            span: start..self.pos,
+        })
+    }
+
+    /// Parse `(?{...}[tag]D)`, after the first brace.
+    fn parse_callout_contents(&mut self, start: usize) -> Result<Node, Error> {
+        let invalid = |parser: &Self| {
+            parser.error(onig_sys::ONIGERR_INVALID_CALLOUT_PATTERN, start..parser.pos)
+        };
+        let mut braces = 1;
+        while self.eat('{') {
+            braces += 1;
+        }
+        let close = "}".repeat(braces);
+        let len = self.rest().find(&close).ok_or_else(|| invalid(self))?;
+        let contents = self.rest()[..len].to_owned();
+        self.pos += len + braces;
+        if self.peek() == Some('}') {
+            return Err(invalid(self));
+        }
+        let tag = self.parse_callout_tag()?;
+        let direction = match self.peek() {
+            Some('X') => CalloutDirection::Both,
+            Some('<') => CalloutDirection::Retraction,
+            Some('>') => CalloutDirection::Progress,
+            _ => CalloutDirection::Progress,
+        };
+        if matches!(self.peek(), Some('X' | '<' | '>')) {
+            self.pos += 1;
+        }
+        if !self.eat(')') {
+            return Err(invalid(self));
+        }
+        Ok(Node {
+            kind: NodeKind::Callout(Callout::Contents {
+                contents,
+                tag,
+                direction,
+            }),
+            span: start..self.pos,
+        })
+    }
+
+    /// Parse `(*NAME[tag]{args})`, after the asterisk.
+    fn parse_callout_name(&mut self, start: usize) -> Result<Node, Error> {
+        let name_start = self.pos;
+        let name = self.scan_identifier();
+        if name.is_empty() {
+            return Err(self.error(onig_sys::ONIGERR_INVALID_CALLOUT_NAME, name_start..self.pos));
+        }
+        let tag = self.parse_callout_tag()?;
+        let mut args = Vec::new();
+        if self.eat('{') {
+            let len = self.rest().find('}').ok_or_else(|| {
+                self.error(
+                    onig_sys::ONIGERR_INVALID_CALLOUT_ARG,
+                    start..self.pattern.len(),
+                )
+            })?;
+            args = self.rest()[..len].split(',').map(str::to_owned).collect();
+            self.pos += len + 1;
+        }
+        if !self.eat(')') {
+            return Err(self.error(onig_sys::ONIGERR_INVALID_CALLOUT_PATTERN, start..self.pos));
+        }
+        Ok(Node {
+            kind: NodeKind::Callout(Callout::Name {
+                name: name.to_owned(),
+                tag,
+                args,
+            }),
+            span: start..self.pos,
+        })
+    }
+
+    /// Parse an optional `[tag]`.
+    fn parse_callout_tag(&mut self) -> Result<Option<String>, Error> {
+        if !self.eat('[') {
+            return Ok(None);
+        }
+        let start = self.pos;
+        let tag = self.scan_identifier();
+        if tag.is_empty() || !self.eat(']') {
+            return Err(self.error(onig_sys::ONIGERR_INVALID_CALLOUT_TAG_NAME, start..self.pos));
+        }
+        Ok(Some(tag.to_owned()))
+    }
+
+    /// Scan a callout name or tag: a letter or underscore, then
+    /// letters, digits and underscores.
+    fn scan_identifier(&mut self) -> &'p str {
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .take_while(|&(i, c)| {
+                c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+            })
+            .count();
+        self.pos += len;
+        &rest[..len]
+    }
+
+    // Bracket expressions
+
+    /// Parse a bracket expression, after the `[`.
+    fn parse_class(&mut self, start: usize) -> Result<Class, Error> {
+        use SyntaxOperator as Op;
+        let negated = self.eat('^');
+        let mut operands = Vec::new();
+        let mut items = Vec::new();
+        let mut state = ClassState::Start;
+        if self.peek() == Some(']') {
+            // `[]...]` starts with a literal `]`.
+            if !self.rest()[1..].contains(']') {
+                return Err(self.error(onig_sys::ONIGERR_EMPTY_CHAR_CLASS, start..self.pos + 1));
+            }
+            self.pos += 1;
+            self.class_char(&mut items, &mut state, ']', self.pos - 1)?;
+        }
+        loop {
+            let at = self.pos;
+            let c = match self.bump() {
+                Some(c) => c,
+                None => {
+                    return Err(self.error(
+                        onig_sys::ONIGERR_PREMATURE_END_OF_CHAR_CLASS,
+                        start..self.pos,
+                    ))
+                }
+            };
+            match c {
+                ']' => break,
+                '[' if self.has(Op::SYNTAX_OPERATOR_POSIX_BRACKET) && self.at_posix() => {
+                    match self.parse_posix()? {
+                        Some(posix) => {
+                            self.class_set(&mut items, &mut state, ClassItemKind::Posix(posix), at)?
+                        }
+                        None => self.class_char(&mut items, &mut state, '[', at)?,
+                    }
+                }
+                '[' if self.has(Op::SYNTAX_OPERATOR_CCLASS_SET_OP) => {
+                    self.nested_class(&mut items, &mut state, at)?
+                }
+                '&' if self.has(Op::SYNTAX_OPERATOR_CCLASS_SET_OP) && self.peek() == Some('&') => {
+                    self.pos += 1;
+                    operands.push(std::mem::take(&mut items));
+                    state = ClassState::Start;
+                }
+                '-' => self.class_range_operator(&mut items, &mut state, at)?,
+                c if self.is_escape(c)
+                    && self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_BACKSLASH_ESCAPE_IN_CC) =>
+                {
+                    self.parse_class_escape(&mut items, &mut state, at)?
+                }
+                c => self.class_char(&mut items, &mut state, c, at)?,
+            }
+        }
+        operands.push(items);
+        Ok(Class { negated, operands })
+    }
+
+    /// Parse a class nested in another, after the `[`.
+    fn nested_class(
+        &mut self,
+        items: &mut Vec<ClassItem>,
+        state: &mut ClassState,
+        at: usize,
+    ) -> Result<(), Error> {
+        // Oniguruma drops an unfinished range, so `[a-[b]]` is `[b]`.
+        if let ClassState::Range(_) = *state {
+            items.pop();
+        }
+        let class = self.parse_class(at)?;
+        items.push(ClassItem {
+            kind: ClassItemKind::Class(class),
+            span: at..self.pos,
+        });
+        *state = ClassState::Complete;
+        Ok(())
+    }
+
+    /// Is the class closed or intersected after a `-`?
+    fn range_operator_is_literal(&self) -> bool {
+        self.peek() == Some(']')
+            || (self.has(SyntaxOperator::SYNTAX_OPERATOR_CCLASS_SET_OP)
+                && self.rest().starts_with("&&"))
+    }
+
+    /// Add a character, or finish a range with it.
+    fn class_char(
+        &self,
+        items: &mut Vec<ClassItem>,
+        state: &mut ClassState,
+        c: char,
+        at: usize,
+    ) -> Result<(), Error> {
+        if let ClassState::Range(from) = *state {
+            let first = items.pop().unwrap();
+            let span = first.span.start..self.pos;
+            if from > c && !self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_ALLOW_EMPTY_RANGE_IN_CC) {
+                return Err(self.error(onig_sys::ONIGERR_EMPTY_RANGE_IN_CHAR_CLASS, span));
+            }
+            items.push(ClassItem {
+                kind: ClassItemKind::Range(from, c),
+                span,
+            });
+            *state = ClassState::Complete;
+        } else {
+            items.push(ClassItem {
+                kind: ClassItemKind::Literal(c),
+                span: at..self.pos,
+            });
+            *state = ClassState::Char(c);
+        }
+        Ok(())
+    }
+
+    /// Add a byte. Oniguruma compares bytes with characters by value
+    /// in ranges, so `[a-\xff]` is `[a-ÿ]`.
+    fn class_byte(
+        &self,
+        items: &mut Vec<ClassItem>,
+        state: &mut ClassState,
+        b: u8,
+        at: usize,
+    ) -> Result<(), Error> {
+        if let ClassState::Range(_) = *state {
+            return self.class_char(items, state, char::from(b), at);
+        }
+        items.push(ClassItem {
+            kind: ClassItemKind::Byte(b),
+            span: at..self.pos,
+        });
+        *state = ClassState::Char(char::from(b));
+        Ok(())
+    }
+
+    /// Add an item which can't be part of a range.
+    fn class_set(
+        &self,
+        items: &mut Vec<ClassItem>,
+        state: &mut ClassState,
+        kind: ClassItemKind,
+        at: usize,
+    ) -> Result<(), Error> {
+        if let ClassState::Range(_) = *state {
+            return Err(self.error(
+                onig_sys::ONIGERR_CHAR_CLASS_VALUE_AT_END_OF_RANGE,
+                at..self.pos,
+            ));
+        }
+        items.push(ClassItem {
+            kind,
+            span: at..self.pos,
+        });
+        *state = ClassState::Set;
+        Ok(())
+    }
+
+    /// Handle a `-`, which is a range operator after a character and a
+    /// literal elsewhere.
+    fn class_range_operator(
+        &self,
+        items: &mut Vec<ClassItem>,
+        state: &mut ClassState,
+        at: usize,
+    ) -> Result<(), Error> {
+        match *state {
+            ClassState::Char(c) if !self.range_operator_is_literal() => {
+                *state = ClassState::Range(c);
+                Ok(())
+            }
+            ClassState::Set | ClassState::Complete
+                if !self.range_operator_is_literal()
+                    && (!matches!(state, ClassState::Complete)
+                        || !self.allows(
+                            SyntaxBehavior::SYNTAX_BEHAVIOR_ALLOW_DOUBLE_RANGE_OP_IN_CC,
+                        )) =>
+            {
+                Err(self.error(
+                    onig_sys::ONIGERR_UNMATCHED_RANGE_SPECIFIER_IN_CHAR_CLASS,
+                    at..self.pos,
+                ))
+            }
+            _ => self.class_char(items, state, '-', at),
+        }
+    }
+
+    /// Parse an escape in a bracket expression, after the escape
+    /// character.
+    fn parse_class_escape(
+        &mut self,
+        items: &mut Vec<ClassItem>,
+        state: &mut ClassState,
+        at: usize,
+    ) -> Result<(), Error> {
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Err(self.error(onig_sys::ONIGERR_END_PATTERN_AT_ESCAPE, at..self.pos)),
+        };
+        if let Some(escape) = self.class_escape(c) {
+            return self.class_set(items, state, ClassItemKind::Escape(escape), at);
+        }
+        if (c == 'p' || c == 'P')
+            && self.has(SyntaxOperator::SYNTAX_OPERATOR_ESC_P_BRACE_CHAR_PROPERTY)
+            && self.peek() == Some('{')
+        {
+            let property = self.parse_property(c == 'P')?;
+            return self.class_set(items, state, ClassItemKind::Property(property), at);
+        }
+        match self.parse_value(at, c, true)? {
+            Value::Char(c) => self.class_char(items, state, c, at),
+            Value::Byte(b) => self.class_byte(items, state, b, at),
+            Value::List(chars) => {
+                for (c, span, range_end) in chars {
+                    if range_end {
+                        match *state {
+                            ClassState::Char(from) => *state = ClassState::Range(from),
+                            _ => {
+                                return Err(
+                                    self.error(onig_sys::ONIGERR_INVALID_CODE_POINT_VALUE, span)
+                                )
+                            }
+                        }
+                    }
+                    self.class_char(items, state, c, span.start)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Is a `[` followed by what looks like a POSIX bracket? It has
+    /// to be closed by `:]` before the class is.
+    fn at_posix(&self) -> bool {
+        let rest = match self.rest().strip_prefix(':') {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if rest[i..].starts_with(":]") {
+                return true;
+            } else if self.is_escape(c) {
+                chars.next();
+            } else if c == ']' {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// Parse a POSIX bracket, after the `[`. Returns `None`, with
+    /// nothing consumed, if the name isn't known, in which case the
+    /// `[` is a literal.
+    fn parse_posix(&mut self) -> Result<Option<Posix>, Error> {
+        const NAMES: &[&str] = &[
+            "alnum", "alpha", "blank", "cntrl", "digit", "graph", "lower", "print", "punct",
+            "space", "upper", "xdigit", "word", "ascii",
+        ];
+        let open = self.pos;
+        self.pos += 1;
+        let negated = self.eat('^');
+        let name_start = self.pos;
+        if self.rest().chars().count() >= 7 {
+            if let Some(name) = NAMES.iter().find(|&&name| self.rest().starts_with(name)) {
+                self.pos += name.len();
+                if !self.rest().starts_with(":]") {
+                    return Err(self.error(
+                        onig_sys::ONIGERR_INVALID_POSIX_BRACKET_TYPE,
+                        name_start..self.pos,
+                    ));
+                }
+                self.pos += 2;
+                return Ok(Some(Posix {
+                    name: (*name).to_owned(),
+                    negated,
+                }));
+            }
+        }
+        // A misspelt name is an error.
+        let rest = self.rest();
+        let name_len = rest
+            .chars()
+            .take(20)
+            .take_while(|&c| c != ':' && c != ']')
+            .map(char::len_utf8)
+            .sum::<usize>();
+        if rest[name_len..].starts_with(":]") {
+            return Err(self.error(
+                onig_sys::ONIGERR_INVALID_POSIX_BRACKET_TYPE,
+                name_start..name_start + name_len,
+            ));
+        }
+        self.pos = open;
+        Ok(None)
+    }
+
+    // Resolving references
+
+    /// Number the capture groups once every group is known, and check
+    /// the references to them. `numbers` maps the groups in the order
+    /// they were opened to their numbers.
+    fn resolve(
+        &self,
+        node: &mut Node,
+        numbers: &[Option<usize>],
+        only_named: bool,
+    ) -> Result<(), Error> {
+        let span = node.span.clone();
+        match node.kind {
+            NodeKind::Group(ref mut group) => {
+                if let GroupKind::Capture { ref mut index, .. } = group.kind {
+                    *index = index.and_then(|i| numbers[i - 1]);
+                }
+                self.resolve(&mut group.body, numbers, only_named)
+            }
+            NodeKind::Backref(ref mut backref) => {
+                self.resolve_backref(backref, span, numbers, only_named)
+            }
+            NodeKind::Call(ref mut call) => {
+                match call.target {
+                    Reference::Name(ref name) => {
+                        let mut groups = self
+                            .captures
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, capture)| capture.name.as_ref() == Some(name));
+                        let name_span = self.name_span(&span, name);
+                        call.group = match (groups.next(), groups.next()) {
+                            (Some((i, _)), None) => i + 1,
+                            (None, _) => {
+                                return Err(self
+                                    .error(onig_sys::ONIGERR_UNDEFINED_NAME_REFERENCE, name_span))
+                            }
+                            _ => {
+                                return Err(self.error(
+                                    onig_sys::ONIGERR_MULTIPLEX_DEFINITION_NAME_CALL,
+                                    name_span,
+                                ))
+                            }
+                        };
+                    }
+                    _ if only_named => {
+                        return Err(self
+                            .error(onig_sys::ONIGERR_NUMBERED_BACKREF_OR_CALL_NOT_ALLOWED, span))
+                    }
+                    _ if call.group > numbers.len() => {
+                        return Err(self.error(onig_sys::ONIGERR_UNDEFINED_GROUP_REFERENCE, span))
+                    }
+                    _ => {}
+                }
+                if call.group > 0 {
+                    call.group = numbers[call.group - 1].unwrap_or(0);
+                }
+                Ok(())
+            }
+            NodeKind::Conditional(ref mut conditional) => {
+                match conditional.condition {
+                    Condition::Backref(ref mut backref) => {
+                        self.resolve_backref(backref, span, numbers, only_named)?
+                    }
+                    Condition::Expression(ref mut expr) => {
+                        self.resolve(expr, numbers, only_named)?
+                    }
+                }
+                self.resolve(&mut conditional.yes, numbers, only_named)?;
+                match conditional.no {
+                    Some(ref mut no) => self.resolve(no, numbers, only_named),
+                    None => Ok(()),
+                }
+            }
+            NodeKind::Absent(ref mut absent) => match absent {
+                Absent::Repeater(body) | Absent::Range(body) => {
+                    self.resolve(body, numbers, only_named)
+                }
+                Absent::Expression { absent, expr } => {
+                    self.resolve(absent, numbers, only_named)?;
+                    self.resolve(expr, numbers, only_named)
+                }
+                Absent::Clear => Ok(()),
+            },
+            NodeKind::Repeat(ref mut repeat) => self.resolve(&mut repeat.body, numbers, only_named),
+            NodeKind::Concat(ref mut nodes) | NodeKind::Alternation(ref mut nodes) => nodes
+                .iter_mut()
+                .try_for_each(|node| self.resolve(node, numbers, only_named)),
+            _ => Ok(()),
+        }
+    }
+
+    fn resolve_backref(
+        &self,
+        backref: &mut Backref,
+        span: Span,
+        numbers: &[Option<usize>],
+        only_named: bool,
+    ) -> Result<(), Error> {
+        if let Reference::Number(_) | Reference::Relative(_) = backref.target {
+            if only_named {
+                return Err(
+                    self.error(onig_sys::ONIGERR_NUMBERED_BACKREF_OR_CALL_NOT_ALLOWED, span)
+                );
+            }
+        }
+        let mut groups = Vec::with_capacity(backref.groups.len());
+        for &group in &backref.groups {
+            match numbers.get(group.wrapping_sub(1)) {
+                Some(&Some(number)) => groups.push(number),
+                _ => return Err(self.error(onig_sys::ONIGERR_INVALID_BACKREF, span)),
+            }
+        }
+        backref.groups = groups;
+        Ok(())
+    }
+
+    /// The span of `name` in a reference spanning `span`.
+    fn name_span(&self, span: &Span, name: &str) -> Span {
+        match self.pattern[span.clone()].rfind(name) {
+            Some(offset) => span.start + offset..span.start + offset + name.len(),
+            None => span.clone(),
+        }
+    }
+}
+
+/// Repeat `node` as `quantifier` says.
+fn repeat(node: Node, quantifier: Quantifier) -> Node {
+    Node {
+        span: node.span.start..quantifier.span.end,
+        kind: NodeKind::Repeat(Repeat {
+            min: quantifier.min,
+            max: quantifier.max,
+            greed: quantifier.greed,
+            body: Box::new(node),
+        }),
+    }
+}
+
+/// Can't `node` be repeated? Anchors, look-arounds, `\K` and callouts
+/// can't, and neither can a non-capturing group around one. Like
+/// Oniguruma, an alternation is invalid if any branch is, while a
+/// sequence is always valid.
+fn is_invalid_repeat_target(node: &Node) -> bool {
+    match node.kind {
+        NodeKind::Anchor(_) | NodeKind::Special(Special::Keep) | NodeKind::Callout(_) => true,
+        NodeKind::Group(ref group) => match group.kind {
+            GroupKind::LookAhead
+            | GroupKind::NegativeLookAhead
+            | GroupKind::LookBehind
+            | GroupKind::NegativeLookBehind => true,
+            GroupKind::NonCapture => is_invalid_repeat_target(&group.body),
+            _ => false,
+        },
+        NodeKind::Alternation(ref branches) => branches.iter().any(is_invalid_repeat_target),
+        _ => false,
+    }
+}
+
+/// Split the text of a reference into the group and, for
+/// back-references, the nest level. Returns `None` if the text is
+/// malformed.
+fn split_reference(text: &str, level: bool) -> Option<(Reference, Option<i32>)> {
+    let (group, nest) = match text.rfind(['+', '-']) {
+        Some(i) if level && i > 0 && is_number(&text[i + 1..]) => {
+            (&text[..i], Some(text[i..].parse().ok()?))
+        }
+        _ => (text, None),
+    };
+    let target = if is_number(group) {
+        Reference::Number(group.parse().ok()?)
+    } else if group.starts_with(['+', '-']) && is_number(&group[1..]) {
+        Reference::Relative(group.parse().ok()?)
+    } else if level && group.contains(['+', '-']) {
+        return None;
+    } else {
+        Reference::Name(group.to_owned())
+    };
+    Some((target, nest))
+}
+
+fn is_number(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Is `name` a property of the UTF-8 encoding, or one defined with
+/// [`define_user_property`](crate::define_user_property)?
+fn property_exists(name: &str) -> bool {
+    let encoding = Encoding::UTF8.as_raw();
+    let start = name.as_ptr() as *mut u8;
+    unsafe {
+        match (*encoding).property_name_to_ctype {
+            Some(to_ctype) => to_ctype(encoding, start, start.add(name.len())) >= 0,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Regex;
+
+    /// Patterns for every construct, valid and invalid, to check
+    /// against Oniguruma in each syntax.
+    const CORPUS: &[&str] = &[
+        // Literals, escapes and anchors
+        "",
+        "abc",
+        "é日😀",
+        r"a\.b\*c",
+        r"\t\n\r\f\a\e\v",
+        r"\x41\x7\xz",
+        r"\xc3\xa9",
+        r"\xe6\x97\xa5",
+        r"\xc3",
+        r"\xa9",
+        r"\xf0",
+        r"\xf5",
+        r"\711",
+        r"\400",
+        r"\xff",
+        r"\x{41}\x{1F600}",
+        r"\x{41 42 43}",
+        r"\x{}",
+        r"\x{3,2}",
+        r"\x{3",
+        r"\x{3 }",
+        r"\x{ 3}",
+        r"\x{41  42}",
+        "\\x{41\n42}",
+        r"\x{41-43}",
+        r"\o{101 102}",
+        r"[\x{41-43}]",
+        r"[\x{41 - 43 45}]",
+        r"[\x{41-43-45}]",
+        r"[\x{41-}]",
+        r"[\x{-41}]",
+        r"[a-\x{41 42}]",
+        r"[\x{30}-\x{41 42}]",
+        r"[\x{41 }]",
+        r"\x{g}",
+        r"\x{123456789}",
+        r"\o{101}",
+        r"A",
+        r"\u004",
+        r"\0\01\012\0123",
+        r"\101\377",
+        r"\8\9",
+        r"\cA\c?",
+        r"\c",
+        r"\C-a\M-a",
+        r"\C-",
+        r"\c\xc3",
+        r"\c\n\C-\M-a",
+        r"\M-\x{41 42}",
+        r"\c\",
+        r"\",
+        r"\w\W\s\S\d\D\h\H",
+        r"\b\B\A\z\Z\G",
+        r"\<\>\`\'",
+        r"\y\Y\X",
+        r"\y?",
+        r"\K\R\N\O",
+        r"\Qa.b*\Ec",
+        r"\Qa.b",
+        r"^a$",
+        r".",
+        r"\p{Alpha}\P{Greek}",
+        r"\p{^Alpha}\P{^Digit}",
+        r"\p{Foo}",
+        r"\p{Alpha",
+        r"\p{(}",
+        r"\pL",
+        // Repetition
+        "a*b+c?",
+        "a*?b+?c??",
+        "a*+b++c?+",
+        "a{2}b{2,}c{2,3}d{,3}",
+        "a{2}?b{2,3}?",
+        "a{2}+b{2,3}+",
+        "a{3,2}",
+        "a{3,2}?",
+        "a{,}",
+        "a{",
+        "a{1",
+        "a{1,2",
+        "a{x}",
+        "{1}",
+        "a{100001}",
+        "a{99999999999}",
+        "a**",
+        "a*{2}",
+        "*a",
+        "+a",
+        "?a",
+        "a|*b",
+        "^*",
+        "$+",
+        r"\b*",
+        r"\A?",
+        r"\K*",
+        "(?=a)*",
+        "(?<!a)+",
+        "(?:^)*",
+        "(?:(?:^))*",
+        "(?:^|a)*",
+        "(?:^a)*",
+        "(^)*",
+        "(?i:^)*",
+        "(?i)*a",
+        "a(?#comment)*",
+        r"\Qab\E*",
+        r"a\*",
+        r"a\{2\}",
+        r"a\+\?",
+        // Alternation and groups
+        "a|b|",
+        "|",
+        "(a|b)c",
+        "(a",
+        "a)",
+        "a)b",
+        "((a)",
+        "()",
+        "(?:a)",
+        "(?>a)",
+        "(?=a)(?!b)(?<=c)(?<!d)",
+        "(?<=a|b)",
+        "(?<n>a)(?'m'b)",
+        "(?<n>a)(?<n>b)",
+        "(?P<n>a)(?P=n)(?P>n)",
+        "(?<>a)",
+        "(?<1a>a)",
+        "(?<-a>a)",
+        "(?<a-b>a)",
+        "(?<a)b>a)",
+        "(?<a",
+        "(?@a)",
+        "(?@<n>a)",
+        "(?",
+        "(?)",
+        "(?Q)",
+        "(?#comment)a",
+        "(?#unterminated",
+        r"\(a\)",
+        r"\(a",
+        r"a\|b",
+        // Options
+        "(?i)a",
+        "(?i:a)",
+        "(?-i)a",
+        "(?im-sx)a",
+        "(?imsx)a",
+        "(?s)a",
+        "(?a)a",
+        "(?-)a",
+        "(?i-)a",
+        "(?^i)a",
+        "(?W)a(?D:b)(?S)c(?P)d",
+        "(?y{g})a(?y{w})b",
+        "(?-y{g})a",
+        "(?y{x})a",
+        "(?I)a",
+        "(?IL)a",
+        "(?C:a)",
+        "(?-I)a",
+        "a(?I)",
+        "(?i)(?L)a",
+        "(?#comment)(?I)a",
+        "(?x)(?I)a",
+        " (?I)a",
+        "(?I)a|b\\g<0>",
+        "(?x) a b # comment\n c",
+        "(?x: a ) b",
+        "(?x)a (?-x) b",
+        "(?x)a(?#c) *",
+        "(?x)a # ( unbalanced",
+        "(?x)[ ]",
+        // Back-references and calls
+        r"(a)\1",
+        r"\1(a)",
+        r"(a)\2",
+        r"\10",
+        r"(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)\10",
+        r"\1000",
+        r"\k<1>(a)",
+        r"(a)\k<2>",
+        r"(a)\k<-1>",
+        r"\k<-1>",
+        r"(a)\k<+1>",
+        r"(a)\k<1+1>",
+        r"(?<n>a)\k<n>",
+        r"(?<n>a)\k'n'",
+        r"\k<n>(?<n>a)",
+        r"(?<n>a)\k<n-1>",
+        r"(?<n>a)\k<n+a>",
+        r"(?<n>a)\k<m>",
+        r"(?<n>a)\k<n",
+        r"\k<>",
+        r"(?<n>a)(b)\1",
+        r"(?<n>a)(b)\k<1>",
+        r"(?<n>a)(?<n>b)\k<n>",
+        r"\k",
+        r"\kx",
+        r"(a)\g<1>",
+        r"\g<1>(a)",
+        r"\g<2>(a)",
+        r"a|b\g<0>",
+        r"(a)\g<-1>",
+        r"(a)\g<+1>(b)",
+        r"(a)\g<+2>",
+        r"a|b\g<-1>",
+        r"(?<n>a)\g<n>",
+        r"\g<n>(?<n>a)",
+        r"\g<m>(?<n>a)",
+        r"(?<n>a)(?<n>b)\g<n>",
+        r"(?<n>a)\g<1>",
+        r"(?<n>a)\g<0>",
+        r"\g<é>(?<é>a)",
+        r"\g",
+        "(?R)",
+        "(?&n)(?<n>a)",
+        "(?&)",
+        "(?1)(a)",
+        "(?-1)(a)",
+        "(?R",
+        // Conditionals
+        "(a)(?(1)b|c)",
+        "(?(1)b)",
+        "(?(1)b)(a)",
+        "(a)(?(1)b|c|d)",
+        "(?<n>a)(?(<n>)b|c)",
+        "(?<n>a)(?('n')b)",
+        "(?<n>a)(?(n)b)",
+        "(?(<n>)b)(?<n>a)",
+        "(?(a)b|c)",
+        "(?(a|b)c)",
+        "(?()a)",
+        "(?(a)",
+        "(?(a))",
+        "(a)(?(1))",
+        "(?(?=a)b)",
+        "(?<n>a)(?(1)b)",
+        // Absent groups
+        "(?~abc)",
+        "(?~|abc|\\d+)",
+        "(?~|abc)x",
+        "(?~|)",
+        "(?~|a|b|c)",
+        "(?~|a|b)*",
+        "(?~abc)*",
+        "(?~|a)*",
+        // Callouts
+        "a(?{x})b",
+        "a(?{{x}})b",
+        "a(?{x}[tag]X)b",
+        "a(?{x}<)b",
+        "a(?{x)b",
+        "a(?{x}[1])b",
+        "(*FAIL)",
+        "a(*MAX{2})b",
+        "a(*COUNT[t]{X})b",
+        "(*)",
+        "(*FAIL",
+        "a(*FAIL)*",
+        // Bracket expressions
+        "[abc]",
+        "[^abc]",
+        "[a-z0-9_]",
+        "[]a]",
+        "[^]a]",
+        "[]",
+        "[^]",
+        "[a",
+        "[a-]",
+        "[-a]",
+        "[a-b-c]",
+        "[a-\\w]",
+        "[\\w-a]",
+        "[\\w-]",
+        "[b-a]",
+        "[!--]",
+        "[--a]",
+        "[\\]]",
+        "[\\\\]",
+        "[\\x41-\\x5a]",
+        "[a-\\xff]",
+        "[\\xff-a]",
+        "[\\xa9-\\xff]",
+        "[\\xff-\\x{100}]",
+        "[\\777]",
+        "[\\xc3\\xc3]",
+        "[\\xc3a]",
+        "[é-\\xc3\\xff]",
+        "[\\xc3\\x04-z]",
+        "[\\xc3\\251]",
+        "\\xc3\\251",
+        "[a-\\377]",
+        "[\\u0041-\\u005a]",
+        "[\\101]",
+        "[\\b]",
+        "[\\d\\p{Greek}\\P{^Alpha}]",
+        "[\\p{Foo}]",
+        "[[:alpha:][:^digit:]]",
+        "[[:foo:]]",
+        "[[:alpha]",
+        "[[:alphax:]]",
+        "[[:alpha:]-a]",
+        "[:alpha:]",
+        "[[abc]]",
+        "[a[bc]d]",
+        "[a-z&&[^aeiou]]",
+        "[a-z&&b]",
+        "[&&a]",
+        "[a&&]",
+        "[a-&&b]",
+        "[[a]-b]",
+        "[a-[b]]",
+        "[a-[b]-c]",
+        "[[:a]",
+        "[[:a]]",
+        "[[:*?[:alpha:]]",
+        "[[:a:]b:]]",
+        "[[:abcdefghijklmnopqrstu:]]",
+        "[[:abcdefghijklmnopqrst:]]",
+        "[é-ü]",
+        "[\\x{41 42}]",
+        "[\\",
+    ];
+
+    fn syntaxes() -> Vec<(&'static str, &'static Syntax)> {
+        vec![
+            ("ruby", Syntax::ruby()),
+            ("oniguruma", Syntax::oniguruma()),
+            ("perl", Syntax::perl()),
+            ("perl_ng", Syntax::perl_ng()),
+            ("java", Syntax::java()),
+            ("python", Syntax::python()),
+            ("posix_basic", Syntax::posix_basic()),
+            ("posix_extended", Syntax::posix_extended()),
+            ("emacs", Syntax::emacs()),
+            ("grep", Syntax::grep()),
+            ("gnu_regex", Syntax::gnu_regex()),
+            ("asis", Syntax::asis()),
+        ]
+    }
+
+    fn compiles(pattern: &str, options: RegexOptions, syntax: &Syntax) -> bool {
+        Regex::with_options(pattern, options, syntax).is_ok()
+    }
+
+    #[test]
+    fn test_parse_agrees_with_oniguruma() {
+        let mut mismatches = Vec::new();
+        for (name, syntax) in syntaxes() {
+            for pattern in CORPUS {
+                let parsed = parse(pattern, RegexOptions::REGEX_OPTION_NONE, syntax);
+                if parsed.is_ok() != compiles(pattern, RegexOptions::REGEX_OPTION_NONE, syntax) {
+                    mismatches.push(format!("{}: {:?} parsed as {:?}", name, pattern, parsed));
+                }
+            }
+        }
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
+
+    fn node(kind: NodeKind, span: Span) -> Node {
+        Node { kind, span }
+    }
+
+    fn lit(c: char, at: usize) -> Node {
+        node(NodeKind::Literal(c), at..at + 1)
+    }
+
+    fn ruby(pattern: &str) -> Ast {
+        parse(pattern, RegexOptions::REGEX_OPTION_NONE, Syntax::ruby()).unwrap()
+    }
+
+    fn error_at(pattern: &str, syntax: &Syntax) -> (i32, Option<Span>) {
+        let e = parse(pattern, RegexOptions::REGEX_OPTION_NONE, syntax).unwrap_err();
+        (e.code(), e.pattern_span())
+    }
+
+    #[test]
+    fn test_parse_nodes_and_spans() {
+        let ast = ruby(r"a(?:b|c)*\d");
+        let group = Group {
+            kind: GroupKind::NonCapture,
+            body: Box::new(node(
+                NodeKind::Alternation(vec![lit('b', 4), lit('c', 6)]),
+                4..7,
+            )),
+        };
+        let repeat = Repeat {
+            min: 0,
+            max: None,
+            greed: Greed::Greedy,
+            body: Box::new(node(NodeKind::Group(group), 1..8)),
+        };
+        let digit = ClassEscape {
+            kind: ClassEscapeKind::Digit,
+            negated: false,
+        };
+        assert_eq!(
+            ast.root,
+            node(
+                NodeKind::Concat(vec![
+                    lit('a', 0),
+                    node(NodeKind::Repeat(repeat), 1..9),
+                    node(NodeKind::ClassEscape(digit), 9..11),
+                ]),
+                0..11
+            )
+        );
+        assert_eq!(ast.captures, 0);
+    }
+
+    #[test]
+    fn test_parse_follows_syntax() {
+        let grep = parse(
+            r"\(a\|b\)\+",
+            RegexOptions::REGEX_OPTION_NONE,
+            Syntax::grep(),
+        )
+        .unwrap();
+        match grep.root.kind {
+            NodeKind::Repeat(ref repeat) => {
+                assert_eq!((repeat.min, repeat.max), (1, None));
+                assert!(matches!(
+                    repeat.body.kind,
+                    NodeKind::Group(ref group) if matches!(group.kind, GroupKind::Capture { .. })
+                ));
+            }
+            ref kind => panic!("unexpected {:?}", kind),
+        }
+        let literals = parse("(a|b)+", RegexOptions::REGEX_OPTION_NONE, Syntax::grep()).unwrap();
+        assert!(matches!(literals.root.kind, NodeKind::Concat(ref nodes) if nodes.len() == 6));
+
+        let quoted = parse(r"\Qa.b\E+", RegexOptions::REGEX_OPTION_NONE, Syntax::perl()).unwrap();
+        match quoted.root.kind {
+            NodeKind::Concat(ref nodes) => {
+                assert_eq!(nodes[..2], [lit('a', 2), lit('.', 3)]);
+                assert_eq!(nodes[2].span, 4..8);
+            }
+            ref kind => panic!("unexpected {:?}", kind),
+        }
+
+        let extended = parse(
+            "a # comment\n b",
+            RegexOptions::REGEX_OPTION_EXTEND,
+            Syntax::ruby(),
+        )
+        .unwrap();
+        assert_eq!(
+            extended.root.kind,
+            NodeKind::Concat(vec![lit('a', 0), lit('b', 13)])
+        );
+    }
+
+    #[test]
+    fn test_parse_capture_numbering() {
+        let ast = ruby("(a)(?<n>b)(c)");
+        assert_eq!(ast.captures, 1);
+        assert_eq!(ast.names, vec![("n".to_string(), vec![1])]);
+        let indices: Vec<_> = match ast.root.kind {
+            NodeKind::Concat(ref nodes) => nodes
+                .iter()
+                .map(|n| match n.kind {
+                    NodeKind::Group(ref group) => match group.kind {
+                        GroupKind::Capture { index, .. } => index,
+                        _ => panic!("unexpected {:?}", group.kind),
+                    },
+                    _ => panic!("unexpected {:?}", n.kind),
+                })
+                .collect(),
+            ref kind => panic!("unexpected {:?}", kind),
+        };
+        assert_eq!(indices, vec![None, Some(1), None]);
+
+        let all = parse(
+            "(a)(?<n>b)(c)",
+            RegexOptions::REGEX_OPTION_CAPTURE_GROUP,
+            Syntax::ruby(),
+        )
+        .unwrap();
+        assert_eq!(all.captures, 3);
+        assert_eq!(all.names, vec![("n".to_string(), vec![2])]);
+    }
+
+    #[test]
+    fn test_parse_multiplex_names() {
+        let ast = ruby(r"(?<n>a)(?<n>b)\k<n>");
+        assert_eq!(ast.captures, 2);
+        assert_eq!(ast.names, vec![("n".to_string(), vec![1, 2])]);
+        match ast.root.kind {
+            NodeKind::Concat(ref nodes) => assert_eq!(
+                nodes[2],
+                node(
+                    NodeKind::Backref(Backref {
+                        target: Reference::Name("n".to_string()),
+                        level: None,
+                        groups: vec![1, 2],
+                    }),
+                    14..19
+                )
+            ),
+            ref kind => panic!("unexpected {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_parse_class_items() {
+        let ast = ruby(r"[^a-c\w[:alpha:]&&[^x]]");
+        let inner = Class {
+            negated: true,
+            operands: vec![vec![ClassItem {
+                kind: ClassItemKind::Literal('x'),
+                span: 20..21,
+            }]],
+        };
+        let class = Class {
+            negated: true,
+            operands: vec![
+                vec![
+                    ClassItem {
+                        kind: ClassItemKind::Range('a', 'c'),
+                        span: 2..5,
+                    },
+                    ClassItem {
+                        kind: ClassItemKind::Escape(ClassEscape {
+                            kind: ClassEscapeKind::Word,
+                            negated: false,
+                        }),
+                        span: 5..7,
+                    },
+                    ClassItem {
+                        kind: ClassItemKind::Posix(Posix {
+                            name: "alpha".to_string(),
+                            negated: false,
+                        }),
+                        span: 7..16,
+                    },
+                ],
+                vec![ClassItem {
+                    kind: ClassItemKind::Class(inner),
+                    span: 18..22,
+                }],
+            ],
+        };
+        assert_eq!(ast.root, node(NodeKind::Class(class), 0..23));
+    }
+
+    #[test]
+    fn test_parse_conditionals_and_absent() {
+        let ast = ruby("(a)(?(1)b|c|d)");
+        match ast.root.kind {
+            NodeKind::Concat(ref nodes) => match nodes[1].kind {
+                NodeKind::Conditional(ref cond) => {
+                    assert!(matches!(
+                        cond.condition,
+                        Condition::Backref(Backref { ref groups, .. }) if groups == &[1]
+                    ));
+                    assert_eq!(*cond.yes, lit('b', 8));
+                    assert_eq!(
+                        cond.no,
+                        Some(Box::new(node(
+                            NodeKind::Alternation(vec![lit('c', 10), lit('d', 12)]),
+                            10..13
+                        )))
+                    );
+                }
+                ref kind => panic!("unexpected {:?}", kind),
+            },
+            ref kind => panic!("unexpected {:?}", kind),
+        }
+
+        let absent = ruby("(?~|a|b|c)");
+        assert_eq!(
+            absent.root,
+            node(
+                NodeKind::Absent(Absent::Expression {
+                    absent: Box::new(lit('a', 4)),
+                    expr: Box::new(node(
+                        NodeKind::Alternation(vec![lit('b', 6), lit('c', 8)]),
+                        6..9
+                    )),
+                }),
+                0..10
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_isolated_options() {
+        let ignore_case = || Options {
+            on: vec![Flag::IgnoreCase],
+            off: vec![],
+        };
+        let ast = ruby("a(?i)b|c");
+        let group = Group {
+            kind: GroupKind::Options(ignore_case()),
+            body: Box::new(node(
+                NodeKind::Alternation(vec![lit('b', 5), lit('c', 7)]),
+                5..8,
+            )),
+        };
+        assert_eq!(
+            ast.root.kind,
+            NodeKind::Concat(vec![lit('a', 0), node(NodeKind::Group(group), 1..8)])
+        );
+
+        let perl = parse("a(?i)b|c", RegexOptions::REGEX_OPTION_NONE, Syntax::perl()).unwrap();
+        assert_eq!(
+            perl.root.kind,
+            NodeKind::Alternation(vec![
+                node(
+                    NodeKind::Concat(vec![
+                        lit('a', 0),
+                        node(NodeKind::Options(ignore_case()), 1..5),
+                        lit('b', 5),
+                    ]),
+                    0..6
+                ),
+                lit('c', 7),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_error_spans() {
+        let ruby = Syntax::ruby();
+        assert_eq!(error_at("a(b", ruby), (-117, Some(1..3)));
+        assert_eq!(error_at("a)", ruby), (-116, Some(1..2)));
+        assert_eq!(error_at("[b-a]", ruby), (-203, Some(1..4)));
+        assert_eq!(error_at(r"(?<n>a)\k<m>", ruby), (-217, Some(10..11)));
+        assert_eq!(error_at(r"(?<n>a)(b)\1", ruby), (-209, Some(10..12)));
+        assert_eq!(error_at(r"\p{Foo}", ruby), (-223, Some(3..6)));
+        assert_eq!(error_at("a{3,2}", Syntax::perl()), (-202, Some(1..6)));
+    }
+}
@@ -0,0 +1,287 @@
+//! Resource Budgets
+//!
+//! This module contains the `Budget` used to compile and search with
+//! patterns from untrusted sources. A budget bounds both the cost of
+//! compiling the pattern and the cost of each search made with it,
+//! so a hostile pattern fails with an error rather than tying up the
+//! process.
+
+use super::{limits, Error, ErrorKind, MatchParam, Regex, RegexOptions, Syntax, REGEX_NEW_MUTEX};
+
+/// Resource Budget for Untrusted Patterns
+///
+/// The compile time limits are applied when the regex is created by
+/// [`Regex::untrusted`]. The search time limits are stored with the
+/// regex and applied to every search it makes which doesn't take an
+/// explicit `MatchParam`.
+///
+/// Searches which hit a limit fail with an `Error` for which
+/// [`Error::is_limit_exceeded`] returns `true`. The `try_*` search
+/// methods, such as [`Regex::try_find_iter`], report these errors;
+/// the other search methods panic on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Budget {
+    /// The maximum length of the pattern, in bytes.
+    pub max_pattern_len: usize,
+    /// The maximum nesting depth of the pattern. `0` uses
+    /// Oniguruma's default.
+    pub parse_depth: u32,
+    /// The maximum number of capture groups in the pattern.
+    pub captures: u32,
+    /// The maximum number of backtracks at each start position in a
+    /// search. `0` means unlimited.
+    pub retry_limit_in_match: u32,
+    /// The maximum number of backtracks over a whole search. `0`
+    /// means unlimited.
+    pub retry_limit_in_search: u32,
+    /// The maximum number of entries on the backtracking stack. `0`
+    /// means unlimited.
+    pub match_stack_limit: u32,
+}
+
+impl Budget {
+    /// Match Parameters for a Search
+    ///
+    /// Returns a new `MatchParam` with the search time limits of this
+    /// budget, for use with `search_with_param` and friends.
+    pub fn match_param(&self) -> MatchParam {
+        let mut param = MatchParam::default();
+        param.set_retry_limit_in_match(self.retry_limit_in_match);
+        param.set_retry_limit_in_search(self.retry_limit_in_search);
+        param.set_match_stack_limit(self.match_stack_limit);
+        param
+    }
+}
+
+impl Default for Budget {
+    /// A conservative budget, suitable for short user supplied
+    /// patterns.
+    fn default() -> Self {
+        Budget {
+            max_pattern_len: 4096,
+            parse_depth: 64,
+            captures: 64,
+            retry_limit_in_match: 100_000,
+            retry_limit_in_search: 1_000_000,
+            match_stack_limit: 100_000,
+        }
+    }
+}
+
+impl Regex<'static> {
+    /// Create a Regex from an Untrusted Pattern
+    ///
+    /// Compiles `pattern` with the default options and syntax while
+    /// enforcing the compile time limits of `budget`. The search time
+    /// limits of `budget` are kept with the regex and applied to every
+    /// search made with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regex pattern to compile.
+    /// * `budget` - The limits to compile and search with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::{Budget, Regex};
+    ///
+    /// let budget = Budget::default();
+    /// let nested = format!("{}a{}", "(".repeat(100), ")".repeat(100));
+    /// let err = Regex::untrusted(&nested, budget).unwrap_err();
+    /// assert!(err.is_limit_exceeded());
+    ///
+    /// let r = Regex::untrusted(r"(\w|\w\w)*\d", budget).unwrap();
+    /// let text = "a".repeat(50);
+    /// let res: Result<Vec<_>, _> = r.try_find_iter(&text).collect();
+    /// assert!(res.unwrap_err().is_limit_exceeded());
+    /// ```
+    pub fn untrusted(pattern: &str, budget: Budget) -> Result<Regex<'static>, Error> {
+        if pattern.len() > budget.max_pattern_len {
+            return Err(Error::with_kind(
+                ErrorKind::PatternTooLong,
+                format!(
+                    "pattern of {} bytes is longer than the limit of {} bytes",
+                    pattern.len(),
+                    budget.max_pattern_len
+                ),
+            ));
+        }
+        let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+        let mut regex = limits::with_compile_limits(budget.parse_depth, budget.captures, || {
+            Regex::compile_locked(pattern, RegexOptions::REGEX_OPTION_NONE, Syntax::default())
+        })?;
+        regex.budget = Some(budget);
+        Ok(regex)
+    }
+}
+
+impl<'syntax> Regex<'syntax> {
+    /// Get the Budget of the Regex
+    ///
+    /// Returns the budget this regex was created with by
+    /// [`Regex::untrusted`], if any.
+    pub fn budget(&self) -> Option<&Budget> {
+        self.budget.as_ref()
+    }
+
+    /// The match parameters for searches which aren't given any.
+    pub(crate) fn default_match_param(&self) -> MatchParam {
+        match self.budget {
+            Some(budget) => budget.match_param(),
+            None => MatchParam::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RegexSet, Region, SearchOptions};
+
+    #[test]
+    fn test_untrusted_pattern_len() {
+        let budget = Budget {
+            max_pattern_len: 4,
+            ..Budget::default()
+        };
+        assert!(Regex::untrusted("abcd", budget).is_ok());
+        let err = Regex::untrusted("abcde", budget).unwrap_err();
+        assert!(err.is_limit_exceeded());
+    }
+
+    #[test]
+    fn test_untrusted_captures() {
+        let budget = Budget {
+            captures: 2,
+            ..Budget::default()
+        };
+        assert!(Regex::untrusted("(a)(b)", budget).is_ok());
+        let err = Regex::untrusted("(a)(b)(c)", budget).unwrap_err();
+        assert!(err.is_limit_exceeded());
+        // The limits only apply to the untrusted regex.
+        assert!(Regex::new("(a)(b)(c)").is_ok());
+    }
+
+    #[test]
+    fn test_untrusted_search_budget() {
+        let regex = Regex::untrusted(r"(\w|\w\w)*\d", Budget::default()).unwrap();
+        assert_eq!(regex.budget(), Some(&Budget::default()));
+        let text = "a".repeat(50);
+
+        let mut matches = regex.try_find_iter(&text);
+        assert!(matches.next().unwrap().unwrap_err().is_limit_exceeded());
+        assert!(matches.next().is_none());
+
+        let err = regex.try_replace_all(&text, "x").unwrap_err();
+        assert!(err.is_limit_exceeded());
+        assert!(regex.try_split(&text).any(|s| s.is_err()));
+        assert!(regex.try_captures_iter(&text).any(|c| c.is_err()));
+
+        assert_eq!(
+            regex.try_replace_all("ab1 c2", "<$0>").unwrap(),
+            "<ab1> <c2>"
+        );
+    }
+
+    #[test]
+    fn test_untrusted_try_helpers() {
+        let regex = Regex::untrusted(r"(\w|\w\w)*\d", Budget::default()).unwrap();
+        let text = "a".repeat(50);
+
+        assert!(regex.try_find(&text).unwrap_err().is_limit_exceeded());
+        assert!(regex.try_captures(&text).is_err());
+        assert!(regex.try_is_match(&text).is_err());
+        assert!(regex.try_replace(&text, "x").is_err());
+        assert!(regex.try_replacen(&text, 2, "x").is_err());
+        let mut splits = regex.try_splitn(&text, 3);
+        assert!(splits.next().unwrap().is_err());
+        assert!(splits.next().is_none());
+
+        assert_eq!(regex.try_find("ab1").unwrap(), Some((0, 3)));
+        assert!(regex.try_is_match("ab1").unwrap());
+        assert_eq!(regex.try_replace("a1 b2", "x").unwrap(), "x b2");
+        let parts: Result<Vec<_>, _> = regex.try_splitn("a1-b2-c3", 2).collect();
+        assert_eq!(parts.unwrap(), vec!["", "-b2-c3"]);
+    }
+
+    #[test]
+    fn test_untrusted_scan_budget() {
+        let regex = Regex::untrusted(r"(\w|\w\w)*\d", Budget::default()).unwrap();
+        let text = "a".repeat(50);
+        let mut region = Region::new();
+        let r = regex.scan_with_region(
+            &text,
+            &mut region,
+            SearchOptions::SEARCH_OPTION_NONE,
+            |_, _, _| true,
+        );
+        assert!(Error::from_code(r).is_limit_exceeded());
+        let r = regex.scan_with_region(
+            "a1 b2",
+            &mut region,
+            SearchOptions::SEARCH_OPTION_NONE,
+            |_, _, _| true,
+        );
+        assert_eq!(r, 2);
+
+        // The budget stops the scan rather than letting it run away.
+        let bytes = crate::bytes::Regex::from(regex);
+        let found = std::cell::Cell::new(0);
+        bytes.scan(text.as_bytes(), |_, _| {
+            found.set(found.get() + 1);
+            true
+        });
+        assert_eq!(found.get(), 0);
+    }
+
+    #[test]
+    fn test_untrusted_regex_set_budget() {
+        let regex = Regex::untrusted(r"(\w|\w\w)*\d", Budget::default()).unwrap();
+        let set = RegexSet::from_regexes(vec![regex]).unwrap();
+        let text = "a".repeat(50);
+        let res = std::panic::catch_unwind(|| set.find(&text));
+        assert!(res.is_err());
+        assert_eq!(set.find("ab1"), Some((0, (0, 3))));
+
+        assert!(set.try_find(&text).unwrap_err().is_limit_exceeded());
+        assert!(set.try_captures(&text).is_err());
+        let mut matches = set.try_find_iter(&text);
+        assert!(matches.next().unwrap().is_err());
+        assert!(matches.next().is_none());
+        assert!(set.try_captures_iter(&text).any(|c| c.is_err()));
+        let found: Result<Vec<_>, _> = set.try_find_iter("a1 b2").collect();
+        assert_eq!(found.unwrap(), vec![(0, (0, 2)), (0, (3, 5))]);
+    }
+
+    #[test]
+    fn test_untrusted_bytes_budget() {
+        let regex = Regex::untrusted(r"(\w|\w\w)*\d", Budget::default()).unwrap();
+        let bytes = crate::bytes::Regex::from(regex);
+        let text = "a".repeat(50);
+        let text = text.as_bytes();
+
+        assert!(bytes.try_find(text).unwrap_err().is_limit_exceeded());
+        assert!(bytes.try_captures(text).is_err());
+        let mut matches = bytes.try_find_iter(text);
+        assert!(matches.next().unwrap().is_err());
+        assert!(matches.next().is_none());
+        assert!(bytes.try_captures_iter(text).any(|c| c.is_err()));
+        assert!(bytes.try_split(text).any(|s| s.is_err()));
+        assert!(bytes.try_splitn(text, 2).any(|s| s.is_err()));
+        assert!(bytes.try_replace_all(text, b"x").is_err());
+
+        assert_eq!(bytes.try_find(b"-ab1\xff").unwrap(), Some((1, 4)));
+        assert_eq!(bytes.try_replace(b"a1 b2", b"x").unwrap(), b"x b2");
+        let parts: Result<Vec<_>, _> = bytes.try_split(b"a1-b2").collect();
+        assert_eq!(parts.unwrap(), vec![&b""[..], b"-"]);
+    }
+
+    #[test]
+    fn test_untrusted_search_panics() {
+        let regex = Regex::untrusted(r"(\w|\w\w)*\d", Budget::default()).unwrap();
+        let text = "a".repeat(50);
+        let res = std::panic::catch_unwind(|| regex.find_iter(&text).count());
+        assert!(res.is_err());
+    }
+}
@@ -4,6 +4,8 @@
 //! Rust strings into oniguruma char buffers to search and compile
 //! with.
 
+use crate::Encoding;
+
 /// Encoded String Buffer
 ///
 /// Represents a buffer of characters with encoding information
@@ -22,8 +24,8 @@ pub trait EncodedChars {
     fn limit_ptr(&self) -> *const onig_sys::OnigUChar;
 
     /// The encoding of the contents of the buffer
-    fn encoding(&self) -> onig_sys::OnigEncoding {
-        &raw mut onig_sys::OnigEncodingUTF8
+    fn encoding(&self) -> Encoding {
+        Encoding::UTF8
     }
 
     /// The length of this buffer
@@ -57,9 +59,10 @@ where
 /// Byte Buffer
 ///
 /// Represents a buffer of bytes, with an encoding.
+#[derive(Debug, Clone, Copy)]
 pub struct EncodedBytes<'a> {
     bytes: &'a [u8],
-    enc: onig_sys::OnigEncoding,
+    enc: Encoding,
 }
 
 impl<'a> EncodedBytes<'a> {
@@ -73,7 +76,7 @@ impl<'a> EncodedBytes<'a> {
     /// # Returns
     ///
     /// A new buffer instance
-    pub fn from_parts(bytes: &'a [u8], enc: onig_sys::OnigEncoding) -> EncodedBytes<'a> {
+    pub fn from_parts(bytes: &'a [u8], enc: Encoding) -> EncodedBytes<'a> {
         EncodedBytes { bytes, enc }
     }
 
@@ -89,9 +92,63 @@ impl<'a> EncodedBytes<'a> {
     pub fn ascii(bytes: &'a [u8]) -> EncodedBytes<'a> {
         EncodedBytes {
             bytes,
-            enc: &raw mut onig_sys::OnigEncodingASCII,
+            enc: Encoding::ASCII,
         }
     }
+
+    /// The contents of the buffer
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Count the Characters in the Buffer
+    ///
+    /// See `Encoding::char_count`.
+    pub fn char_count(&self) -> usize {
+        self.enc.char_count(self.bytes)
+    }
+
+    /// Character Index of a Byte Offset
+    ///
+    /// See `Encoding::char_index`.
+    pub fn char_index(&self, pos: usize) -> usize {
+        self.enc.char_index(self.bytes, pos)
+    }
+
+    /// Is the Buffer Valid in its Encoding?
+    ///
+    /// See `Encoding::is_valid`.
+    pub fn is_valid(&self) -> bool {
+        self.enc.is_valid(self.bytes)
+    }
+
+    /// Start of the Previous Character
+    ///
+    /// See `Encoding::prev_char_head`.
+    pub fn prev_char_head(&self, pos: usize) -> Option<usize> {
+        self.enc.prev_char_head(self.bytes, pos)
+    }
+
+    /// Step Back a Number of Characters
+    ///
+    /// See `Encoding::step_back`.
+    pub fn step_back(&self, pos: usize, n: usize) -> Option<usize> {
+        self.enc.step_back(self.bytes, pos, n)
+    }
+
+    /// Snap a Byte Offset Back to a Character Boundary
+    ///
+    /// See `Encoding::left_adjust_char_head`.
+    pub fn left_adjust_char_head(&self, pos: usize) -> usize {
+        self.enc.left_adjust_char_head(self.bytes, pos)
+    }
+
+    /// Snap a Byte Offset Forward to a Character Boundary
+    ///
+    /// See `Encoding::right_adjust_char_head`.
+    pub fn right_adjust_char_head(&self, pos: usize) -> usize {
+        self.enc.right_adjust_char_head(self.bytes, pos)
+    }
 }
 
 impl<'a> EncodedChars for EncodedBytes<'a> {
@@ -103,7 +160,7 @@ impl<'a> EncodedChars for EncodedBytes<'a> {
         self.bytes[self.bytes.len()..].as_ptr()
     }
 
-    fn encoding(&self) -> onig_sys::OnigEncoding {
+    fn encoding(&self) -> Encoding {
         self.enc
     }
 
@@ -112,6 +169,170 @@ impl<'a> EncodedChars for EncodedBytes<'a> {
     }
 }
 
+macro_rules! code_unit_buffer {
+    ($(#[$doc:meta])* $name:ident, $unit:ty, $native:ident, $le:ident, $be:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy)]
+        pub struct $name<'a> {
+            units: &'a [$unit],
+            enc: Encoding,
+        }
+
+        impl<'a> $name<'a> {
+            /// New Buffer in Native Byte Order
+            ///
+            /// # Arguments
+            ///
+            ///  * `units` - Code units in the byte order of the platform
+            pub fn new(units: &'a [$unit]) -> $name<'a> {
+                $name {
+                    units,
+                    enc: Encoding::$native,
+                }
+            }
+
+            /// New Little Endian Buffer
+            ///
+            /// # Arguments
+            ///
+            ///  * `units` - Code units stored in little endian byte
+            ///    order, e.g. read unconverted from a little endian file
+            pub fn le(units: &'a [$unit]) -> $name<'a> {
+                $name {
+                    units,
+                    enc: Encoding::$le,
+                }
+            }
+
+            /// New Big Endian Buffer
+            ///
+            /// # Arguments
+            ///
+            ///  * `units` - Code units stored in big endian byte order,
+            ///    e.g. read unconverted from a big endian file
+            pub fn be(units: &'a [$unit]) -> $name<'a> {
+                $name {
+                    units,
+                    enc: Encoding::$be,
+                }
+            }
+
+            /// The code units in the buffer
+            pub fn as_units(&self) -> &'a [$unit] {
+                self.units
+            }
+
+            /// The contents of the buffer as bytes
+            ///
+            /// The returned buffer can be used with the character
+            /// helpers on `EncodedBytes`.
+            pub fn as_encoded_bytes(&self) -> EncodedBytes<'a> {
+                let bytes = unsafe {
+                    std::slice::from_raw_parts(
+                        self.units.as_ptr() as *const u8,
+                        std::mem::size_of_val(self.units),
+                    )
+                };
+                EncodedBytes::from_parts(bytes, self.enc)
+            }
+
+            /// Code Unit Index of a Byte Offset
+            ///
+            /// Maps a byte offset, such as one returned in a `Region`,
+            /// to an index into the code units.
+            ///
+            /// # Panics
+            ///
+            /// If `pos` isn't on a code unit boundary, or is past the
+            /// end of the buffer.
+            pub fn unit_index(&self, pos: usize) -> usize {
+                let size = std::mem::size_of::<$unit>();
+                assert!(
+                    pos % size == 0 && pos / size <= self.units.len(),
+                    "Onig: byte offset {} is not a code unit boundary in the buffer",
+                    pos
+                );
+                pos / size
+            }
+
+            /// Byte Offset of a Code Unit Index
+            ///
+            /// Maps an index into the code units to the byte offset
+            /// searches work with.
+            pub fn byte_offset(&self, index: usize) -> usize {
+                index * std::mem::size_of::<$unit>()
+            }
+
+            /// Code Units Between Byte Offsets
+            ///
+            /// Slices the buffer using the byte offsets of a match.
+            ///
+            /// # Panics
+            ///
+            /// If either offset isn't on a code unit boundary, or is past
+            /// the end of the buffer.
+            pub fn slice(&self, start: usize, end: usize) -> &'a [$unit] {
+                &self.units[self.unit_index(start)..self.unit_index(end)]
+            }
+        }
+
+        impl<'a> EncodedChars for $name<'a> {
+            fn start_ptr(&self) -> *const onig_sys::OnigUChar {
+                self.units.as_ptr() as *const onig_sys::OnigUChar
+            }
+
+            fn limit_ptr(&self) -> *const onig_sys::OnigUChar {
+                self.units[self.units.len()..].as_ptr() as *const onig_sys::OnigUChar
+            }
+
+            fn encoding(&self) -> Encoding {
+                self.enc
+            }
+
+            fn len(&self) -> usize {
+                std::mem::size_of_val(self.units)
+            }
+        }
+    };
+}
+
+code_unit_buffer! {
+    /// UTF-16 Buffer
+    ///
+    /// Represents a buffer of UTF-16 code units in native, little
+    /// endian or big endian byte order. Regexes which search it can be
+    /// compiled from UTF-8 patterns with `Regex::with_target_encoding`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::{EncodedUtf16, Encoding, Regex, RegexOptions, Syntax};
+    ///
+    /// let units: Vec<u16> = "hello wörld".encode_utf16().collect();
+    /// let text = EncodedUtf16::new(&units);
+    /// let r = Regex::with_target_encoding(
+    ///     "w.rld",
+    ///     Encoding::UTF16,
+    ///     RegexOptions::REGEX_OPTION_NONE,
+    ///     Syntax::default(),
+    /// )
+    /// .unwrap();
+    /// let (start, end) = r.find_with_encoding(text).unwrap();
+    /// assert_eq!(text.unit_index(start), 6);
+    /// assert_eq!(String::from_utf16_lossy(text.slice(start, end)), "wörld");
+    /// ```
+    EncodedUtf16, u16, UTF16, UTF16_LE, UTF16_BE
+}
+
+code_unit_buffer! {
+    /// UTF-32 Buffer
+    ///
+    /// Represents a buffer of UTF-32 code units in native, little
+    /// endian or big endian byte order. Regexes which search it can be
+    /// compiled from UTF-8 patterns with `Regex::with_target_encoding`.
+    EncodedUtf32, u32, UTF32, UTF32_LE, UTF32_BE
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -119,26 +340,17 @@ pub mod tests {
     #[test]
     pub fn rust_string_encoding_is_utf8() {
         let foo = "foo";
-        assert_eq!(
-            &raw mut onig_sys::OnigEncodingUTF8 as onig_sys::OnigEncoding,
-            foo.encoding()
-        );
+        assert_eq!(Encoding::UTF8, foo.encoding());
 
         let bar = String::from(".*");
-        assert_eq!(
-            &raw mut onig_sys::OnigEncodingUTF8 as onig_sys::OnigEncoding,
-            bar.encoding()
-        );
+        assert_eq!(Encoding::UTF8, bar.encoding());
     }
 
     #[test]
     pub fn rust_bytes_encoding_is_ascii() {
         let fizz = b"fizz";
         let buff = EncodedBytes::ascii(fizz);
-        assert_eq!(
-            &raw mut onig_sys::OnigEncodingASCII as onig_sys::OnigEncoding,
-            buff.encoding()
-        );
+        assert_eq!(Encoding::ASCII, buff.encoding());
     }
 
     #[test]
@@ -163,14 +375,73 @@ pub mod tests {
     #[test]
     pub fn byte_buffer_create() {
         let buff = b"hello world";
-        let enc_buffer = EncodedBytes::from_parts(buff, &raw mut onig_sys::OnigEncodingASCII);
-        assert_eq!(
-            &raw mut onig_sys::OnigEncodingASCII as onig_sys::OnigEncoding,
-            enc_buffer.encoding()
-        );
+        let enc_buffer = EncodedBytes::from_parts(buff, Encoding::ASCII);
+        assert_eq!(Encoding::ASCII, enc_buffer.encoding());
         assert_eq!(
             enc_buffer.limit_ptr() as usize - enc_buffer.start_ptr() as usize,
             buff.len()
         );
     }
+
+    #[test]
+    pub fn byte_buffer_maps_region_offsets_to_chars() {
+        // "あいう" in EUC-JP
+        let text = b"\xa4\xa2\xa4\xa4\xa4\xa6";
+        let buff = EncodedBytes::from_parts(text, Encoding::EUC_JP);
+        let regex =
+            crate::Regex::with_encoding(EncodedBytes::from_parts(b"\xa4\xa6", Encoding::EUC_JP))
+                .unwrap();
+        let (start, end) = regex.find_with_encoding(buff).unwrap();
+        assert_eq!((start, end), (4, 6));
+        assert_eq!(buff.char_index(start), 2);
+        assert_eq!(buff.char_count(), 3);
+        assert!(buff.is_valid());
+        assert_eq!(buff.prev_char_head(start), Some(2));
+        assert_eq!(buff.step_back(end, 3), Some(0));
+        assert_eq!(buff.left_adjust_char_head(5), 4);
+        assert_eq!(buff.right_adjust_char_head(5), 6);
+    }
+
+    #[test]
+    pub fn utf16_buffer_maps_region_offsets_to_units() {
+        let units: Vec<u16> = "a😀b".encode_utf16().map(u16::to_be).collect();
+        let buff = EncodedUtf16::be(&units);
+        assert_eq!(buff.encoding(), Encoding::UTF16_BE);
+        assert_eq!(buff.len(), 8);
+        let regex = crate::Regex::with_target_encoding(
+            "b",
+            Encoding::UTF16_BE,
+            crate::RegexOptions::REGEX_OPTION_NONE,
+            crate::Syntax::default(),
+        )
+        .unwrap();
+        let (start, end) = regex.find_with_encoding(buff).unwrap();
+        assert_eq!((start, end), (6, 8));
+        assert_eq!(buff.unit_index(start), 3);
+        assert_eq!(buff.byte_offset(3), start);
+        assert_eq!(buff.as_encoded_bytes().char_index(start), 2);
+    }
+
+    #[test]
+    pub fn utf32_buffer_searches_native_units() {
+        let units: Vec<u32> = "xé😀".chars().map(|c| c as u32).collect();
+        let buff = EncodedUtf32::new(&units);
+        assert_eq!(buff.encoding(), Encoding::UTF32);
+        let regex = crate::Regex::with_target_encoding(
+            "É.",
+            Encoding::UTF32,
+            crate::RegexOptions::REGEX_OPTION_IGNORECASE,
+            crate::Syntax::default(),
+        )
+        .unwrap();
+        let (start, end) = regex.find_with_encoding(buff).unwrap();
+        assert_eq!(buff.slice(start, end), &units[1..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "code unit boundary")]
+    pub fn utf16_buffer_rejects_unaligned_offsets() {
+        let units = [0x61u16];
+        EncodedUtf16::new(&units).unit_index(1);
+    }
 }
@@ -0,0 +1,355 @@
+//! Regex Builder
+//!
+//! This module contains the `RegexBuilder`, which collects the
+//! settings used to compile a regex before compiling it with `build`.
+
+use super::{
+    limits, CaseFold, EncodedChars, Encoding, Error, ErrorKind, Regex, RegexOptions, Syntax,
+    REGEX_NEW_MUTEX,
+};
+
+/// A Configurable Builder for a `Regex`
+///
+/// Each setter returns the builder, so settings can be chained and
+/// finished with a call to [`build`](RegexBuilder::build). The builder
+/// can be used to compile any number of regexes.
+///
+/// # Examples
+///
+/// ```
+/// use onig::RegexBuilder;
+///
+/// let r = RegexBuilder::new(r"hello \s+ (world)")
+///     .case_insensitive(true)
+///     .extended(true)
+///     .build()
+///     .unwrap();
+/// assert!(r.is_match("HELLO   WORLD"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RegexBuilder<'syntax> {
+    pattern: Vec<u8>,
+    pattern_encoding: Encoding,
+    target_encoding: Option<Encoding>,
+    options: RegexOptions,
+    syntax: &'syntax Syntax,
+    case_fold: Option<CaseFold>,
+    pattern_len_limit: Option<usize>,
+    parse_depth_limit: Option<u32>,
+    capture_num_limit: Option<u32>,
+    capture_history: Option<bool>,
+}
+
+impl RegexBuilder<'static> {
+    /// Create a Builder for a UTF-8 Pattern
+    ///
+    /// The builder starts with no options, the default syntax and the
+    /// process-wide limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regex pattern to compile.
+    pub fn new(pattern: &str) -> Self {
+        RegexBuilder::with_encoding(pattern)
+    }
+
+    /// Create a Builder for an Encoded Pattern
+    ///
+    /// The pattern encoding is taken from `pattern`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regex pattern to compile.
+    pub fn with_encoding<T>(pattern: T) -> Self
+    where
+        T: EncodedChars,
+    {
+        let bytes = unsafe { std::slice::from_raw_parts(pattern.start_ptr(), pattern.len()) };
+        RegexBuilder {
+            pattern: bytes.to_vec(),
+            pattern_encoding: pattern.encoding(),
+            target_encoding: None,
+            options: RegexOptions::REGEX_OPTION_NONE,
+            syntax: Syntax::default(),
+            case_fold: None,
+            pattern_len_limit: None,
+            parse_depth_limit: None,
+            capture_num_limit: None,
+            capture_history: None,
+        }
+    }
+}
+
+impl<'syntax> RegexBuilder<'syntax> {
+    /// Set the Syntax
+    ///
+    /// The built `Regex` borrows `syntax` for its entire lifetime.
+    pub fn syntax<'s>(self, syntax: &'s Syntax) -> RegexBuilder<'s> {
+        RegexBuilder {
+            pattern: self.pattern,
+            pattern_encoding: self.pattern_encoding,
+            target_encoding: self.target_encoding,
+            options: self.options,
+            syntax,
+            case_fold: self.case_fold,
+            pattern_len_limit: self.pattern_len_limit,
+            parse_depth_limit: self.parse_depth_limit,
+            capture_num_limit: self.capture_num_limit,
+            capture_history: self.capture_history,
+        }
+    }
+
+    /// Replace the Options
+    ///
+    /// Replaces all the compile options, including those set by the
+    /// toggles such as `case_insensitive`.
+    pub fn options(mut self, options: RegexOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the Pattern Encoding
+    ///
+    /// The encoding the pattern bytes are written in. Defaults to the
+    /// encoding of the pattern the builder was created with.
+    pub fn pattern_encoding(mut self, encoding: Encoding) -> Self {
+        self.pattern_encoding = encoding;
+        self
+    }
+
+    /// Set the Target Encoding
+    ///
+    /// The encoding of the strings the regex will search. Defaults to
+    /// the pattern encoding. If the encodings differ the pattern is
+    /// transcoded, see [`Regex::with_target_encoding`].
+    pub fn target_encoding(mut self, encoding: Encoding) -> Self {
+        self.target_encoding = Some(encoding);
+        self
+    }
+
+    /// Set the Case Fold Flags
+    ///
+    /// Controls how characters are folded when the regex is case
+    /// insensitive. Defaults to Oniguruma's process-wide default.
+    pub fn case_fold(mut self, case_fold: CaseFold) -> Self {
+        self.case_fold = Some(case_fold);
+        self
+    }
+
+    /// Limit the Pattern Length
+    ///
+    /// `build` fails with `ErrorKind::PatternTooLong` if the pattern
+    /// is longer than `limit` bytes.
+    pub fn pattern_len_limit(mut self, limit: usize) -> Self {
+        self.pattern_len_limit = Some(limit);
+        self
+    }
+
+    /// Limit the Parse Depth
+    ///
+    /// The maximum nesting depth of the pattern. Overrides
+    /// [`limits::parse_depth_limit`] for this regex only.
+    pub fn parse_depth_limit(mut self, depth: u32) -> Self {
+        self.parse_depth_limit = Some(depth);
+        self
+    }
+
+    /// Limit the Number of Captures
+    ///
+    /// The maximum number of capture groups in the pattern. Overrides
+    /// [`limits::capture_num_limit`] for this regex only.
+    pub fn capture_num_limit(mut self, num: u32) -> Self {
+        self.capture_num_limit = Some(num);
+        self
+    }
+
+    /// Toggle `REGEX_OPTION_IGNORECASE`.
+    pub fn case_insensitive(self, yes: bool) -> Self {
+        self.toggle(RegexOptions::REGEX_OPTION_IGNORECASE, yes)
+    }
+
+    /// Toggle `REGEX_OPTION_EXTEND`, which ignores whitespace and
+    /// allows `#` comments in the pattern.
+    pub fn extended(self, yes: bool) -> Self {
+        self.toggle(RegexOptions::REGEX_OPTION_EXTEND, yes)
+    }
+
+    /// Toggle `REGEX_OPTION_MULTILINE`, which allows `.` to match
+    /// newlines.
+    pub fn multi_line(self, yes: bool) -> Self {
+        self.toggle(RegexOptions::REGEX_OPTION_MULTILINE, yes)
+    }
+
+    /// Toggle `REGEX_OPTION_FIND_LONGEST`.
+    pub fn find_longest(self, yes: bool) -> Self {
+        self.toggle(RegexOptions::REGEX_OPTION_FIND_LONGEST, yes)
+    }
+
+    /// Toggle `REGEX_OPTION_WORD_IS_ASCII`, which restricts `\w`, `\b`
+    /// and friends to ASCII.
+    pub fn ascii_word(self, yes: bool) -> Self {
+        self.toggle(RegexOptions::REGEX_OPTION_WORD_IS_ASCII, yes)
+    }
+
+    /// Choose whether plain groups capture
+    ///
+    /// `true` sets `REGEX_OPTION_CAPTURE_GROUP` and `false` sets
+    /// `REGEX_OPTION_DONT_CAPTURE_GROUP`.
+    pub fn capture_group(self, yes: bool) -> Self {
+        self.toggle(RegexOptions::REGEX_OPTION_DONT_CAPTURE_GROUP, !yes)
+            .toggle(RegexOptions::REGEX_OPTION_CAPTURE_GROUP, yes)
+    }
+
+    /// Toggle Capture History
+    ///
+    /// Enables or disables `(?@...)` groups, which record every
+    /// iteration of a repeated group in [`Captures::history`], without
+    /// editing the syntax by hand. If the syntax needs changing, the
+    /// regex is compiled with its own copy of it.
+    ///
+    /// [`Captures::history`]: crate::Captures::history
+    pub fn capture_history(mut self, yes: bool) -> Self {
+        self.capture_history = Some(yes);
+        self
+    }
+
+    fn toggle(mut self, option: RegexOptions, yes: bool) -> Self {
+        self.options.set(option, yes);
+        self
+    }
+
+    /// Compile the Regex
+    ///
+    /// Compiles the pattern with the current settings. If the regex
+    /// fails to compile the returned `Error` contains more information.
+    pub fn build(&self) -> Result<Regex<'syntax>, Error> {
+        if let Some(limit) = self.pattern_len_limit {
+            if self.pattern.len() > limit {
+                return Err(Error::with_kind(
+                    ErrorKind::PatternTooLong,
+                    format!(
+                        "pattern of {} bytes is longer than the limit of {} bytes",
+                        self.pattern.len(),
+                        limit
+                    ),
+                ));
+            }
+        }
+        let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+        if self.parse_depth_limit.is_none() && self.capture_num_limit.is_none() {
+            return self.compile_locked();
+        }
+        let parse_depth = self
+            .parse_depth_limit
+            .unwrap_or_else(limits::parse_depth_limit);
+        let captures = self
+            .capture_num_limit
+            .unwrap_or_else(limits::capture_num_limit);
+        limits::with_compile_limits(parse_depth, captures, || self.compile_locked())
+    }
+
+    fn compile_locked(&self) -> Result<Regex<'syntax>, Error> {
+        let compile = |syntax| {
+            Regex::compile_deluxe_locked(
+                &self.pattern,
+                self.pattern_encoding,
+                self.target_encoding.unwrap_or(self.pattern_encoding),
+                self.options,
+                self.case_fold,
+                syntax,
+            )
+        };
+        match self
+            .capture_history
+            .and_then(|yes| self.syntax.with_capture_history(yes))
+        {
+            Some(toggled) => Regex::compile_owning_syntax(toggled, compile),
+            None => compile(self.syntax),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EncodedBytes;
+
+    #[test]
+    fn test_builder_toggles() {
+        let r = RegexBuilder::new("a.c # comment")
+            .case_insensitive(true)
+            .extended(true)
+            .multi_line(true)
+            .build()
+            .unwrap();
+        assert!(r.is_match("A\nC"));
+
+        let r = RegexBuilder::new("a.c")
+            .multi_line(true)
+            .multi_line(false)
+            .build()
+            .unwrap();
+        assert!(!r.is_match("a\nc"));
+
+        let r = RegexBuilder::new(r"\w+").ascii_word(true).build().unwrap();
+        assert_eq!(r.find("héllo"), Some((0, 1)));
+
+        let r = RegexBuilder::new("a|bb")
+            .find_longest(true)
+            .build()
+            .unwrap();
+        assert_eq!(r.find("abb"), Some((1, 3)));
+    }
+
+    #[test]
+    fn test_builder_capture_group() {
+        let r = RegexBuilder::new("(a)(?<n>b)")
+            .syntax(Syntax::ruby())
+            .capture_group(true)
+            .build()
+            .unwrap();
+        assert_eq!(r.captures_len(), 2);
+
+        let r = RegexBuilder::new("(a)(b)")
+            .capture_group(false)
+            .build()
+            .unwrap();
+        assert_eq!(r.captures_len(), 0);
+    }
+
+    #[test]
+    fn test_builder_encoding() {
+        let r = RegexBuilder::with_encoding(EncodedBytes::ascii(b"h.llo"))
+            .build()
+            .unwrap();
+        assert_eq!(r.encoding(), Encoding::ASCII);
+
+        let r = RegexBuilder::new("abc")
+            .pattern_encoding(Encoding::ISO_8859_1)
+            .build()
+            .unwrap();
+        assert_eq!(r.encoding(), Encoding::ISO_8859_1);
+    }
+
+    #[test]
+    fn test_builder_limits() {
+        let err = RegexBuilder::new("abcde")
+            .pattern_len_limit(4)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PatternTooLong);
+
+        let err = RegexBuilder::new("(a)(b)(c)")
+            .capture_num_limit(2)
+            .build()
+            .unwrap_err();
+        assert!(err.is_limit_exceeded());
+
+        let deep = format!("{}a{}", "(".repeat(20), ")".repeat(20));
+        let builder = RegexBuilder::new(&deep).parse_depth_limit(10);
+        assert!(builder.build().unwrap_err().is_limit_exceeded());
+        assert!(builder.parse_depth_limit(100).build().is_ok());
+        // The limits only apply to the builder.
+        assert!(Regex::new(&"(a)".repeat(10)).is_ok());
+    }
+}
@@ -0,0 +1,702 @@
+//! Byte Buffer Regex API
+//!
+//! This module contains a [`Regex`] type which mirrors the API of
+//! [`crate::Regex`] but searches byte buffers rather than `str`
+//! slices. This is useful for text which isn't UTF-8, such as logs
+//! in Latin-1 or Shift_JIS, or raw bytes.
+//!
+//! Haystacks are anything implementing [`Haystack`]. Plain `&[u8]`
+//! slices are searched as if they were in the encoding of the regex,
+//! while [`EncodedBytes`] buffers carry their own encoding, which must
+//! match the encoding the regex was compiled with. Matched text is
+//! returned as `&[u8]` slices of the haystack.
+//!
+//! # Example
+//!
+//! ```rust
+//! use onig::bytes::Regex;
+//! use onig::EncodedBytes;
+//!
+//! let re = Regex::new(EncodedBytes::ascii(br"(\w+)=(\d+)")).unwrap();
+//! let pairs = re
+//!     .captures_iter(b"a=1 \xff b=22")
+//!     .map(|caps| (caps.at(1).unwrap(), caps.at(2).unwrap()))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(pairs, vec![(&b"a"[..], &b"1"[..]), (&b"b"[..], &b"22"[..])]);
+//! ```
+
+use std::borrow::Cow;
+
+use super::{
+    EncodedBytes, EncodedChars, Encoding, Error, RegexOptions, Region, SearchOptions, Syntax,
+};
+
+/// Byte Haystack
+///
+/// Represents a buffer of bytes which can be searched with a
+/// [`Regex`].
+pub trait Haystack<'t> {
+    /// The contents of the buffer
+    fn as_bytes(&self) -> &'t [u8];
+
+    /// The encoding of the contents of the buffer
+    ///
+    /// `None` if the buffer has no encoding of its own, in which case
+    /// it is searched as if it was in the encoding of the regex.
+    fn encoding(&self) -> Option<Encoding> {
+        None
+    }
+}
+
+impl<'t> Haystack<'t> for &'t [u8] {
+    fn as_bytes(&self) -> &'t [u8] {
+        self
+    }
+}
+
+impl<'t, const N: usize> Haystack<'t> for &'t [u8; N] {
+    fn as_bytes(&self) -> &'t [u8] {
+        &self[..]
+    }
+}
+
+impl<'t> Haystack<'t> for &'t Vec<u8> {
+    fn as_bytes(&self) -> &'t [u8] {
+        &self[..]
+    }
+}
+
+impl<'t> Haystack<'t> for EncodedBytes<'t> {
+    fn as_bytes(&self) -> &'t [u8] {
+        EncodedBytes::as_bytes(self)
+    }
+
+    fn encoding(&self) -> Option<Encoding> {
+        Some(EncodedChars::encoding(self))
+    }
+}
+
+/// A compiled regular expression for searching byte buffers.
+///
+/// This wraps a [`crate::Regex`] and exposes the same find, captures,
+/// split, replace and scan operations over [`Haystack`]s. The search
+/// methods panic if given an [`EncodedBytes`] haystack whose encoding
+/// differs from the encoding of the regex, in the same way the `str`
+/// based API panics on a search error. The `try_*` forms return an
+/// `Error` instead.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Regex<'syntax> {
+    regex: crate::Regex<'syntax>,
+}
+
+impl<'syntax> Regex<'syntax> {
+    /// Create a Regex
+    ///
+    /// Compiles `pattern` with the default options using the ruby
+    /// syntax. The encoding of the regex is taken from the `pattern`
+    /// buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regex pattern to compile
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::bytes::Regex;
+    /// use onig::EncodedBytes;
+    /// let r = Regex::new(EncodedBytes::ascii(b"hello (\\w+)"));
+    /// assert!(r.is_ok());
+    /// ```
+    pub fn new(pattern: EncodedBytes<'_>) -> Result<Regex<'static>, Error> {
+        crate::Regex::with_encoding(pattern).map(Regex::from)
+    }
+
+    /// Create a Regex, Specifying Options and Syntax
+    ///
+    /// # Arguments
+    ///
+    ///  * `pattern` - The regex pattern to compile.
+    ///  * `options` - The regex compilation options.
+    ///  * `syntax`  - The syntax which the regex is written in.
+    pub fn with_options<'s>(
+        pattern: EncodedBytes<'_>,
+        options: RegexOptions,
+        syntax: &'s Syntax,
+    ) -> Result<Regex<'s>, Error> {
+        crate::Regex::with_options_and_encoding(pattern, options, syntax).map(Regex::from)
+    }
+
+    /// Get the wrapped `str` based regex.
+    pub fn as_regex(&self) -> &crate::Regex<'syntax> {
+        &self.regex
+    }
+
+    /// Unwrap the `str` based regex.
+    pub fn into_regex(self) -> crate::Regex<'syntax> {
+        self.regex
+    }
+
+    /// Get the Encoding of the Regex
+    pub fn encoding(&self) -> Encoding {
+        self.regex.encoding()
+    }
+
+    /// Returns true if and only if the regex matches the whole of
+    /// `text`.
+    pub fn is_match<'t, H>(&self, text: H) -> bool
+    where
+        H: Haystack<'t>,
+    {
+        let text = self.haystack(text);
+        self.regex
+            .match_with_encoding(text, 0, SearchOptions::SEARCH_OPTION_WHOLE_STRING, None)
+            .map(|r| r == text.as_bytes().len())
+            .unwrap_or(false)
+    }
+
+    /// Returns the start and end byte offsets of the first match of
+    /// the regex in `text`, or `None` if there is no match.
+    pub fn find<'t, H>(&self, text: H) -> Option<(usize, usize)>
+    where
+        H: Haystack<'t>,
+    {
+        self.regex.find_with_encoding(self.haystack(text))
+    }
+
+    /// Returns the start and end byte offsets of the first match of
+    /// the regex in `text`, like `find`, but returns an `Error` if the
+    /// search fails rather than panicking.
+    pub fn try_find<'t, H>(&self, text: H) -> Result<Option<(usize, usize)>, Error>
+    where
+        H: Haystack<'t>,
+    {
+        let text = self.encoded(text);
+        let mut region = Region::new();
+        let r = self.try_search(text, &mut region)?;
+        Ok(r.and_then(|_| region.pos(0)))
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first
+    /// match in `text`. Capture group `0` always corresponds to the
+    /// entire match. If no match is found, then `None` is returned.
+    pub fn captures<'r, 't, H>(&'r self, text: H) -> Option<Captures<'r, 't>>
+    where
+        H: Haystack<'t>,
+    {
+        let text = self.haystack(text);
+        let mut region = Region::new();
+        self.regex
+            .search_with_encoding(
+                text,
+                0,
+                text.as_bytes().len(),
+                SearchOptions::SEARCH_OPTION_NONE,
+                Some(&mut region),
+            )
+            .map(|pos| Captures::new(&self.regex, text.as_bytes(), region, pos))
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first
+    /// match in `text`, like `captures`, but returns an `Error` if the
+    /// search fails rather than panicking.
+    pub fn try_captures<'r, 't, H>(&'r self, text: H) -> Result<Option<Captures<'r, 't>>, Error>
+    where
+        H: Haystack<'t>,
+    {
+        let text = self.encoded(text);
+        let mut region = Region::new();
+        let r = self.try_search(text, &mut region)?;
+        Ok(r.map(|pos| Captures::new(&self.regex, text.as_bytes(), region, pos)))
+    }
+
+    /// Returns an iterator for each successive non-overlapping match
+    /// in `text`, returning the start and end byte indices with
+    /// respect to `text`.
+    pub fn find_iter<'r, 't, H>(&'r self, text: H) -> FindMatches<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        FindMatches::new(&self.regex, self.haystack(text))
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups
+    /// matched in `text`.
+    pub fn captures_iter<'r, 't, H>(&'r self, text: H) -> FindCaptures<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        FindCaptures::new(&self.regex, self.haystack(text))
+    }
+
+    /// Returns an iterator of slices of `text` delimited by a match
+    /// of the regular expression.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::bytes::Regex;
+    /// # use onig::EncodedBytes;
+    /// let re = Regex::new(EncodedBytes::ascii(b"[ \t]+")).unwrap();
+    /// let fields: Vec<&[u8]> = re.split(b"a b \t  c\td").collect();
+    /// assert_eq!(fields, vec![&b"a"[..], b"b", b"c", b"d"]);
+    /// ```
+    pub fn split<'r, 't, H>(&'r self, text: H) -> RegexSplits<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        RegexSplits::new(self.find_iter(text))
+    }
+
+    /// Returns an iterator of at most `limit` slices of `text`
+    /// delimited by a match of the regular expression. (A `limit` of
+    /// `0` will return no slices.) The remainder of the buffer that is
+    /// not split will be the last element in the iterator.
+    pub fn splitn<'r, 't, H>(&'r self, text: H, limit: usize) -> RegexSplitsN<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        RegexSplitsN::new(self.split(text), limit)
+    }
+
+    /// Returns an iterator for each successive non-overlapping match
+    /// in `text`, like `find_iter`, but yielding an `Error` if a search
+    /// fails rather than panicking.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::bytes::Regex;
+    /// # use onig::EncodedBytes;
+    /// let re = Regex::new(EncodedBytes::ascii(b"\\d+")).unwrap();
+    /// let found: Result<Vec<_>, _> = re.try_find_iter(b"a1 \xff b22").collect();
+    /// assert_eq!(found.unwrap(), vec![(1, 2), (6, 8)]);
+    /// ```
+    pub fn try_find_iter<'r, 't, H>(&'r self, text: H) -> TryFindMatches<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        crate::TryFindMatches(FindMatches::new(&self.regex, self.encoded(text)))
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups
+    /// matched in `text`, like `captures_iter`, but yielding an `Error`
+    /// if a search fails rather than panicking.
+    pub fn try_captures_iter<'r, 't, H>(&'r self, text: H) -> TryFindCaptures<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        crate::TryFindCaptures(FindCaptures::new(&self.regex, self.encoded(text)))
+    }
+
+    /// Returns an iterator of slices of `text` delimited by a match of
+    /// the regular expression, like `split`, but yielding an `Error` if
+    /// a search fails rather than panicking.
+    pub fn try_split<'r, 't, H>(&'r self, text: H) -> TryRegexSplits<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        crate::TryRegexSplits(RegexSplits::new(FindMatches::new(
+            &self.regex,
+            self.encoded(text),
+        )))
+    }
+
+    /// Returns an iterator of at most `limit` slices of `text`
+    /// delimited by a match of the regular expression, like `splitn`,
+    /// but yielding an `Error` if a search fails rather than panicking.
+    pub fn try_splitn<'r, 't, H>(
+        &'r self,
+        text: H,
+        limit: usize,
+    ) -> TryRegexSplitsN<'r, 'syntax, 't>
+    where
+        H: Haystack<'t>,
+    {
+        let splits = RegexSplits::new(FindMatches::new(&self.regex, self.encoded(text)));
+        crate::TryRegexSplitsN(RegexSplitsN::new(splits, limit))
+    }
+
+    /// Replaces the leftmost-first match with the replacement
+    /// provided. If no match is found, then a copy of the buffer is
+    /// returned unchanged.
+    pub fn replace<'t, H, R>(&self, text: H, rep: R) -> Vec<u8>
+    where
+        H: Haystack<'t>,
+        R: Replacer,
+    {
+        self.replacen(text, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the
+    /// replacement provided. This is the same as calling `replacen`
+    /// with `limit` set to `0`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::bytes::{Captures, Regex};
+    /// # use onig::EncodedBytes;
+    /// let re = Regex::new(EncodedBytes::ascii(b"\\d+")).unwrap();
+    /// let doubled = re.replace_all(b"1 and 23", |caps: &Captures| {
+    ///     caps.at(0).unwrap().repeat(2)
+    /// });
+    /// assert_eq!(doubled, b"11 and 2323");
+    /// ```
+    pub fn replace_all<'t, H, R>(&self, text: H, rep: R) -> Vec<u8>
+    where
+        H: Haystack<'t>,
+        R: Replacer,
+    {
+        self.replacen(text, 0, rep)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches in `text` with
+    /// the replacement provided. If `limit` is 0, then all
+    /// non-overlapping matches are replaced.
+    pub fn replacen<'t, H, R>(&self, text: H, limit: usize, mut rep: R) -> Vec<u8>
+    where
+        H: Haystack<'t>,
+        R: Replacer,
+    {
+        let bytes = text.as_bytes();
+        let mut new = Vec::with_capacity(bytes.len());
+        let mut last_match = 0;
+        for (i, cap) in self.captures_iter(text).enumerate() {
+            if limit > 0 && i >= limit {
+                break;
+            }
+            // unwrap on 0 is OK because captures only reports matches
+            let (s, e) = cap.pos(0).unwrap();
+            new.extend_from_slice(&bytes[last_match..s]);
+            new.extend_from_slice(&rep.reg_replace(&cap));
+            last_match = e;
+        }
+        new.extend_from_slice(&bytes[last_match..]);
+        new
+    }
+
+    /// Replaces the leftmost-first match with the replacement
+    /// provided, like `replace`, but returning an `Error` if the search
+    /// fails rather than panicking.
+    pub fn try_replace<'t, H, R>(&self, text: H, rep: R) -> Result<Vec<u8>, Error>
+    where
+        H: Haystack<'t>,
+        R: Replacer,
+    {
+        self.try_replacen(text, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the
+    /// replacement provided, like `replace_all`, but returning an
+    /// `Error` if a search fails rather than panicking.
+    pub fn try_replace_all<'t, H, R>(&self, text: H, rep: R) -> Result<Vec<u8>, Error>
+    where
+        H: Haystack<'t>,
+        R: Replacer,
+    {
+        self.try_replacen(text, 0, rep)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches in `text` with
+    /// the replacement provided, like `replacen`, but returning an
+    /// `Error` if a search fails rather than panicking.
+    pub fn try_replacen<'t, H, R>(
+        &self,
+        text: H,
+        limit: usize,
+        mut rep: R,
+    ) -> Result<Vec<u8>, Error>
+    where
+        H: Haystack<'t>,
+        R: Replacer,
+    {
+        let bytes = text.as_bytes();
+        let mut new = Vec::with_capacity(bytes.len());
+        let mut last_match = 0;
+        for (i, cap) in self.try_captures_iter(text).enumerate() {
+            if limit > 0 && i >= limit {
+                break;
+            }
+            let cap = cap?;
+            // unwrap on 0 is OK because captures only reports matches
+            let (s, e) = cap.pos(0).unwrap();
+            new.extend_from_slice(&bytes[last_match..s]);
+            new.extend_from_slice(&rep.reg_replace(&cap));
+            last_match = e;
+        }
+        new.extend_from_slice(&bytes[last_match..]);
+        Ok(new)
+    }
+
+    /// Scan a Pattern and Observe Captures
+    ///
+    /// The scan function takes a haystack `text` and invokes the
+    /// given `callback` for each capture of this expression.
+    pub fn scan<'r, 't, H, CB>(&'r self, text: H, callback: CB)
+    where
+        H: Haystack<'t>,
+        CB: Fn(i32, Captures<'r, 't>) -> bool,
+    {
+        let text = self.haystack(text);
+        let mut region = Region::new();
+        self.regex.scan_with_encoding(
+            text,
+            &mut region,
+            SearchOptions::SEARCH_OPTION_NONE,
+            |n, s, region| {
+                let captures =
+                    Captures::new(&self.regex, text.as_bytes(), region.clone(), s as usize);
+                callback(n, captures)
+            },
+        );
+    }
+
+    /// Search the whole of `text` for the first match, filling in
+    /// `region`.
+    fn try_search(
+        &self,
+        text: EncodedBytes<'_>,
+        region: &mut Region,
+    ) -> Result<Option<usize>, Error> {
+        self.regex.search_with_param(
+            text,
+            0,
+            text.as_bytes().len(),
+            SearchOptions::SEARCH_OPTION_NONE,
+            Some(region),
+            self.regex.default_match_param(),
+        )
+    }
+
+    /// Attach the regex's encoding to `text`, checking that it agrees
+    /// with the haystack's own encoding if it has one.
+    fn haystack<'t, H>(&self, text: H) -> EncodedBytes<'t>
+    where
+        H: Haystack<'t>,
+    {
+        let enc = self.encoding();
+        match text.encoding() {
+            Some(text_enc) if text_enc != enc => panic!(
+                "Onig: Regex search error: Regex encoding does not match haystack encoding ({:?}, {:?})",
+                text_enc, enc
+            ),
+            _ => EncodedBytes::from_parts(text.as_bytes(), enc),
+        }
+    }
+
+    /// Attach the regex's encoding to `text` unless it has one of its
+    /// own, leaving any mismatch to be reported by the search.
+    fn encoded<'t, H>(&self, text: H) -> EncodedBytes<'t>
+    where
+        H: Haystack<'t>,
+    {
+        let enc = text.encoding().unwrap_or_else(|| self.encoding());
+        EncodedBytes::from_parts(text.as_bytes(), enc)
+    }
+}
+
+impl<'syntax> From<crate::Regex<'syntax>> for Regex<'syntax> {
+    fn from(regex: crate::Regex<'syntax>) -> Regex<'syntax> {
+        Regex { regex }
+    }
+}
+
+/// Replacer describes types that can be used to replace matches in a
+/// byte buffer.
+///
+/// Implementations are provided for replacement using byte slices and
+/// `FnMut` callbacks.
+pub trait Replacer {
+    /// Returns a possibly owned buffer that is used to replace the
+    /// match corresponding to the `caps` capture group.
+    fn reg_replace(&mut self, caps: &Captures) -> Cow<'_, [u8]>;
+}
+
+/// Replacement using Literal Byte Slices
+impl Replacer for &[u8] {
+    fn reg_replace(&mut self, _: &Captures) -> Cow<'_, [u8]> {
+        (*self).into()
+    }
+}
+
+/// Replacement using Literal Byte Arrays
+impl<const N: usize> Replacer for &[u8; N] {
+    fn reg_replace(&mut self, _: &Captures) -> Cow<'_, [u8]> {
+        (&self[..]).into()
+    }
+}
+
+/// Replacement using `FnMut` Callbacks
+impl<F> Replacer for F
+where
+    F: FnMut(&Captures) -> Vec<u8>,
+{
+    fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, [u8]> {
+        (*self)(caps).into()
+    }
+}
+
+/// Captures represents a group of captured byte slices for a single
+/// match.
+///
+/// This is [`crate::Captures`] over a byte buffer; see there for the
+/// methods available.
+pub type Captures<'r, 't> = crate::Captures<'r, 't, [u8]>;
+
+/// An iterator over capture groups for a particular match of a
+/// regular expression.
+pub type SubCaptures<'t> = crate::SubCaptures<'t, [u8]>;
+
+/// An iterator over capture group positions for a particular match of
+/// a regular expression.
+pub type SubCapturesPos<'t> = crate::SubCapturesPos<'t, [u8]>;
+
+/// An iterator over the named capture groups for a particular match
+/// of a regular expression.
+pub type SubCapturesNamed<'r, 't> = crate::SubCapturesNamed<'r, 't, [u8]>;
+
+/// An iterator over all non-overlapping matches for a particular
+/// buffer.
+pub type FindMatches<'r, 'syntax, 't> = crate::FindMatches<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+/// An iterator that yields all non-overlapping capture groups matching
+/// a particular regular expression.
+pub type FindCaptures<'r, 'syntax, 't> = crate::FindCaptures<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+/// Yields all slices delimited by a regular expression match.
+pub type RegexSplits<'r, 'syntax, 't> = crate::RegexSplits<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+/// Yields at most `N` slices delimited by a regular expression match.
+pub type RegexSplitsN<'r, 'syntax, 't> = crate::RegexSplitsN<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+/// An iterator over all non-overlapping matches for a particular
+/// buffer, which reports search errors.
+pub type TryFindMatches<'r, 'syntax, 't> = crate::TryFindMatches<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+/// An iterator over all non-overlapping capture groups matching a
+/// particular regular expression, which reports search errors.
+pub type TryFindCaptures<'r, 'syntax, 't> =
+    crate::TryFindCaptures<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+/// Yields all slices delimited by a regular expression match,
+/// reporting search errors.
+pub type TryRegexSplits<'r, 'syntax, 't> = crate::TryRegexSplits<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+/// Yields at most `N` slices delimited by a regular expression match,
+/// reporting search errors.
+pub type TryRegexSplitsN<'r, 'syntax, 't> =
+    crate::TryRegexSplitsN<'r, 'syntax, 't, EncodedBytes<'t>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latin1(bytes: &[u8]) -> EncodedBytes<'_> {
+        EncodedBytes::from_parts(bytes, Encoding::ISO_8859_1)
+    }
+
+    fn sjis(bytes: &[u8]) -> EncodedBytes<'_> {
+        EncodedBytes::from_parts(bytes, Encoding::SJIS)
+    }
+
+    #[test]
+    fn test_bytes_find_invalid_utf8() {
+        let re = Regex::new(EncodedBytes::ascii(b"b+")).unwrap();
+        let text = b"\xffabb\xfe";
+        assert_eq!(re.find(text), Some((2, 4)));
+        assert_eq!(re.find_iter(text).collect::<Vec<_>>(), vec![(2, 4)]);
+        assert!(re.is_match(b"bbb"));
+        assert!(!re.is_match(text));
+    }
+
+    #[test]
+    fn test_bytes_latin1_case_insensitive() {
+        let re = Regex::with_options(
+            latin1(b"caf\xe9"),
+            RegexOptions::REGEX_OPTION_IGNORECASE,
+            Syntax::default(),
+        )
+        .unwrap();
+        let text = latin1(b"CAF\xc9 and caf\xe9");
+        let found = re
+            .captures_iter(text)
+            .map(|caps| caps.at(0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![&b"CAF\xc9"[..], b"caf\xe9"]);
+    }
+
+    #[test]
+    fn test_bytes_empty_matches_step_whole_chars() {
+        let re = Regex::new(sjis(b"")).unwrap();
+        // `\x82\xa0` is a single two byte character in Shift_JIS
+        let matches = re.find_iter(sjis(b"a\x82\xa0b")).collect::<Vec<_>>();
+        assert_eq!(matches, vec![(0, 0), (1, 1), (3, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn test_bytes_captures_by_name() {
+        let re = Regex::new(EncodedBytes::ascii(b"(?<key>\\w+)=(?<value>\\w*)")).unwrap();
+        let caps = re.captures(b"\xff key=val").unwrap();
+        assert_eq!(caps.offset(), 2);
+        assert_eq!(caps.name("key"), Some(&b"key"[..]));
+        assert_eq!(caps.pos_by_name("value"), Some((6, 9)));
+        assert_eq!(caps.name("missing"), None);
+        assert_eq!(
+            caps.iter().collect::<Vec<_>>(),
+            vec![Some(&b"key=val"[..]), Some(b"key"), Some(b"val")]
+        );
+        assert_eq!(
+            caps.iter_named().collect::<Vec<_>>(),
+            vec![("key", Some(&b"key"[..])), ("value", Some(b"val"))]
+        );
+    }
+
+    #[test]
+    fn test_bytes_split() {
+        let re = Regex::new(EncodedBytes::ascii(b",")).unwrap();
+        let text = b"a,\xff,c".to_vec();
+        let parts = re.split(&text).collect::<Vec<_>>();
+        assert_eq!(parts, vec![&b"a"[..], b"\xff", b"c"]);
+        let parts = re.splitn(&text, 2).collect::<Vec<_>>();
+        assert_eq!(parts, vec![&b"a"[..], b"\xff,c"]);
+    }
+
+    #[test]
+    fn test_bytes_replace() {
+        let re = Regex::new(EncodedBytes::ascii(b"\\d")).unwrap();
+        assert_eq!(re.replace(b"a1b2", b"#"), b"a#b2");
+        assert_eq!(re.replace_all(b"a1b2\xff", &b"#"[..]), b"a#b#\xff");
+        assert_eq!(
+            re.replacen(b"1234", 3, |caps: &Captures| caps.at(0).unwrap().repeat(2)),
+            b"1122334"
+        );
+    }
+
+    #[test]
+    fn test_bytes_scan() {
+        let re = Regex::new(EncodedBytes::ascii(b"\\d+")).unwrap();
+        let found = std::cell::RefCell::new(Vec::new());
+        re.scan(b"1 \xff 23", |n, caps| {
+            found.borrow_mut().push((n, caps.at(0).unwrap()));
+            true
+        });
+        assert_eq!(found.into_inner(), vec![(0, &b"1"[..]), (1, b"23")]);
+    }
+
+    #[test]
+    fn test_bytes_try_encoding_mismatch() {
+        let re = Regex::new(EncodedBytes::ascii(b"a")).unwrap();
+        let err = re.try_find(latin1(b"a")).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::EncodingMismatch);
+        assert!(re.try_find_iter(latin1(b"a")).all(|m| m.is_err()));
+        assert_eq!(re.try_find(b"ba").unwrap(), Some((1, 2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "encoding does not match")]
+    fn test_bytes_encoding_mismatch_panics() {
+        let re = Regex::new(EncodedBytes::ascii(b"a")).unwrap();
+        re.find(latin1(b"a"));
+    }
+}
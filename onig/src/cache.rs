@@ -0,0 +1,368 @@
+//! Compiled Regex Cache
+//!
+//! Compiling a regex takes a process-wide lock, so programs which
+//! compile the same patterns over and over on many threads spend
+//! their time waiting for each other. A `RegexCache` keeps recently
+//! used regexes so each pattern is compiled once and then shared.
+//!
+//! # Examples
+//!
+//! ```
+//! use onig::cache::RegexCache;
+//!
+//! let cache = RegexCache::new(16);
+//! let first = cache.get(r"\d+").unwrap();
+//! let second = cache.get(r"\d+").unwrap();
+//! assert!(std::sync::Arc::ptr_eq(&first, &second));
+//! assert_eq!(cache.stats().hits, 1);
+//! ```
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+
+use super::{
+    CaseFold, EncodedChars, Encoding, Error, Regex, RegexOptions, Syntax, REGEX_NEW_MUTEX,
+};
+
+/// The capacity of the cache used by `Regex::cached`.
+const GLOBAL_CAPACITY: usize = 256;
+
+static GLOBAL: Lazy<RegexCache<'static>> = Lazy::new(|| RegexCache::new(GLOBAL_CAPACITY));
+
+/// The Process-wide Cache
+///
+/// Returns the cache used by [`Regex::cached`], e.g. to check its
+/// statistics.
+pub fn global() -> &'static RegexCache<'static> {
+    &GLOBAL
+}
+
+/// Cache Statistics
+///
+/// A snapshot of the counters of a `RegexCache`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups which found a compiled regex.
+    pub hits: u64,
+    /// Lookups which had to compile the pattern.
+    pub misses: u64,
+    /// Regexes dropped from the cache to make room for others.
+    pub evictions: u64,
+    /// The number of regexes in the cache.
+    pub len: usize,
+}
+
+/// Everything which affects how a pattern compiles.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    pattern: Vec<u8>,
+    encoding: Encoding,
+    options: RegexOptions,
+    /// The global default when the regex was compiled.
+    case_fold: CaseFold,
+    /// Syntaxes are compared by identity, as regexes borrow them.
+    syntax: usize,
+}
+
+#[derive(Debug)]
+struct Entry<'syntax> {
+    regex: Arc<Regex<'syntax>>,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+struct Inner<'syntax> {
+    entries: HashMap<Key, Entry<'syntax>>,
+    /// Incremented on every lookup, to find the least recently used
+    /// entry.
+    clock: u64,
+}
+
+/// Bounded Cache of Compiled Regexes
+///
+/// Regexes are keyed by their pattern bytes, encoding, options,
+/// syntax and the global default case fold, and shared as
+/// `Arc<Regex>`. When the cache is full the least recently used regex
+/// is dropped from it. Patterns which fail to compile aren't cached.
+///
+/// The cache can be shared between threads. Its lock isn't held while
+/// a pattern compiles, so lookups of cached regexes don't wait for
+/// compilations on other threads.
+#[derive(Debug)]
+pub struct RegexCache<'syntax> {
+    inner: Mutex<Inner<'syntax>>,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<'syntax> RegexCache<'syntax> {
+    /// Create a Cache
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of regexes to keep. A cache
+    ///   with no capacity compiles every pattern.
+    pub fn new(capacity: usize) -> Self {
+        RegexCache {
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                clock: 0,
+            }),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Get a UTF-8 Regex
+    ///
+    /// Returns the cached regex for `pattern` with no options and the
+    /// default syntax, compiling it if needed.
+    pub fn get(&self, pattern: &str) -> Result<Arc<Regex<'syntax>>, Error> {
+        self.get_with_options_and_encoding(
+            pattern,
+            RegexOptions::REGEX_OPTION_NONE,
+            Syntax::default(),
+        )
+    }
+
+    /// Get a Regex, Specifying Options and Encoding
+    ///
+    /// Returns the cached regex for `pattern`, compiling it with
+    /// `Regex::with_options_and_encoding` if needed.
+    ///
+    /// # Arguments
+    ///
+    ///  * `pattern` - The regex pattern.
+    ///  * `options` - The regex compilation options.
+    ///  * `syntax`  - The syntax which the regex is written in.
+    pub fn get_with_options_and_encoding<T>(
+        &self,
+        pattern: T,
+        options: RegexOptions,
+        syntax: &'syntax Syntax,
+    ) -> Result<Arc<Regex<'syntax>>, Error>
+    where
+        T: EncodedChars,
+    {
+        let bytes = unsafe { std::slice::from_raw_parts(pattern.start_ptr(), pattern.len()) };
+        let key = Key {
+            pattern: bytes.to_vec(),
+            encoding: pattern.encoding(),
+            options,
+            case_fold: CaseFold::global_default(),
+            syntax: syntax as *const Syntax as usize,
+        };
+
+        if let Some(regex) = self.lookup(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(regex);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        // Compile with the key's case fold, in case the default changes
+        // in the meantime.
+        let regex = {
+            let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+            Regex::compile_deluxe_locked(
+                bytes,
+                key.encoding,
+                key.encoding,
+                options,
+                Some(key.case_fold),
+                syntax,
+            )?
+        };
+        let regex = Arc::new(regex);
+        Ok(self.insert(key, regex))
+    }
+
+    /// Find a regex and mark it as used.
+    fn lookup(&self, key: &Key) -> Option<Arc<Regex<'syntax>>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let now = inner.clock;
+        let entry = inner.entries.get_mut(key)?;
+        entry.last_used = now;
+        Some(entry.regex.clone())
+    }
+
+    /// Add a regex, making room for it if needed. If another thread
+    /// added the same regex first, that one is returned instead.
+    fn insert(&self, key: Key, regex: Arc<Regex<'syntax>>) -> Arc<Regex<'syntax>> {
+        if self.capacity == 0 {
+            return regex;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let now = inner.clock;
+        if let Some(entry) = inner.entries.get_mut(&key) {
+            entry.last_used = now;
+            return entry.regex.clone();
+        }
+        if inner.entries.len() >= self.capacity {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        inner.entries.insert(
+            key,
+            Entry {
+                regex: regex.clone(),
+                last_used: now,
+            },
+        );
+        regex
+    }
+
+    /// The maximum number of regexes the cache keeps.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of regexes in the cache.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Is the cache empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every regex from the cache. Regexes which are still in use
+    /// elsewhere stay alive, and the counters aren't reset.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().entries.clear();
+    }
+
+    /// Get the Statistics
+    ///
+    /// Returns a snapshot of the hit, miss and eviction counters, and
+    /// the number of regexes in the cache.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            len: self.len(),
+        }
+    }
+}
+
+impl Regex<'static> {
+    /// Get a Regex From the Process-wide Cache
+    ///
+    /// Returns a shared regex for `pattern`, compiled with no options
+    /// and the default syntax. The pattern is only compiled the first
+    /// time it's used, or after it has been evicted from the cache.
+    /// See [`cache::global`](crate::cache::global).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::Regex;
+    /// let r = Regex::cached(r"(\w+)@example\.com").unwrap();
+    /// assert!(r.is_match("user@example.com"));
+    /// ```
+    pub fn cached(pattern: &str) -> Result<Arc<Regex<'static>>, Error> {
+        GLOBAL.get(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EncodedBytes;
+
+    #[test]
+    fn test_cache_hits_and_misses() {
+        let cache = RegexCache::new(4);
+        let a = cache.get("a+").unwrap();
+        let b = cache.get("a+").unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(cache.get("a(").is_err());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                evictions: 0,
+                len: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let cache = RegexCache::new(8);
+        let plain = cache.get("a").unwrap();
+        let ignorecase = cache
+            .get_with_options_and_encoding(
+                "a",
+                RegexOptions::REGEX_OPTION_IGNORECASE,
+                Syntax::default(),
+            )
+            .unwrap();
+        let ruby = cache
+            .get_with_options_and_encoding("a", RegexOptions::REGEX_OPTION_NONE, Syntax::ruby())
+            .unwrap();
+        let ascii = cache
+            .get_with_options_and_encoding(
+                EncodedBytes::ascii(b"a"),
+                RegexOptions::REGEX_OPTION_NONE,
+                Syntax::default(),
+            )
+            .unwrap();
+        assert!(!Arc::ptr_eq(&plain, &ignorecase));
+        assert!(!Arc::ptr_eq(&plain, &ruby));
+        assert!(!Arc::ptr_eq(&plain, &ascii));
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = RegexCache::new(2);
+        let a = cache.get("a").unwrap();
+        cache.get("b").unwrap();
+        cache.get("a").unwrap();
+        cache.get("c").unwrap();
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.len(), 2);
+        // "b" was evicted, "a" was kept.
+        assert!(Arc::ptr_eq(&a, &cache.get("a").unwrap()));
+        let misses = cache.stats().misses;
+        cache.get("b").unwrap();
+        assert_eq!(cache.stats().misses, misses + 1);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_cache_without_capacity() {
+        let cache = RegexCache::new(0);
+        let a = cache.get("a").unwrap();
+        assert!(!Arc::ptr_eq(&a, &cache.get("a").unwrap()));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_global_cache() {
+        let a = Regex::cached("global cache test").unwrap();
+        let b = Regex::cached("global cache test").unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(global().stats().hits >= 1);
+    }
+}
@@ -0,0 +1,900 @@
+//! Callouts
+//!
+//! Oniguruma can call back into user code while a search or match is
+//! running. Callouts appear in a pattern either as contents,
+//! `(?{...})`, or by name, `(*NAME)`. Contents callouts are handled by
+//! closures attached to a [`MatchParam`](crate::MatchParam) with
+//! `set_progress_callout` and `set_retraction_callout`. Named callouts
+//! are registered once for the whole process with [`NamedCallout`].
+//!
+//! Each callout is handed a [`CalloutArgs`] describing the state of the
+//! match and returns a [`CalloutResult`] which decides whether the
+//! match continues, backtracks or is aborted.
+//!
+//! If a callout panics the search is aborted and the panic is resumed
+//! once the search returns.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_uint, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+use std::sync::{Arc, Mutex};
+use std::{mem, slice, str};
+
+use once_cell::sync::Lazy;
+use onig_sys::{OnigCalloutArgs, OnigType, OnigValue};
+
+use super::{Encoding, Error, Region, REGEX_NEW_MUTEX};
+
+/// The result of a callout, which decides how matching continues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutResult {
+    /// Continue matching
+    Success,
+    /// Fail at this point and backtrack
+    Fail,
+    /// Abort the whole search. The search returns an error.
+    Abort,
+}
+
+impl CalloutResult {
+    fn as_raw(self) -> c_int {
+        match self {
+            CalloutResult::Success => onig_sys::OnigCalloutResult_ONIG_CALLOUT_SUCCESS as c_int,
+            CalloutResult::Fail => onig_sys::OnigCalloutResult_ONIG_CALLOUT_FAIL as c_int,
+            CalloutResult::Abort => onig_sys::ONIG_ABORT,
+        }
+    }
+}
+
+/// When a callout is being invoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutIn {
+    /// The matcher is moving forward through the callout
+    Progress,
+    /// The matcher is backtracking through the callout
+    Retraction,
+}
+
+/// The type of an argument to a named callout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutArgType {
+    /// An integer, e.g. `(*NAME{10})`
+    Long,
+    /// A single character, e.g. `(*NAME{X})`
+    Char,
+    /// A string, e.g. `(*NAME{abc})`
+    String,
+    /// The tag of another callout, e.g. `(*NAME{tag})`
+    Tag,
+}
+
+impl CalloutArgType {
+    fn as_raw(self) -> OnigType {
+        match self {
+            CalloutArgType::Long => onig_sys::OnigType_ONIG_TYPE_LONG,
+            CalloutArgType::Char => onig_sys::OnigType_ONIG_TYPE_CHAR,
+            CalloutArgType::String => onig_sys::OnigType_ONIG_TYPE_STRING,
+            CalloutArgType::Tag => onig_sys::OnigType_ONIG_TYPE_TAG,
+        }
+    }
+}
+
+/// A callout argument or data value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutValue<'a> {
+    /// An integer
+    Long(i64),
+    /// A character code point
+    Char(u32),
+    /// A string, in the encoding of the regex
+    String(&'a [u8]),
+    /// The callout number of a tagged callout
+    Tag(i32),
+}
+
+impl<'a> CalloutValue<'a> {
+    /// The type of this value.
+    pub fn arg_type(&self) -> CalloutArgType {
+        match self {
+            CalloutValue::Long(_) => CalloutArgType::Long,
+            CalloutValue::Char(_) => CalloutArgType::Char,
+            CalloutValue::String(_) => CalloutArgType::String,
+            CalloutValue::Tag(_) => CalloutArgType::Tag,
+        }
+    }
+
+    /// Convert from a raw type and value. `None` if the value is unset
+    /// or of a type which can't be represented.
+    #[allow(clippy::unnecessary_cast)] // `c_long` is 32 bits on Windows
+    unsafe fn from_raw(ty: OnigType, val: &OnigValue) -> Option<CalloutValue<'a>> {
+        match ty {
+            onig_sys::OnigType_ONIG_TYPE_LONG => Some(CalloutValue::Long(val.l as i64)),
+            onig_sys::OnigType_ONIG_TYPE_CHAR => Some(CalloutValue::Char(val.c)),
+            onig_sys::OnigType_ONIG_TYPE_STRING => Some(CalloutValue::String(
+                slice::from_raw_parts(val.s.start, val.s.end as usize - val.s.start as usize),
+            )),
+            onig_sys::OnigType_ONIG_TYPE_TAG => Some(CalloutValue::Tag(val.tag)),
+            _ => None,
+        }
+    }
+
+    fn to_raw(self) -> OnigValue {
+        match self {
+            CalloutValue::Long(l) => OnigValue { l: l as _ },
+            CalloutValue::Char(c) => OnigValue { c },
+            CalloutValue::String(s) => OnigValue {
+                s: onig_sys::OnigValue__bindgen_ty_1 {
+                    start: s.as_ptr() as *mut _,
+                    end: s[s.len()..].as_ptr() as *mut _,
+                },
+            },
+            CalloutValue::Tag(tag) => OnigValue { tag },
+        }
+    }
+}
+
+/// The state of a match as seen by a callout.
+///
+/// Positions are byte offsets into the string being searched.
+pub struct CalloutArgs<'a> {
+    raw: *mut OnigCalloutArgs,
+    _marker: PhantomData<&'a OnigCalloutArgs>,
+}
+
+impl<'a> CalloutArgs<'a> {
+    unsafe fn from_raw(raw: *mut OnigCalloutArgs) -> CalloutArgs<'a> {
+        CalloutArgs {
+            raw,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The number of the callout within the regex. Callouts are
+    /// numbered from 1 in order of appearance.
+    pub fn callout_num(&self) -> i32 {
+        unsafe { onig_sys::onig_get_callout_num_by_callout_args(self.raw) }
+    }
+
+    /// Whether the matcher is progressing or retracting.
+    pub fn callout_in(&self) -> CalloutIn {
+        let callout_in = unsafe { onig_sys::onig_get_callout_in_by_callout_args(self.raw) };
+        if callout_in == onig_sys::OnigCalloutIn_ONIG_CALLOUT_IN_RETRACTION {
+            CalloutIn::Retraction
+        } else {
+            CalloutIn::Progress
+        }
+    }
+
+    /// The name of a named callout. `None` for contents callouts.
+    pub fn name(&self) -> Option<&'a str> {
+        let id = unsafe { onig_sys::onig_get_name_id_by_callout_args(self.raw) };
+        if id < 0 {
+            return None;
+        }
+        let name = unsafe { onig_sys::onig_get_callout_name_by_name_id(id) };
+        if name.is_null() {
+            return None;
+        }
+        unsafe { CStr::from_ptr(name as *const _) }.to_str().ok()
+    }
+
+    /// The contents of a contents callout, `(?{contents})`. `None` for
+    /// named callouts.
+    pub fn contents(&self) -> Option<&'a [u8]> {
+        unsafe {
+            let start = onig_sys::onig_get_contents_by_callout_args(self.raw);
+            let end = onig_sys::onig_get_contents_end_by_callout_args(self.raw);
+            if start.is_null() {
+                None
+            } else {
+                Some(slice::from_raw_parts(start, end as usize - start as usize))
+            }
+        }
+    }
+
+    /// The tag of the callout, e.g. `tag` in `(*NAME[tag])` or
+    /// `(?{...}[tag])`. `None` if the callout isn't tagged.
+    pub fn tag(&self) -> Option<&'a str> {
+        let num = self.callout_num();
+        unsafe {
+            let reg = onig_sys::onig_get_regex_by_callout_args(self.raw);
+            // `onig_callout_tag_is_exist_at_callout_num` is off by one in
+            // some Oniguruma releases, so check the tag bounds instead.
+            let start = onig_sys::onig_get_callout_tag_start(reg, num);
+            let end = onig_sys::onig_get_callout_tag_end(reg, num);
+            if start.is_null() || end.is_null() {
+                return None;
+            }
+            str::from_utf8(slice::from_raw_parts(start, end as usize - start as usize)).ok()
+        }
+    }
+
+    /// The number of arguments the named callout accepts.
+    pub fn args_len(&self) -> usize {
+        unsafe { onig_sys::onig_get_args_num_by_callout_args(self.raw).max(0) as usize }
+    }
+
+    /// The number of arguments written in the pattern for this call.
+    pub fn passed_args_len(&self) -> usize {
+        unsafe { onig_sys::onig_get_passed_args_num_by_callout_args(self.raw).max(0) as usize }
+    }
+
+    /// The argument at `index`, with any omitted optional arguments
+    /// filled in with their defaults.
+    pub fn arg(&self, index: usize) -> Option<CalloutValue<'a>> {
+        let index = c_int::try_from(index).ok()?;
+        let mut ty: OnigType = onig_sys::OnigType_ONIG_TYPE_VOID;
+        let mut val: OnigValue = unsafe { mem::zeroed() };
+        let r =
+            unsafe { onig_sys::onig_get_arg_by_callout_args(self.raw, index, &mut ty, &mut val) };
+        if r != onig_sys::ONIG_NORMAL as c_int {
+            return None;
+        }
+        unsafe { CalloutValue::from_raw(ty, &val) }
+    }
+
+    /// The whole string being searched.
+    pub fn string(&self) -> &'a [u8] {
+        unsafe {
+            let start = onig_sys::onig_get_string_by_callout_args(self.raw);
+            let end = onig_sys::onig_get_string_end_by_callout_args(self.raw);
+            slice::from_raw_parts(start, end as usize - start as usize)
+        }
+    }
+
+    /// The position the current match attempt started at.
+    pub fn start(&self) -> usize {
+        self.offset(unsafe { onig_sys::onig_get_start_by_callout_args(self.raw) })
+    }
+
+    /// The end of the search range.
+    pub fn right_range(&self) -> usize {
+        self.offset(unsafe { onig_sys::onig_get_right_range_by_callout_args(self.raw) })
+    }
+
+    /// The current matching position.
+    pub fn current(&self) -> usize {
+        self.offset(unsafe { onig_sys::onig_get_current_by_callout_args(self.raw) })
+    }
+
+    /// The number of retries performed so far in this match attempt.
+    pub fn retry_counter(&self) -> u64 {
+        unsafe { onig_sys::onig_get_retry_counter_by_callout_args(self.raw) as u64 }
+    }
+
+    /// The start and end of capture group `group` at this point in the
+    /// match. `None` if the group hasn't matched (yet). Groups are
+    /// numbered from 1; the whole match isn't available in a callout.
+    pub fn capture(&self, group: usize) -> Option<(usize, usize)> {
+        let group = c_int::try_from(group).ok()?;
+        let (mut beg, mut end) = (0, 0);
+        let r = unsafe {
+            onig_sys::onig_get_capture_range_in_callout(self.raw, group, &mut beg, &mut end)
+        };
+        if r != onig_sys::ONIG_NORMAL as c_int || beg == onig_sys::ONIG_REGION_NOTPOS {
+            None
+        } else {
+            Some((beg as usize, end as usize))
+        }
+    }
+
+    /// The value in data slot `slot` of this callout. `None` if the
+    /// slot has not been set during this search.
+    pub fn data(&self, slot: usize) -> Option<CalloutValue<'a>> {
+        self.get_data(slot, |ty, val, slot| unsafe {
+            onig_sys::onig_get_callout_data_by_callout_args_self(self.raw, slot, ty, val)
+        })
+    }
+
+    /// The value in data slot `slot` of the callout tagged `tag`. This
+    /// can be used to read the state of builtin callouts such as
+    /// `(*COUNT[tag])`.
+    pub fn data_by_tag(&self, tag: &str, slot: usize) -> Option<CalloutValue<'a>> {
+        let num = unsafe {
+            let reg = onig_sys::onig_get_regex_by_callout_args(self.raw);
+            onig_sys::onig_get_callout_num_by_tag(reg, tag.as_ptr(), tag[tag.len()..].as_ptr())
+        };
+        if num <= 0 {
+            return None;
+        }
+        self.get_data(slot, |ty, val, slot| unsafe {
+            onig_sys::onig_get_callout_data_by_callout_args(self.raw, num, slot, ty, val)
+        })
+    }
+
+    /// Store `value` in data slot `slot` of this callout. Data is
+    /// cleared at the start of each search.
+    pub fn set_data(&self, slot: usize, value: i64) -> Result<(), Error> {
+        let slot = c_int::try_from(slot)
+            .map_err(|e| Error::custom("Invalid callout data slot").with_source(e))?;
+        let mut val = CalloutValue::Long(value).to_raw();
+        let r = unsafe {
+            onig_sys::onig_set_callout_data_by_callout_args_self(
+                self.raw,
+                slot,
+                onig_sys::OnigType_ONIG_TYPE_LONG,
+                &mut val,
+            )
+        };
+        if r == onig_sys::ONIG_NORMAL as c_int {
+            Ok(())
+        } else {
+            Err(Error::from_code(r))
+        }
+    }
+
+    fn get_data<F>(&self, slot: usize, get: F) -> Option<CalloutValue<'a>>
+    where
+        F: FnOnce(*mut OnigType, *mut OnigValue, c_int) -> c_int,
+    {
+        let slot = c_int::try_from(slot).ok()?;
+        let mut ty: OnigType = onig_sys::OnigType_ONIG_TYPE_VOID;
+        let mut val: OnigValue = unsafe { mem::zeroed() };
+        if get(&mut ty, &mut val, slot) != onig_sys::ONIG_NORMAL as c_int {
+            return None;
+        }
+        unsafe { CalloutValue::from_raw(ty, &val) }
+    }
+
+    fn offset(&self, ptr: *const onig_sys::OnigUChar) -> usize {
+        let start = unsafe { onig_sys::onig_get_string_by_callout_args(self.raw) };
+        ptr as usize - start as usize
+    }
+}
+
+type CalloutFn = Box<dyn FnMut(&CalloutArgs) -> CalloutResult>;
+
+/// The callouts attached to a `MatchParam`. A pointer to this is the
+/// callout user data of the match param.
+#[derive(Default)]
+pub(crate) struct CalloutHandlers {
+    pub(crate) progress: Option<CalloutFn>,
+    pub(crate) retraction: Option<CalloutFn>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl CalloutHandlers {
+    /// Take the payload of any panic raised by a callout during the
+    /// last search.
+    pub(crate) fn take_panic(&mut self) -> Option<Box<dyn Any + Send>> {
+        self.panic.take()
+    }
+
+    fn call<F>(&mut self, callout: F) -> c_int
+    where
+        F: FnOnce(&mut CalloutHandlers) -> CalloutResult,
+    {
+        if self.panic.is_some() {
+            return onig_sys::ONIG_ABORT;
+        }
+        match catch_unwind(AssertUnwindSafe(|| callout(self))) {
+            Ok(result) => result.as_raw(),
+            Err(panic) => {
+                self.panic = Some(panic);
+                onig_sys::ONIG_ABORT
+            }
+        }
+    }
+}
+
+pub(crate) unsafe extern "C" fn progress_trampoline(
+    args: *mut OnigCalloutArgs,
+    user_data: *mut c_void,
+) -> c_int {
+    let handlers = &mut *(user_data as *mut CalloutHandlers);
+    handlers.call(|handlers| match handlers.progress.as_mut() {
+        Some(callout) => callout(&CalloutArgs::from_raw(args)),
+        None => CalloutResult::Success,
+    })
+}
+
+pub(crate) unsafe extern "C" fn retraction_trampoline(
+    args: *mut OnigCalloutArgs,
+    user_data: *mut c_void,
+) -> c_int {
+    let handlers = &mut *(user_data as *mut CalloutHandlers);
+    handlers.call(|handlers| match handlers.retraction.as_mut() {
+        Some(callout) => callout(&CalloutArgs::from_raw(args)),
+        None => CalloutResult::Success,
+    })
+}
+
+type NamedCalloutFn = Arc<dyn Fn(&CalloutArgs) -> CalloutResult + Send + Sync>;
+
+/// Registered named callouts, keyed by callout name id.
+static NAMED_CALLOUTS: Lazy<Mutex<HashMap<c_int, NamedCalloutFn>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+unsafe extern "C" fn named_trampoline(args: *mut OnigCalloutArgs, user_data: *mut c_void) -> c_int {
+    let id = onig_sys::onig_get_name_id_by_callout_args(args);
+    let callout = NAMED_CALLOUTS.lock().unwrap().get(&id).cloned();
+    let callout = match callout {
+        Some(callout) => callout,
+        None => return CalloutResult::Success.as_raw(),
+    };
+    if user_data.is_null() {
+        // Nowhere to stash a panic for this search, so don't let one
+        // unwind into Oniguruma.
+        return match catch_unwind(AssertUnwindSafe(|| callout(&CalloutArgs::from_raw(args)))) {
+            Ok(result) => result.as_raw(),
+            Err(_) => std::process::abort(),
+        };
+    }
+    let handlers = &mut *(user_data as *mut CalloutHandlers);
+    handlers.call(|_| callout(&CalloutArgs::from_raw(args)))
+}
+
+/// Named Callout Definition
+///
+/// Describes a callout which can be invoked from patterns as
+/// `(*NAME)`, `(*NAME[tag])` or `(*NAME{args})`. Once registered the
+/// callout is available to every regex compiled afterwards with the
+/// same encoding. Registering a name again replaces its callout.
+///
+/// # Example
+///
+/// ```rust
+/// use onig::{CalloutArgType, CalloutResult, CalloutValue, NamedCallout, Regex};
+///
+/// // Only allow a match to continue at an even position.
+/// NamedCallout::new("EVEN")
+///     .register(|args| {
+///         if args.current() % 2 == 0 {
+///             CalloutResult::Success
+///         } else {
+///             CalloutResult::Fail
+///         }
+///     })
+///     .unwrap();
+///
+/// let re = Regex::new("a+(*EVEN)").unwrap();
+/// assert_eq!(re.find("aaa"), Some((0, 2)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct NamedCallout {
+    name: String,
+    encoding: Encoding,
+    in_progress: bool,
+    in_retraction: bool,
+    arg_types: Vec<CalloutArgType>,
+    defaults: Vec<CalloutValue<'static>>,
+    required_after_optional: bool,
+}
+
+impl NamedCallout {
+    /// Start defining a callout called `name` for UTF-8 regexes. By
+    /// default the callout takes no arguments and is invoked only on
+    /// progress.
+    pub fn new(name: &str) -> NamedCallout {
+        NamedCallout {
+            name: name.to_owned(),
+            encoding: Encoding::UTF8,
+            in_progress: true,
+            in_retraction: false,
+            arg_types: Vec::new(),
+            defaults: Vec::new(),
+            required_after_optional: false,
+        }
+    }
+
+    /// Register the callout for regexes with the given encoding rather
+    /// than UTF-8.
+    pub fn encoding(mut self, encoding: Encoding) -> NamedCallout {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Whether the callout is invoked as the matcher progresses.
+    pub fn in_progress(mut self, yes: bool) -> NamedCallout {
+        self.in_progress = yes;
+        self
+    }
+
+    /// Whether the callout is invoked as the matcher backtracks.
+    pub fn in_retraction(mut self, yes: bool) -> NamedCallout {
+        self.in_retraction = yes;
+        self
+    }
+
+    /// Add a required argument of the given type.
+    pub fn arg(mut self, arg_type: CalloutArgType) -> NamedCallout {
+        self.required_after_optional |= !self.defaults.is_empty();
+        self.arg_types.push(arg_type);
+        self
+    }
+
+    /// Add an optional argument which takes the value `default` when
+    /// omitted. Optional arguments must follow all required ones.
+    pub fn optional_arg(mut self, default: CalloutValue<'static>) -> NamedCallout {
+        self.arg_types.push(default.arg_type());
+        self.defaults.push(default);
+        self
+    }
+
+    /// Register `callout` under this definition's name.
+    pub fn register<F>(self, callout: F) -> Result<(), Error>
+    where
+        F: Fn(&CalloutArgs) -> CalloutResult + Send + Sync + 'static,
+    {
+        if self.required_after_optional {
+            return Err(Error::custom(
+                "Required callout arguments must precede optional ones",
+            ));
+        }
+        let mut callout_in = 0;
+        if self.in_progress {
+            callout_in |= onig_sys::OnigCalloutIn_ONIG_CALLOUT_IN_PROGRESS;
+        }
+        if self.in_retraction {
+            callout_in |= onig_sys::OnigCalloutIn_ONIG_CALLOUT_IN_RETRACTION;
+        }
+        let mut arg_types = self
+            .arg_types
+            .iter()
+            .map(|ty| ty.as_raw() as c_uint)
+            .collect::<Vec<_>>();
+        let mut defaults = self
+            .defaults
+            .iter()
+            .map(|value| value.to_raw())
+            .collect::<Vec<_>>();
+        let name = self.name.as_bytes();
+
+        let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+        let id = unsafe {
+            onig_sys::onig_set_callout_of_name(
+                self.encoding.as_raw(),
+                onig_sys::OnigCalloutType_ONIG_CALLOUT_TYPE_SINGLE,
+                name.as_ptr() as *mut _,
+                name[name.len()..].as_ptr() as *mut _,
+                callout_in as c_int,
+                Some(named_trampoline),
+                None,
+                arg_types.len() as c_int,
+                if arg_types.is_empty() {
+                    null_mut()
+                } else {
+                    arg_types.as_mut_ptr()
+                },
+                defaults.len() as c_int,
+                if defaults.is_empty() {
+                    null_mut()
+                } else {
+                    defaults.as_mut_ptr()
+                },
+            )
+        };
+        if id < 0 {
+            return Err(Error::from_code(id));
+        }
+        NAMED_CALLOUTS.lock().unwrap().insert(id, Arc::new(callout));
+        Ok(())
+    }
+}
+
+type EachMatchFn = Arc<dyn Fn(&EachMatch) -> bool + Send + Sync>;
+
+/// The callback set with `set_each_match_callback`.
+static EACH_MATCH: Lazy<Mutex<Option<EachMatchFn>>> = Lazy::new(|| Mutex::new(None));
+
+thread_local! {
+    /// The payload of a panic raised by the each-match callback on
+    /// this thread, waiting to be resumed once the search returns.
+    static EACH_MATCH_PANIC: std::cell::RefCell<Option<Box<dyn Any + Send>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+unsafe extern "C" fn each_match_trampoline(
+    string: *const onig_sys::OnigUChar,
+    end: *const onig_sys::OnigUChar,
+    match_start: *const onig_sys::OnigUChar,
+    region: *mut onig_sys::OnigRegion,
+    _user_data: *mut c_void,
+) -> c_int {
+    let callback = EACH_MATCH.lock().unwrap().clone();
+    let callback = match callback {
+        Some(callback) => callback,
+        None => return onig_sys::ONIG_NORMAL as c_int,
+    };
+    let each_match = EachMatch {
+        string: slice::from_raw_parts(string, end as usize - string as usize),
+        start: match_start as usize - string as usize,
+        region: (region as *const Region).as_ref(),
+    };
+    match catch_unwind(AssertUnwindSafe(|| callback(&each_match))) {
+        Ok(true) => onig_sys::ONIG_NORMAL as c_int,
+        Ok(false) => onig_sys::ONIG_ABORT,
+        Err(panic) => {
+            EACH_MATCH_PANIC.with(|stash| *stash.borrow_mut() = Some(panic));
+            onig_sys::ONIG_ABORT
+        }
+    }
+}
+
+/// Resume any panic raised by the each-match callback during the last
+/// search on this thread.
+pub(crate) fn resume_each_match_panic() {
+    if let Some(payload) = EACH_MATCH_PANIC.with(|stash| stash.borrow_mut().take()) {
+        std::panic::resume_unwind(payload);
+    }
+}
+
+/// A match passed to the each-match callback.
+#[derive(Debug)]
+pub struct EachMatch<'a> {
+    string: &'a [u8],
+    start: usize,
+    region: Option<&'a Region>,
+}
+
+impl<'a> EachMatch<'a> {
+    /// The string being searched.
+    pub fn string(&self) -> &'a [u8] {
+        self.string
+    }
+
+    /// The byte offset the match starts at.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The groups of the match, if the search was given a `Region`.
+    pub fn region(&self) -> Option<&'a Region> {
+        self.region
+    }
+}
+
+/// Set the Each-Match Callback
+///
+/// Searches made with `SEARCH_OPTION_CALLBACK_EACH_MATCH` call
+/// `callback` for every way the pattern matches at every position,
+/// rather than stopping at the first match, and then report no match.
+/// The callback returns `false` to abort the search, which then
+/// returns an error. The callback is shared by the whole process, and
+/// setting it again replaces it.
+///
+/// If the callback panics the search is aborted and the panic is
+/// resumed once the search returns.
+///
+/// # Example
+///
+/// ```rust
+/// use onig::{Regex, Region, SearchOptions};
+/// use std::sync::{Arc, Mutex};
+///
+/// let starts = Arc::new(Mutex::new(Vec::new()));
+/// let seen = starts.clone();
+/// onig::set_each_match_callback(move |m| {
+///     seen.lock().unwrap().push(m.start());
+///     true
+/// });
+/// let re = Regex::new("b").unwrap();
+/// let r = re.search_with_options("abcb", 0, 4,
+///     SearchOptions::SEARCH_OPTION_CALLBACK_EACH_MATCH, None);
+/// assert_eq!(r, None);
+/// assert_eq!(*starts.lock().unwrap(), [1, 3]);
+/// # onig::clear_each_match_callback();
+/// ```
+pub fn set_each_match_callback<F>(callback: F)
+where
+    F: Fn(&EachMatch) -> bool + Send + Sync + 'static,
+{
+    *EACH_MATCH.lock().unwrap() = Some(Arc::new(callback));
+    unsafe {
+        onig_sys::onig_set_callback_each_match(Some(each_match_trampoline));
+    }
+}
+
+/// Clear the Each-Match Callback
+///
+/// Searches made with `SEARCH_OPTION_CALLBACK_EACH_MATCH` go back to
+/// reporting their first match.
+pub fn clear_each_match_callback() {
+    *EACH_MATCH.lock().unwrap() = None;
+    unsafe {
+        onig_sys::onig_set_callback_each_match(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    fn search(re: &Regex, text: &str, param: MatchParam) -> Result<Option<usize>, Error> {
+        re.search_with_param(
+            text,
+            0,
+            text.len(),
+            SearchOptions::SEARCH_OPTION_NONE,
+            None,
+            param,
+        )
+    }
+
+    fn long(value: Option<CalloutValue>) -> Option<i64> {
+        match value {
+            Some(CalloutValue::Long(n)) => Some(n),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_progress_callout_sees_contents_and_position() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut param = MatchParam::default();
+        let log = seen.clone();
+        param.set_progress_callout(move |args| {
+            let contents = String::from_utf8(args.contents().unwrap().to_vec()).unwrap();
+            log.borrow_mut().push((
+                contents,
+                args.current(),
+                args.capture(1),
+                args.tag().map(str::to_owned),
+            ));
+            CalloutResult::Success
+        });
+        let re = Regex::new("(a+)(?{mark}[t])b").unwrap();
+        assert_eq!(search(&re, "xaab", param).unwrap(), Some(1));
+        assert_eq!(
+            *seen.borrow(),
+            vec![("mark".to_string(), 3, Some((1, 3)), Some("t".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_progress_callout_fail_backtracks() {
+        let mut param = MatchParam::default();
+        param.set_progress_callout(|args| {
+            assert_eq!(args.tag(), None);
+            if args.current() == 2 {
+                CalloutResult::Success
+            } else {
+                CalloutResult::Fail
+            }
+        });
+        let re = Regex::new("a+(?{x})").unwrap();
+        let mut region = Region::new();
+        re.search_with_param(
+            "aaa",
+            0,
+            3,
+            SearchOptions::SEARCH_OPTION_NONE,
+            Some(&mut region),
+            param,
+        )
+        .unwrap();
+        assert_eq!(region.pos(0), Some((0, 2)));
+    }
+
+    #[test]
+    fn test_retraction_callout() {
+        let count = Rc::new(RefCell::new(0));
+        let mut param = MatchParam::default();
+        let counter = count.clone();
+        param.set_retraction_callout(move |args| {
+            assert_eq!(args.callout_in(), CalloutIn::Retraction);
+            *counter.borrow_mut() += 1;
+            CalloutResult::Success
+        });
+        let re = Regex::new("a(?{x}<)c|ab").unwrap();
+        assert_eq!(search(&re, "ab", param).unwrap(), Some(0));
+        assert_eq!(*count.borrow(), 1);
+    }
+
+    #[test]
+    fn test_abort_is_an_error() {
+        let mut param = MatchParam::default();
+        param.set_progress_callout(|_| CalloutResult::Abort);
+        let re = Regex::new("a(?{x})").unwrap();
+        let err = search(&re, "a", param).unwrap_err();
+        assert_eq!(err.code(), onig_sys::ONIG_ABORT);
+    }
+
+    #[test]
+    #[should_panic(expected = "callout panicked")]
+    fn test_callout_panic_is_resumed() {
+        let mut param = MatchParam::default();
+        param.set_progress_callout(|_| panic!("callout panicked"));
+        let re = Regex::new("a(?{x})").unwrap();
+        let _ = search(&re, "a", param);
+    }
+
+    #[test]
+    fn test_callout_data_and_builtin_count() {
+        let counts = Rc::new(RefCell::new(Vec::new()));
+        let mut param = MatchParam::default();
+        let log = counts.clone();
+        param.set_progress_callout(move |args| {
+            let previous = long(args.data(0)).unwrap_or(0);
+            args.set_data(0, previous + 1).unwrap();
+            log.borrow_mut()
+                .push((long(args.data(0)), long(args.data_by_tag("c", 0))));
+            CalloutResult::Success
+        });
+        let re = Regex::new("(?:(*COUNT[c])a(?{x}))+").unwrap();
+        assert_eq!(search(&re, "aa", param).unwrap(), Some(0));
+        assert_eq!(
+            *counts.borrow(),
+            vec![(Some(1), Some(1)), (Some(2), Some(2)),]
+        );
+    }
+
+    #[test]
+    fn test_named_callout_with_args() {
+        NamedCallout::new("ATMOST")
+            .arg(CalloutArgType::Long)
+            .optional_arg(CalloutValue::String(b"unused"))
+            .register(|args| {
+                assert_eq!(args.name(), Some("ATMOST"));
+                assert_eq!(args.args_len(), 2);
+                assert_eq!(args.passed_args_len(), 1);
+                assert_eq!(args.arg(1), Some(CalloutValue::String(b"unused")));
+                match args.arg(0) {
+                    Some(CalloutValue::Long(max)) if args.current() as i64 > max => {
+                        CalloutResult::Fail
+                    }
+                    _ => CalloutResult::Success,
+                }
+            })
+            .unwrap();
+        let re = Regex::new("a+(*ATMOST{2})").unwrap();
+        assert_eq!(re.find("aaaa"), Some((0, 2)));
+    }
+
+    #[test]
+    fn test_named_callout_rejects_bad_definitions() {
+        assert!(NamedCallout::new("BAD ARGS")
+            .register(|_| CalloutResult::Success)
+            .is_err());
+        assert!(NamedCallout::new("ORDER")
+            .optional_arg(CalloutValue::Long(1))
+            .arg(CalloutArgType::Long)
+            .register(|_| CalloutResult::Success)
+            .is_err());
+    }
+
+    #[test]
+    fn test_each_match_callback() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        set_each_match_callback(move |m| {
+            assert_eq!(m.string(), b"xaa ya");
+            let end = m.region().and_then(|region| region.pos(0)).map(|pos| pos.1);
+            log.lock().unwrap().push((m.start(), end));
+            true
+        });
+        let re = Regex::new("a+").unwrap();
+        let each_match = SearchOptions::SEARCH_OPTION_CALLBACK_EACH_MATCH;
+        let mut region = Region::new();
+        let r = re.search_with_options("xaa ya", 0, 6, each_match, Some(&mut region));
+        assert_eq!(r, None);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [(1, Some(3)), (1, Some(2)), (2, Some(3)), (5, Some(6))]
+        );
+
+        set_each_match_callback(|m| m.start() < 2);
+        let err = re
+            .search_with_param("xaa ya", 0, 6, each_match, None, MatchParam::default())
+            .unwrap_err();
+        assert_eq!(err.code(), onig_sys::ONIG_ABORT);
+
+        set_each_match_callback(|_| panic!("each match panicked"));
+        let panic =
+            std::panic::catch_unwind(|| re.search_with_options("a", 0, 1, each_match, None));
+        assert!(panic.is_err());
+
+        clear_each_match_callback();
+        assert_eq!(
+            re.search_with_options("xaa ya", 0, 6, each_match, None),
+            Some(1)
+        );
+    }
+}
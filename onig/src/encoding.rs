@@ -0,0 +1,515 @@
+//! Character Encodings
+//!
+//! This module contains a safe handle to the character encodings
+//! which Oniguruma supports. Patterns and haystacks each carry an
+//! `Encoding`, and a regex can only search text in the encoding it
+//! was compiled with.
+
+use std::ffi::CStr;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Character Encoding
+///
+/// A handle to one of the encodings built into Oniguruma. Encodings
+/// are compared by identity, so two handles are equal if they refer
+/// to the same Oniguruma encoding.
+///
+/// # Examples
+///
+/// ```
+/// use onig::Encoding;
+/// let enc = Encoding::from_name("shift_jis").unwrap();
+/// assert_eq!(enc, Encoding::SJIS);
+/// assert_eq!(enc.name(), "Shift_JIS");
+/// ```
+#[derive(Clone, Copy)]
+pub struct Encoding {
+    raw: onig_sys::OnigEncoding,
+}
+
+// Encodings are statics within Oniguruma which are never modified
+// through an `Encoding`, so they can be shared between threads.
+unsafe impl Send for Encoding {}
+unsafe impl Sync for Encoding {}
+
+macro_rules! encodings {
+    ($($(#[$doc:meta])* $name:ident => $raw:ident,)*) => {
+        impl Encoding {
+            $(
+                $(#[$doc])*
+                pub const $name: Encoding = Encoding {
+                    raw: &raw mut onig_sys::$raw,
+                };
+            )*
+        }
+
+        /// Every encoding Oniguruma ships with.
+        const ALL_ENCODINGS: &[Encoding] = &[$(Encoding::$name),*];
+    };
+}
+
+encodings! {
+    /// US-ASCII
+    ASCII => OnigEncodingASCII,
+    /// ISO-8859-1 (Latin-1)
+    ISO_8859_1 => OnigEncodingISO_8859_1,
+    /// ISO-8859-2 (Latin-2)
+    ISO_8859_2 => OnigEncodingISO_8859_2,
+    /// ISO-8859-3 (Latin-3)
+    ISO_8859_3 => OnigEncodingISO_8859_3,
+    /// ISO-8859-4 (Latin-4)
+    ISO_8859_4 => OnigEncodingISO_8859_4,
+    /// ISO-8859-5 (Latin/Cyrillic)
+    ISO_8859_5 => OnigEncodingISO_8859_5,
+    /// ISO-8859-6 (Latin/Arabic)
+    ISO_8859_6 => OnigEncodingISO_8859_6,
+    /// ISO-8859-7 (Latin/Greek)
+    ISO_8859_7 => OnigEncodingISO_8859_7,
+    /// ISO-8859-8 (Latin/Hebrew)
+    ISO_8859_8 => OnigEncodingISO_8859_8,
+    /// ISO-8859-9 (Latin-5)
+    ISO_8859_9 => OnigEncodingISO_8859_9,
+    /// ISO-8859-10 (Latin-6)
+    ISO_8859_10 => OnigEncodingISO_8859_10,
+    /// ISO-8859-11 (Latin/Thai)
+    ISO_8859_11 => OnigEncodingISO_8859_11,
+    /// ISO-8859-13 (Latin-7)
+    ISO_8859_13 => OnigEncodingISO_8859_13,
+    /// ISO-8859-14 (Latin-8)
+    ISO_8859_14 => OnigEncodingISO_8859_14,
+    /// ISO-8859-15 (Latin-9)
+    ISO_8859_15 => OnigEncodingISO_8859_15,
+    /// ISO-8859-16 (Latin-10)
+    ISO_8859_16 => OnigEncodingISO_8859_16,
+    /// UTF-8
+    UTF8 => OnigEncodingUTF8,
+    /// UTF-16, big endian
+    UTF16_BE => OnigEncodingUTF16_BE,
+    /// UTF-16, little endian
+    UTF16_LE => OnigEncodingUTF16_LE,
+    /// UTF-32, big endian
+    UTF32_BE => OnigEncodingUTF32_BE,
+    /// UTF-32, little endian
+    UTF32_LE => OnigEncodingUTF32_LE,
+    /// EUC-JP
+    EUC_JP => OnigEncodingEUC_JP,
+    /// EUC-TW
+    EUC_TW => OnigEncodingEUC_TW,
+    /// EUC-KR
+    EUC_KR => OnigEncodingEUC_KR,
+    /// EUC-CN
+    EUC_CN => OnigEncodingEUC_CN,
+    /// Shift_JIS
+    SJIS => OnigEncodingSJIS,
+    /// KOI8-R
+    KOI8_R => OnigEncodingKOI8_R,
+    /// Windows-1251
+    CP1251 => OnigEncodingCP1251,
+    /// Big5
+    BIG5 => OnigEncodingBIG5,
+    /// GB 18030
+    GB18030 => OnigEncodingGB18030,
+}
+
+impl Encoding {
+    /// UTF-16 in the byte order of the target platform
+    #[cfg(target_endian = "little")]
+    pub const UTF16: Encoding = Encoding::UTF16_LE;
+    /// UTF-16 in the byte order of the target platform
+    #[cfg(target_endian = "big")]
+    pub const UTF16: Encoding = Encoding::UTF16_BE;
+    /// UTF-32 in the byte order of the target platform
+    #[cfg(target_endian = "little")]
+    pub const UTF32: Encoding = Encoding::UTF32_LE;
+    /// UTF-32 in the byte order of the target platform
+    #[cfg(target_endian = "big")]
+    pub const UTF32: Encoding = Encoding::UTF32_BE;
+}
+
+impl Encoding {
+    /// Find an Encoding by Name
+    ///
+    /// Names are compared ignoring case, `-` and `_`, so `"utf-8"`,
+    /// `"UTF8"` and `"Utf_8"` all find `Encoding::UTF8`. The
+    /// aliases `ASCII` and `SJIS` are also accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the encoding to find.
+    ///
+    /// # Returns
+    ///
+    /// The encoding, or `None` if Oniguruma has no encoding with this
+    /// name.
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        let wanted = normalize(name);
+        match wanted.as_str() {
+            "ascii" => return Some(Encoding::ASCII),
+            "sjis" => return Some(Encoding::SJIS),
+            _ => {}
+        }
+        ALL_ENCODINGS
+            .iter()
+            .find(|enc| normalize(enc.name()) == wanted)
+            .copied()
+    }
+
+    /// Encoding from a Raw Pointer
+    ///
+    /// Wrap an `OnigEncoding` obtained from `onig_sys`.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must point to a valid Oniguruma encoding which lives for
+    /// the rest of the program, such as one of the encodings built
+    /// into Oniguruma.
+    pub unsafe fn from_raw(raw: onig_sys::OnigEncoding) -> Encoding {
+        Encoding { raw }
+    }
+
+    /// The Raw Encoding Pointer
+    ///
+    /// Get the `OnigEncoding` for passing to `onig_sys` functions.
+    pub fn as_raw(&self) -> onig_sys::OnigEncoding {
+        self.raw
+    }
+
+    /// The Name of the Encoding
+    ///
+    /// Returns the name Oniguruma uses for this encoding, e.g.
+    /// `"UTF-8"` or `"Shift_JIS"`.
+    pub fn name(&self) -> &'static str {
+        unsafe {
+            CStr::from_ptr((*self.raw).name as *const _)
+                .to_str()
+                .unwrap_or("")
+        }
+    }
+}
+
+impl Encoding {
+    /// Count the Characters in a Buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Text in this encoding.
+    ///
+    /// # Returns
+    ///
+    /// The number of characters in `bytes`. A truncated character at
+    /// the end of the buffer is counted as one character.
+    pub fn char_count(&self, bytes: &[u8]) -> usize {
+        let mut count = 0;
+        let mut pos = 0;
+        while pos < bytes.len() {
+            pos += self.char_len(bytes, pos);
+            count += 1;
+        }
+        count
+    }
+
+    /// Character Index of a Byte Offset
+    ///
+    /// Maps a byte offset, such as one returned in a `Region`, to the
+    /// number of characters before it.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Text in this encoding.
+    /// * `pos` - Byte offset into `bytes`.
+    ///
+    /// # Panics
+    ///
+    /// If `pos` is greater than the length of `bytes`.
+    pub fn char_index(&self, bytes: &[u8], pos: usize) -> usize {
+        self.char_count(&bytes[..pos])
+    }
+
+    /// Validate a Buffer
+    ///
+    /// Returns `true` if `bytes` is a sequence of complete, valid
+    /// characters in this encoding.
+    pub fn is_valid(&self, bytes: &[u8]) -> bool {
+        let start = bytes.as_ptr();
+        unsafe {
+            onig_sys::onigenc_is_valid_mbc_string(self.raw, start, start.add(bytes.len())) != 0
+        }
+    }
+
+    /// Start of the Previous Character
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Text in this encoding.
+    /// * `pos` - Byte offset into `bytes`.
+    ///
+    /// # Returns
+    ///
+    /// The byte offset of the character before `pos`, or `None` if
+    /// `pos` is at the start of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// If `pos` is greater than the length of `bytes`.
+    pub fn prev_char_head(&self, bytes: &[u8], pos: usize) -> Option<usize> {
+        self.step_back(bytes, pos, 1)
+    }
+
+    /// Step Back a Number of Characters
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Text in this encoding.
+    /// * `pos` - Byte offset into `bytes` to step back from.
+    /// * `n` - The number of characters to step back.
+    ///
+    /// # Returns
+    ///
+    /// The byte offset `n` characters before `pos`, or `None` if
+    /// there are fewer than `n` characters before `pos`.
+    ///
+    /// # Panics
+    ///
+    /// If `pos` is greater than the length of `bytes`.
+    pub fn step_back(&self, bytes: &[u8], pos: usize, n: usize) -> Option<usize> {
+        assert_pos(bytes, pos);
+        let mut pos = pos;
+        for _ in 0..n {
+            if pos == 0 {
+                return None;
+            }
+            pos = self.left_adjust_char_head(bytes, pos - 1);
+        }
+        Some(pos)
+    }
+
+    /// Snap a Byte Offset Back to a Character Boundary
+    ///
+    /// # Returns
+    ///
+    /// The byte offset of the start of the character containing
+    /// `pos`. Offsets which are already on a character boundary,
+    /// including the end of the buffer, are returned unchanged.
+    ///
+    /// # Panics
+    ///
+    /// If `pos` is greater than the length of `bytes`.
+    pub fn left_adjust_char_head(&self, bytes: &[u8], pos: usize) -> usize {
+        assert_pos(bytes, pos);
+        if pos == bytes.len() {
+            return pos;
+        }
+        // Oniguruma reads up to a whole character past the offset it
+        // adjusts, so only ask it about offsets where that stays inside
+        // the buffer and walk forward from there.
+        let safe = pos.min(bytes.len().saturating_sub(self.max_enc_len()));
+        let mut head = if safe == 0 {
+            0
+        } else {
+            let start = bytes.as_ptr();
+            let head = unsafe {
+                onig_sys::onigenc_get_left_adjust_char_head(self.raw, start, start.add(safe))
+            };
+            head as usize - start as usize
+        };
+        loop {
+            let next = head + self.char_len(bytes, head);
+            if next > pos {
+                return head;
+            }
+            head = next;
+        }
+    }
+
+    /// Snap a Byte Offset Forward to a Character Boundary
+    ///
+    /// # Returns
+    ///
+    /// The byte offset of the first character starting at or after
+    /// `pos`, or the length of the buffer if there is none.
+    ///
+    /// # Panics
+    ///
+    /// If `pos` is greater than the length of `bytes`.
+    pub fn right_adjust_char_head(&self, bytes: &[u8], pos: usize) -> usize {
+        let head = self.left_adjust_char_head(bytes, pos);
+        if head == pos {
+            pos
+        } else {
+            head + self.char_len(bytes, head)
+        }
+    }
+
+    /// The longest character in the encoding, in bytes.
+    pub(crate) fn max_enc_len(&self) -> usize {
+        unsafe { (*self.raw).max_enc_len }.max(1) as usize
+    }
+
+    /// The length of the character at `pos`, which must be inside
+    /// `bytes`. A truncated character is cut short at the end of the
+    /// buffer.
+    fn char_len(&self, bytes: &[u8], pos: usize) -> usize {
+        let rest = &bytes[pos..];
+        let mbc_enc_len = match unsafe { (*self.raw).mbc_enc_len } {
+            Some(mbc_enc_len) => mbc_enc_len,
+            None => return 1,
+        };
+        // Oniguruma reads as much of a character as it needs to find its
+        // length, so give it a padded copy of a short tail.
+        let len = if rest.len() >= self.max_enc_len() {
+            unsafe { mbc_enc_len(rest.as_ptr()) }
+        } else {
+            let mut tail = rest.to_vec();
+            tail.resize(self.max_enc_len(), 0);
+            unsafe { mbc_enc_len(tail.as_ptr()) }
+        };
+        usize::try_from(len).unwrap_or(1).clamp(1, rest.len())
+    }
+}
+
+/// Check a byte offset is within a buffer.
+fn assert_pos(bytes: &[u8], pos: usize) {
+    assert!(
+        pos <= bytes.len(),
+        "Onig: position {} is past the end of a buffer of length {}",
+        pos,
+        bytes.len()
+    );
+}
+
+/// Lowercase a name and strip the separators out of it.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::UTF8
+    }
+}
+
+impl PartialEq for Encoding {
+    fn eq(&self, other: &Encoding) -> bool {
+        std::ptr::eq(self.raw, other.raw)
+    }
+}
+
+impl Eq for Encoding {}
+
+impl Hash for Encoding {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.raw as usize).hash(state)
+    }
+}
+
+impl fmt::Debug for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Encoding").field(&self.name()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_names() {
+        assert_eq!(Encoding::UTF8.name(), "UTF-8");
+        assert_eq!(Encoding::ASCII.name(), "US-ASCII");
+        assert_eq!(Encoding::UTF16_LE.name(), "UTF-16LE");
+        assert_eq!(format!("{:?}", Encoding::SJIS), "Encoding(\"Shift_JIS\")");
+    }
+
+    #[test]
+    fn test_encoding_from_name() {
+        for enc in ALL_ENCODINGS {
+            assert_eq!(Encoding::from_name(enc.name()), Some(*enc));
+        }
+        assert_eq!(Encoding::from_name("utf8"), Some(Encoding::UTF8));
+        assert_eq!(Encoding::from_name("ascii"), Some(Encoding::ASCII));
+        assert_eq!(Encoding::from_name("EUC-JP"), Some(Encoding::EUC_JP));
+        assert_eq!(
+            Encoding::from_name("iso_8859_5"),
+            Some(Encoding::ISO_8859_5)
+        );
+        assert_eq!(Encoding::from_name("ebcdic"), None);
+    }
+
+    #[test]
+    fn test_encoding_eq() {
+        assert_eq!(Encoding::default(), Encoding::UTF8);
+        assert_ne!(Encoding::UTF16_BE, Encoding::UTF16_LE);
+    }
+
+    #[test]
+    fn test_sjis_char_helpers() {
+        // "aあいb" in Shift_JIS
+        let text = b"a\x82\xa0\x82\xa2b";
+        let sjis = Encoding::SJIS;
+        assert!(sjis.is_valid(text));
+        assert!(!sjis.is_valid(&text[..2]));
+        assert_eq!(sjis.char_count(text), 4);
+        assert_eq!(sjis.char_index(text, 3), 2);
+        assert_eq!(sjis.char_index(text, 6), 4);
+        assert_eq!(sjis.prev_char_head(text, 5), Some(3));
+        assert_eq!(sjis.prev_char_head(text, 0), None);
+        assert_eq!(sjis.step_back(text, 6, 3), Some(1));
+        assert_eq!(sjis.step_back(text, 6, 5), None);
+        assert_eq!(sjis.left_adjust_char_head(text, 2), 1);
+        assert_eq!(sjis.left_adjust_char_head(text, 6), 6);
+        assert_eq!(sjis.right_adjust_char_head(text, 2), 3);
+        assert_eq!(sjis.right_adjust_char_head(text, 3), 3);
+    }
+
+    #[test]
+    fn test_utf8_char_helpers() {
+        let text = "héllo".as_bytes();
+        assert_eq!(Encoding::UTF8.char_count(text), 5);
+        assert_eq!(Encoding::UTF8.left_adjust_char_head(text, 2), 1);
+        assert_eq!(Encoding::UTF8.right_adjust_char_head(text, 2), 3);
+        assert!(!Encoding::UTF8.is_valid(b"a\xc3"));
+    }
+
+    #[test]
+    fn test_odd_length_utf16_char_helpers() {
+        // "ab" and half of a code unit in UTF-16LE
+        let text = b"a\0b\0c";
+        let utf16 = Encoding::UTF16_LE;
+        assert_eq!(utf16.char_count(text), 3);
+        assert_eq!(utf16.left_adjust_char_head(text, 3), 2);
+        assert_eq!(utf16.left_adjust_char_head(text, 4), 4);
+        assert_eq!(utf16.right_adjust_char_head(text, 3), 4);
+        assert_eq!(utf16.prev_char_head(text, 5), Some(4));
+        assert_eq!(utf16.step_back(text, 5, 3), Some(0));
+
+        // "a" and a high surrogate cut off from its low one
+        let text = b"a\0\x3d\xd8\x00";
+        assert_eq!(utf16.char_count(text), 2);
+        assert_eq!(utf16.left_adjust_char_head(text, 4), 2);
+        assert_eq!(utf16.right_adjust_char_head(text, 3), 5);
+        assert_eq!(Encoding::UTF16_BE.char_count(b"\0a\xd8"), 2);
+        assert_eq!(Encoding::UTF32_LE.char_count(b"a\0\0\0b\0"), 2);
+    }
+
+    #[test]
+    fn test_truncated_gb18030_char_helpers() {
+        // "a" and the first two bytes of the four byte U+0080
+        let text = b"a\x81\x30";
+        let gb18030 = Encoding::GB18030;
+        assert_eq!(gb18030.char_count(text), 2);
+        assert_eq!(gb18030.left_adjust_char_head(text, 2), 1);
+        assert_eq!(gb18030.right_adjust_char_head(text, 2), 3);
+        assert_eq!(gb18030.step_back(text, 3, 1), Some(1));
+        assert_eq!(gb18030.char_count(b"a\x81\x30\x81\x30"), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "past the end")]
+    fn test_char_helpers_check_bounds() {
+        Encoding::UTF8.step_back(b"abc", 4, 1);
+    }
+}
@@ -0,0 +1,360 @@
+//! Errors
+//!
+//! This module contains the `Error` type returned when compiling or
+//! searching fails, and the `ErrorKind` used to tell the different
+//! failures apart.
+
+use std::ops::Range;
+use std::os::raw::c_int;
+use std::ptr::null;
+use std::{error, fmt, str};
+
+/// The Kind of an Error
+///
+/// Groups the many `ONIGERR_*` codes, and the errors raised by this
+/// crate itself, into kinds which callers can match on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The pattern is malformed, e.g. an unmatched parenthesis or a
+    /// repeat operator with nothing to repeat.
+    Syntax,
+    /// A number, range or character value in the pattern is out of
+    /// range or inconsistent.
+    InvalidValue,
+    /// A group, back-reference, name or property in the pattern is
+    /// invalid or undefined.
+    InvalidReference,
+    /// A callout in the pattern is malformed or isn't registered.
+    InvalidCallout,
+    /// The pattern nests deeper than the parse depth limit.
+    ParseDepthLimit,
+    /// The pattern has more capture groups than the capture limit.
+    TooManyCaptures,
+    /// The pattern is longer than the limit of its
+    /// [`Budget`](crate::Budget).
+    PatternTooLong,
+    /// A search exceeded the match stack limit.
+    MatchStackLimit,
+    /// A search exceeded the retry limit at one start position.
+    RetryLimitInMatch,
+    /// A search exceeded the retry limit over the whole search.
+    RetryLimitInSearch,
+    /// A search exceeded the subexpression call limit.
+    SubexpCallLimitInSearch,
+    /// A callout aborted the search.
+    Aborted,
+    /// Oniguruma ran out of memory.
+    Memory,
+    /// The haystack's encoding differs from the regex's encoding.
+    EncodingMismatch,
+    /// An argument, option or combination of them is invalid, e.g. an
+    /// offset past the end of the haystack.
+    InvalidArgument,
+    /// An internal error within Oniguruma.
+    Internal,
+    /// Reading the haystack from a stream failed. The underlying
+    /// `std::io::Error` is the error's source.
+    Io,
+    /// The pattern uses a construct which the target syntax of a
+    /// [`translate`](crate::translate) can't express.
+    Untranslatable,
+}
+
+impl ErrorKind {
+    /// The kind of error an Oniguruma error code represents.
+    fn from_code(code: c_int) -> ErrorKind {
+        use onig_sys::*;
+        match code {
+            ONIG_ABORT => ErrorKind::Aborted,
+            ONIGERR_MEMORY => ErrorKind::Memory,
+            ONIGERR_MATCH_STACK_LIMIT_OVER => ErrorKind::MatchStackLimit,
+            ONIGERR_PARSE_DEPTH_LIMIT_OVER => ErrorKind::ParseDepthLimit,
+            ONIGERR_RETRY_LIMIT_IN_MATCH_OVER => ErrorKind::RetryLimitInMatch,
+            ONIGERR_RETRY_LIMIT_IN_SEARCH_OVER => ErrorKind::RetryLimitInSearch,
+            ONIGERR_SUBEXP_CALL_LIMIT_IN_SEARCH_OVER => ErrorKind::SubexpCallLimitInSearch,
+            ONIGERR_TOO_MANY_CAPTURES => ErrorKind::TooManyCaptures,
+            ONIGERR_INVALID_ARGUMENT
+            | ONIGERR_NOT_SUPPORTED_ENCODING_COMBINATION
+            | ONIGERR_INVALID_COMBINATION_OF_OPTIONS => ErrorKind::InvalidArgument,
+            ONIGERR_TOO_BIG_BACKREF_NUMBER
+            | ONIGERR_INVALID_BACKREF
+            | ONIGERR_NUMBERED_BACKREF_OR_CALL_NOT_ALLOWED
+            | ONIGERR_TOO_LONG_PROPERTY_NAME => ErrorKind::InvalidReference,
+            ONIGERR_INVALID_IF_ELSE_SYNTAX
+            | ONIGERR_INVALID_ABSENT_GROUP_PATTERN
+            | ONIGERR_INVALID_ABSENT_GROUP_GENERATOR_PATTERN
+            | ONIGERR_VERY_INEFFICIENT_PATTERN => ErrorKind::Syntax,
+            ONIGERR_INVALID_CODE_POINT_VALUE | ONIGERR_TOO_BIG_WIDE_CHAR_VALUE => {
+                ErrorKind::InvalidValue
+            }
+            -199..=-100 => ErrorKind::Syntax,
+            -212..=-200 => ErrorKind::InvalidValue,
+            -223..=-213 => ErrorKind::InvalidReference,
+            -232..=-227 => ErrorKind::InvalidCallout,
+            _ => ErrorKind::Internal,
+        }
+    }
+
+    /// Is this Kind a Resource Limit?
+    ///
+    /// Returns `true` for the kinds raised when compiling or searching
+    /// hits a limit, rather than because the pattern or arguments
+    /// were invalid.
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::ParseDepthLimit
+                | ErrorKind::TooManyCaptures
+                | ErrorKind::PatternTooLong
+                | ErrorKind::MatchStackLimit
+                | ErrorKind::RetryLimitInMatch
+                | ErrorKind::RetryLimitInSearch
+                | ErrorKind::SubexpCallLimitInSearch
+        )
+    }
+}
+
+/// This struture represents an error from the underlying Oniguruma libray.
+pub struct Error {
+    kind: ErrorKind,
+    code: Option<c_int>,
+    description: String,
+    span: Option<Range<usize>>,
+    source: Option<Box<dyn error::Error + Send + Sync>>,
+}
+
+impl Error {
+    /// Build an error from the error info filled in by `onig_new`.
+    /// `pattern` is the pattern buffer being compiled, which the info
+    /// may point into.
+    pub(crate) fn from_code_and_info(
+        code: c_int,
+        info: &onig_sys::OnigErrorInfo,
+        pattern: &[u8],
+    ) -> Self {
+        let mut error = Error::new(code, info);
+        error.span = pattern_span(info, pattern);
+        error
+    }
+
+    pub(crate) fn from_code(code: c_int) -> Self {
+        Error::new(code, null())
+    }
+
+    pub(crate) fn custom<T: Into<String>>(message: T) -> Self {
+        Error::with_kind(ErrorKind::InvalidArgument, message)
+    }
+
+    pub(crate) fn with_kind<T: Into<String>>(kind: ErrorKind, message: T) -> Self {
+        Error {
+            kind,
+            code: None,
+            description: message.into(),
+            span: None,
+            source: None,
+        }
+    }
+
+    /// The haystack passed to a search isn't in the regex's encoding.
+    pub(crate) fn encoding_mismatch<T: fmt::Debug, R: fmt::Debug>(haystack: T, regex: R) -> Self {
+        Error::with_kind(
+            ErrorKind::EncodingMismatch,
+            format!(
+                "Regex encoding does not match haystack encoding ({:?}, {:?})",
+                haystack, regex
+            ),
+        )
+    }
+
+    /// Reading a stream failed.
+    pub(crate) fn io(err: std::io::Error) -> Self {
+        Error::with_kind(ErrorKind::Io, format!("Failed to read stream: {}", err)).with_source(err)
+    }
+
+    /// Attach the underlying error which caused this one.
+    pub(crate) fn with_source<E>(mut self, source: E) -> Self
+    where
+        E: error::Error + Send + Sync + 'static,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Point the error at the part of the pattern at fault.
+    pub(crate) fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Map the pattern span, e.g. back from a transcoded pattern to
+    /// the pattern the user wrote.
+    pub(crate) fn map_span<F: Fn(usize) -> usize>(mut self, map: F) -> Self {
+        self.span = self.span.map(|span| map(span.start)..map(span.end));
+        self
+    }
+
+    fn new(code: c_int, info: *const onig_sys::OnigErrorInfo) -> Self {
+        let buff = &mut [0; onig_sys::ONIG_MAX_ERROR_MESSAGE_LEN as usize];
+        let len = unsafe { onig_sys::onig_error_code_to_str(buff.as_mut_ptr(), code, info) };
+        let description = match str::from_utf8(&buff[..len as usize]) {
+            Ok(description) => description.to_owned(),
+            Err(e) => {
+                return Error::with_kind(ErrorKind::Internal, "Onig error string was invalid UTF-8")
+                    .with_source(e)
+            }
+        };
+        Error {
+            kind: ErrorKind::from_code(code),
+            code: Some(code),
+            description,
+            span: None,
+            source: None,
+        }
+    }
+
+    /// Return the kind of error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Return Oniguruma engine error code.
+    ///
+    /// Errors raised by this crate rather than the engine return `-1`.
+    pub fn code(&self) -> i32 {
+        self.code.unwrap_or(-1)
+    }
+
+    /// Return error description provided by Oniguruma engine.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Return the Location of the Error in the Pattern
+    ///
+    /// The byte range of the pattern which caused a compile error.
+    /// Oniguruma only reports a location for some errors, such as
+    /// undefined names and invalid property names, so this is `None`
+    /// for other errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::{ErrorKind, Regex};
+    /// let pattern = r"a\p{Nope}b";
+    /// let err = Regex::new(pattern).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::InvalidReference);
+    /// assert_eq!(&pattern[err.pattern_span().unwrap()], "Nope");
+    /// ```
+    pub fn pattern_span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// Was a Resource Limit Exceeded?
+    ///
+    /// Returns `true` if compiling or searching stopped because it hit
+    /// one of the limits in a [`Budget`](crate::Budget) or the
+    /// [`limits`](crate::limits) module, rather than because the
+    /// pattern or arguments were invalid.
+    pub fn is_limit_exceeded(&self) -> bool {
+        self.kind.is_limit_exceeded()
+    }
+}
+
+/// The range of `pattern` which `info` points at, if it points into
+/// the pattern at all.
+fn pattern_span(info: &onig_sys::OnigErrorInfo, pattern: &[u8]) -> Option<Range<usize>> {
+    if info.par.is_null() || info.par_end.is_null() {
+        return None;
+    }
+    let start = pattern.as_ptr() as usize;
+    let (par, par_end) = (info.par as usize, info.par_end as usize);
+    if par < start || par > par_end || par_end > start + pattern.len() {
+        return None;
+    }
+    Some(par - start..par_end - start)
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn error::Error + 'static))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Oniguruma error: {}", self.description())
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error({:?}", self.kind)?;
+        if let Some(code) = self.code {
+            write!(f, " {}", code)?;
+        }
+        write!(f, ", {})", self.description())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Regex;
+
+    #[test]
+    fn test_compile_error_kinds() {
+        let kind = |pattern: &str| Regex::new(pattern).unwrap_err().kind();
+        assert_eq!(kind("a)"), ErrorKind::Syntax);
+        assert_eq!(kind("(a"), ErrorKind::Syntax);
+        assert_eq!(kind("a{1000000}"), ErrorKind::InvalidValue);
+        assert_eq!(kind(r"\k<nope>"), ErrorKind::InvalidReference);
+        assert_eq!(kind(r"\p{foo}"), ErrorKind::InvalidReference);
+        assert_eq!(kind("(*nope)"), ErrorKind::InvalidCallout);
+    }
+
+    #[test]
+    fn test_pattern_span() {
+        let pattern = r"(?<a>x)\k<missing>";
+        let err = Regex::new(pattern).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidReference);
+        assert_eq!(&pattern[err.pattern_span().unwrap()], "missing");
+        assert_eq!(Regex::new("a)").unwrap_err().pattern_span(), None);
+    }
+
+    #[test]
+    fn test_crate_error_kinds() {
+        let regex = Regex::new("a").unwrap();
+        let ascii = crate::EncodedBytes::ascii(b"a");
+        let err = regex
+            .search_with_param(
+                ascii,
+                0,
+                1,
+                crate::SearchOptions::SEARCH_OPTION_NONE,
+                None,
+                crate::MatchParam::default(),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingMismatch);
+        assert_eq!(err.code(), -1);
+
+        let err = crate::limits::set_subexp_call_max_nest_level(u32::MAX).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidArgument);
+        assert!(error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_limit_kinds() {
+        let err = Error::from_code(onig_sys::ONIGERR_RETRY_LIMIT_IN_SEARCH_OVER);
+        assert_eq!(err.kind(), ErrorKind::RetryLimitInSearch);
+        assert!(err.is_limit_exceeded());
+        assert!(!ErrorKind::Syntax.is_limit_exceeded());
+    }
+}
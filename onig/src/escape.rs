@@ -0,0 +1,236 @@
+//! Pattern Escaping
+//!
+//! This module quotes literal text so it can be embedded in a
+//! pattern. Which characters need quoting, and how, depends on the
+//! syntax: in `Syntax::grep()` a bare `(` is a literal but `\(` opens
+//! a group, while in `Syntax::ruby()` it's the other way around.
+
+use super::{RegexOptions, Syntax, SyntaxOperator};
+
+/// Escape Text for the Default Syntax
+///
+/// Returns a pattern which matches `text` literally when compiled with
+/// [`Syntax::default`]. See [`Syntax::escape`].
+///
+/// # Examples
+///
+/// ```
+/// use onig::Regex;
+/// let pattern = format!("^{}$", onig::escape("1+1=2 (maybe?)"));
+/// assert!(Regex::new(&pattern).unwrap().is_match("1+1=2 (maybe?)"));
+/// ```
+pub fn escape(text: &str) -> String {
+    Syntax::default().escape(text)
+}
+
+impl Syntax {
+    /// Escape Text for this Syntax
+    ///
+    /// Returns a pattern which matches `text` literally when compiled
+    /// with this syntax. The enabled operators and meta characters of
+    /// the syntax decide which characters are quoted, so the result is
+    /// only valid for this syntax and its current settings.
+    ///
+    /// Special characters are preceded by the escape character, unless
+    /// the escaped form is an operator too, in which case they're
+    /// wrapped in a bracket expression. Characters which can't be
+    /// quoted at all, e.g. in a syntax without an escape character or
+    /// bracket expressions, are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::Syntax;
+    /// assert_eq!(Syntax::ruby().escape("a.b(c)"), r"a\.b\(c\)");
+    /// assert_eq!(Syntax::grep().escape("a.b(c)"), r"a\.b(c)");
+    /// ```
+    pub fn escape(&self, text: &str) -> String {
+        let escape = self.escape_char();
+        let mut escaped = String::with_capacity(text.len() * 2);
+        for c in text.chars() {
+            if Some(c) == escape {
+                escaped.push(c);
+                escaped.push(c);
+            } else if !self.is_meta(c) {
+                escaped.push(c);
+            } else if let Some(escape) = escape.filter(|_| !self.is_escape_operator(c)) {
+                escaped.push(escape);
+                escaped.push(c);
+            } else if self.has(SyntaxOperator::SYNTAX_OPERATOR_BRACKET_CC) && c != '^' {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            } else {
+                // There's no way to quote the character.
+                escaped.push(c);
+            }
+        }
+        escaped
+    }
+
+    /// The escape character, if escapes are enabled.
+    pub(crate) fn escape_char(&self) -> Option<char> {
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_INEFFECTIVE_ESCAPE) {
+            return None;
+        }
+        char::from_u32(self.meta_chars().esc)
+    }
+
+    /// Does `c` have a special meaning when it isn't escaped?
+    pub(crate) fn is_meta(&self, c: char) -> bool {
+        let table = self.meta_chars();
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_VARIABLE_META_CHARACTERS)
+            && [
+                table.anychar,
+                table.anytime,
+                table.zero_or_one_time,
+                table.one_or_more_time,
+                table.anychar_anytime,
+            ]
+            .contains(&(c as u32))
+        {
+            return true;
+        }
+        if self.options().contains(RegexOptions::REGEX_OPTION_EXTEND)
+            && (c == '#' || c.is_ascii_whitespace())
+        {
+            return true;
+        }
+        let op = match c {
+            '.' => SyntaxOperator::SYNTAX_OPERATOR_DOT_ANYCHAR,
+            '*' => SyntaxOperator::SYNTAX_OPERATOR_ASTERISK_ZERO_INF,
+            '+' => {
+                SyntaxOperator::SYNTAX_OPERATOR_PLUS_ONE_INF
+                    | SyntaxOperator::SYNTAX_OPERATOR_PLUS_POSSESSIVE_REPEAT
+                    | SyntaxOperator::SYNTAX_OPERATOR_PLUS_POSSESSIVE_INTERVAL
+            }
+            '?' => {
+                SyntaxOperator::SYNTAX_OPERATOR_QMARK_ZERO_ONE
+                    | SyntaxOperator::SYNTAX_OPERATOR_QMARK_NON_GREEDY
+            }
+            '{' => SyntaxOperator::SYNTAX_OPERATOR_BRACE_INTERVAL,
+            '|' => SyntaxOperator::SYNTAX_OPERATOR_VBAR_ALT,
+            '(' | ')' => SyntaxOperator::SYNTAX_OPERATOR_LPAREN_SUBEXP,
+            '[' => SyntaxOperator::SYNTAX_OPERATOR_BRACKET_CC,
+            '^' | '$' => SyntaxOperator::SYNTAX_OPERATOR_LINE_ANCHOR,
+            _ => return false,
+        };
+        self.operators().intersects(op)
+    }
+
+    /// Is `c` an operator when it's escaped?
+    pub(crate) fn is_escape_operator(&self, c: char) -> bool {
+        let op = match c {
+            '*' => SyntaxOperator::SYNTAX_OPERATOR_ESC_ASTERISK_ZERO_INF,
+            '+' => SyntaxOperator::SYNTAX_OPERATOR_ESC_PLUS_ONE_INF,
+            '?' => SyntaxOperator::SYNTAX_OPERATOR_ESC_QMARK_ZERO_ONE,
+            '{' => SyntaxOperator::SYNTAX_OPERATOR_ESC_BRACE_INTERVAL,
+            '|' => SyntaxOperator::SYNTAX_OPERATOR_ESC_VBAR_ALT,
+            '(' | ')' => SyntaxOperator::SYNTAX_OPERATOR_ESC_LPAREN_SUBEXP,
+            // Escaped letters and digits are classes, anchors and
+            // back-references.
+            c => return c.is_ascii_alphanumeric(),
+        };
+        self.operators().intersects(op)
+    }
+
+    fn has(&self, op: SyntaxOperator) -> bool {
+        self.operators().contains(op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MetaChar, MetaCharType, Regex};
+
+    /// A small xorshift generator, so the tests are repeatable.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn text(&mut self) -> String {
+            const CHARS: &[char] = &[
+                'a', 'Z', '0', '9', ' ', '\t', '\n', 'é', '日', '😀', '!', '"', '#', '$', '%', '&',
+                '\'', '(', ')', '*', '+', ',', '-', '.', '/', ':', ';', '<', '=', '>', '?', '@',
+                '[', '\\', ']', '^', '_', '`', '{', '|', '}', '~',
+            ];
+            let len = self.next() % 12;
+            (0..len)
+                .map(|_| CHARS[(self.next() % CHARS.len() as u64) as usize])
+                .collect()
+        }
+    }
+
+    fn assert_literal(syntax: &Syntax, text: &str) {
+        let pattern = syntax.escape(text);
+        let regex = Regex::with_options(&pattern, RegexOptions::REGEX_OPTION_NONE, syntax)
+            .unwrap_or_else(|e| panic!("{:?} escaped as {:?}: {}", text, pattern, e));
+        assert_eq!(
+            regex.find(text),
+            Some((0, text.len())),
+            "{:?} escaped as {:?}",
+            text,
+            pattern
+        );
+        if !text.is_empty() {
+            let padded = format!("x{}x", text);
+            assert_eq!(regex.find(&padded), Some((1, text.len() + 1)));
+        }
+    }
+
+    #[test]
+    fn test_escape_examples() {
+        assert_eq!(escape("plain text é"), "plain text é");
+        assert_eq!(escape(r"a\b"), r"a\\b");
+        assert_eq!(escape("[x]{1}^$"), r"\[x]\{1}\^\$");
+        assert_eq!(Syntax::posix_basic().escape("(a|b)*"), r"(a|b)\*");
+        assert_eq!(Syntax::grep().escape("a+(b)"), "a+(b)");
+    }
+
+    #[test]
+    fn test_escape_is_literal() {
+        let mut custom = *Syntax::ruby();
+        custom.enable_operators(SyntaxOperator::SYNTAX_OPERATOR_VARIABLE_META_CHARACTERS);
+        custom.set_meta_char(MetaCharType::META_CHAR_ESCAPE, MetaChar::Character('%'));
+        custom.set_meta_char(MetaCharType::META_CHAR_ANYCHAR, MetaChar::Character('_'));
+        let mut both = *Syntax::grep();
+        both.enable_operators(
+            SyntaxOperator::SYNTAX_OPERATOR_LPAREN_SUBEXP
+                | SyntaxOperator::SYNTAX_OPERATOR_PLUS_ONE_INF,
+        );
+        assert_eq!(both.escape("(a+)"), "[(]a[+][)]");
+        let mut extended = *Syntax::perl();
+        extended.set_options(RegexOptions::REGEX_OPTION_EXTEND);
+        let syntaxes = [
+            Syntax::default(),
+            Syntax::ruby(),
+            Syntax::perl(),
+            Syntax::perl_ng(),
+            Syntax::python(),
+            Syntax::java(),
+            Syntax::oniguruma(),
+            Syntax::posix_basic(),
+            Syntax::posix_extended(),
+            Syntax::emacs(),
+            Syntax::grep(),
+            Syntax::gnu_regex(),
+            Syntax::asis(),
+            &custom,
+            &both,
+            &extended,
+        ];
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for syntax in syntaxes {
+            for _ in 0..300 {
+                assert_literal(syntax, &rng.text());
+            }
+        }
+    }
+}
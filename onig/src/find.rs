@@ -1,11 +1,15 @@
-use super::{Regex, Region, SearchOptions};
+use super::callout::resume_each_match_panic;
+use super::{CaptureHistory, EncodedBytes, EncodedChars, Error, Regex, Region, SearchOptions};
 use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ops::{Index, Range};
+use std::vec;
 
 impl<'syntax> Regex<'syntax> {
     /// Returns the capture groups corresponding to the leftmost-first match
     /// in text. Capture group `0` always corresponds to the entire match.
     /// If no match is found, then `None` is returned.
-    pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+    pub fn captures<'r, 't>(&'r self, text: &'t str) -> Option<Captures<'r, 't>> {
         let mut region = Region::new();
         self.search_with_options(
             text,
@@ -14,11 +18,25 @@ impl<'syntax> Regex<'syntax> {
             SearchOptions::SEARCH_OPTION_NONE,
             Some(&mut region),
         )
-        .map(|pos| Captures {
+        .map(|pos| Captures::new(self, text, region, pos))
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first
+    /// match in text, like `captures`, but returns an `Error` if the
+    /// search fails rather than panicking.
+    pub fn try_captures<'r, 't>(
+        &'r self,
+        text: &'t str,
+    ) -> Result<Option<Captures<'r, 't>>, Error> {
+        let mut region = Region::new();
+        let r = self.try_search_with_options(
             text,
-            region,
-            offset: pos,
-        })
+            0,
+            text.len(),
+            SearchOptions::SEARCH_OPTION_NONE,
+            Some(&mut region),
+        )?;
+        Ok(r.map(|pos| Captures::new(self, text, region, pos)))
     }
 
     /// Returns an iterator for each successive non-overlapping match in `text`,
@@ -44,13 +62,7 @@ impl<'syntax> Regex<'syntax> {
     /// # }
     /// ```
     pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> FindMatches<'r, 'syntax, 't> {
-        FindMatches {
-            regex: self,
-            region: Region::new(),
-            text,
-            last_end: 0,
-            last_match_end: None,
-        }
+        FindMatches::new(self, text)
     }
 
     /// Returns an iterator over all the non-overlapping capture groups matched
@@ -78,12 +90,7 @@ impl<'syntax> Regex<'syntax> {
     /// # }
     /// ```
     pub fn captures_iter<'r, 't>(&'r self, text: &'t str) -> FindCaptures<'r, 'syntax, 't> {
-        FindCaptures {
-            regex: self,
-            text,
-            last_end: 0,
-            last_match_end: None,
-        }
+        FindCaptures::new(self, text)
     }
 
     /// Returns an iterator of substrings of `text` delimited by a match
@@ -106,10 +113,7 @@ impl<'syntax> Regex<'syntax> {
     /// # }
     /// ```
     pub fn split<'r, 't>(&'r self, text: &'t str) -> RegexSplits<'r, 'syntax, 't> {
-        RegexSplits {
-            finder: self.find_iter(text),
-            last: 0,
-        }
+        RegexSplits::new(self.find_iter(text))
     }
 
     /// Returns an iterator of at most `limit` substrings of `text` delimited
@@ -135,10 +139,49 @@ impl<'syntax> Regex<'syntax> {
     /// # }
     /// ```
     pub fn splitn<'r, 't>(&'r self, text: &'t str, limit: usize) -> RegexSplitsN<'r, 'syntax, 't> {
-        RegexSplitsN {
-            splits: self.split(text),
-            n: limit,
-        }
+        RegexSplitsN::new(self.split(text), limit)
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `text`, like `find_iter`, but yielding an `Error` if a search
+    /// fails rather than panicking. Searches fail when they exceed the
+    /// regex's [`Budget`](crate::Budget) or another resource limit.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::Regex;
+    /// let re = Regex::new(r"\d+").unwrap();
+    /// let found: Result<Vec<_>, _> = re.try_find_iter("a1 b22").collect();
+    /// assert_eq!(found.unwrap(), vec![(1, 2), (4, 6)]);
+    /// ```
+    pub fn try_find_iter<'r, 't>(&'r self, text: &'t str) -> TryFindMatches<'r, 'syntax, 't> {
+        TryFindMatches(self.find_iter(text))
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups
+    /// matched in `text`, like `captures_iter`, but yielding an `Error`
+    /// if a search fails rather than panicking.
+    pub fn try_captures_iter<'r, 't>(&'r self, text: &'t str) -> TryFindCaptures<'r, 'syntax, 't> {
+        TryFindCaptures(self.captures_iter(text))
+    }
+
+    /// Returns an iterator of substrings of `text` delimited by a match
+    /// of the regular expression, like `split`, but yielding an `Error`
+    /// if a search fails rather than panicking.
+    pub fn try_split<'r, 't>(&'r self, text: &'t str) -> TryRegexSplits<'r, 'syntax, 't> {
+        TryRegexSplits(self.split(text))
+    }
+
+    /// Returns an iterator of at most `limit` substrings of `text`
+    /// delimited by a match of the regular expression, like `splitn`,
+    /// but yielding an `Error` if a search fails rather than panicking.
+    pub fn try_splitn<'r, 't>(
+        &'r self,
+        text: &'t str,
+        limit: usize,
+    ) -> TryRegexSplitsN<'r, 'syntax, 't> {
+        TryRegexSplitsN(self.splitn(text, limit))
     }
 
     /// Scan the given slice, capturing into the given region and
@@ -148,18 +191,31 @@ impl<'syntax> Regex<'syntax> {
         to_search: &str,
         region: &mut Region,
         options: SearchOptions,
+        callback: F,
+    ) -> i32
+    where
+        F: Fn(i32, i32, &Region) -> bool,
+    {
+        self.scan_with_encoding(to_search, region, options, callback)
+    }
+
+    /// Scan the given buffer, capturing into the given region and
+    /// executing a callback for each match. The encoding of `chars`
+    /// is not checked against the regex.
+    pub(crate) fn scan_with_encoding<T, F>(
+        &self,
+        chars: T,
+        region: &mut Region,
+        options: SearchOptions,
         mut callback: F,
     ) -> i32
     where
+        T: EncodedChars,
         F: Fn(i32, i32, &Region) -> bool,
     {
         use onig_sys::{onig_scan, OnigRegion};
         use std::os::raw::{c_int, c_void};
 
-        // Find the bounds of the string we're searching
-        let start = to_search.as_ptr();
-        let end = to_search[to_search.len()..].as_ptr();
-
         unsafe extern "C" fn scan_cb<F>(
             i: c_int,
             j: c_int,
@@ -178,26 +234,85 @@ impl<'syntax> Regex<'syntax> {
             }
         }
 
-        unsafe {
+        if options.validate(true).is_err() {
+            return onig_sys::ONIGERR_INVALID_ARGUMENT;
+        }
+        if self.budget().is_some() {
+            // `onig_scan` can't be given a match param, so search for
+            // each match ourselves to apply the budget.
+            return self.scan_with_budget(chars, region, options, callback);
+        }
+
+        let r = unsafe {
             onig_scan(
                 self.raw,
-                start,
-                end,
+                chars.start_ptr(),
+                chars.limit_ptr(),
                 (&mut region.raw) as *mut ::onig_sys::OnigRegion,
                 options.bits(),
                 Some(scan_cb::<F>),
                 &mut callback as *mut F as *mut c_void,
             )
+        };
+        resume_each_match_panic();
+        r
+    }
+
+    /// Scan the given buffer as `onig_scan` does, searching with the
+    /// regex's budget.
+    fn scan_with_budget<T, F>(
+        &self,
+        chars: T,
+        region: &mut Region,
+        options: SearchOptions,
+        callback: F,
+    ) -> i32
+    where
+        T: EncodedChars,
+        F: Fn(i32, i32, &Region) -> bool,
+    {
+        let bytes = unsafe { std::slice::from_raw_parts(chars.start_ptr(), chars.len()) };
+        let encoding = chars.encoding();
+        let text = EncodedBytes::from_parts(bytes, encoding);
+        let mut count = 0;
+        let mut start = 0;
+        while start <= bytes.len() {
+            let r = self.search_with_param(
+                text,
+                start,
+                bytes.len(),
+                options,
+                Some(region),
+                self.default_match_param(),
+            );
+            let pos = match r {
+                Ok(Some(pos)) => pos,
+                Ok(None) => break,
+                Err(err) => return err.code(),
+            };
+            if !callback(count, pos as i32, region) {
+                return -1;
+            }
+            count += 1;
+            let (_, end) = region.pos(0).unwrap();
+            if end > start {
+                start = end;
+            } else if start < bytes.len() {
+                start = encoding.right_adjust_char_head(bytes, start + 1);
+            } else {
+                break;
+            }
         }
+        count
     }
 
     /// Scan a Pattern and Observe Captures
     ///
     /// The scan function takes a haystack `to_search` and invokes the
     /// given `callback` for each capture of this expression.
-    pub fn scan<'t, CB>(&self, to_search: &'t str, callback: CB)
+    pub fn scan<'r, 't, CB>(&'r self, to_search: &'t str, callback: CB)
     where
-        CB: Fn(i32, Captures<'t>) -> bool,
+        CB: Fn(i32, Captures<'r, 't>) -> bool,
     {
         let mut region = Region::new();
         self.scan_with_region(
@@ -205,32 +320,136 @@ impl<'syntax> Regex<'syntax> {
             &mut region,
             SearchOptions::SEARCH_OPTION_NONE,
             |n, s, region| {
-                let captures = Captures {
-                    text: to_search,
-                    region: region.clone(),
-                    offset: s as usize,
-                };
+                let captures = Captures::new(self, to_search, region.clone(), s as usize);
                 callback(n, captures)
             },
         );
     }
 }
 
+/// Text the Search Iterators Walk Through
+///
+/// Implemented for `&str`, and for [`EncodedBytes`] to back the
+/// [`bytes`](crate::bytes) API. Matches are returned as slices of the
+/// text.
+pub trait SearchText<'t>: EncodedChars + Copy {
+    /// The type matches are returned as, `str` or `[u8]`.
+    type Slice: ?Sized + Index<Range<usize>, Output = Self::Slice> + 't;
+
+    /// The whole of the text.
+    fn as_slice(&self) -> &'t Self::Slice;
+
+    /// Byte position of the character following the one at `pos`.
+    fn next_char(&self, pos: usize) -> usize;
+}
+
+impl<'t> SearchText<'t> for &'t str {
+    type Slice = str;
+
+    fn as_slice(&self) -> &'t str {
+        self
+    }
+
+    fn next_char(&self, pos: usize) -> usize {
+        pos + self[pos..].chars().next().map_or(1, |c| c.len_utf8())
+    }
+}
+
+impl<'t> SearchText<'t> for EncodedBytes<'t> {
+    type Slice = [u8];
+
+    fn as_slice(&self) -> &'t [u8] {
+        EncodedBytes::as_bytes(self)
+    }
+
+    fn next_char(&self, pos: usize) -> usize {
+        if pos + 1 >= EncodedChars::len(self) {
+            return pos + 1;
+        }
+        self.right_adjust_char_head(pos + 1)
+    }
+}
+
+/// Where the next search of an iterator over non-overlapping matches
+/// starts.
+#[derive(Debug, Default)]
+pub(crate) struct MatchCursor {
+    last_end: usize,
+    last_match_end: Option<usize>,
+}
+
+impl MatchCursor {
+    /// Search for the next match with `search`, which is given the
+    /// position to search from and returns what it found along with
+    /// the extent of the match. The cursor is finished after an error.
+    pub(crate) fn next<'t, H, T, F>(&mut self, text: H, mut search: F) -> Option<Result<T, Error>>
+    where
+        H: SearchText<'t>,
+        F: FnMut(usize) -> Result<Option<(T, (usize, usize))>, Error>,
+    {
+        loop {
+            if self.last_end > text.len() {
+                return None;
+            }
+            let (found, (s, e)) = match search(self.last_end) {
+                Ok(Some(found)) => found,
+                Ok(None) => return None,
+                Err(err) => {
+                    self.last_end = text.len() + 1;
+                    return Some(Err(err));
+                }
+            };
+
+            // Don't accept empty matches immediately following the last match.
+            // i.e., no infinite loops please.
+            if e == s && self.last_match_end == Some(e) {
+                self.last_end = text.next_char(self.last_end);
+                continue;
+            }
+            self.last_end = e;
+            self.last_match_end = Some(e);
+            return Some(Ok(found));
+        }
+    }
+}
+
 /// Captures represents a group of captured strings for a single match.
 ///
 /// The 0th capture always corresponds to the entire match. Each subsequent
 /// index corresponds to the next capture group in the regex. Positions
 /// returned from a capture group are always byte indices.
 ///
-/// `'t` is the lifetime of the matched text.
+/// `'r` is the lifetime of the `Regex` which matched, and `'t` is the
+/// lifetime of the matched text. `S` is the type of the matched text,
+/// `[u8]` for the [`bytes`](crate::bytes) API.
 #[derive(Debug)]
-pub struct Captures<'t> {
-    text: &'t str,
+pub struct Captures<'r, 't, S: ?Sized = str> {
+    regex: &'r Regex<'r>,
+    text: &'t S,
     region: Region,
     offset: usize,
 }
 
-impl<'t> Captures<'t> {
+impl<'r, 't, S> Captures<'r, 't, S>
+where
+    S: ?Sized + Index<Range<usize>, Output = S>,
+{
+    /// Create a new set of captures from a region filled by a search
+    /// of `text` with `regex` which matched at `offset`.
+    pub(crate) fn new(
+        regex: &'r Regex<'r>,
+        text: &'t S,
+        region: Region,
+        offset: usize,
+    ) -> Captures<'r, 't, S> {
+        Captures {
+            regex,
+            text,
+            region,
+            offset,
+        }
+    }
+
     /// Returns the start and end positions of the Nth capture group. Returns
     /// `None` if i is not a valid capture group or if the capture group did
     /// not match anything. The positions returned are always byte indices with
@@ -241,10 +460,43 @@ impl<'t> Captures<'t> {
 
     /// Returns the matched string for the capture group `i`. If `i` isn't
     /// a valid capture group or didn't match anything, then `None` is returned.
-    pub fn at(&self, pos: usize) -> Option<&'t str> {
+    pub fn at(&self, pos: usize) -> Option<&'t S> {
         self.pos(pos).map(|(beg, end)| &self.text[beg..end])
     }
 
+    /// Returns the start and end positions of the capture group named
+    /// `name`. Returns `None` if there is no group with the given name,
+    /// or if the group did not match anything.
+    ///
+    /// If more than one group shares the name then the last of those
+    /// groups which matched is used, as in Ruby.
+    pub fn pos_by_name(&self, name: &str) -> Option<(usize, usize)> {
+        self.regex
+            .name_to_group(name, &self.region)
+            .and_then(|group| self.pos(group))
+    }
+
+    /// Returns the matched string for the capture group named `name`.
+    /// If there is no group with the given name, or the group didn't
+    /// match anything, then `None` is returned.
+    ///
+    /// If more than one group shares the name then the last of those
+    /// groups which matched is used, as in Ruby.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::Regex;
+    /// let re = Regex::new(r"(?<year>\d{4})-(?<month>\d{2})").unwrap();
+    /// let caps = re.captures("on 2023-07").unwrap();
+    /// assert_eq!(caps.name("year"), Some("2023"));
+    /// assert_eq!(caps.name("day"), None);
+    /// ```
+    pub fn name(&self, name: &str) -> Option<&'t S> {
+        self.pos_by_name(name)
+            .map(|(beg, end)| &self.text[beg..end])
+    }
+
     /// Returns the number of captured groups.
     pub fn len(&self) -> usize {
         self.region.len()
@@ -257,14 +509,29 @@ impl<'t> Captures<'t> {
 
     /// Creates an iterator of all the capture groups in order of appearance in
     /// the regular expression.
-    pub fn iter(&'t self) -> SubCaptures<'t> {
+    pub fn iter(&'t self) -> SubCaptures<'t, S> {
         SubCaptures { idx: 0, caps: self }
     }
 
+    /// Creates an iterator of the named capture groups in order of
+    /// appearance in the regular expression. Each item is the group's
+    /// name along with the matched string, resolved as `name` would.
+    pub fn iter_named(&self) -> SubCapturesNamed<'r, 't, S> {
+        let names = self
+            .regex
+            .name_table()
+            .into_iter()
+            .map(|(name, _)| (name, self.name(name)))
+            .collect::<Vec<_>>();
+        SubCapturesNamed {
+            names: names.into_iter(),
+        }
+    }
+
     /// Creates an iterator of all the capture group positions in order of
     /// appearance in the regular expression. Positions are byte indices in
     /// terms of the original string matched.
-    pub fn iter_pos(&'t self) -> SubCapturesPos<'t> {
+    pub fn iter_pos(&'t self) -> SubCapturesPos<'t, S> {
         SubCapturesPos { idx: 0, caps: self }
     }
 
@@ -272,21 +539,50 @@ impl<'t> Captures<'t> {
     pub fn offset(&self) -> usize {
         self.offset
     }
+
+    /// The whole string slice which was searched.
+    pub(crate) fn text(&self) -> &'t S {
+        self.text
+    }
+}
+
+impl<'r, 't> Captures<'r, 't> {
+    /// Returns the capture history of the match.
+    ///
+    /// The history records every capture made by `(?@...)` groups,
+    /// including each iteration of a repeated group. Returns `None` if
+    /// the pattern has no such groups. See [`CaptureHistory`] and
+    /// [`RegexBuilder::capture_history`](crate::RegexBuilder::capture_history).
+    pub fn history(&self) -> Option<CaptureHistory<'_, 't>> {
+        let tree = self.region.tree()?;
+        let mut names = vec![None; self.len()];
+        for (name, groups) in self.regex.name_table() {
+            for &group in groups {
+                if let Some(slot) = names.get_mut(group as usize) {
+                    *slot = Some(name);
+                }
+            }
+        }
+        Some(CaptureHistory::new(tree, self.text, names))
+    }
 }
 
 /// An iterator over capture groups for a particular match of a regular
 /// expression.
 ///
 /// `'t` is the lifetime of the matched text.
-pub struct SubCaptures<'t> {
+pub struct SubCaptures<'t, S: ?Sized = str> {
     idx: usize,
-    caps: &'t Captures<'t>,
+    caps: &'t Captures<'t, 't, S>,
 }
 
-impl<'t> Iterator for SubCaptures<'t> {
-    type Item = Option<&'t str>;
+impl<'t, S> Iterator for SubCaptures<'t, S>
+where
+    S: ?Sized + Index<Range<usize>, Output = S>,
+{
+    type Item = Option<&'t S>;
 
-    fn next(&mut self) -> Option<Option<&'t str>> {
+    fn next(&mut self) -> Option<Option<&'t S>> {
         if self.idx < self.caps.len() {
             self.idx += 1;
             Some(self.caps.at(self.idx - 1))
@@ -296,30 +592,36 @@ impl<'t> Iterator for SubCaptures<'t> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let size = self.caps.len();
+        let size = self.caps.len() - self.idx;
         (size, Some(size))
     }
 
     fn count(self) -> usize {
-        self.caps.len()
+        self.caps.len() - self.idx
     }
 }
 
-impl<'t> FusedIterator for SubCaptures<'t> {}
+impl<'t, S> FusedIterator for SubCaptures<'t, S> where S: ?Sized + Index<Range<usize>, Output = S> {}
 
-impl<'t> ExactSizeIterator for SubCaptures<'t> {}
+impl<'t, S> ExactSizeIterator for SubCaptures<'t, S> where
+    S: ?Sized + Index<Range<usize>, Output = S>
+{
+}
 
 /// An iterator over capture group positions for a particular match of
 /// a regular expression.
 ///
 /// Positions are byte indices in terms of the original
 /// string matched. `'t` is the lifetime of the matched text.
-pub struct SubCapturesPos<'t> {
+pub struct SubCapturesPos<'t, S: ?Sized = str> {
     idx: usize,
-    caps: &'t Captures<'t>,
+    caps: &'t Captures<'t, 't, S>,
 }
 
-impl<'t> Iterator for SubCapturesPos<'t> {
+impl<'t, S> Iterator for SubCapturesPos<'t, S>
+where
+    S: ?Sized + Index<Range<usize>, Output = S>,
+{
     type Item = Option<(usize, usize)>;
 
     fn next(&mut self) -> Option<Option<(usize, usize)>> {
@@ -332,18 +634,49 @@ impl<'t> Iterator for SubCapturesPos<'t> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let size = self.caps.len();
+        let size = self.caps.len() - self.idx;
         (size, Some(size))
     }
 
     fn count(self) -> usize {
-        self.caps.len()
+        self.caps.len() - self.idx
     }
 }
 
-impl<'t> FusedIterator for SubCapturesPos<'t> {}
+impl<'t, S> FusedIterator for SubCapturesPos<'t, S> where S: ?Sized + Index<Range<usize>, Output = S>
+{}
 
-impl<'t> ExactSizeIterator for SubCapturesPos<'t> {}
+impl<'t, S> ExactSizeIterator for SubCapturesPos<'t, S> where
+    S: ?Sized + Index<Range<usize>, Output = S>
+{
+}
+
+/// An iterator over the named capture groups for a particular match
+/// of a regular expression.
+///
+/// Each item is the name of the group along with the matched string,
+/// or `None` if the group didn't match. `'r` is the lifetime of the
+/// `Regex` which matched, and `'t` is the lifetime of the matched
+/// text.
+pub struct SubCapturesNamed<'r, 't, S: ?Sized = str> {
+    names: vec::IntoIter<(&'r str, Option<&'t S>)>,
+}
+
+impl<'r, 't, S: ?Sized> Iterator for SubCapturesNamed<'r, 't, S> {
+    type Item = (&'r str, Option<&'t S>);
+
+    fn next(&mut self) -> Option<(&'r str, Option<&'t S>)> {
+        self.names.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
+
+impl<'r, 't, S: ?Sized> FusedIterator for SubCapturesNamed<'r, 't, S> {}
+
+impl<'r, 't, S: ?Sized> ExactSizeIterator for SubCapturesNamed<'r, 't, S> {}
 
 /// An iterator over all non-overlapping matches for a particular string.
 ///
@@ -354,50 +687,62 @@ impl<'t> ExactSizeIterator for SubCapturesPos<'t> {}
 /// `'r` is the lifetime of the `Regex` struct, `'syntax` is the lifetime of
 /// the [`Syntax`](crate::Syntax) used to compile the regex, and `'t` is the
 /// lifetime of the matched string.
-pub struct FindMatches<'r, 'syntax, 't> {
+pub struct FindMatches<'r, 'syntax, 't, H = &'t str> {
     regex: &'r Regex<'syntax>,
     region: Region,
-    text: &'t str,
-    last_end: usize,
-    last_match_end: Option<usize>,
+    text: H,
+    cursor: MatchCursor,
+    _text: PhantomData<&'t ()>,
 }
 
-impl<'r, 'syntax, 't> Iterator for FindMatches<'r, 'syntax, 't> {
+impl<'r, 'syntax, 't, H> FindMatches<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    pub(crate) fn new(regex: &'r Regex<'syntax>, text: H) -> Self {
+        FindMatches {
+            regex,
+            region: Region::new(),
+            text,
+            cursor: MatchCursor::default(),
+            _text: PhantomData,
+        }
+    }
+
+    /// Find the next match, reporting any search error. The iterator
+    /// is finished after an error.
+    fn try_next(&mut self) -> Option<Result<(usize, usize), Error>> {
+        let (regex, text, region) = (self.regex, self.text, &mut self.region);
+        self.cursor.next(text, |from| {
+            region.clear();
+            let r = regex.search_with_param(
+                text,
+                from,
+                text.len(),
+                SearchOptions::SEARCH_OPTION_NONE,
+                Some(&mut *region),
+                regex.default_match_param(),
+            )?;
+            Ok(r.map(|_| {
+                let pos = region.pos(0).unwrap();
+                (pos, pos)
+            }))
+        })
+    }
+}
+
+impl<'r, 'syntax, 't, H> Iterator for FindMatches<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
     type Item = (usize, usize);
 
     fn next(&mut self) -> Option<(usize, usize)> {
-        if self.last_end > self.text.len() {
-            return None;
-        }
-        self.region.clear();
-        self.regex.search_with_options(
-            self.text,
-            self.last_end,
-            self.text.len(),
-            SearchOptions::SEARCH_OPTION_NONE,
-            Some(&mut self.region),
-        )?;
-        let (s, e) = self.region.pos(0).unwrap();
-
-        // Don't accept empty matches immediately following the last match.
-        // i.e., no infinite loops please.
-        if e == s && self.last_match_end.map_or(false, |l| l == e) {
-            self.last_end += self.text[self.last_end..]
-                .chars()
-                .next()
-                .map(|c| c.len_utf8())
-                .unwrap_or(1);
-            return self.next();
-        } else {
-            self.last_end = e;
-            self.last_match_end = Some(e);
-        }
-
-        Some((s, e))
+        self.try_next().map(unwrap_search)
     }
 }
 
-impl<'r, 'syntax, 't> FusedIterator for FindMatches<'r, 'syntax, 't> {}
+impl<'r, 'syntax, 't, H> FusedIterator for FindMatches<'r, 'syntax, 't, H> where H: SearchText<'t> {}
 
 /// An iterator that yields all non-overlapping capture groups matching a
 /// particular regular expression.
@@ -407,89 +752,119 @@ impl<'r, 'syntax, 't> FusedIterator for FindMatches<'r, 'syntax, 't> {}
 /// `'r` is the lifetime of the `Regex` struct, `'syntax` is the lifetime of
 /// the [`Syntax`](crate::Syntax) used to compile the regex, and `'t` is the
 /// lifetime of the matched string.
-pub struct FindCaptures<'r, 'syntax, 't> {
+pub struct FindCaptures<'r, 'syntax, 't, H = &'t str> {
     regex: &'r Regex<'syntax>,
-    text: &'t str,
-    last_end: usize,
-    last_match_end: Option<usize>,
+    text: H,
+    cursor: MatchCursor,
+    _text: PhantomData<&'t ()>,
 }
 
-impl<'r, 'syntax, 't> Iterator for FindCaptures<'r, 'syntax, 't> {
-    type Item = Captures<'t>;
-
-    fn next(&mut self) -> Option<Captures<'t>> {
-        if self.last_end > self.text.len() {
-            return None;
+impl<'r, 'syntax, 't, H> FindCaptures<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    pub(crate) fn new(regex: &'r Regex<'syntax>, text: H) -> Self {
+        FindCaptures {
+            regex,
+            text,
+            cursor: MatchCursor::default(),
+            _text: PhantomData,
         }
+    }
 
-        let mut region = Region::new();
-        let r = self.regex.search_with_options(
-            self.text,
-            self.last_end,
-            self.text.len(),
-            SearchOptions::SEARCH_OPTION_NONE,
-            Some(&mut region),
-        )?;
-        let (s, e) = region.pos(0).unwrap();
-
-        // Don't accept empty matches immediately following the last match.
-        // i.e., no infinite loops please.
-        if e == s && self.last_match_end.map_or(false, |l| l == e) {
-            self.last_end += self.text[self.last_end..]
-                .chars()
-                .next()
-                .map(|c| c.len_utf8())
-                .unwrap_or(1);
-            return self.next();
-        } else {
-            self.last_end = e;
-            self.last_match_end = Some(e);
-        }
-        Some(Captures {
-            text: self.text,
-            region,
-            offset: r,
+    /// Find the next set of captures, reporting any search error. The
+    /// iterator is finished after an error.
+    fn try_next(&mut self) -> Option<Result<Captures<'r, 't, H::Slice>, Error>> {
+        let (regex, text) = (self.regex, self.text);
+        self.cursor.next(text, |from| {
+            let mut region = Region::new();
+            let r = regex.search_with_param(
+                text,
+                from,
+                text.len(),
+                SearchOptions::SEARCH_OPTION_NONE,
+                Some(&mut region),
+                regex.default_match_param(),
+            )?;
+            Ok(r.map(|r| {
+                let pos = region.pos(0).unwrap();
+                (Captures::new(regex, text.as_slice(), region, r), pos)
+            }))
         })
     }
 }
 
-impl<'r, 'syntax, 't> FusedIterator for FindCaptures<'r, 'syntax, 't> {}
+impl<'r, 'syntax, 't, H> Iterator for FindCaptures<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    type Item = Captures<'r, 't, H::Slice>;
+
+    fn next(&mut self) -> Option<Captures<'r, 't, H::Slice>> {
+        self.try_next().map(unwrap_search)
+    }
+}
+
+impl<'r, 'syntax, 't, H> FusedIterator for FindCaptures<'r, 'syntax, 't, H> where H: SearchText<'t> {}
 
 /// Yields all substrings delimited by a regular expression match.
 ///
 /// `'r` is the lifetime of the compiled expression, `'syntax` is the lifetime
 /// of the [`Syntax`](crate::Syntax) used to compile the regex, and `'t` is
 /// the lifetime of the string being split.
-pub struct RegexSplits<'r, 'syntax, 't> {
-    finder: FindMatches<'r, 'syntax, 't>,
+pub struct RegexSplits<'r, 'syntax, 't, H = &'t str> {
+    finder: FindMatches<'r, 'syntax, 't, H>,
     last: usize,
 }
 
-impl<'r, 'syntax, 't> Iterator for RegexSplits<'r, 'syntax, 't> {
-    type Item = &'t str;
+impl<'r, 'syntax, 't, H> RegexSplits<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    pub(crate) fn new(finder: FindMatches<'r, 'syntax, 't, H>) -> Self {
+        RegexSplits { finder, last: 0 }
+    }
 
-    fn next(&mut self) -> Option<&'t str> {
+    /// Find the next substring, reporting any search error. The
+    /// iterator is finished after an error.
+    fn try_next(&mut self) -> Option<Result<&'t H::Slice, Error>> {
         let text = self.finder.text;
-        match self.finder.next() {
+        let len = text.len();
+        match self.finder.try_next() {
             None => {
-                if self.last >= text.len() {
+                if self.last >= len {
                     None
                 } else {
-                    let s = &text[self.last..];
-                    self.last = text.len();
-                    Some(s)
+                    let s = &text.as_slice()[self.last..len];
+                    self.last = len;
+                    Some(Ok(s))
                 }
             }
-            Some((s, e)) => {
-                let matched = &text[self.last..s];
+            Some(Ok((s, e))) => {
+                let matched = &text.as_slice()[self.last..s];
                 self.last = e;
-                Some(matched)
+                Some(Ok(matched))
+            }
+            Some(Err(err)) => {
+                self.last = len;
+                Some(Err(err))
             }
         }
     }
 }
 
-impl<'r, 'syntax, 't> FusedIterator for RegexSplits<'r, 'syntax, 't> {}
+impl<'r, 'syntax, 't, H> Iterator for RegexSplits<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    type Item = &'t H::Slice;
+
+    fn next(&mut self) -> Option<&'t H::Slice> {
+        self.try_next().map(unwrap_search)
+    }
+}
+
+impl<'r, 'syntax, 't, H> FusedIterator for RegexSplits<'r, 'syntax, 't, H> where H: SearchText<'t> {}
 
 /// Yields at most `N` substrings delimited by a regular expression match.
 ///
@@ -498,33 +873,164 @@ impl<'r, 'syntax, 't> FusedIterator for RegexSplits<'r, 'syntax, 't> {}
 /// `'r` is the lifetime of the compiled expression, `'syntax` is the lifetime
 /// of the [`Syntax`](crate::Syntax) used to compile the regex, and `'t` is
 /// the lifetime of the string being split.
-pub struct RegexSplitsN<'r, 'syntax, 't> {
-    splits: RegexSplits<'r, 'syntax, 't>,
+pub struct RegexSplitsN<'r, 'syntax, 't, H = &'t str> {
+    splits: RegexSplits<'r, 'syntax, 't, H>,
     n: usize,
 }
 
-impl<'r, 'syntax, 't> Iterator for RegexSplitsN<'r, 'syntax, 't> {
-    type Item = &'t str;
+impl<'r, 'syntax, 't, H> RegexSplitsN<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    pub(crate) fn new(splits: RegexSplits<'r, 'syntax, 't, H>, n: usize) -> Self {
+        RegexSplitsN { splits, n }
+    }
 
-    fn next(&mut self) -> Option<&'t str> {
+    /// Find the next substring, reporting any search error. The
+    /// iterator is finished after an error.
+    fn try_next(&mut self) -> Option<Result<&'t H::Slice, Error>> {
         if self.n == 0 {
             return None;
         }
         self.n -= 1;
         if self.n == 0 {
             let text = self.splits.finder.text;
-            Some(&text[self.splits.last..])
+            Some(Ok(&text.as_slice()[self.splits.last..text.len()]))
         } else {
-            self.splits.next()
+            let next = self.splits.try_next();
+            if let Some(Err(_)) = next {
+                self.n = 0;
+            }
+            next
         }
     }
+}
+
+impl<'r, 'syntax, 't, H> Iterator for RegexSplitsN<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    type Item = &'t H::Slice;
+
+    fn next(&mut self) -> Option<&'t H::Slice> {
+        self.try_next().map(unwrap_search)
+    }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
         (0, Some(self.n))
     }
 }
 
-impl<'r, 'syntax, 't> FusedIterator for RegexSplitsN<'r, 'syntax, 't> {}
+impl<'r, 'syntax, 't, H> FusedIterator for RegexSplitsN<'r, 'syntax, 't, H> where H: SearchText<'t> {}
+
+/// Unwrap the result of a search made by one of the infallible
+/// iterators, panicking on error as `search_with_options` does.
+pub(crate) fn unwrap_search<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("Onig: Regex search error: {}", e.description()),
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular
+/// string, which reports search errors.
+///
+/// This is the fallible form of [`FindMatches`], returned by
+/// [`Regex::try_find_iter`]. After yielding an error the iterator is
+/// finished.
+pub struct TryFindMatches<'r, 'syntax, 't, H = &'t str>(pub(crate) FindMatches<'r, 'syntax, 't, H>);
+
+impl<'r, 'syntax, 't, H> Iterator for TryFindMatches<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    type Item = Result<(usize, usize), Error>;
+
+    fn next(&mut self) -> Option<Result<(usize, usize), Error>> {
+        self.0.try_next()
+    }
+}
+
+impl<'r, 'syntax, 't, H> FusedIterator for TryFindMatches<'r, 'syntax, 't, H> where H: SearchText<'t>
+{}
+
+/// An iterator over all non-overlapping capture groups matching a
+/// particular regular expression, which reports search errors.
+///
+/// This is the fallible form of [`FindCaptures`], returned by
+/// [`Regex::try_captures_iter`]. After yielding an error the iterator
+/// is finished.
+pub struct TryFindCaptures<'r, 'syntax, 't, H = &'t str>(
+    pub(crate) FindCaptures<'r, 'syntax, 't, H>,
+);
+
+impl<'r, 'syntax, 't, H> Iterator for TryFindCaptures<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    type Item = Result<Captures<'r, 't, H::Slice>, Error>;
+
+    fn next(&mut self) -> Option<Result<Captures<'r, 't, H::Slice>, Error>> {
+        self.0.try_next()
+    }
+}
+
+impl<'r, 'syntax, 't, H> FusedIterator for TryFindCaptures<'r, 'syntax, 't, H> where
+    H: SearchText<'t>
+{
+}
+
+/// Yields all substrings delimited by a regular expression match,
+/// reporting search errors.
+///
+/// This is the fallible form of [`RegexSplits`], returned by
+/// [`Regex::try_split`]. After yielding an error the iterator is
+/// finished.
+pub struct TryRegexSplits<'r, 'syntax, 't, H = &'t str>(pub(crate) RegexSplits<'r, 'syntax, 't, H>);
+
+impl<'r, 'syntax, 't, H> Iterator for TryRegexSplits<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    type Item = Result<&'t H::Slice, Error>;
+
+    fn next(&mut self) -> Option<Result<&'t H::Slice, Error>> {
+        self.0.try_next()
+    }
+}
+
+impl<'r, 'syntax, 't, H> FusedIterator for TryRegexSplits<'r, 'syntax, 't, H> where H: SearchText<'t>
+{}
+
+/// Yields at most `N` substrings delimited by a regular expression
+/// match, reporting search errors.
+///
+/// This is the fallible form of [`RegexSplitsN`], returned by
+/// [`Regex::try_splitn`]. After yielding an error the iterator is
+/// finished.
+pub struct TryRegexSplitsN<'r, 'syntax, 't, H = &'t str>(
+    pub(crate) RegexSplitsN<'r, 'syntax, 't, H>,
+);
+
+impl<'r, 'syntax, 't, H> Iterator for TryRegexSplitsN<'r, 'syntax, 't, H>
+where
+    H: SearchText<'t>,
+{
+    type Item = Result<&'t H::Slice, Error>;
+
+    fn next(&mut self) -> Option<Result<&'t H::Slice, Error>> {
+        self.0.try_next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'r, 'syntax, 't, H> FusedIterator for TryRegexSplitsN<'r, 'syntax, 't, H> where
+    H: SearchText<'t>
+{
+}
 
 #[cfg(test)]
 mod tests {
@@ -624,4 +1130,38 @@ mod tests {
             .collect::<Vec<_>>();
         assert_eq!(vec![4, 13], all_caps);
     }
+
+    #[test]
+    fn test_captures_name() {
+        let reg = Regex::new(r"(?<first>\w+)\s+(?<last>\w+)(?<suffix>\s+jr)?").unwrap();
+        let caps = reg.captures("alan turing").unwrap();
+        assert_eq!(caps.name("first"), Some("alan"));
+        assert_eq!(caps.name("last"), Some("turing"));
+        assert_eq!(caps.name("suffix"), None);
+        assert_eq!(caps.name("missing"), None);
+        assert_eq!(caps.pos_by_name("last"), Some((5, 11)));
+        assert_eq!(caps.pos_by_name("missing"), None);
+    }
+
+    #[test]
+    fn test_captures_name_duplicate_uses_last_matched() {
+        let reg = Regex::new(r"(?<x>a)|(?<x>b)").unwrap();
+        let caps = reg.captures("a").unwrap();
+        assert_eq!(caps.name("x"), Some("a"));
+        let caps = reg.captures("b").unwrap();
+        assert_eq!(caps.name("x"), Some("b"));
+        assert_eq!(caps.pos_by_name("x"), Some((0, 1)));
+    }
+
+    #[test]
+    fn test_captures_iter_named() {
+        let reg = Regex::new(r"(?<z>\d)(?<y>x)?(?<a>\d)").unwrap();
+        let caps = reg.captures("12").unwrap();
+        let named = caps.iter_named().collect::<Vec<_>>();
+        assert_eq!(named, vec![("z", Some("1")), ("y", None), ("a", Some("2"))]);
+
+        let reg = Regex::new(r"(\d)(\d)").unwrap();
+        let caps = reg.captures("12").unwrap();
+        assert_eq!(caps.iter_named().count(), 0);
+    }
 }
@@ -1,5 +1,7 @@
 use bitflags::bitflags;
-use std::os::raw::c_uint;
+use std::os::raw::{c_int, c_uint};
+
+use crate::Error;
 
 bitflags! {
     /// Regex parsing and compilation options.
@@ -37,6 +39,32 @@ bitflags! {
         /// Named and no-named group captured.
         const REGEX_OPTION_CAPTURE_GROUP
             = onig_sys::ONIG_OPTION_CAPTURE_GROUP;
+        /// Limit `IGNORECASE` to ASCII characters. Implies
+        /// `REGEX_OPTION_IGNORECASE`.
+        const REGEX_OPTION_IGNORECASE_IS_ASCII
+            = onig_sys::ONIG_OPTION_IGNORECASE_IS_ASCII;
+        /// `\w`, `\p{Word}`, `[[:word:]]`, `\b` and `\B` only
+        /// match ASCII characters.
+        const REGEX_OPTION_WORD_IS_ASCII
+            = onig_sys::ONIG_OPTION_WORD_IS_ASCII;
+        /// `\d`, `\p{Digit}` and `[[:digit:]]` only match ASCII
+        /// characters.
+        const REGEX_OPTION_DIGIT_IS_ASCII
+            = onig_sys::ONIG_OPTION_DIGIT_IS_ASCII;
+        /// `\s`, `\p{Space}` and `[[:space:]]` only match ASCII
+        /// characters.
+        const REGEX_OPTION_SPACE_IS_ASCII
+            = onig_sys::ONIG_OPTION_SPACE_IS_ASCII;
+        /// All POSIX brackets and `\p{...}` properties only match
+        /// ASCII characters.
+        const REGEX_OPTION_POSIX_IS_ASCII
+            = onig_sys::ONIG_OPTION_POSIX_IS_ASCII;
+        /// `\X`, `\y` and `\Y` work on extended grapheme clusters.
+        const REGEX_OPTION_TEXT_SEGMENT_EXTENDED_GRAPHEME_CLUSTER
+            = onig_sys::ONIG_OPTION_TEXT_SEGMENT_EXTENDED_GRAPHEME_CLUSTER;
+        /// `\X`, `\y` and `\Y` work on words.
+        const REGEX_OPTION_TEXT_SEGMENT_WORD
+            = onig_sys::ONIG_OPTION_TEXT_SEGMENT_WORD;
     }
 }
 
@@ -56,6 +84,128 @@ bitflags! {
         /// Try and match the pattern against the whole string.
         const SEARCH_OPTION_WHOLE_STRING
             = onig_sys::ONIG_OPTION_MATCH_WHOLE_STRING;
+        /// The region is a POSIX `regmatch_t` array. Only usable
+        /// when no `Region` is passed to the search.
+        const SEARCH_OPTION_POSIX_REGION
+            = onig_sys::ONIG_OPTION_POSIX_REGION;
+        /// Check the string is valid in the regex's encoding before
+        /// searching.
+        const SEARCH_OPTION_CHECK_VALIDITY_OF_STRING
+            = onig_sys::ONIG_OPTION_CHECK_VALIDITY_OF_STRING;
+        /// String head isn't considered as begin of string (`\A`).
+        const SEARCH_OPTION_NOT_BEGIN_STRING
+            = onig_sys::ONIG_OPTION_NOT_BEGIN_STRING;
+        /// String end isn't considered as end of string (`\z`, `\Z`).
+        const SEARCH_OPTION_NOT_END_STRING
+            = onig_sys::ONIG_OPTION_NOT_END_STRING;
+        /// Search start isn't considered as the search position (`\G`).
+        const SEARCH_OPTION_NOT_BEGIN_POSITION
+            = onig_sys::ONIG_OPTION_NOT_BEGIN_POSITION;
+        /// Call the callback set with `set_each_match_callback` for
+        /// every match found.
+        const SEARCH_OPTION_CALLBACK_EACH_MATCH
+            = onig_sys::ONIG_OPTION_CALLBACK_EACH_MATCH;
+    }
+}
+
+bitflags! {
+    /// Case folding behaviour for `REGEX_OPTION_IGNORECASE`.
+    ///
+    /// Set per regex with `RegexBuilder::case_fold`, or for every regex
+    /// compiled without explicit flags with `CaseFold::set_global_default`.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+    pub struct CaseFold: onig_sys::OnigCaseFoldType {
+        /// Only fold ASCII characters.
+        const CASE_FOLD_ASCII_ONLY
+            = onig_sys::ONIGENC_CASE_FOLD_ASCII_ONLY;
+        /// Use the Turkish and Azeri folding of dotted and dotless I.
+        /// Only some encodings support this.
+        const CASE_FOLD_TURKISH_AZERI
+            = onig_sys::ONIGENC_CASE_FOLD_TURKISH_AZERI;
+        /// Fold characters to multi character sequences (`ß` to `ss`).
+        const CASE_FOLD_MULTI_CHAR
+            = onig_sys::INTERNAL_ONIGENC_CASE_FOLD_MULTI_CHAR;
+    }
+}
+
+impl CaseFold {
+    /// Get the Default Case Fold Flags
+    ///
+    /// The flags used by regexes compiled without explicit case fold
+    /// flags. Oniguruma's initial default is `CASE_FOLD_MULTI_CHAR`.
+    pub fn global_default() -> CaseFold {
+        CaseFold::from_bits_retain(unsafe { onig_sys::onig_get_default_case_fold_flag() })
+    }
+
+    /// Set the Default Case Fold Flags
+    ///
+    /// The default is shared by every thread in the process and is
+    /// read when a regex is compiled, so regexes which have already
+    /// been compiled keep their flags. Setting it is serialised with
+    /// regex compilation.
+    ///
+    /// # Arguments
+    ///
+    /// * `case_fold` - The flags for regexes compiled without explicit
+    ///   case fold flags.
+    pub fn set_global_default(case_fold: CaseFold) -> Result<(), Error> {
+        let _guard = crate::REGEX_NEW_MUTEX.lock().unwrap();
+        let r = unsafe { onig_sys::onig_set_default_case_fold_flag(case_fold.bits()) };
+        if r == onig_sys::ONIG_NORMAL as c_int {
+            Ok(())
+        } else {
+            Err(Error::from_code(r))
+        }
+    }
+}
+
+impl RegexOptions {
+    /// Check the options can be used to compile a regex.
+    pub(crate) fn validate(self) -> Result<(), Error> {
+        let unknown = self.bits() & !Self::all().bits();
+        if unknown != 0 {
+            return Err(Error::custom(format!(
+                "Invalid regex options: {:#x} are not compile time options",
+                unknown
+            )));
+        }
+        if self.contains(Self::REGEX_OPTION_DONT_CAPTURE_GROUP | Self::REGEX_OPTION_CAPTURE_GROUP) {
+            return Err(Error::custom(
+                "Invalid regex options: DONT_CAPTURE_GROUP and CAPTURE_GROUP are exclusive",
+            ));
+        }
+        if self.contains(
+            Self::REGEX_OPTION_TEXT_SEGMENT_EXTENDED_GRAPHEME_CLUSTER
+                | Self::REGEX_OPTION_TEXT_SEGMENT_WORD,
+        ) {
+            return Err(Error::custom(
+                "Invalid regex options: only one TEXT_SEGMENT option can be set",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl SearchOptions {
+    /// Check the options can be used for a search or match.
+    ///
+    /// # Arguments
+    ///
+    /// * `has_region` - Whether a `Region` is passed to Oniguruma.
+    pub(crate) fn validate(self, has_region: bool) -> Result<(), Error> {
+        let unknown = self.bits() & !Self::all().bits();
+        if unknown != 0 {
+            return Err(Error::custom(format!(
+                "Invalid search options: {:#x} are not search time options",
+                unknown
+            )));
+        }
+        if has_region && self.contains(Self::SEARCH_OPTION_POSIX_REGION) {
+            return Err(Error::custom(
+                "Invalid search options: POSIX_REGION can't be used with a Region",
+            ));
+        }
+        Ok(())
     }
 }
 
@@ -75,9 +225,15 @@ bitflags! {
         /// `?`
         const SYNTAX_OPERATOR_QMARK_ZERO_ONE
             = (onig_sys::ONIG_SYN_OP_QMARK_ZERO_ONE as u64);
-    /// ?P
-    const SYNTAX_OPERATOR_QMARK_CAPITAL_P_NAME
-        = (onig_sys::ONIG_SYN_OP2_QMARK_CAPITAL_P_NAME as u64);
+        /// `\*`
+        const SYNTAX_OPERATOR_ESC_ASTERISK_ZERO_INF
+            = (onig_sys::ONIG_SYN_OP_ESC_ASTERISK_ZERO_INF as u64);
+        /// `\+`
+        const SYNTAX_OPERATOR_ESC_PLUS_ONE_INF
+            = (onig_sys::ONIG_SYN_OP_ESC_PLUS_ONE_INF as u64);
+        /// `\?`
+        const SYNTAX_OPERATOR_ESC_QMARK_ZERO_ONE
+            = (onig_sys::ONIG_SYN_OP_ESC_QMARK_ZERO_ONE as u64);
         /// `{lower,upper}`
         const SYNTAX_OPERATOR_BRACE_INTERVAL
             = (onig_sys::ONIG_SYN_OP_BRACE_INTERVAL as u64);
@@ -147,6 +303,9 @@ bitflags! {
         /// `\x{7HHHHHHH}`
         const SYNTAX_OPERATOR_ESC_X_BRACE_HEX8
             = (onig_sys::ONIG_SYN_OP_ESC_X_BRACE_HEX8 as u64);
+        /// `\o{1OOOOOOOOOO}`
+        const SYNTAX_OPERATOR_ESC_O_BRACE_OCTAL
+            = (onig_sys::ONIG_SYN_OP_ESC_O_BRACE_OCTAL as u64);
         /// Variable meta characters
         const SYNTAX_OPERATOR_VARIABLE_META_CHARACTERS
             = (onig_sys::ONIG_SYN_OP_VARIABLE_META_CHARACTERS as u64);
@@ -210,6 +369,39 @@ bitflags! {
         /// `\`
         const SYNTAX_OPERATOR_INEFFECTIVE_ESCAPE
             = (onig_sys::ONIG_SYN_OP2_INEFFECTIVE_ESCAPE as u64) << 32;
+        /// `(?(cond)yes|no)`
+        const SYNTAX_OPERATOR_QMARK_LPAREN_IF_ELSE
+            = (onig_sys::ONIG_SYN_OP2_QMARK_LPAREN_IF_ELSE as u64) << 32;
+        /// `\K`
+        const SYNTAX_OPERATOR_ESC_CAPITAL_K_KEEP
+            = (onig_sys::ONIG_SYN_OP2_ESC_CAPITAL_K_KEEP as u64) << 32;
+        /// `\R`
+        const SYNTAX_OPERATOR_ESC_CAPITAL_R_GENERAL_NEWLINE
+            = (onig_sys::ONIG_SYN_OP2_ESC_CAPITAL_R_GENERAL_NEWLINE as u64) << 32;
+        /// `\N, \O`
+        const SYNTAX_OPERATOR_ESC_CAPITAL_N_O_SUPER_DOT
+            = (onig_sys::ONIG_SYN_OP2_ESC_CAPITAL_N_O_SUPER_DOT as u64) << 32;
+        /// `(?~...)`
+        const SYNTAX_OPERATOR_QMARK_TILDE_ABSENT_GROUP
+            = (onig_sys::ONIG_SYN_OP2_QMARK_TILDE_ABSENT_GROUP as u64) << 32;
+        /// `\X, \y, \Y`
+        const SYNTAX_OPERATOR_ESC_X_Y_TEXT_SEGMENT
+            = (onig_sys::ONIG_SYN_OP2_ESC_X_Y_TEXT_SEGMENT as u64) << 32;
+        /// `(?&name), (?n), (?R), (?0)`
+        const SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL
+            = (onig_sys::ONIG_SYN_OP2_QMARK_PERL_SUBEXP_CALL as u64) << 32;
+        /// `(?{...}), (?{{...}})`
+        const SYNTAX_OPERATOR_QMARK_BRACE_CALLOUT_CONTENTS
+            = (onig_sys::ONIG_SYN_OP2_QMARK_BRACE_CALLOUT_CONTENTS as u64) << 32;
+        /// `(*name), (*name{args})`
+        const SYNTAX_OPERATOR_ASTERISK_CALLOUT_NAME
+            = (onig_sys::ONIG_SYN_OP2_ASTERISK_CALLOUT_NAME as u64) << 32;
+        /// `(?imxWDSPy), (?-imxWDSP)`
+        const SYNTAX_OPERATOR_OPTION_ONIGURUMA
+            = (onig_sys::ONIG_SYN_OP2_OPTION_ONIGURUMA as u64) << 32;
+        /// `(?P<name>...), (?P=name), (?P>name)`
+        const SYNTAX_OPERATOR_QMARK_CAPITAL_P_NAME
+            = (onig_sys::ONIG_SYN_OP2_QMARK_CAPITAL_P_NAME as u64) << 32;
     }
 }
 
@@ -217,6 +409,9 @@ bitflags! {
     /// Defines the behaviour of regex operators.
     #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
     pub struct SyntaxBehavior: onig_sys::OnigSyntaxBehavior {
+        /// `^, $` anywhere in the pattern. Not implemented by Oniguruma
+        const SYNTAX_BEHAVIOR_CONTEXT_INDEP_ANCHORS
+            = onig_sys::ONIG_SYN_CONTEXT_INDEP_ANCHORS;
         /// `?, *, +, {n,m}`
         const SYNTAX_BEHAVIOR_CONTEXT_INDEP_REPEAT_OPS
             = onig_sys::ONIG_SYN_CONTEXT_INDEP_REPEAT_OPS;
@@ -247,15 +442,33 @@ bitflags! {
         /// `a{n}?=(?:a{n})?`
         const SYNTAX_BEHAVIOR_FIXED_INTERVAL_IS_GREEDY_ONLY
             = onig_sys::ONIG_SYN_FIXED_INTERVAL_IS_GREEDY_ONLY;
+        /// `a(?i)b|c` also ignores case in `c`
+        const SYNTAX_BEHAVIOR_ISOLATED_OPTION_CONTINUE_BRANCH
+            = onig_sys::ONIG_SYN_ISOLATED_OPTION_CONTINUE_BRANCH;
+        /// `(?<=a|bc)`, `(?<=a+)`
+        const SYNTAX_BEHAVIOR_VARIABLE_LEN_LOOK_BEHIND
+            = onig_sys::ONIG_SYN_VARIABLE_LEN_LOOK_BEHIND;
+        /// Python quirks, e.g. `\Z` only matches at the very end
+        const SYNTAX_BEHAVIOR_PYTHON
+            = onig_sys::ONIG_SYN_PYTHON;
+        /// `(?C)`, `(?I)`, `(?L)` whole pattern options
+        const SYNTAX_BEHAVIOR_WHOLE_OPTIONS
+            = onig_sys::ONIG_SYN_WHOLE_OPTIONS;
         /// `[^...]`
         const SYNTAX_BEHAVIOR_NOT_NEWLINE_IN_NEGATIVE_CC
             = onig_sys::ONIG_SYN_NOT_NEWLINE_IN_NEGATIVE_CC;
         /// `[..\w..] etc..`
         const SYNTAX_BEHAVIOR_BACKSLASH_ESCAPE_IN_CC
             = onig_sys::ONIG_SYN_BACKSLASH_ESCAPE_IN_CC;
+        /// `[b-a]` is empty rather than an error
+        const SYNTAX_BEHAVIOR_ALLOW_EMPTY_RANGE_IN_CC
+            = onig_sys::ONIG_SYN_ALLOW_EMPTY_RANGE_IN_CC;
         /// `[0-9-a]=[0-9\-a]`
         const SYNTAX_BEHAVIOR_ALLOW_DOUBLE_RANGE_OP_IN_CC
             = onig_sys::ONIG_SYN_ALLOW_DOUBLE_RANGE_OP_IN_CC;
+        /// `[a-\x{7fffffff}]` is allowed
+        const SYNTAX_BEHAVIOR_ALLOW_INVALID_CODE_END_OF_RANGE_IN_CC
+            = onig_sys::ONIG_SYN_ALLOW_INVALID_CODE_END_OF_RANGE_IN_CC;
         /// `[,-,]`
         const SYNTAX_BEHAVIOR_WARN_CC_OP_NOT_ESCAPED
             = onig_sys::ONIG_SYN_WARN_CC_OP_NOT_ESCAPED;
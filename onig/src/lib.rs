@@ -89,50 +89,75 @@
 
 use once_cell::sync::Lazy;
 
+mod budget;
 mod buffers;
+mod builder;
+mod callout;
+mod encoding;
+mod error;
+mod escape;
 mod find;
 mod flags;
 mod match_param;
 mod names;
 mod region;
+mod regset;
 mod replace;
+mod source;
 mod syntax;
+mod transcode;
+mod translate;
 mod tree;
 mod utils;
 
 #[cfg(feature = "std-pattern")]
 mod pattern;
 
+pub mod ast;
+pub mod bytes;
+pub mod cache;
+pub mod limits;
+pub mod stream;
+
 // re-export the onig types publically
-pub use crate::buffers::{EncodedBytes, EncodedChars};
+pub use crate::budget::Budget;
+pub use crate::buffers::{EncodedBytes, EncodedChars, EncodedUtf16, EncodedUtf32};
+pub use crate::builder::RegexBuilder;
+pub use crate::callout::{
+    clear_each_match_callback, set_each_match_callback, CalloutArgType, CalloutArgs, CalloutIn,
+    CalloutResult, CalloutValue, EachMatch, NamedCallout,
+};
+pub use crate::encoding::Encoding;
+pub use crate::error::{Error, ErrorKind};
+pub use crate::escape::escape;
 pub use crate::find::{
-    Captures, FindCaptures, FindMatches, RegexSplits, RegexSplitsN, SubCaptures, SubCapturesPos,
+    Captures, FindCaptures, FindMatches, RegexSplits, RegexSplitsN, SearchText, SubCaptures,
+    SubCapturesNamed, SubCapturesPos, TryFindCaptures, TryFindMatches, TryRegexSplits,
+    TryRegexSplitsN,
 };
 pub use crate::flags::*;
 pub use crate::match_param::MatchParam;
+pub use crate::names::CaptureNames;
 pub use crate::region::Region;
-pub use crate::replace::Replacer;
+pub use crate::regset::{
+    RegexSet, RegexSetCaptures, RegexSetLead, RegexSetMatches, TryRegexSetCaptures,
+    TryRegexSetMatches,
+};
+pub use crate::replace::{NoExpand, Replacer};
 pub use crate::syntax::{MetaChar, Syntax};
-pub use crate::tree::{CaptureTreeNode, CaptureTreeNodeIter};
+pub use crate::translate::translate;
+pub use crate::tree::{
+    CaptureHistory, CaptureHistoryBreadthFirst, CaptureHistoryDepthFirst, CaptureHistoryNode,
+    CaptureTreeNode, CaptureTreeNodeIter,
+};
 pub use crate::utils::{copyright, define_user_property, version};
 
+use std::fmt;
 use std::marker::PhantomData;
-use std::os::raw::c_int;
-use std::ptr::{null, null_mut};
+use std::ptr::null_mut;
 use std::sync::Mutex;
-use std::{error, fmt, str};
 
-#[derive(Debug)]
-enum ErrorData {
-    OnigError(c_int),
-    Custom,
-}
-
-/// This struture represents an error from the underlying Oniguruma libray.
-pub struct Error {
-    data: ErrorData,
-    description: String,
-}
+use crate::source::Source;
 
 /// This struct is a wrapper around an Oniguruma regular expression
 /// pointer. This represents a compiled regex which can be used in
@@ -143,77 +168,24 @@ pub struct Error {
 /// When using the default (static) syntax via [`Regex::new`] or
 /// [`Regex::with_encoding`], the lifetime is `'static` and imposes no
 /// additional restriction.
-#[derive(Debug, Eq, PartialEq)]
+///
+/// Regexes compare equal when they were compiled from the same pattern
+/// with the same encodings, options, case fold flags, syntax and
+/// budget. Cloning a regex compiles its pattern again.
+#[derive(Debug)]
 pub struct Regex<'syntax> {
     raw: onig_sys::OnigRegex,
+    source: Source,
+    budget: Option<Budget>,
+    /// A copy of the syntax made by `RegexBuilder::capture_history`,
+    /// which Oniguruma points into.
+    owned_syntax: Option<Box<Syntax>>,
     _syntax: PhantomData<&'syntax Syntax>,
 }
 
 unsafe impl<'syntax> Send for Regex<'syntax> {}
 unsafe impl<'syntax> Sync for Regex<'syntax> {}
 
-impl Error {
-    fn from_code_and_info(code: c_int, info: &onig_sys::OnigErrorInfo) -> Self {
-        Error::new(code, info)
-    }
-
-    fn from_code(code: c_int) -> Self {
-        Error::new(code, null())
-    }
-
-    fn custom<T: Into<String>>(message: T) -> Self {
-        Error {
-            data: ErrorData::Custom,
-            description: message.into(),
-        }
-    }
-
-    fn new(code: c_int, info: *const onig_sys::OnigErrorInfo) -> Self {
-        let buff = &mut [0; onig_sys::ONIG_MAX_ERROR_MESSAGE_LEN as usize];
-        let len = unsafe { onig_sys::onig_error_code_to_str(buff.as_mut_ptr(), code, info) };
-        let description = if let Ok(description) = str::from_utf8(&buff[..len as usize]) {
-            description
-        } else {
-            return Self::custom("Onig error string was invalid UTF-8");
-        };
-        Error {
-            data: ErrorData::OnigError(code),
-            description: description.to_owned(),
-        }
-    }
-
-    /// Return Oniguruma engine error code.
-    pub fn code(&self) -> i32 {
-        match self.data {
-            ErrorData::OnigError(code) => code,
-            _ => -1,
-        }
-    }
-
-    /// Return error description provided by Oniguruma engine.
-    pub fn description(&self) -> &str {
-        &self.description
-    }
-}
-
-impl error::Error for Error {
-    fn description(&self) -> &str {
-        &self.description
-    }
-}
-
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Oniguruma error: {}", self.description())
-    }
-}
-
-impl fmt::Debug for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error({:?}, {})", self.data, self.description())
-    }
-}
-
 static REGEX_NEW_MUTEX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
 impl<'syntax> Regex<'syntax> {
@@ -344,42 +316,160 @@ impl<'syntax> Regex<'syntax> {
     where
         T: EncodedChars,
     {
-        // Convert the rust types to those required for the call to
-        // `onig_new`.
-        let mut reg: onig_sys::OnigRegex = null_mut();
-        let reg_ptr = &mut reg as *mut onig_sys::OnigRegex;
-
-        // We can use this later to get an error message to pass back
-        // if regex creation fails.
-        let mut error = onig_sys::OnigErrorInfo {
-            enc: null_mut(),
-            par: null_mut(),
-            par_end: null_mut(),
-        };
+        // Grab a lock to make sure that `onig_new` isn't called by
+        // more than one thread at a time.
+        let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+        Regex::compile_locked(pattern, option, syntax)
+    }
+
+    /// Create a Regex Which Searches Another Encoding
+    ///
+    /// Attempts to compile `pattern` into a new `Regex` which searches
+    /// text in the `target` encoding. The pattern is transcoded from
+    /// its own encoding into `target`, so UTF-8 patterns, such as Rust
+    /// string literals, can be used to search UTF-16 and UTF-32 text.
+    /// Pure ASCII patterns can target any ASCII compatible encoding.
+    ///
+    /// Haystacks passed to searches must be in the `target` encoding,
+    /// which is returned by [`Regex::encoding`]. Use a
+    /// [`RegexBuilder`] to set the case fold flags too.
+    ///
+    /// # Arguments
+    ///
+    ///  * `pattern` - The regex pattern to compile.
+    ///  * `target`  - The encoding of the text the regex will search.
+    ///  * `option`  - The regex compilation options.
+    ///  * `syntax`  - The syntax which the regex is written in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::{EncodedBytes, Encoding, Regex, RegexOptions, SearchOptions, Syntax};
+    ///
+    /// let r = Regex::with_target_encoding(
+    ///     "wörld",
+    ///     Encoding::UTF16_LE,
+    ///     RegexOptions::REGEX_OPTION_NONE,
+    ///     Syntax::default(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(r.encoding(), Encoding::UTF16_LE);
+    ///
+    /// let utf16: Vec<u8> = "hello wörld".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    /// let text = EncodedBytes::from_parts(&utf16, Encoding::UTF16_LE);
+    /// let pos = r.search_with_encoding(text, 0, utf16.len(), SearchOptions::SEARCH_OPTION_NONE, None);
+    /// assert_eq!(pos, Some(12));
+    /// ```
+    pub fn with_target_encoding<'s, T>(
+        pattern: T,
+        target: Encoding,
+        option: RegexOptions,
+        syntax: &'s Syntax,
+    ) -> Result<Regex<'s>, Error>
+    where
+        T: EncodedChars,
+    {
+        let encoding = pattern.encoding();
+        let pattern = unsafe { std::slice::from_raw_parts(pattern.start_ptr(), pattern.len()) };
+        let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+        Regex::compile_deluxe_locked(pattern, encoding, target, option, None, syntax)
+    }
+
+    /// Compile a Regex While Holding the Lock
+    ///
+    /// The body of `with_options_and_encoding`. The caller must hold
+    /// `REGEX_NEW_MUTEX`.
+    fn compile_locked<'s, T>(
+        pattern: T,
+        option: RegexOptions,
+        syntax: &'s Syntax,
+    ) -> Result<Regex<'s>, Error>
+    where
+        T: EncodedChars,
+    {
+        let encoding = pattern.encoding();
+        let pattern = unsafe { std::slice::from_raw_parts(pattern.start_ptr(), pattern.len()) };
+        Regex::compile_deluxe_locked(pattern, encoding, encoding, option, None, syntax)
+    }
 
-        let err = unsafe {
-            // Grab a lock to make sure that `onig_new` isn't called by
-            // more than one thread at a time.
-            let _guard = REGEX_NEW_MUTEX.lock().unwrap();
-            onig_sys::onig_new(
-                reg_ptr,
-                pattern.start_ptr(),
-                pattern.limit_ptr(),
-                option.bits(),
-                pattern.encoding(),
-                syntax as *const Syntax as *mut Syntax as *mut onig_sys::OnigSyntaxType,
-                &mut error,
+    /// Compile a Regex With `onig_new_deluxe` While Holding the Lock
+    ///
+    /// The caller must hold `REGEX_NEW_MUTEX`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern bytes, in `pattern_encoding`.
+    /// * `pattern_encoding` - The encoding the pattern is written in.
+    /// * `target_encoding` - The encoding of the strings to be searched.
+    ///   If this differs from `pattern_encoding` the pattern is
+    ///   transcoded, and any error span refers to the original pattern.
+    /// * `option` - The regex compilation options.
+    /// * `case_fold` - The case fold flags, or `None` for Oniguruma's
+    ///   default.
+    /// * `syntax` - The syntax which the regex is written in.
+    pub(crate) fn compile_deluxe_locked<'s>(
+        pattern: &[u8],
+        pattern_encoding: Encoding,
+        target_encoding: Encoding,
+        option: RegexOptions,
+        case_fold: Option<CaseFold>,
+        syntax: &'s Syntax,
+    ) -> Result<Regex<'s>, Error> {
+        option.validate()?;
+
+        let case_fold = case_fold.unwrap_or_else(CaseFold::global_default);
+
+        // Oniguruma can't compile a pattern for another encoding, so
+        // transcode it ourselves.
+        let raw = if pattern_encoding != target_encoding {
+            let transcoded = transcode::transcode(pattern, pattern_encoding, target_encoding)?;
+            new_deluxe(
+                &transcoded.bytes,
+                target_encoding,
+                option,
+                case_fold,
+                syntax,
             )
+            .map_err(|e| e.map_span(|pos| transcoded.source_offset(pos)))?
+        } else {
+            new_deluxe(pattern, target_encoding, option, case_fold, syntax)?
         };
 
-        if err == onig_sys::ONIG_NORMAL as i32 {
-            Ok(Regex {
-                raw: reg,
-                _syntax: PhantomData,
-            })
-        } else {
-            Err(Error::from_code_and_info(err, &error))
-        }
+        Ok(Regex {
+            raw,
+            source: Source::new(
+                pattern,
+                pattern_encoding,
+                target_encoding,
+                option,
+                case_fold,
+            ),
+            budget: None,
+            owned_syntax: None,
+            _syntax: PhantomData,
+        })
+    }
+
+    /// Compile a Regex Which Owns its Syntax
+    ///
+    /// Calls `compile` with `syntax`, and keeps the syntax alive for as
+    /// long as the compiled regex. The caller must hold
+    /// `REGEX_NEW_MUTEX`.
+    pub(crate) fn compile_owning_syntax<'s, F>(
+        syntax: Syntax,
+        compile: F,
+    ) -> Result<Regex<'s>, Error>
+    where
+        F: FnOnce(&'s Syntax) -> Result<Regex<'s>, Error>,
+    {
+        let syntax = Box::new(syntax);
+        // The box's contents don't move when the box does, so the
+        // pointer Oniguruma keeps stays valid until the regex is
+        // dropped, which frees the regex before its fields.
+        let borrowed = unsafe { &*(&*syntax as *const Syntax) };
+        let mut regex = compile(borrowed)?;
+        regex.owned_syntax = Some(syntax);
+        Ok(regex)
     }
 
     /// Match String
@@ -423,6 +513,27 @@ impl<'syntax> Regex<'syntax> {
         self.match_with_encoding(str, at, options, region)
     }
 
+    /// Match String, Reporting Errors
+    ///
+    /// Works the same way as `match_with_options`, but returns an
+    /// `Error` if matching fails, e.g. because a resource limit was
+    /// exceeded, rather than panicking.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Some(len))` if the regex matched, with `len` being the number
+    /// of bytes matched. `Ok(None)` if the regex doesn't match. `Err`
+    /// with an `Error` if an error occurred.
+    pub fn try_match_with_options(
+        &self,
+        str: &str,
+        at: usize,
+        options: SearchOptions,
+        region: Option<&mut Region>,
+    ) -> Result<Option<usize>, Error> {
+        self.match_with_param(str, at, options, region, self.default_match_param())
+    }
+
     /// Match String with Encoding
     ///
     /// Match the regex against a string. This method will start at
@@ -470,7 +581,7 @@ impl<'syntax> Regex<'syntax> {
     where
         T: EncodedChars,
     {
-        let match_param = MatchParam::default();
+        let match_param = self.default_match_param();
         let result = self.match_with_param(chars, at, options, region, match_param);
 
         match result {
@@ -525,18 +636,15 @@ impl<'syntax> Regex<'syntax> {
         at: usize,
         options: SearchOptions,
         region: Option<&mut Region>,
-        match_param: MatchParam,
+        mut match_param: MatchParam,
     ) -> Result<Option<usize>, Error>
     where
         T: EncodedChars,
     {
         if chars.encoding() != self.encoding() {
-            return Err(Error::custom(format!(
-                "Regex encoding does not match haystack encoding ({0:?}, {1:?})",
-                chars.encoding(),
-                self.encoding()
-            )));
+            return Err(Error::encoding_mismatch(chars.encoding(), self.encoding()));
         }
+        options.validate(region.is_some())?;
         let r = unsafe {
             let offset = chars.start_ptr().add(at);
             if offset > chars.limit_ptr() {
@@ -555,6 +663,7 @@ impl<'syntax> Regex<'syntax> {
                 match_param.as_raw(),
             )
         };
+        match_param.resume_callout_panic();
 
         if r >= 0 {
             Ok(Some(r as usize))
@@ -610,6 +719,41 @@ impl<'syntax> Regex<'syntax> {
         self.search_with_encoding(str, from, to, options, region)
     }
 
+    /// Search Pattern in String, Reporting Errors
+    ///
+    /// Works the same way as `search_with_options`, but returns an
+    /// `Error` if the search fails, e.g. because a resource limit was
+    /// exceeded, rather than panicking.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Some(pos))` if the regex matches, where `pos` is the
+    /// byte-position of the start of the match. `Ok(None)` if the regex
+    /// doesn't match anywhere in `str`. `Err` with an `Error` if an
+    /// error occurred.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::{Regex, SearchOptions};
+    ///
+    /// let r = Regex::new("l{1,2}").unwrap();
+    /// let res = r.try_search_with_options("hello", 0, 5, SearchOptions::SEARCH_OPTION_NONE, None);
+    /// assert_eq!(res.unwrap(), Some(2));
+    /// let res = r.try_search_with_options("hello", 6, 5, SearchOptions::SEARCH_OPTION_NONE, None);
+    /// assert!(res.is_err());
+    /// ```
+    pub fn try_search_with_options(
+        &self,
+        str: &str,
+        from: usize,
+        to: usize,
+        options: SearchOptions,
+        region: Option<&mut Region>,
+    ) -> Result<Option<usize>, Error> {
+        self.search_with_param(str, from, to, options, region, self.default_match_param())
+    }
+
     /// Search for a Pattern in a String with an Encoding
     ///
     /// Search for matches the regex in a string. This method will
@@ -660,7 +804,7 @@ impl<'syntax> Regex<'syntax> {
     where
         T: EncodedChars,
     {
-        let match_param = MatchParam::default();
+        let match_param = self.default_match_param();
         let result = self.search_with_param(chars, from, to, options, region, match_param);
 
         match result {
@@ -718,19 +862,16 @@ impl<'syntax> Regex<'syntax> {
         to: usize,
         options: SearchOptions,
         region: Option<&mut Region>,
-        match_param: MatchParam,
+        mut match_param: MatchParam,
     ) -> Result<Option<usize>, Error>
     where
         T: EncodedChars,
     {
         let (beg, end) = (chars.start_ptr(), chars.limit_ptr());
         if chars.encoding() != self.encoding() {
-            return Err(Error::custom(format!(
-                "Regex encoding does not match haystack encoding ({0:?}, {1:?})",
-                chars.encoding(),
-                self.encoding()
-            )));
+            return Err(Error::encoding_mismatch(chars.encoding(), self.encoding()));
         }
+        options.validate(region.is_some())?;
         let r = unsafe {
             let start = beg.add(from);
             let range = beg.add(to);
@@ -754,6 +895,7 @@ impl<'syntax> Regex<'syntax> {
                 match_param.as_raw(),
             )
         };
+        match_param.resume_callout_panic();
 
         if r >= 0 {
             Ok(Some(r as usize))
@@ -781,6 +923,15 @@ impl<'syntax> Regex<'syntax> {
             .unwrap_or(false)
     }
 
+    /// Returns true if and only if the regex matches the whole string,
+    /// like `is_match`, but returns an `Error` if matching fails rather
+    /// than panicking.
+    pub fn try_is_match(&self, text: &str) -> Result<bool, Error> {
+        let r =
+            self.try_match_with_options(text, 0, SearchOptions::SEARCH_OPTION_WHOLE_STRING, None)?;
+        Ok(r == Some(text.len()))
+    }
+
     /// Find a Match in a Buffer, With Encoding
     ///
     /// Finds the first match of the regular expression within the
@@ -803,6 +954,28 @@ impl<'syntax> Regex<'syntax> {
         self.find_with_encoding(text)
     }
 
+    /// Find a Match in a String, Reporting Errors
+    ///
+    /// Finds the first match of the regular expression within the
+    /// string, like `find`, but returns an `Error` if the search fails
+    /// rather than panicking.
+    ///
+    /// # Returns
+    ///
+    ///  The offset of the start and end of the first match, or `None`
+    ///  if no match exists.
+    pub fn try_find(&self, text: &str) -> Result<Option<(usize, usize)>, Error> {
+        let mut region = Region::new();
+        let r = self.try_search_with_options(
+            text,
+            0,
+            text.len(),
+            SearchOptions::SEARCH_OPTION_NONE,
+            Some(&mut region),
+        )?;
+        Ok(r.and_then(|_| region.pos(0)))
+    }
+
     /// Find a Match in a Buffer, With Encoding
     ///
     /// Finds the first match of the regular expression within the
@@ -838,10 +1011,65 @@ impl<'syntax> Regex<'syntax> {
     ///
     /// # Returns
     ///
-    /// Returns a reference to an oniguruma encoding which was used
-    /// when this regex was created.
-    pub fn encoding(&self) -> onig_sys::OnigEncoding {
-        unsafe { onig_sys::onig_get_encoding(self.raw) }
+    /// Returns the encoding of the text this regex searches. This is
+    /// the target encoding the regex was created with, which may differ
+    /// from the encoding the pattern was written in.
+    pub fn encoding(&self) -> Encoding {
+        unsafe { Encoding::from_raw(onig_sys::onig_get_encoding(self.raw)) }
+    }
+
+    /// Get the Pattern of the Regex
+    ///
+    /// Returns the pattern this regex was compiled from, as it was
+    /// written. Patterns which aren't valid UTF-8, such as those
+    /// compiled from an `EncodedBytes` in another encoding, are
+    /// converted lossily. The `Display` implementation writes the same
+    /// string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onig::Regex;
+    /// let r = Regex::new(r"\d+").unwrap();
+    /// assert_eq!(r.as_str(), r"\d+");
+    /// assert_eq!(r.to_string(), r"\d+");
+    /// ```
+    pub fn as_str(&self) -> &str {
+        self.source.as_str()
+    }
+
+    /// Get the Options of the Regex
+    ///
+    /// Returns the options the regex was compiled with, including any
+    /// which were enabled by its syntax.
+    pub fn options(&self) -> RegexOptions {
+        RegexOptions::from_bits_retain(unsafe { onig_sys::onig_get_options(self.raw) })
+    }
+
+    /// Get the Syntax of the Regex
+    ///
+    /// Returns the syntax the regex was compiled with.
+    pub fn syntax(&self) -> &Syntax {
+        // The regex either borrows the syntax it was compiled with for
+        // `'syntax` or owns it, and `Syntax` wraps `OnigSyntaxType`.
+        unsafe { &*(onig_sys::onig_get_syntax(self.raw) as *const Syntax) }
+    }
+
+    /// Do Unnamed Groups Capture?
+    ///
+    /// Returns `true` if plain, unnamed groups in the pattern are
+    /// capture groups. Depending on the syntax and options, unnamed
+    /// groups stop capturing when the pattern contains named groups.
+    pub fn noname_group_capture_is_active(&self) -> bool {
+        unsafe { onig_sys::onig_noname_group_capture_is_active(self.raw) != 0 }
+    }
+
+    /// Get the Case Fold Flags of the Regex
+    ///
+    /// Returns the flags which control how characters are folded when
+    /// the regex is case insensitive.
+    pub fn case_fold_flag(&self) -> CaseFold {
+        CaseFold::from_bits_retain(unsafe { onig_sys::onig_get_case_fold_flag(self.raw) })
     }
 
     /// Get the Number of Capture Groups in this Pattern
@@ -863,6 +1091,104 @@ impl<'syntax> Drop for Regex<'syntax> {
     }
 }
 
+/// Call `onig_new_deluxe` with a pattern in the target encoding.
+fn new_deluxe(
+    pattern: &[u8],
+    encoding: Encoding,
+    option: RegexOptions,
+    case_fold: CaseFold,
+    syntax: &Syntax,
+) -> Result<onig_sys::OnigRegex, Error> {
+    // Convert the rust types to those required for the call to
+    // `onig_new_deluxe`.
+    let mut reg: onig_sys::OnigRegex = null_mut();
+    let reg_ptr = &mut reg as *mut onig_sys::OnigRegex;
+    let mut info = onig_sys::OnigCompileInfo {
+        num_of_elements: 5,
+        pattern_enc: encoding.as_raw(),
+        target_enc: encoding.as_raw(),
+        syntax: syntax as *const Syntax as *mut Syntax as *mut onig_sys::OnigSyntaxType,
+        option: option.bits(),
+        case_fold_flag: case_fold.bits(),
+    };
+
+    // We can use this later to get an error message to pass back
+    // if regex creation fails.
+    let mut error = onig_sys::OnigErrorInfo {
+        enc: null_mut(),
+        par: null_mut(),
+        par_end: null_mut(),
+    };
+
+    let err = unsafe {
+        onig_sys::onig_new_deluxe(
+            reg_ptr,
+            pattern.as_ptr(),
+            pattern.as_ptr().add(pattern.len()),
+            &mut info,
+            &mut error,
+        )
+    };
+
+    if err == onig_sys::ONIG_NORMAL as i32 {
+        Ok(reg)
+    } else {
+        Err(Error::from_code_and_info(err, &error, pattern))
+    }
+}
+
+impl<'syntax> Clone for Regex<'syntax> {
+    /// Compile the regex again, from the pattern and settings it was
+    /// compiled with.
+    ///
+    /// # Panics
+    ///
+    /// If Oniguruma fails to compile the pattern, which should only
+    /// happen if it runs out of memory.
+    fn clone(&self) -> Self {
+        let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+        // The pattern compiled within the limits in force at the time.
+        // Lift them so the clone doesn't fail if they've been lowered.
+        let mut regex =
+            limits::with_compile_limits(u32::MAX, onig_sys::ONIG_MAX_CAPTURE_NUM, || {
+                match &self.owned_syntax {
+                    Some(syntax) => Regex::compile_owning_syntax(**syntax, |syntax| {
+                        self.source.compile_locked(syntax)
+                    }),
+                    // Without a copy of its own, the regex borrows its
+                    // syntax for `'syntax`.
+                    None => self.source.compile_locked(unsafe {
+                        &*(onig_sys::onig_get_syntax(self.raw) as *const Syntax)
+                    }),
+                }
+            })
+            .expect("Onig: Failed to recompile a cloned regex");
+        regex.budget = self.budget;
+        regex
+    }
+}
+
+impl<'syntax> PartialEq for Regex<'syntax> {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+            && match (&self.owned_syntax, &other.owned_syntax) {
+                (Some(syntax), Some(other)) => syntax.same_settings(other),
+                (None, None) => std::ptr::eq(self.syntax(), other.syntax()),
+                _ => false,
+            }
+            && self.budget == other.budget
+    }
+}
+
+impl<'syntax> Eq for Regex<'syntax> {}
+
+impl<'syntax> fmt::Display for Regex<'syntax> {
+    /// Writes the pattern the regex was compiled from.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1099,8 +1425,7 @@ mod tests {
         // while the Syntax is alive.
         let mut custom_syntax = *Syntax::default();
         let regex =
-            Regex::with_options("hello", RegexOptions::REGEX_OPTION_NONE, &custom_syntax)
-                .unwrap();
+            Regex::with_options("hello", RegexOptions::REGEX_OPTION_NONE, &custom_syntax).unwrap();
         assert!(regex.is_match("hello"));
         // regex must be dropped before custom_syntax goes out of scope.
         // The compiler enforces this via the `'syntax` lifetime on `Regex`.
@@ -1108,4 +1433,298 @@ mod tests {
         // Now it's safe to modify/drop custom_syntax.
         custom_syntax.enable_operators(SyntaxOperator::SYNTAX_OPERATOR_DOT_ANYCHAR);
     }
+
+    fn find_with(pattern: &str, options: RegexOptions, text: &str) -> Option<(usize, usize)> {
+        Regex::with_options(pattern, options, Syntax::default())
+            .unwrap()
+            .find(text)
+    }
+
+    #[test]
+    fn test_ascii_only_options() {
+        assert_eq!(
+            find_with(r"\w+", RegexOptions::REGEX_OPTION_NONE, "café"),
+            Some((0, 5))
+        );
+        assert_eq!(
+            find_with(r"\w+", RegexOptions::REGEX_OPTION_WORD_IS_ASCII, "café"),
+            Some((0, 3))
+        );
+        assert_eq!(
+            find_with(r"\d", RegexOptions::REGEX_OPTION_NONE, "x٣"),
+            Some((1, 3))
+        );
+        assert_eq!(
+            find_with(r"\d", RegexOptions::REGEX_OPTION_DIGIT_IS_ASCII, "x٣"),
+            None
+        );
+        assert_eq!(
+            find_with(r"\s", RegexOptions::REGEX_OPTION_NONE, "x\u{3000}"),
+            Some((1, 4))
+        );
+        assert_eq!(
+            find_with(
+                r"\s",
+                RegexOptions::REGEX_OPTION_SPACE_IS_ASCII,
+                "x\u{3000}"
+            ),
+            None
+        );
+        assert_eq!(
+            find_with(r"[[:alpha:]]", RegexOptions::REGEX_OPTION_NONE, "é"),
+            Some((0, 2))
+        );
+        assert_eq!(
+            find_with(
+                r"[[:alpha:]]",
+                RegexOptions::REGEX_OPTION_POSIX_IS_ASCII,
+                "é"
+            ),
+            None
+        );
+        assert_eq!(
+            find_with("é", RegexOptions::REGEX_OPTION_IGNORECASE, "É"),
+            Some((0, 2))
+        );
+        assert_eq!(
+            find_with("é", RegexOptions::REGEX_OPTION_IGNORECASE_IS_ASCII, "É"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_text_segment_options() {
+        let grapheme = RegexOptions::REGEX_OPTION_TEXT_SEGMENT_EXTENDED_GRAPHEME_CLUSTER;
+        let word = RegexOptions::REGEX_OPTION_TEXT_SEGMENT_WORD;
+        assert_eq!(find_with(r"\X", grapheme, "e\u{301}x"), Some((0, 3)));
+        assert_eq!(find_with(r"\X", grapheme, "hello world"), Some((0, 1)));
+        assert_eq!(find_with(r"\X", word, "hello world"), Some((0, 5)));
+        assert_eq!(find_with(r"a\y", grapheme, "ab"), Some((0, 1)));
+        assert_eq!(find_with(r"a\y", word, "ab"), None);
+    }
+
+    #[test]
+    fn test_invalid_regex_options() {
+        let both_segments = RegexOptions::REGEX_OPTION_TEXT_SEGMENT_WORD
+            | RegexOptions::REGEX_OPTION_TEXT_SEGMENT_EXTENDED_GRAPHEME_CLUSTER;
+        assert!(Regex::with_options("a", both_segments, Syntax::default()).is_err());
+        let both_captures = RegexOptions::REGEX_OPTION_CAPTURE_GROUP
+            | RegexOptions::REGEX_OPTION_DONT_CAPTURE_GROUP;
+        assert!(Regex::with_options("a", both_captures, Syntax::default()).is_err());
+        let search_only = RegexOptions::from_bits_retain(onig_sys::ONIG_OPTION_NOTBOL);
+        assert!(Regex::with_options("a", search_only, Syntax::default()).is_err());
+    }
+
+    #[test]
+    fn test_search_time_options() {
+        let search = |pattern: &str, from: usize, options: SearchOptions| {
+            Regex::new(pattern)
+                .unwrap()
+                .search_with_param("ab", from, 2, options, None, MatchParam::default())
+                .unwrap()
+        };
+        assert_eq!(
+            search(r"\Aa", 0, SearchOptions::SEARCH_OPTION_NONE),
+            Some(0)
+        );
+        assert_eq!(
+            search(r"\Aa", 0, SearchOptions::SEARCH_OPTION_NOT_BEGIN_STRING),
+            None
+        );
+        assert_eq!(
+            search(r"b\z", 0, SearchOptions::SEARCH_OPTION_NONE),
+            Some(1)
+        );
+        assert_eq!(
+            search(r"b\z", 0, SearchOptions::SEARCH_OPTION_NOT_END_STRING),
+            None
+        );
+        assert_eq!(
+            search(r"\Gb", 1, SearchOptions::SEARCH_OPTION_NONE),
+            Some(1)
+        );
+        assert_eq!(
+            search(r"\Gb", 1, SearchOptions::SEARCH_OPTION_NOT_BEGIN_POSITION),
+            None
+        );
+        assert_eq!(
+            search("b", 0, SearchOptions::SEARCH_OPTION_POSIX_REGION),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_invalid_search_options() {
+        let regex = Regex::new("a").unwrap();
+        let mut region = Region::new();
+        let res = regex.search_with_param(
+            "a",
+            0,
+            1,
+            SearchOptions::SEARCH_OPTION_POSIX_REGION,
+            Some(&mut region),
+            MatchParam::default(),
+        );
+        assert!(res.is_err());
+        let compile_only = SearchOptions::from_bits_retain(onig_sys::ONIG_OPTION_IGNORECASE);
+        let res = regex.match_with_param("a", 0, compile_only, None, MatchParam::default());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_target_encoding() {
+        let utf32: Vec<u8> = "Straße 😀!"
+            .chars()
+            .flat_map(|c| (c as u32).to_be_bytes())
+            .collect();
+        let text = EncodedBytes::from_parts(&utf32, Encoding::UTF32_BE);
+        let regex = Regex::with_target_encoding(
+            "(STRASSE) (.)",
+            Encoding::UTF32_BE,
+            RegexOptions::REGEX_OPTION_IGNORECASE,
+            Syntax::default(),
+        )
+        .unwrap();
+        assert_eq!(regex.encoding(), Encoding::UTF32_BE);
+
+        let mut region = Region::new();
+        let pos = regex.search_with_encoding(
+            text,
+            0,
+            utf32.len(),
+            SearchOptions::SEARCH_OPTION_NONE,
+            Some(&mut region),
+        );
+        assert_eq!(pos, Some(0));
+        assert_eq!(region.pos(1), Some((0, 24)));
+        assert_eq!(region.pos(2), Some((28, 32)));
+
+        // Haystacks must be in the target encoding.
+        let res = regex.try_find("Straße 😀!");
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::EncodingMismatch);
+    }
+
+    #[test]
+    fn test_target_encoding_error_span() {
+        let pattern = r"é\k<missing>";
+        let err = RegexBuilder::new(pattern)
+            .target_encoding(Encoding::UTF16_LE)
+            .build()
+            .unwrap_err();
+        assert_eq!(&pattern[err.pattern_span().unwrap()], "missing");
+
+        let err = Regex::with_target_encoding(
+            "é",
+            Encoding::SJIS,
+            RegexOptions::REGEX_OPTION_NONE,
+            Syntax::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingMismatch);
+    }
+
+    #[test]
+    fn test_case_fold_multi_char() {
+        let fold = |pattern: &str, case_fold: CaseFold| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .case_fold(case_fold)
+                .build()
+                .unwrap()
+        };
+
+        let multi = fold("straße", CaseFold::CASE_FOLD_MULTI_CHAR);
+        assert_eq!(multi.case_fold_flag(), CaseFold::CASE_FOLD_MULTI_CHAR);
+        assert!(multi.is_match("STRASSE"));
+        assert!(fold("STRASSE", CaseFold::CASE_FOLD_MULTI_CHAR).is_match("straße"));
+
+        let single = fold("straße", CaseFold::empty());
+        assert_eq!(single.case_fold_flag(), CaseFold::empty());
+        assert!(!single.is_match("STRASSE"));
+        assert!(single.is_match("STRAßE"));
+
+        let ascii = fold("é", CaseFold::CASE_FOLD_ASCII_ONLY);
+        assert!(!ascii.is_match("É"));
+        assert!(fold("é", CaseFold::empty()).is_match("É"));
+    }
+
+    #[test]
+    fn test_regex_introspection() {
+        let regex = Regex::with_options(
+            "(a)(?<n>b)",
+            RegexOptions::REGEX_OPTION_IGNORECASE,
+            Syntax::ruby(),
+        )
+        .unwrap();
+        assert_eq!(regex.as_str(), "(a)(?<n>b)");
+        assert_eq!(format!("/{}/", regex), "/(a)(?<n>b)/");
+        assert!(regex
+            .options()
+            .contains(RegexOptions::REGEX_OPTION_IGNORECASE));
+        assert!(std::ptr::eq(regex.syntax(), Syntax::ruby()));
+        assert!(!regex.noname_group_capture_is_active());
+        assert!(Regex::new("(a)").unwrap().noname_group_capture_is_active());
+    }
+
+    #[test]
+    fn test_regex_as_str_keeps_source() {
+        let regex = Regex::with_target_encoding(
+            "wörld",
+            Encoding::UTF16_LE,
+            RegexOptions::REGEX_OPTION_NONE,
+            Syntax::default(),
+        )
+        .unwrap();
+        assert_eq!(regex.as_str(), "wörld");
+
+        // "あ" in Shift_JIS isn't valid UTF-8.
+        let sjis = EncodedBytes::from_parts(b"\x82\xa0+", Encoding::SJIS);
+        let regex = Regex::with_encoding(sjis).unwrap();
+        assert_eq!(regex.as_str(), "\u{fffd}\u{fffd}+");
+    }
+
+    #[test]
+    fn test_regex_eq_compares_source() {
+        let regex = Regex::new(r"(\w+) \d").unwrap();
+        assert_eq!(regex, Regex::new(r"(\w+) \d").unwrap());
+        assert_ne!(regex, Regex::new(r"(\w+) \D").unwrap());
+        let ignorecase = Regex::with_options(
+            r"(\w+) \d",
+            RegexOptions::REGEX_OPTION_IGNORECASE,
+            Syntax::default(),
+        )
+        .unwrap();
+        assert_ne!(regex, ignorecase);
+        let ruby =
+            Regex::with_options(r"(\w+) \d", RegexOptions::REGEX_OPTION_NONE, Syntax::ruby())
+                .unwrap();
+        assert_ne!(regex, ruby);
+        let untrusted = Regex::untrusted(r"(\w+) \d", Budget::default()).unwrap();
+        assert_ne!(regex, untrusted);
+    }
+
+    #[test]
+    fn test_regex_clone() {
+        let regex = RegexBuilder::new("STRASSE (.)")
+            .case_insensitive(true)
+            .case_fold(CaseFold::empty())
+            .target_encoding(Encoding::UTF32)
+            .build()
+            .unwrap();
+        let clone = regex.clone();
+        assert_eq!(clone, regex);
+        assert_ne!(clone.raw, regex.raw);
+        assert_eq!(clone.as_str(), "STRASSE (.)");
+        assert_eq!(clone.encoding(), Encoding::UTF32);
+        assert_eq!(clone.case_fold_flag(), CaseFold::empty());
+        assert_eq!(clone.options(), regex.options());
+        drop(regex);
+
+        let units: Vec<u32> = "strasse ß".chars().map(|c| c as u32).collect();
+        let text = EncodedUtf32::new(&units);
+        assert_eq!(clone.find_with_encoding(text), Some((0, 36)));
+
+        let untrusted = Regex::untrusted("a+", Budget::default()).unwrap();
+        assert_eq!(untrusted.clone().budget(), Some(&Budget::default()));
+    }
 }
@@ -0,0 +1,228 @@
+//! Process-wide Limits
+//!
+//! Oniguruma keeps a number of limits in global state which cap how
+//! much work compiling or searching with a regex can do. They are
+//! useful when patterns or haystacks come from untrusted sources.
+//!
+//! # Thread Safety
+//!
+//! The limits are shared by every thread in the process. Setters are
+//! serialised with regex compilation, so a limit which is read at
+//! compile time (`parse_depth_limit`, `capture_num_limit`) never
+//! changes part way through compiling a pattern. Limits which are
+//! read at search time are not synchronised with searches already
+//! running on other threads, so configure them once at start up
+//! before searching. `MatchParam`s take a copy of the match stack and
+//! retry limits when they are created, and can override them for a
+//! single search.
+//!
+//! # Examples
+//!
+//! ```
+//! use onig::{limits, Regex};
+//!
+//! limits::set_parse_depth_limit(64).unwrap();
+//! assert_eq!(limits::parse_depth_limit(), 64);
+//! let deep = format!("{}a{}", "(".repeat(100), ")".repeat(100));
+//! assert!(Regex::new(&deep).is_err());
+//! # limits::set_parse_depth_limit(0).unwrap();
+//! ```
+
+use std::os::raw::{c_int, c_uint, c_ulong};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::{Error, REGEX_NEW_MUTEX};
+
+/// Oniguruma has no getter for the capture limit, so we track the
+/// last value set here.
+static CAPTURE_NUM_LIMIT: AtomicU32 = AtomicU32::new(onig_sys::ONIG_MAX_CAPTURE_NUM);
+
+/// Convert an Oniguruma status code into a `Result`.
+fn check(r: c_int) -> Result<(), Error> {
+    if r == onig_sys::ONIG_NORMAL as c_int {
+        Ok(())
+    } else {
+        Err(Error::from_code(r))
+    }
+}
+
+/// Run a setter while holding the compilation lock.
+fn with_lock<F: FnOnce() -> c_int>(set: F) -> Result<(), Error> {
+    let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+    check(set())
+}
+
+/// Convert a limit to a `c_ulong`, which is 32 bits on Windows.
+fn to_ulong(limit: u64) -> Result<c_ulong, Error> {
+    c_ulong::try_from(limit).map_err(|e| {
+        Error::custom(format!("Limit {} is too large for this platform", limit)).with_source(e)
+    })
+}
+
+/// Convert a limit to a `c_int`.
+fn to_int(limit: u32) -> Result<c_int, Error> {
+    c_int::try_from(limit)
+        .map_err(|e| Error::custom(format!("Limit {} is too large", limit)).with_source(e))
+}
+
+/// Get the Match Stack Limit
+///
+/// The maximum number of entries on the backtracking stack in a
+/// single match. `0` means unlimited.
+pub fn match_stack_limit_size() -> u32 {
+    unsafe { onig_sys::onig_get_match_stack_limit_size() }
+}
+
+/// Set the Match Stack Limit
+///
+/// # Arguments
+///
+/// * `size` - The maximum number of stack entries, or `0` for no
+///   limit.
+pub fn set_match_stack_limit_size(size: u32) -> Result<(), Error> {
+    with_lock(|| unsafe { onig_sys::onig_set_match_stack_limit_size(size as c_uint) })
+}
+
+/// Get the Retry Limit in Match
+///
+/// The maximum number of backtracks at a single start position.
+/// `0` means unlimited.
+#[allow(clippy::unnecessary_cast)] // c_ulong is 32 bits on Windows
+pub fn retry_limit_in_match() -> u64 {
+    unsafe { onig_sys::onig_get_retry_limit_in_match() as u64 }
+}
+
+/// Set the Retry Limit in Match
+///
+/// # Arguments
+///
+/// * `limit` - The maximum number of backtracks at a single start
+///   position, or `0` for no limit.
+pub fn set_retry_limit_in_match(limit: u64) -> Result<(), Error> {
+    let limit = to_ulong(limit)?;
+    with_lock(|| unsafe { onig_sys::onig_set_retry_limit_in_match(limit) })
+}
+
+/// Get the Retry Limit in Search
+///
+/// The maximum number of backtracks over a whole search, across all
+/// start positions. `0` means unlimited.
+#[allow(clippy::unnecessary_cast)] // c_ulong is 32 bits on Windows
+pub fn retry_limit_in_search() -> u64 {
+    unsafe { onig_sys::onig_get_retry_limit_in_search() as u64 }
+}
+
+/// Set the Retry Limit in Search
+///
+/// # Arguments
+///
+/// * `limit` - The maximum number of backtracks over a whole search,
+///   or `0` for no limit.
+pub fn set_retry_limit_in_search(limit: u64) -> Result<(), Error> {
+    let limit = to_ulong(limit)?;
+    with_lock(|| unsafe { onig_sys::onig_set_retry_limit_in_search(limit) })
+}
+
+/// Get the Subexpression Call Limit in Search
+///
+/// The maximum number of subexpression calls, `\g<name>`, in a single
+/// search. `0` means unlimited.
+#[allow(clippy::unnecessary_cast)] // c_ulong is 32 bits on Windows
+pub fn subexp_call_limit_in_search() -> u64 {
+    unsafe { onig_sys::onig_get_subexp_call_limit_in_search() as u64 }
+}
+
+/// Set the Subexpression Call Limit in Search
+///
+/// # Arguments
+///
+/// * `limit` - The maximum number of subexpression calls in a search,
+///   or `0` for no limit.
+pub fn set_subexp_call_limit_in_search(limit: u64) -> Result<(), Error> {
+    let limit = to_ulong(limit)?;
+    with_lock(|| unsafe { onig_sys::onig_set_subexp_call_limit_in_search(limit) })
+}
+
+/// Get the Subexpression Call Nest Limit
+///
+/// The maximum depth to which subexpression calls may be nested.
+pub fn subexp_call_max_nest_level() -> u32 {
+    unsafe { onig_sys::onig_get_subexp_call_max_nest_level() as u32 }
+}
+
+/// Set the Subexpression Call Nest Limit
+///
+/// # Arguments
+///
+/// * `level` - The maximum nesting depth of subexpression calls.
+pub fn set_subexp_call_max_nest_level(level: u32) -> Result<(), Error> {
+    let level = to_int(level)?;
+    with_lock(|| unsafe { onig_sys::onig_set_subexp_call_max_nest_level(level) })
+}
+
+/// Get the Parse Depth Limit
+///
+/// The maximum nesting depth of groups and other constructs in a
+/// pattern being compiled.
+pub fn parse_depth_limit() -> u32 {
+    unsafe { onig_sys::onig_get_parse_depth_limit() }
+}
+
+/// Set the Parse Depth Limit
+///
+/// # Arguments
+///
+/// * `depth` - The maximum nesting depth of a pattern, or `0` to
+///   restore Oniguruma's default.
+pub fn set_parse_depth_limit(depth: u32) -> Result<(), Error> {
+    with_lock(|| unsafe { onig_sys::onig_set_parse_depth_limit(depth as c_uint) })
+}
+
+/// Get the Capture Group Limit
+///
+/// The maximum number of capture groups in a pattern being compiled.
+pub fn capture_num_limit() -> u32 {
+    CAPTURE_NUM_LIMIT.load(Ordering::SeqCst)
+}
+
+/// Set the Capture Group Limit
+///
+/// # Arguments
+///
+/// * `num` - The maximum number of capture groups in a pattern.
+pub fn set_capture_num_limit(num: u32) -> Result<(), Error> {
+    let raw = to_int(num)?;
+    let _guard = REGEX_NEW_MUTEX.lock().unwrap();
+    check(unsafe { onig_sys::onig_set_capture_num_limit(raw) })?;
+    CAPTURE_NUM_LIMIT.store(num, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Compile With Temporary Limits
+///
+/// Replace the parse depth and capture limits while running
+/// `compile`, then restore them. The caller must hold
+/// `REGEX_NEW_MUTEX`, so no other compilation sees the temporary
+/// limits.
+pub(crate) fn with_compile_limits<T, F>(
+    parse_depth: u32,
+    captures: u32,
+    compile: F,
+) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    let captures = to_int(captures)?;
+    let previous_depth = parse_depth_limit();
+    let previous_captures = CAPTURE_NUM_LIMIT.load(Ordering::SeqCst) as c_int;
+    let result = unsafe {
+        check(onig_sys::onig_set_parse_depth_limit(parse_depth as c_uint))
+            .and_then(|_| check(onig_sys::onig_set_capture_num_limit(captures)))
+    }
+    .and_then(|_| compile());
+    unsafe {
+        onig_sys::onig_set_parse_depth_limit(previous_depth as c_uint);
+        onig_sys::onig_set_capture_num_limit(previous_captures);
+    }
+    result
+}
@@ -3,11 +3,18 @@
 //! Contains the definition for the `MatchParam` struct. This can be
 //! used to control the behavior of searching and matching.
 
-use std::os::raw::{c_uint, c_ulong};
+use std::os::raw::{c_uint, c_ulong, c_void};
+use std::panic;
+
+use super::callout::{
+    progress_trampoline, resume_each_match_panic, retraction_trampoline, CalloutHandlers,
+};
+use super::{CalloutArgs, CalloutResult};
 
 /// Parameters for a Match or Search.
 pub struct MatchParam {
     raw: *mut onig_sys::OnigMatchParam,
+    callouts: Box<CalloutHandlers>,
 }
 
 impl MatchParam {
@@ -25,6 +32,80 @@ impl MatchParam {
         }
     }
 
+    /// Set the retry limit in search
+    ///
+    /// Limits the number of backtracks over the whole search, across
+    /// all start positions, rather than at each position.
+    pub fn set_retry_limit_in_search(&mut self, limit: u32) {
+        unsafe {
+            onig_sys::onig_set_retry_limit_in_search_of_match_param(self.raw, c_ulong::from(limit));
+        }
+    }
+
+    /// Set the progress callout
+    ///
+    /// The callout is invoked for each contents callout, `(?{...})`, in
+    /// the pattern as the matcher moves forward through it. See the
+    /// [`callout`](crate::CalloutArgs) types for what is available to
+    /// the callout.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use onig::{CalloutResult, MatchParam, Regex, SearchOptions};
+    ///
+    /// let mut param = MatchParam::default();
+    /// param.set_progress_callout(|args| {
+    ///     // Refuse to match past the third byte
+    ///     if args.current() > 3 {
+    ///         CalloutResult::Fail
+    ///     } else {
+    ///         CalloutResult::Success
+    ///     }
+    /// });
+    /// let re = Regex::new("a+(?{limit})").unwrap();
+    /// let mut region = onig::Region::new();
+    /// re.search_with_param("aaaaa", 0, 5, SearchOptions::SEARCH_OPTION_NONE,
+    ///                      Some(&mut region), param).unwrap();
+    /// assert_eq!(region.pos(0), Some((0, 3)));
+    /// ```
+    pub fn set_progress_callout<F>(&mut self, callout: F)
+    where
+        F: FnMut(&CalloutArgs) -> CalloutResult + 'static,
+    {
+        self.callouts.progress = Some(Box::new(callout));
+        unsafe {
+            onig_sys::onig_set_progress_callout_of_match_param(self.raw, Some(progress_trampoline));
+        }
+    }
+
+    /// Set the retraction callout
+    ///
+    /// The callout is invoked for each contents callout marked for
+    /// retraction, `(?{...}<)` or `(?{...}X)`, as the matcher
+    /// backtracks through it.
+    pub fn set_retraction_callout<F>(&mut self, callout: F)
+    where
+        F: FnMut(&CalloutArgs) -> CalloutResult + 'static,
+    {
+        self.callouts.retraction = Some(Box::new(callout));
+        unsafe {
+            onig_sys::onig_set_retraction_callout_of_match_param(
+                self.raw,
+                Some(retraction_trampoline),
+            );
+        }
+    }
+
+    /// Resume any panic raised by a callout, or by the each-match
+    /// callback, during the last search.
+    pub(crate) fn resume_callout_panic(&mut self) {
+        if let Some(payload) = self.callouts.take_panic() {
+            panic::resume_unwind(payload);
+        }
+        resume_each_match_panic();
+    }
+
     /// Get the Raw `OnigMatchParam` Pointer
     pub fn as_raw(&self) -> *mut onig_sys::OnigMatchParam {
         self.raw
@@ -33,12 +114,17 @@ impl MatchParam {
 
 impl Default for MatchParam {
     fn default() -> Self {
+        let mut callouts = Box::<CalloutHandlers>::default();
         let raw = unsafe {
             let new = onig_sys::onig_new_match_param();
             onig_sys::onig_initialize_match_param(new);
+            onig_sys::onig_set_callout_user_data_of_match_param(
+                new,
+                &mut *callouts as *mut CalloutHandlers as *mut c_void,
+            );
             new
         };
-        MatchParam { raw }
+        MatchParam { raw, callouts }
     }
 }
 
@@ -71,4 +157,24 @@ mod test {
         let mut mp = MatchParam::default();
         mp.set_retry_limit_in_match(1000);
     }
+
+    #[test]
+    pub fn set_retry_limit_in_search() {
+        let mut mp = MatchParam::default();
+        mp.set_retry_limit_in_search(1000);
+        let regex = crate::Regex::new(r"(\w|\w\w)*\d").unwrap();
+        let text = "a".repeat(40);
+        let res = regex.search_with_param(
+            &text,
+            0,
+            text.len(),
+            crate::SearchOptions::SEARCH_OPTION_NONE,
+            None,
+            mp,
+        );
+        assert_eq!(
+            res.unwrap_err().code(),
+            onig_sys::ONIGERR_RETRY_LIMIT_IN_SEARCH_OVER
+        );
+    }
 }
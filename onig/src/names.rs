@@ -1,10 +1,12 @@
+use std::iter::FusedIterator;
 use std::os::raw::{c_int, c_void};
 use std::slice;
-use std::str::from_utf8_unchecked;
+use std::str::{from_utf8, from_utf8_unchecked};
+use std::vec;
 
 use onig_sys::{OnigRegex, OnigUChar};
 
-use super::Regex;
+use super::{Regex, Region};
 
 impl<'syntax> Regex<'syntax> {
     /// Returns the number of named groups into regex.
@@ -53,8 +55,112 @@ impl<'syntax> Regex<'syntax> {
             )
         }
     }
+
+    /// Returns an iterator over the named groups in the regex, in
+    /// order of appearance in the pattern. Each item is the group name
+    /// along with the indices of the groups which share that name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::Regex;
+    /// let re = Regex::new("(?<foo>he)(?<bar>l+)(?<bar>o)").unwrap();
+    /// let names = re.capture_names().collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     names,
+    ///     vec![("foo".to_string(), vec![1]), ("bar".to_string(), vec![2, 3])]
+    /// );
+    /// ```
+    pub fn capture_names(&self) -> CaptureNames {
+        let names = self
+            .name_table()
+            .into_iter()
+            .map(|(name, groups)| (name.to_owned(), groups.to_vec()))
+            .collect::<Vec<_>>();
+        CaptureNames {
+            names: names.into_iter(),
+        }
+    }
+
+    /// Collects the name table of the regex, ordered by the first group
+    /// of each name. The names and group indices borrow from the
+    /// compiled regex. Names which aren't valid UTF-8 are skipped.
+    pub(crate) fn name_table(&self) -> Vec<(&str, &[u32])> {
+        unsafe extern "C" fn table_cb(
+            name: *const OnigUChar,
+            name_end: *const OnigUChar,
+            ngroup_num: c_int,
+            group_nums: *mut c_int,
+            _regex: OnigRegex,
+            arg: *mut c_void,
+        ) -> c_int {
+            let name = slice::from_raw_parts(name, name_end as usize - name as usize);
+            let groups = slice::from_raw_parts(group_nums as *const u32, ngroup_num as usize);
+            let table = &mut *(arg as *mut Vec<(&[u8], &[u32])>);
+            table.push((name, groups));
+            0
+        }
+
+        let mut table = Vec::<(&[u8], &[u32])>::new();
+        unsafe {
+            onig_sys::onig_foreach_name(
+                self.raw,
+                Some(table_cb),
+                &mut table as *mut Vec<(&[u8], &[u32])> as *mut c_void,
+            );
+        }
+        let mut table = table
+            .into_iter()
+            .filter_map(|(name, groups)| from_utf8(name).ok().map(|name| (name, groups)))
+            .collect::<Vec<_>>();
+        table.sort_by_key(|&(_, groups)| groups.first().copied());
+        table
+    }
+
+    /// Resolves the group number for `name` in a region filled by a
+    /// match of this regex. If more than one group shares the name
+    /// then the last of those groups which matched is returned.
+    pub(crate) fn name_to_group(&self, name: &str, region: &Region) -> Option<usize> {
+        let group = unsafe {
+            onig_sys::onig_name_to_backref_number(
+                self.raw,
+                name.as_ptr(),
+                name[name.len()..].as_ptr(),
+                region.raw_mut(),
+            )
+        };
+        if group < 0 {
+            None
+        } else {
+            Some(group as usize)
+        }
+    }
+}
+
+/// An iterator over the named groups of a regex.
+///
+/// Returned by [`Regex::capture_names`]. Each item is the group name
+/// along with the indices of the groups which share that name.
+pub struct CaptureNames {
+    names: vec::IntoIter<(String, Vec<u32>)>,
+}
+
+impl Iterator for CaptureNames {
+    type Item = (String, Vec<u32>);
+
+    fn next(&mut self) -> Option<(String, Vec<u32>)> {
+        self.names.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
 }
 
+impl FusedIterator for CaptureNames {}
+
+impl ExactSizeIterator for CaptureNames {}
+
 #[cfg(test)]
 mod tests {
     use super::super::*;
@@ -88,4 +194,20 @@ mod tests {
             vec![("foo".into(), vec![1u32]), ("bar".into(), vec![2u32, 3])]
         );
     }
+
+    #[test]
+    fn test_regex_capture_names() {
+        let regex = Regex::new("(he)(l+)(o)").unwrap();
+        assert_eq!(regex.capture_names().count(), 0);
+        let regex = Regex::new("(?<z>a)(?<y>b)(?<x>c)(?<y>d)").unwrap();
+        let names = regex.capture_names().collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                ("z".to_string(), vec![1]),
+                ("y".to_string(), vec![2, 4]),
+                ("x".to_string(), vec![3]),
+            ]
+        );
+    }
 }
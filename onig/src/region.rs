@@ -193,7 +193,7 @@ impl Region {
     /// Convert a reference to self to a mutable pointer. This
     /// shouldn't ever actually be used to mutate the underlying
     /// region. It's needed to match the bindgened types though.
-    fn raw_mut(&self) -> *mut onig_sys::OnigRegion {
+    pub(crate) fn raw_mut(&self) -> *mut onig_sys::OnigRegion {
         &self.raw as *const onig_sys::OnigRegion as *mut onig_sys::OnigRegion
     }
 }
@@ -0,0 +1,844 @@
+//! Regex Sets
+//!
+//! Contains the definition for the `RegexSet` struct. A set allows a
+//! group of compiled regular expressions to be searched for in a
+//! single pass over a string using Oniguruma's `onig_regset_*` API.
+
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::os::raw::c_int;
+use std::ptr::null_mut;
+
+use super::find::MatchCursor;
+use super::{Captures, EncodedChars, Error, MatchParam, Regex, Region, SearchOptions, Syntax};
+
+/// Regex Set Search Lead
+///
+/// Controls how the patterns within a `RegexSet` are tried against
+/// the string being searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RegexSetLead {
+    /// At each position in the string try every regex in turn. The
+    /// match which starts earliest wins, with ties broken by the
+    /// order of the regexes within the set.
+    #[default]
+    Position,
+    /// Search the whole string with each regex in turn. The result is
+    /// the same as `Position`, but can be faster when the set
+    /// contains only a few patterns and the string is long.
+    Regex,
+    /// Search the whole string with each regex in turn, returning the
+    /// first regex in the set which matches anywhere. The position of
+    /// the match is not taken into account.
+    PriorityToRegexOrder,
+}
+
+impl RegexSetLead {
+    fn as_raw(self) -> onig_sys::OnigRegSetLead {
+        match self {
+            RegexSetLead::Position => onig_sys::OnigRegSetLead_ONIG_REGSET_POSITION_LEAD,
+            RegexSetLead::Regex => onig_sys::OnigRegSetLead_ONIG_REGSET_REGEX_LEAD,
+            RegexSetLead::PriorityToRegexOrder => {
+                onig_sys::OnigRegSetLead_ONIG_REGSET_PRIORITY_TO_REGEX_ORDER
+            }
+        }
+    }
+}
+
+/// A Set of Compiled Regular Expressions
+///
+/// Searches a string for the first match of any of the contained
+/// regexes. Each search reports the index of the regex which
+/// matched, the position of the match and the capture groups for
+/// that regex. All regexes within the set must share the same
+/// encoding, and regexes compiled with `REGEX_OPTION_FIND_LONGEST`
+/// can't be added to a set.
+///
+/// Searching a set updates the state held inside the Oniguruma set
+/// object, so unlike a `Regex` a `RegexSet` can't be shared between
+/// threads.
+///
+/// # Examples
+///
+/// ```
+/// use onig::RegexSet;
+///
+/// let set = RegexSet::new(&[r"\d+", r"[a-z]+"]).unwrap();
+/// let tokens = set.find_iter("abc 123").collect::<Vec<_>>();
+/// assert_eq!(tokens, vec![(1, (0, 3)), (0, (4, 7))]);
+/// ```
+#[derive(Debug)]
+pub struct RegexSet<'syntax> {
+    raw: *mut onig_sys::OnigRegSet,
+    regexes: Vec<Regex<'syntax>>,
+    lead: RegexSetLead,
+    _syntax: PhantomData<&'syntax Syntax>,
+}
+
+unsafe impl<'syntax> Send for RegexSet<'syntax> {}
+
+impl<'syntax> RegexSet<'syntax> {
+    /// Create a Regex Set
+    ///
+    /// Compiles each of the given `patterns` with the default options
+    /// and syntax and places them in a new set. If any of the patterns
+    /// fail to compile then the error is returned.
+    ///
+    /// # Arguments
+    ///
+    ///  * `patterns` - The regex patterns to compile.
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<RegexSet<'static>, Error> {
+        let regexes = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        RegexSet::from_regexes(regexes)
+    }
+
+    /// Create a Regex Set from Compiled Regexes
+    ///
+    /// Takes ownership of each of the given regexes and places them in
+    /// a new set in order.
+    ///
+    /// # Arguments
+    ///
+    ///  * `regexes` - The compiled regexes to add to the set.
+    pub fn from_regexes<I>(regexes: I) -> Result<RegexSet<'syntax>, Error>
+    where
+        I: IntoIterator<Item = Regex<'syntax>>,
+    {
+        let mut raw = null_mut();
+        let r = unsafe { onig_sys::onig_regset_new(&mut raw, 0, null_mut()) };
+        if r != onig_sys::ONIG_NORMAL as i32 {
+            return Err(Error::from_code(r));
+        }
+        let mut set = RegexSet {
+            raw,
+            regexes: Vec::new(),
+            lead: RegexSetLead::default(),
+            _syntax: PhantomData,
+        };
+        for regex in regexes {
+            set.add(regex)?;
+        }
+        Ok(set)
+    }
+
+    /// Add a Regex to the Set
+    ///
+    /// The regex is placed at the end of the set. Fails if the regex's
+    /// encoding differs from the other members of the set, or if it
+    /// was compiled with `REGEX_OPTION_FIND_LONGEST`.
+    pub fn add(&mut self, regex: Regex<'syntax>) -> Result<(), Error> {
+        let r = unsafe { onig_sys::onig_regset_add(self.raw, regex.raw) };
+        if r != onig_sys::ONIG_NORMAL as i32 {
+            return Err(Error::from_code(r));
+        }
+        self.regexes.push(regex);
+        Ok(())
+    }
+
+    /// Replace a Regex in the Set
+    ///
+    /// Replaces the regex at `index` with `regex`, returning the regex
+    /// which was previously at that position.
+    pub fn replace(
+        &mut self,
+        index: usize,
+        regex: Regex<'syntax>,
+    ) -> Result<Regex<'syntax>, Error> {
+        if index >= self.regexes.len() {
+            return Err(Error::custom(format!(
+                "Regex set index {} is out of range",
+                index
+            )));
+        }
+        let r = unsafe { onig_sys::onig_regset_replace(self.raw, index as c_int, regex.raw) };
+        if r != onig_sys::ONIG_NORMAL as i32 {
+            return Err(Error::from_code(r));
+        }
+        Ok(std::mem::replace(&mut self.regexes[index], regex))
+    }
+
+    /// Remove a Regex from the Set
+    ///
+    /// Removes the regex at `index`, shifting all of the following
+    /// regexes down by one. Returns `None` if `index` is out of range.
+    pub fn remove(&mut self, index: usize) -> Option<Regex<'syntax>> {
+        if index >= self.regexes.len() {
+            return None;
+        }
+        unsafe {
+            onig_sys::onig_regset_replace(self.raw, index as c_int, null_mut());
+        }
+        Some(self.regexes.remove(index))
+    }
+
+    /// Get the Number of Regexes in the Set
+    pub fn len(&self) -> usize {
+        self.regexes.len()
+    }
+
+    /// Is the Set Empty?
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
+
+    /// Get the Regexes in the Set
+    ///
+    /// The indices reported by searches index into this slice.
+    pub fn regexes(&self) -> &[Regex<'syntax>] {
+        &self.regexes
+    }
+
+    /// Get the Lead Used by `find` and `captures`
+    pub fn lead(&self) -> RegexSetLead {
+        self.lead
+    }
+
+    /// Set the Lead Used by `find` and `captures`
+    ///
+    /// Controls the lead used by `find`, `captures`, `find_iter` and
+    /// `captures_iter`. Defaults to `RegexSetLead::Position`.
+    pub fn set_lead(&mut self, lead: RegexSetLead) {
+        self.lead = lead;
+    }
+
+    /// Search for the Set in a String
+    ///
+    /// Search for the first match of any regex in the set within
+    /// `str`. Only forward searches are supported, so `from` must not
+    /// be greater than `to`.
+    ///
+    /// # Arguments
+    ///
+    ///  * `str` - The string to search in.
+    ///  * `from` - The byte index in the passed slice to start search
+    ///  * `to` - The byte index in the passed slice to finish search
+    ///  * `lead` - How the regexes in the set are tried.
+    ///  * `options` - The options for the search.
+    ///
+    /// # Returns
+    ///
+    /// `Some((index, pos))` if one of the regexes matched, where
+    /// `index` is the position of the matching regex within the set
+    /// and `pos` is the byte-position of the start of the match.
+    /// `None` if no regex in the set matches.
+    pub fn search_with_options(
+        &self,
+        str: &str,
+        from: usize,
+        to: usize,
+        lead: RegexSetLead,
+        options: SearchOptions,
+    ) -> Option<(usize, usize)> {
+        self.search_with_encoding(str, from, to, lead, options)
+    }
+
+    /// Search for the Set in a String with an Encoding
+    ///
+    /// As `search_with_options`, but for any encoded buffer. The
+    /// encoding of the buffer must match the encoding of the regexes
+    /// in the set.
+    pub fn search_with_encoding<T>(
+        &self,
+        chars: T,
+        from: usize,
+        to: usize,
+        lead: RegexSetLead,
+        options: SearchOptions,
+    ) -> Option<(usize, usize)>
+    where
+        T: EncodedChars,
+    {
+        unwrap_set_search(self.try_search_with_encoding(chars, from, to, lead, options))
+    }
+
+    /// Search for the Set in a String with an Encoding, Reporting Errors
+    ///
+    /// As `search_with_encoding`, but errors from the engine, such as
+    /// a regex in the set exceeding its [`Budget`](crate::Budget), are
+    /// returned rather than causing a panic.
+    pub fn try_search_with_encoding<T>(
+        &self,
+        chars: T,
+        from: usize,
+        to: usize,
+        lead: RegexSetLead,
+        options: SearchOptions,
+    ) -> Result<Option<(usize, usize)>, Error>
+    where
+        T: EncodedChars,
+    {
+        // Each regex searches within its own budget, if it has one.
+        let mut match_params = self
+            .regexes
+            .iter()
+            .map(Regex::default_match_param)
+            .collect::<Vec<_>>();
+        let raw_params = match_params.iter().map(MatchParam::as_raw).collect();
+        let result = self.search_with_raw_params(chars, from, to, lead, options, raw_params);
+        for match_param in &mut match_params {
+            match_param.resume_callout_panic();
+        }
+        result
+    }
+
+    /// Search for the Set with Encoding and Match Param
+    ///
+    /// As `search_with_encoding`, but errors from the engine are
+    /// returned rather than causing a panic. The same `match_param` is
+    /// used for every regex in the set.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Some((index, pos)))` if one of the regexes matched.
+    /// `Ok(None)` if none of the regexes match. `Err` with an `Error`
+    /// if an error occurred (e.g. retry-limit-in-match exceeded).
+    pub fn search_with_param<T>(
+        &self,
+        chars: T,
+        from: usize,
+        to: usize,
+        lead: RegexSetLead,
+        options: SearchOptions,
+        mut match_param: MatchParam,
+    ) -> Result<Option<(usize, usize)>, Error>
+    where
+        T: EncodedChars,
+    {
+        let raw_params = vec![match_param.as_raw(); self.regexes.len()];
+        let result = self.search_with_raw_params(chars, from, to, lead, options, raw_params);
+        match_param.resume_callout_panic();
+        result
+    }
+
+    /// Search with one match param for each regex in the set. The
+    /// caller resumes any callout panics.
+    fn search_with_raw_params<T>(
+        &self,
+        chars: T,
+        from: usize,
+        to: usize,
+        lead: RegexSetLead,
+        options: SearchOptions,
+        mut match_params: Vec<*mut onig_sys::OnigMatchParam>,
+    ) -> Result<Option<(usize, usize)>, Error>
+    where
+        T: EncodedChars,
+    {
+        if let Some(first) = self.regexes.first() {
+            if chars.encoding() != first.encoding() {
+                return Err(Error::encoding_mismatch(chars.encoding(), first.encoding()));
+            }
+        }
+        if from > to {
+            return Err(Error::custom("Regex sets only support forward searches"));
+        }
+        if to > chars.len() {
+            return Err(Error::custom("Limit of match should be before end"));
+        }
+        // The set always searches with the regions of its members.
+        options.validate(true)?;
+        if self.regexes.is_empty() {
+            return Ok(None);
+        }
+        let mut match_pos: c_int = 0;
+        let r = unsafe {
+            let beg = chars.start_ptr();
+            onig_sys::onig_regset_search_with_param(
+                self.raw,
+                beg,
+                chars.limit_ptr(),
+                beg.add(from),
+                beg.add(to),
+                lead.as_raw(),
+                options.bits(),
+                match_params.as_mut_ptr(),
+                &mut match_pos,
+            )
+        };
+
+        if r >= 0 {
+            Ok(Some((r as usize, match_pos as usize)))
+        } else if r == onig_sys::ONIG_MISMATCH {
+            Ok(None)
+        } else {
+            Err(Error::from_code(r))
+        }
+    }
+
+    /// Find the First Match of the Set
+    ///
+    /// # Returns
+    ///
+    /// The index of the regex that matched, along with the start and
+    /// end of the match. `None` if no regex in the set matches.
+    pub fn find(&self, text: &str) -> Option<(usize, (usize, usize))> {
+        unwrap_set_search(self.try_find(text))
+    }
+
+    /// Find the first match of the set, like `find`, but returns an
+    /// `Error` if the search fails rather than panicking.
+    pub fn try_find(&self, text: &str) -> Result<Option<SetMatch>, Error> {
+        let found = self.try_search_region(text, 0)?;
+        Ok(found.and_then(|(index, region)| region.pos(0).map(|pos| (index, pos))))
+    }
+
+    /// Returns the index of the regex which matched first, along with
+    /// its capture groups. If no match is found `None` is returned.
+    pub fn captures<'r, 't>(&'r self, text: &'t str) -> Option<(usize, Captures<'r, 't>)> {
+        unwrap_set_search(self.try_captures(text))
+    }
+
+    /// Returns the index of the regex which matched first, along with
+    /// its capture groups, like `captures`, but returns an `Error` if
+    /// the search fails rather than panicking.
+    pub fn try_captures<'r, 't>(
+        &'r self,
+        text: &'t str,
+    ) -> Result<Option<(usize, Captures<'r, 't>)>, Error> {
+        let found = self.try_search_region(text, 0)?;
+        Ok(found.map(|(index, region)| {
+            let offset = region.pos(0).map_or(0, |(beg, _)| beg);
+            (
+                index,
+                Captures::new(&self.regexes[index], text, region, offset),
+            )
+        }))
+    }
+
+    /// Returns an iterator over each successive non-overlapping match
+    /// of any regex in the set. Each item is the index of the regex
+    /// which matched along with the start and end of the match.
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> RegexSetMatches<'r, 'syntax, 't> {
+        RegexSetMatches {
+            set: self,
+            text,
+            cursor: MatchCursor::default(),
+        }
+    }
+
+    /// Returns an iterator over each successive non-overlapping match
+    /// of any regex in the set. Each item is the index of the regex
+    /// which matched along with its capture groups.
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t str) -> RegexSetCaptures<'r, 'syntax, 't> {
+        RegexSetCaptures {
+            matches: self.find_iter(text),
+        }
+    }
+
+    /// Returns an iterator over each successive non-overlapping match
+    /// of any regex in the set, like `find_iter`, but yielding an
+    /// `Error` if a search fails rather than panicking.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::RegexSet;
+    /// let set = RegexSet::new(&[r"\d+", r"[a-z]+"]).unwrap();
+    /// let found: Result<Vec<_>, _> = set.try_find_iter("ab 12").collect();
+    /// assert_eq!(found.unwrap(), vec![(1, (0, 2)), (0, (3, 5))]);
+    /// ```
+    pub fn try_find_iter<'r, 't>(&'r self, text: &'t str) -> TryRegexSetMatches<'r, 'syntax, 't> {
+        TryRegexSetMatches(self.find_iter(text))
+    }
+
+    /// Returns an iterator over each successive non-overlapping match
+    /// of any regex in the set along with its capture groups, like
+    /// `captures_iter`, but yielding an `Error` if a search fails
+    /// rather than panicking.
+    pub fn try_captures_iter<'r, 't>(
+        &'r self,
+        text: &'t str,
+    ) -> TryRegexSetCaptures<'r, 'syntax, 't> {
+        TryRegexSetCaptures(self.captures_iter(text))
+    }
+
+    /// Search from `from` with the set's lead, copying out the region
+    /// of the regex which matched.
+    fn try_search_region(&self, text: &str, from: usize) -> Result<Option<(usize, Region)>, Error> {
+        let found = self.try_search_with_encoding(
+            text,
+            from,
+            text.len(),
+            self.lead,
+            SearchOptions::SEARCH_OPTION_NONE,
+        )?;
+        Ok(found.map(|(index, _)| {
+            let region = unsafe {
+                Region::clone_from_raw(onig_sys::onig_regset_get_region(self.raw, index as c_int))
+            };
+            (index, region)
+        }))
+    }
+}
+
+/// The index of the regex which matched, along with the start and end
+/// of the match.
+type SetMatch = (usize, (usize, usize));
+
+/// Unwrap the result of a search of a set, panicking on error as
+/// `search_with_encoding` does.
+fn unwrap_set_search<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("Onig: RegexSet search error: {}", e.description()),
+    }
+}
+
+impl<'syntax> Drop for RegexSet<'syntax> {
+    fn drop(&mut self) {
+        unsafe {
+            // The regexes are owned by `self.regexes`, so detach them
+            // from the set before freeing it to avoid a double free.
+            for index in (0..self.regexes.len()).rev() {
+                onig_sys::onig_regset_replace(self.raw, index as c_int, null_mut());
+            }
+            onig_sys::onig_regset_free(self.raw);
+        }
+    }
+}
+
+/// An iterator over all non-overlapping matches of a `RegexSet`.
+///
+/// The iterator yields the index of the regex which matched along
+/// with the start and end of the match. The indices are byte offsets.
+///
+/// `'r` is the lifetime of the `RegexSet`, `'syntax` is the lifetime
+/// of the [`Syntax`](crate::Syntax) used to compile the regexes, and
+/// `'t` is the lifetime of the matched string.
+pub struct RegexSetMatches<'r, 'syntax, 't> {
+    set: &'r RegexSet<'syntax>,
+    text: &'t str,
+    cursor: MatchCursor,
+}
+
+impl<'r, 'syntax, 't> RegexSetMatches<'r, 'syntax, 't> {
+    /// Find the next match, reporting any search error. The iterator
+    /// is finished after an error.
+    fn try_next_region(&mut self) -> Option<Result<(usize, Region), Error>> {
+        let (set, text) = (self.set, self.text);
+        self.cursor.next(text, |from| {
+            let found = set.try_search_region(text, from)?;
+            Ok(found.map(|(index, region)| {
+                let pos = region.pos(0).unwrap();
+                ((index, region), pos)
+            }))
+        })
+    }
+
+    fn try_next(&mut self) -> Option<Result<SetMatch, Error>> {
+        self.try_next_region()
+            .map(|r| r.map(|(index, region)| (index, region.pos(0).unwrap())))
+    }
+}
+
+impl<'r, 'syntax, 't> Iterator for RegexSetMatches<'r, 'syntax, 't> {
+    type Item = (usize, (usize, usize));
+
+    fn next(&mut self) -> Option<(usize, (usize, usize))> {
+        self.try_next().map(unwrap_set_search)
+    }
+}
+
+impl<'r, 'syntax, 't> FusedIterator for RegexSetMatches<'r, 'syntax, 't> {}
+
+/// An iterator that yields all non-overlapping capture groups
+/// matching any regex in a `RegexSet`.
+///
+/// Each item is the index of the regex which matched along with its
+/// capture groups.
+///
+/// `'r` is the lifetime of the `RegexSet`, `'syntax` is the lifetime
+/// of the [`Syntax`](crate::Syntax) used to compile the regexes, and
+/// `'t` is the lifetime of the matched string.
+pub struct RegexSetCaptures<'r, 'syntax, 't> {
+    matches: RegexSetMatches<'r, 'syntax, 't>,
+}
+
+impl<'r, 'syntax, 't> RegexSetCaptures<'r, 'syntax, 't> {
+    /// Find the next set of captures, reporting any search error. The
+    /// iterator is finished after an error.
+    fn try_next(&mut self) -> Option<Result<(usize, Captures<'r, 't>), Error>> {
+        let text = self.matches.text;
+        let set = self.matches.set;
+        self.matches.try_next_region().map(|r| {
+            r.map(|(index, region)| {
+                let offset = region.pos(0).unwrap().0;
+                (
+                    index,
+                    Captures::new(&set.regexes[index], text, region, offset),
+                )
+            })
+        })
+    }
+}
+
+impl<'r, 'syntax, 't> Iterator for RegexSetCaptures<'r, 'syntax, 't> {
+    type Item = (usize, Captures<'r, 't>);
+
+    fn next(&mut self) -> Option<(usize, Captures<'r, 't>)> {
+        self.try_next().map(unwrap_set_search)
+    }
+}
+
+impl<'r, 'syntax, 't> FusedIterator for RegexSetCaptures<'r, 'syntax, 't> {}
+
+/// An iterator over all non-overlapping matches of a `RegexSet`,
+/// which reports search errors.
+///
+/// This is the fallible form of [`RegexSetMatches`], returned by
+/// [`RegexSet::try_find_iter`]. After yielding an error the iterator
+/// is finished.
+pub struct TryRegexSetMatches<'r, 'syntax, 't>(RegexSetMatches<'r, 'syntax, 't>);
+
+impl<'r, 'syntax, 't> Iterator for TryRegexSetMatches<'r, 'syntax, 't> {
+    type Item = Result<(usize, (usize, usize)), Error>;
+
+    fn next(&mut self) -> Option<Result<(usize, (usize, usize)), Error>> {
+        self.0.try_next()
+    }
+}
+
+impl<'r, 'syntax, 't> FusedIterator for TryRegexSetMatches<'r, 'syntax, 't> {}
+
+/// An iterator that yields all non-overlapping capture groups
+/// matching any regex in a `RegexSet`, which reports search errors.
+///
+/// This is the fallible form of [`RegexSetCaptures`], returned by
+/// [`RegexSet::try_captures_iter`]. After yielding an error the
+/// iterator is finished.
+pub struct TryRegexSetCaptures<'r, 'syntax, 't>(RegexSetCaptures<'r, 'syntax, 't>);
+
+impl<'r, 'syntax, 't> Iterator for TryRegexSetCaptures<'r, 'syntax, 't> {
+    type Item = Result<(usize, Captures<'r, 't>), Error>;
+
+    fn next(&mut self) -> Option<Result<(usize, Captures<'r, 't>), Error>> {
+        self.0.try_next()
+    }
+}
+
+impl<'r, 'syntax, 't> FusedIterator for TryRegexSetCaptures<'r, 'syntax, 't> {}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn test_regset_create() {
+        let set = RegexSet::new(&["a+", "b+"]).unwrap();
+        assert_eq!(set.len(), 2);
+        assert!(!set.is_empty());
+    }
+
+    #[test]
+    fn test_regset_empty_never_matches() {
+        let set = RegexSet::new::<&str>(&[]).unwrap();
+        assert!(set.is_empty());
+        assert_eq!(set.find("hello"), None);
+        assert_eq!(set.find_iter("hello").count(), 0);
+
+        // The arguments are still checked before giving up.
+        let search = |from, to, options| {
+            set.search_with_param(
+                "hello",
+                from,
+                to,
+                RegexSetLead::Position,
+                options,
+                MatchParam::default(),
+            )
+        };
+        assert_eq!(
+            search(0, 5, SearchOptions::SEARCH_OPTION_NONE).unwrap(),
+            None
+        );
+        assert!(search(3, 1, SearchOptions::SEARCH_OPTION_NONE).is_err());
+        assert!(search(0, 6, SearchOptions::SEARCH_OPTION_NONE).is_err());
+        assert!(search(0, 5, SearchOptions::SEARCH_OPTION_POSIX_REGION).is_err());
+    }
+
+    #[test]
+    fn test_regset_invalid_pattern() {
+        let e = RegexSet::new(&["a+", "\\p{foo}"]).unwrap_err();
+        assert_eq!(e.code(), -223);
+    }
+
+    #[test]
+    fn test_regset_rejects_find_longest() {
+        let regex = Regex::with_options(
+            "a+",
+            RegexOptions::REGEX_OPTION_FIND_LONGEST,
+            Syntax::default(),
+        )
+        .unwrap();
+        assert!(RegexSet::from_regexes(vec![regex]).is_err());
+    }
+
+    #[test]
+    fn test_regset_search_position_lead() {
+        let set = RegexSet::new(&["world", "o"]).unwrap();
+        let r = set.search_with_options(
+            "hello world",
+            0,
+            11,
+            RegexSetLead::Position,
+            SearchOptions::SEARCH_OPTION_NONE,
+        );
+        assert_eq!(r, Some((1, 4)));
+    }
+
+    #[test]
+    fn test_regset_search_priority_to_regex_order() {
+        let set = RegexSet::new(&["world", "o"]).unwrap();
+        let r = set.search_with_options(
+            "hello world",
+            0,
+            11,
+            RegexSetLead::PriorityToRegexOrder,
+            SearchOptions::SEARCH_OPTION_NONE,
+        );
+        assert_eq!(r, Some((0, 6)));
+    }
+
+    #[test]
+    fn test_regset_search_regex_lead_matches_position_lead() {
+        let set = RegexSet::new(&[r"\d+", "[a-z]+", r"\s"]).unwrap();
+        let text = "  abc 123";
+        let position = set.search_with_options(
+            text,
+            0,
+            text.len(),
+            RegexSetLead::Position,
+            SearchOptions::SEARCH_OPTION_NONE,
+        );
+        let regex = set.search_with_options(
+            text,
+            0,
+            text.len(),
+            RegexSetLead::Regex,
+            SearchOptions::SEARCH_OPTION_NONE,
+        );
+        assert_eq!(position, Some((2, 0)));
+        assert_eq!(position, regex);
+    }
+
+    #[test]
+    fn test_regset_search_invalid_range() {
+        let set = RegexSet::new(&["a"]).unwrap();
+        let r = set.search_with_param(
+            "aaa",
+            2,
+            1,
+            RegexSetLead::Position,
+            SearchOptions::SEARCH_OPTION_NONE,
+            MatchParam::default(),
+        );
+        assert!(r.is_err());
+        let r = set.search_with_param(
+            "aaa",
+            0,
+            4,
+            RegexSetLead::Position,
+            SearchOptions::SEARCH_OPTION_NONE,
+            MatchParam::default(),
+        );
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_regset_encoding_mismatch() {
+        let set = RegexSet::new(&["a"]).unwrap();
+        let r = set.search_with_param(
+            EncodedBytes::ascii(b"a"),
+            0,
+            1,
+            RegexSetLead::Position,
+            SearchOptions::SEARCH_OPTION_NONE,
+            MatchParam::default(),
+        );
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_regset_captures() {
+        let set = RegexSet::new(&[r"(\d+)-(\d+)", r"([a-z]+)"]).unwrap();
+        let (index, caps) = set.captures("x 10-20").unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(caps.at(1), Some("x"));
+        let (index, caps) = set.captures("10-20 x").unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(caps.offset(), 0);
+        assert_eq!(caps.at(2), Some("20"));
+    }
+
+    #[test]
+    fn test_regset_find_iter() {
+        let set = RegexSet::new(&[r"\d+", "[a-z]+"]).unwrap();
+        let ms = set.find_iter("ab12 c3").collect::<Vec<_>>();
+        assert_eq!(ms, vec![(1, (0, 2)), (0, (2, 4)), (1, (5, 6)), (0, (6, 7))]);
+    }
+
+    #[test]
+    fn test_regset_find_iter_zero_length() {
+        let set = RegexSet::new(&[r"\d*"]).unwrap();
+        let ms = set.find_iter("a1bbb2").collect::<Vec<_>>();
+        assert_eq!(
+            ms,
+            vec![
+                (0, (0, 0)),
+                (0, (1, 2)),
+                (0, (3, 3)),
+                (0, (4, 4)),
+                (0, (5, 6))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_regset_captures_iter() {
+        let set = RegexSet::new(&[r"(?<num>\d+)", "(?<word>[a-z]+)"]).unwrap();
+        let caps = set
+            .captures_iter("ab 12")
+            .map(|(index, caps)| (index, caps.offset(), caps.at(1).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(caps, vec![(1, 0, "ab"), (0, 3, "12")]);
+    }
+
+    #[test]
+    fn test_regset_set_lead() {
+        let mut set = RegexSet::new(&["world", "o"]).unwrap();
+        assert_eq!(set.lead(), RegexSetLead::Position);
+        assert_eq!(set.find("hello world"), Some((1, (4, 5))));
+        set.set_lead(RegexSetLead::PriorityToRegexOrder);
+        assert_eq!(set.find("hello world"), Some((0, (6, 11))));
+    }
+
+    #[test]
+    fn test_regset_replace_and_remove() {
+        let mut set = RegexSet::new(&["a", "b"]).unwrap();
+        let old = set.replace(1, Regex::new("c").unwrap()).unwrap();
+        assert!(old.is_match("b"));
+        assert_eq!(set.find("bc"), Some((1, (1, 2))));
+        assert!(set.replace(2, Regex::new("d").unwrap()).is_err());
+
+        let removed = set.remove(0).unwrap();
+        assert!(removed.is_match("a"));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.find("ac"), Some((0, (1, 2))));
+        assert!(set.remove(1).is_none());
+    }
+
+    #[test]
+    fn test_regset_regexes() {
+        let mut set = RegexSet::new(&["a"]).unwrap();
+        set.add(Regex::new("b").unwrap()).unwrap();
+        let found = set
+            .regexes()
+            .iter()
+            .map(|regex| regex.is_match("b"))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![false, true]);
+    }
+}
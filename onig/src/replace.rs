@@ -1,23 +1,53 @@
-use super::{Captures, Regex};
+use super::{Captures, Error, Regex};
 use std::borrow::Cow;
 
 /// Replacer describes types that can be used to replace matches in a string.
 ///
-/// Implementations are provided for replacement using string literals
-/// and `FnMut` callbacks. If this isn't enough for your replacement
-/// needs a user-supplied `Replacer` implemenation can be
-/// provided. For an example of a custom replacer implementation check
-/// out `examples/dollar.rs` in the Onig crate.
+/// Implementations are provided for replacement using template
+/// strings, literal strings wrapped in [`NoExpand`] and `FnMut`
+/// callbacks. If this isn't enough for your replacement needs a
+/// user-supplied `Replacer` implemenation can be provided. For an
+/// example of a custom replacer implementation check out
+/// `examples/dollar.rs` in the Onig crate.
 pub trait Replacer {
     /// Returns a possibly owned string that is used to replace the match
     /// corresponding to the `caps` capture group.
     fn reg_replace(&mut self, caps: &Captures) -> Cow<'_, str>;
 }
 
-/// Replacement using Literal Strings
-impl<'t> Replacer for &'t str {
+/// Replacement using Template Strings
+///
+/// The string is expanded with [`Captures::expand`] for each match.
+impl Replacer for &str {
+    fn reg_replace(&mut self, caps: &Captures) -> Cow<'_, str> {
+        if self.contains(['$', '\\']) {
+            let mut expanded = String::with_capacity(self.len());
+            caps.expand(self, &mut expanded);
+            expanded.into()
+        } else {
+            (*self).into()
+        }
+    }
+}
+
+/// Literal String Replacement
+///
+/// Wraps a string so that it is inserted as-is, without expanding
+/// any capture references.
+///
+/// # Example
+///
+/// ```rust
+/// # use onig::{NoExpand, Regex};
+/// let re = Regex::new(r"\d+").unwrap();
+/// assert_eq!(re.replace_all("1 + 2", NoExpand("$0")), "$0 + $0");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct NoExpand<'t>(pub &'t str);
+
+impl<'t> Replacer for NoExpand<'t> {
     fn reg_replace(&mut self, _: &Captures) -> Cow<'_, str> {
-        (*self).into()
+        self.0.into()
     }
 }
 
@@ -31,6 +61,125 @@ where
     }
 }
 
+/// A reference to captured text within a replacement template.
+enum Reference<'a> {
+    /// A literal `$`, written `$$`
+    Dollar,
+    /// A numbered group, written `$1`, `${1}` or `\k<1>`
+    Group(usize),
+    /// A named group, written `${name}` or `\k<name>`
+    Named(&'a str),
+    /// The whole match, written `$&`
+    Match,
+    /// The text before the match, written `` $` ``
+    PreMatch,
+    /// The text after the match, written `$'`
+    PostMatch,
+}
+
+/// Parse the group between a pair of delimiters, which is either a
+/// group number or a group name.
+fn parse_group(group: &str) -> Option<Reference<'_>> {
+    if group.is_empty() {
+        None
+    } else if group.bytes().all(|b| b.is_ascii_digit()) {
+        group.parse().ok().map(Reference::Group)
+    } else {
+        Some(Reference::Named(group))
+    }
+}
+
+/// Parse the reference at the start of `template`, returning it along
+/// with its length in bytes. `None` if `template` doesn't start with a
+/// valid reference.
+fn parse_reference(template: &str) -> Option<(Reference<'_>, usize)> {
+    let bytes = template.as_bytes();
+    match (bytes.first(), bytes.get(1)) {
+        (Some(b'$'), Some(b'$')) => Some((Reference::Dollar, 2)),
+        (Some(b'$'), Some(b'&')) => Some((Reference::Match, 2)),
+        (Some(b'$'), Some(b'`')) => Some((Reference::PreMatch, 2)),
+        (Some(b'$'), Some(b'\'')) => Some((Reference::PostMatch, 2)),
+        (Some(b'$'), Some(b'{')) => {
+            let end = template.find('}')?;
+            parse_group(&template[2..end]).map(|r| (r, end + 1))
+        }
+        (Some(b'$'), Some(b'0'..=b'9')) => {
+            let end = bytes[1..]
+                .iter()
+                .position(|b| !b.is_ascii_digit())
+                .map_or(bytes.len(), |p| p + 1);
+            parse_group(&template[1..end]).map(|r| (r, end))
+        }
+        (Some(b'\\'), Some(b'k')) if template[2..].starts_with('<') => {
+            let end = template.find('>')?;
+            parse_group(&template[3..end]).map(|r| (r, end + 1))
+        }
+        _ => None,
+    }
+}
+
+impl<'r, 't> Captures<'r, 't> {
+    /// Expands all capture references in `template`, appending the
+    /// result to `dst`.
+    ///
+    /// The following references are recognised:
+    ///
+    ///  * `$N` and `${N}` - the text of group `N`
+    ///  * `${name}` and `\k<name>` - the text of the group called `name`.
+    ///    If more than one group shares the name the last one which
+    ///    matched is used.
+    ///  * `$&` - the whole match
+    ///  * `` $` `` - the text before the match
+    ///  * `$'` - the text after the match
+    ///  * `$$` - a literal `$`
+    ///
+    /// References to groups which don't exist or which didn't match
+    /// expand to the empty string. Anything else, including a `$` or
+    /// `\` which doesn't start a reference, is copied as-is.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use onig::Regex;
+    /// let re = Regex::new(r"(?<first>\w+)\s+(?<last>\w+)").unwrap();
+    /// let caps = re.captures("Bruce Springsteen").unwrap();
+    /// let mut dst = String::new();
+    /// caps.expand(r"${last}, $1 (\k<first>) costs $$5", &mut dst);
+    /// assert_eq!(dst, "Springsteen, Bruce (Bruce) costs $5");
+    /// ```
+    pub fn expand(&self, template: &str, dst: &mut String) {
+        let mut rest = template;
+        while let Some(start) = rest.find(['$', '\\']) {
+            dst.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match parse_reference(rest) {
+                Some((reference, len)) => {
+                    dst.push_str(self.resolve(reference));
+                    rest = &rest[len..];
+                }
+                None => {
+                    dst.push_str(&rest[..1]);
+                    rest = &rest[1..];
+                }
+            }
+        }
+        dst.push_str(rest);
+    }
+
+    /// Look up the text for a reference in a replacement template.
+    fn resolve(&self, reference: Reference<'_>) -> &'t str {
+        let text = self.text();
+        match reference {
+            Reference::Dollar => "$",
+            Reference::Group(group) => self.at(group).unwrap_or(""),
+            Reference::Named(name) => self.name(name).unwrap_or(""),
+            Reference::Match => self.at(0).unwrap_or(""),
+            Reference::PreMatch => self.pos(0).map_or("", |(beg, _)| &text[..beg]),
+            Reference::PostMatch => self.pos(0).map_or("", |(_, end)| &text[end..]),
+        }
+    }
+}
+
 impl<'syntax> Regex<'syntax> {
     /// Replaces the leftmost-first match with the replacement provided.
     /// The replacement can be a regular string or a function that takes
@@ -51,6 +200,16 @@ impl<'syntax> Regex<'syntax> {
     /// # }
     /// ```
     ///
+    /// The replacement string is a template which may refer to capture
+    /// groups. See [`Captures::expand`] for the syntax, and wrap the
+    /// string in [`NoExpand`](crate::NoExpand) to insert it literally:
+    ///
+    /// ```rust
+    /// # use onig::Regex;
+    /// let re = Regex::new(r"(?<y>\d{4})-(?<m>\d{2})").unwrap();
+    /// assert_eq!(re.replace("on 2023-07", "${m}/$1"), "on 07/2023");
+    /// ```
+    ///
     /// But anything satisfying the `Replacer` trait will work. For example,
     /// a closure of type `|&Captures| -> String` provides direct access to the
     /// captures corresponding to a match. This allows one to access
@@ -102,4 +261,90 @@ impl<'syntax> Regex<'syntax> {
         new.push_str(&text[last_match..]);
         new
     }
+
+    /// Replaces the leftmost-first match with the replacement provided,
+    /// like `replace`, but returning an `Error` if the search fails
+    /// rather than panicking.
+    pub fn try_replace<R: Replacer>(&self, text: &str, rep: R) -> Result<String, Error> {
+        self.try_replacen(text, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the
+    /// replacement provided, like `replace_all`, but returning an
+    /// `Error` if a search fails rather than panicking. Searches fail
+    /// when they exceed the regex's [`Budget`](crate::Budget) or
+    /// another resource limit.
+    pub fn try_replace_all<R: Replacer>(&self, text: &str, rep: R) -> Result<String, Error> {
+        self.try_replacen(text, 0, rep)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches in `text` with
+    /// the replacement provided, like `replacen`, but returning an
+    /// `Error` if a search fails rather than panicking.
+    pub fn try_replacen<R: Replacer>(
+        &self,
+        text: &str,
+        limit: usize,
+        mut rep: R,
+    ) -> Result<String, Error> {
+        let mut new = String::with_capacity(text.len());
+        let mut last_match = 0;
+        for (i, cap) in self.try_captures_iter(text).enumerate() {
+            if limit > 0 && i >= limit {
+                break;
+            }
+            let cap = cap?;
+            // unwrap on 0 is OK because captures only reports matches
+            let (s, e) = cap.pos(0).unwrap();
+            new.push_str(&text[last_match..s]);
+            new.push_str(&rep.reg_replace(&cap));
+            last_match = e;
+        }
+        new.push_str(&text[last_match..]);
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    fn expand(pattern: &str, text: &str, template: &str) -> String {
+        let re = Regex::new(pattern).unwrap();
+        let caps = re.captures(text).unwrap();
+        let mut dst = String::new();
+        caps.expand(template, &mut dst);
+        dst
+    }
+
+    #[test]
+    fn test_expand_numbered() {
+        assert_eq!(expand(r"(\w)(\w)", "ab", "$2$1$0"), "baab");
+        assert_eq!(expand(r"(\w)(\w)", "ab", "${2}1"), "b1");
+        assert_eq!(expand(r"(\w)(\w)", "ab", "[$3][${12}]"), "[][]");
+        assert_eq!(expand(r"(a)|(b)", "b", "<$1>"), "<>");
+    }
+
+    #[test]
+    fn test_expand_named() {
+        let pattern = r"(?<k>\w+)=(?<v>\w+)";
+        assert_eq!(expand(pattern, "a=1", "${v}:${k}"), "1:a");
+        assert_eq!(expand(pattern, "a=1", r"\k<v>\k<k>"), "1a");
+        assert_eq!(expand(pattern, "a=1", "${missing}"), "");
+        assert_eq!(expand(r"(?<x>a)|(?<x>b)", "b", "${x}"), "b");
+    }
+
+    #[test]
+    fn test_expand_special() {
+        assert_eq!(expand(r"\d+", "ab12cd", "[$`|$&|$']"), "[ab|12|cd]");
+        assert_eq!(expand(r"\d+", "12", "$$1 costs $"), "$1 costs $");
+        assert_eq!(expand(r"\d+", "12", r"${ \k<x \n $x"), r"${ \k<x \n $x");
+    }
+
+    #[test]
+    fn test_replace_with_template() {
+        let re = Regex::new(r"(\w+)@(\w+)").unwrap();
+        assert_eq!(re.replace_all("a@b c@d", "$2@$1"), "b@a d@c");
+        assert_eq!(re.replace_all("a@b", NoExpand("$2@$1")), "$2@$1");
+    }
 }
@@ -0,0 +1,71 @@
+//! Regex Sources
+//!
+//! This module contains the record of the pattern and settings a
+//! `Regex` was compiled from, which it keeps for introspection and to
+//! recompile the regex when it is cloned.
+
+use super::{CaseFold, Encoding, Error, Regex, RegexOptions, Syntax};
+
+/// The pattern and settings a regex was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Source {
+    /// The pattern, as written.
+    bytes: Box<[u8]>,
+    /// The encoding the pattern is written in.
+    encoding: Encoding,
+    /// The pattern as text, if it isn't valid UTF-8.
+    lossy: Option<Box<str>>,
+    /// The encoding of the text the regex searches.
+    target_encoding: Encoding,
+    /// The options passed when compiling, before the syntax adds any.
+    options: RegexOptions,
+    /// The case fold flags the regex was compiled with.
+    case_fold: CaseFold,
+}
+
+impl Source {
+    /// Record a pattern and the settings it was compiled with.
+    pub(crate) fn new(
+        bytes: &[u8],
+        encoding: Encoding,
+        target_encoding: Encoding,
+        options: RegexOptions,
+        case_fold: CaseFold,
+    ) -> Self {
+        let lossy = match std::str::from_utf8(bytes) {
+            Ok(_) => None,
+            Err(_) => Some(String::from_utf8_lossy(bytes).into()),
+        };
+        Source {
+            bytes: bytes.into(),
+            encoding,
+            lossy,
+            target_encoding,
+            options,
+            case_fold,
+        }
+    }
+
+    /// Compile the pattern again. The caller must hold
+    /// `REGEX_NEW_MUTEX`.
+    pub(crate) fn compile_locked<'s>(&self, syntax: &'s Syntax) -> Result<Regex<'s>, Error> {
+        Regex::compile_deluxe_locked(
+            &self.bytes,
+            self.encoding,
+            self.target_encoding,
+            self.options,
+            Some(self.case_fold),
+            syntax,
+        )
+    }
+
+    /// The pattern as text. Patterns which aren't valid UTF-8 are
+    /// converted lossily.
+    pub(crate) fn as_str(&self) -> &str {
+        match self.lossy {
+            Some(ref lossy) => lossy,
+            // The bytes were checked when the source was created.
+            None => unsafe { std::str::from_utf8_unchecked(&self.bytes) },
+        }
+    }
+}
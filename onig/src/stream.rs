@@ -0,0 +1,668 @@
+//! Streaming Search
+//!
+//! This module searches text read from a `BufRead`, such as a large
+//! log file, without reading all of it into memory. A
+//! `StreamSearcher` keeps a sliding window over the stream and yields
+//! matches with their absolute byte offsets and line numbers, with the
+//! same semantics as `find_iter` and `captures_iter`.
+//!
+//! # Window Size
+//!
+//! A match is only reported once the window holds at least
+//! [`max_match_len`](StreamSearcher::max_match_len) bytes after its
+//! start, so the search can't change as more of the stream is read.
+//! Matches longer than this can be missed altogether, or found shorter
+//! than they are when the pattern also matches what fits in the window.
+//! Lookbehind can see at most `max_match_len` bytes before the search
+//! position.
+//!
+//! Within the stream the edges of the window aren't the start or end
+//! of the haystack, so `^`, `$`, `\A` and `\z` only match at real line
+//! boundaries and at the start and end of the stream.
+//!
+//! # Examples
+//!
+//! ```
+//! use onig::Regex;
+//! use onig::stream::StreamSearcher;
+//!
+//! let log = "ok\nerror: disk full\nok\nerror: no route\n";
+//! let regex = Regex::new(r"^error: (.*)$").unwrap();
+//! let errors: Vec<_> = StreamSearcher::new(&regex, log.as_bytes())
+//!     .captures_iter()
+//!     .map(|caps| caps.unwrap())
+//!     .map(|caps| (caps.line(), caps.get(1).unwrap().as_bytes().to_vec()))
+//!     .collect();
+//! assert_eq!(
+//!     errors,
+//!     vec![(2, b"disk full".to_vec()), (4, b"no route".to_vec())]
+//! );
+//! ```
+
+use std::io::BufRead;
+use std::iter::FusedIterator;
+
+use super::{EncodedBytes, Error, Regex, Region, SearchOptions};
+
+/// The default maximum match length, in bytes.
+const DEFAULT_MAX_MATCH_LEN: usize = 64 * 1024;
+
+/// Streaming Searcher
+///
+/// Holds the settings for a search over a `BufRead`. Finish it with
+/// [`find_iter`](StreamSearcher::find_iter) or
+/// [`captures_iter`](StreamSearcher::captures_iter).
+///
+/// The stream is searched as bytes in the regex's encoding. Read
+/// errors and search errors are yielded by the iterators, which then
+/// finish.
+pub struct StreamSearcher<'r, 'syntax, R> {
+    regex: &'r Regex<'syntax>,
+    reader: R,
+    max_match_len: usize,
+    line_oriented: bool,
+    options: SearchOptions,
+}
+
+impl<'r, 'syntax, R: BufRead> StreamSearcher<'r, 'syntax, R> {
+    /// Create a Searcher
+    ///
+    /// # Arguments
+    ///
+    /// * `regex` - The regex to search with.
+    /// * `reader` - The stream to search.
+    pub fn new(regex: &'r Regex<'syntax>, reader: R) -> Self {
+        StreamSearcher {
+            regex,
+            reader,
+            max_match_len: DEFAULT_MAX_MATCH_LEN,
+            line_oriented: false,
+            options: SearchOptions::SEARCH_OPTION_NONE,
+        }
+    }
+
+    /// Set the Maximum Match Length
+    ///
+    /// The longest match, in bytes, which is guaranteed to be found
+    /// whole. Longer matches can be missed, see the
+    /// [module docs](self). The window holds about twice this plus one
+    /// read from the stream. Defaults to 64 KiB, and is at least the
+    /// longest character in the regex's encoding. Ignored in line
+    /// oriented mode.
+    pub fn max_match_len(mut self, len: usize) -> Self {
+        self.max_match_len = len.max(1);
+        self
+    }
+
+    /// Set Line Oriented Mode
+    ///
+    /// In line oriented mode each line is searched on its own, without
+    /// its line terminator, so matches never span lines and there's no
+    /// limit on the length of a line.
+    pub fn line_oriented(mut self, yes: bool) -> Self {
+        self.line_oriented = yes;
+        self
+    }
+
+    /// Set the Search Options
+    ///
+    /// Options for every search. `SEARCH_OPTION_NOTBOL` and
+    /// `SEARCH_OPTION_NOTEOL` apply to the start and end of the stream,
+    /// or of each line in line oriented mode.
+    pub fn search_options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Iterate Over the Matches
+    ///
+    /// Returns an iterator over the non-overlapping matches in the
+    /// stream.
+    pub fn find_iter(self) -> StreamMatches<'r, 'syntax, R> {
+        StreamMatches(Window::new(self))
+    }
+
+    /// Iterate Over the Capture Groups
+    ///
+    /// Returns an iterator over the capture groups of the
+    /// non-overlapping matches in the stream.
+    pub fn captures_iter(self) -> StreamCapturesIter<'r, 'syntax, R> {
+        StreamCapturesIter(Window::new(self))
+    }
+}
+
+/// A Match in a Stream
+///
+/// A copy of the matched bytes with their position in the stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamMatch {
+    start: u64,
+    end: u64,
+    line: u64,
+    bytes: Vec<u8>,
+}
+
+impl StreamMatch {
+    /// The byte offset of the start of the match in the stream.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The byte offset of the end of the match in the stream.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// The line the match starts on, counting from 1.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The matched bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Take the matched bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Capture Groups of a Match in a Stream
+///
+/// Group `0` is the whole match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamCaptures {
+    groups: Vec<Option<StreamMatch>>,
+}
+
+impl StreamCaptures {
+    /// Get a capture group, or `None` if it didn't participate in the
+    /// match.
+    pub fn get(&self, index: usize) -> Option<&StreamMatch> {
+        self.groups.get(index).and_then(Option::as_ref)
+    }
+
+    /// The line the whole match starts on, counting from 1.
+    pub fn line(&self) -> u64 {
+        self.groups[0].as_ref().map_or(0, StreamMatch::line)
+    }
+
+    /// The number of groups, including the whole match.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Are there no groups? Always `false`, as there's always the
+    /// whole match.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Iterate over the groups.
+    pub fn iter(&self) -> impl Iterator<Item = Option<&StreamMatch>> + '_ {
+        self.groups.iter().map(Option::as_ref)
+    }
+}
+
+/// Iterator over the matches in a stream.
+pub struct StreamMatches<'r, 'syntax, R>(Window<'r, 'syntax, R>);
+
+impl<'r, 'syntax, R: BufRead> Iterator for StreamMatches<'r, 'syntax, R> {
+    type Item = Result<StreamMatch, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window = &mut self.0;
+        window
+            .next_region()
+            .map(|r| r.map(|region| window.group(&region, 0, window.line).unwrap()))
+    }
+}
+
+impl<'r, 'syntax, R: BufRead> FusedIterator for StreamMatches<'r, 'syntax, R> {}
+
+/// Iterator over the capture groups of the matches in a stream.
+pub struct StreamCapturesIter<'r, 'syntax, R>(Window<'r, 'syntax, R>);
+
+impl<'r, 'syntax, R: BufRead> Iterator for StreamCapturesIter<'r, 'syntax, R> {
+    type Item = Result<StreamCaptures, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window = &mut self.0;
+        window.next_region().map(|r| {
+            r.map(|region| StreamCaptures {
+                groups: (0..region.len())
+                    .map(|i| window.group(&region, i, window.line))
+                    .collect(),
+            })
+        })
+    }
+}
+
+impl<'r, 'syntax, R: BufRead> FusedIterator for StreamCapturesIter<'r, 'syntax, R> {}
+
+/// The sliding window over a stream.
+struct Window<'r, 'syntax, R> {
+    regex: &'r Regex<'syntax>,
+    reader: R,
+    max_match_len: usize,
+    line_oriented: bool,
+    options: SearchOptions,
+    /// The part of the stream being searched.
+    buf: Vec<u8>,
+    /// The offset of the window in the stream.
+    offset: u64,
+    /// Where the next search starts in the window.
+    pos: usize,
+    /// The end of the last match in the window.
+    last_match_end: Option<usize>,
+    /// The window holds the end of the haystack.
+    at_end: bool,
+    /// The stream has been read to the end.
+    eof: bool,
+    /// The iterator has finished.
+    done: bool,
+    /// The line number at `line_pos`.
+    line: u64,
+    /// How far into the window lines have been counted.
+    line_pos: usize,
+}
+
+impl<'r, 'syntax, R: BufRead> Window<'r, 'syntax, R> {
+    fn new(searcher: StreamSearcher<'r, 'syntax, R>) -> Self {
+        Window {
+            regex: searcher.regex,
+            reader: searcher.reader,
+            // A match must be able to hold at least one character.
+            max_match_len: searcher
+                .max_match_len
+                .max(searcher.regex.encoding().max_enc_len()),
+            line_oriented: searcher.line_oriented,
+            options: searcher.options,
+            buf: Vec::new(),
+            offset: 0,
+            pos: 0,
+            last_match_end: None,
+            at_end: false,
+            eof: false,
+            done: false,
+            line: if searcher.line_oriented { 0 } else { 1 },
+            line_pos: 0,
+        }
+    }
+
+    /// Find the next match, reading from the stream as needed. After
+    /// the match is returned, `line` is the line it starts on.
+    fn next_region(&mut self) -> Option<Result<Region, Error>> {
+        while !self.done {
+            // Nothing can be decided until there's something to search.
+            let end = self.end();
+            if self.pos <= end && (self.at_end || end > 0) {
+                let mut region = Region::new();
+                match self.search(&mut region) {
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                    Ok(true) => {
+                        let (s, e) = region.pos(0).unwrap();
+                        if self.at_end || s + self.max_match_len < end {
+                            // Don't accept empty matches immediately
+                            // following the last match.
+                            if e == s && self.last_match_end == Some(e) {
+                                self.pos = self.next_char(e);
+                                continue;
+                            }
+                            self.pos = e;
+                            self.last_match_end = Some(e);
+                            self.count_lines(s);
+                            return Some(Ok(region));
+                        }
+                        // The match might change once more is read.
+                        self.pos = s;
+                    }
+                    Ok(false) => {
+                        if !self.at_end {
+                            let undecided = end.saturating_sub(self.max_match_len);
+                            let undecided = self.text().left_adjust_char_head(undecided);
+                            self.pos = self.pos.max(undecided);
+                        }
+                    }
+                }
+            }
+            if let Err(err) = self.refill() {
+                self.done = true;
+                return Some(Err(Error::io(err)));
+            }
+        }
+        None
+    }
+
+    /// Search the window from `pos`.
+    fn search(&self, region: &mut Region) -> Result<bool, Error> {
+        let mut options = self.options;
+        if !self.line_oriented && self.offset > 0 {
+            options |=
+                SearchOptions::SEARCH_OPTION_NOTBOL | SearchOptions::SEARCH_OPTION_NOT_BEGIN_STRING;
+        }
+        if !self.at_end {
+            options |=
+                SearchOptions::SEARCH_OPTION_NOTEOL | SearchOptions::SEARCH_OPTION_NOT_END_STRING;
+        }
+        let r = self.regex.search_with_param(
+            self.text(),
+            self.pos,
+            self.end(),
+            options,
+            Some(region),
+            self.regex.default_match_param(),
+        )?;
+        Ok(r.is_some())
+    }
+
+    /// The window as text in the regex's encoding.
+    fn text(&self) -> EncodedBytes<'_> {
+        EncodedBytes::from_parts(&self.buf, self.regex.encoding())
+    }
+
+    /// The end of the last whole character in the window. A character
+    /// cut in two by the last read is left for the next search.
+    fn end(&self) -> usize {
+        let len = self.buf.len();
+        if self.at_end || len == 0 {
+            return len;
+        }
+        let text = self.text();
+        let last = text.left_adjust_char_head(len - 1);
+        if self.regex.encoding().is_valid(&self.buf[last..]) {
+            len
+        } else {
+            last
+        }
+    }
+
+    /// Byte position of the character following the one at `pos`.
+    fn next_char(&self, pos: usize) -> usize {
+        if pos >= self.buf.len() {
+            return pos + 1;
+        }
+        self.text().right_adjust_char_head(pos + 1)
+    }
+
+    /// Count the lines in the window up to `pos`.
+    fn count_lines(&mut self, pos: usize) {
+        if !self.line_oriented && pos > self.line_pos {
+            self.line += newlines(&self.buf[self.line_pos..pos]);
+            self.line_pos = pos;
+        }
+    }
+
+    /// Copy a group out of the window. `line` is the line at the start
+    /// of the match.
+    fn group(&self, region: &Region, index: usize, line: u64) -> Option<StreamMatch> {
+        let (s, e) = region.pos(index)?;
+        let (ms, _) = region.pos(0)?;
+        let line = if self.line_oriented {
+            line
+        } else if s >= ms {
+            line + newlines(&self.buf[ms..s])
+        } else {
+            line - newlines(&self.buf[s..ms])
+        };
+        Some(StreamMatch {
+            start: self.offset + s as u64,
+            end: self.offset + e as u64,
+            line,
+            bytes: self.buf[s..e].to_vec(),
+        })
+    }
+
+    /// Move the window on through the stream.
+    fn refill(&mut self) -> std::io::Result<()> {
+        if self.line_oriented {
+            return self.next_line();
+        }
+        if self.eof {
+            self.done = true;
+            return Ok(());
+        }
+
+        // Keep some of the window before the search position as
+        // context for `^`, `\b` and lookbehind. The window must start
+        // on a character boundary.
+        let keep_from = self
+            .pos
+            .min(self.buf.len())
+            .saturating_sub(self.max_match_len);
+        let keep_from = self.text().left_adjust_char_head(keep_from);
+        self.count_lines(keep_from);
+        self.buf.drain(..keep_from);
+        self.offset += keep_from as u64;
+        self.pos -= keep_from;
+        self.line_pos -= keep_from;
+        self.last_match_end = self
+            .last_match_end
+            .and_then(|end| end.checked_sub(keep_from));
+
+        // Read enough that a match at the search position can be
+        // decided, and at least one more character.
+        let end = self.end();
+        let wanted = (self.pos.min(end) + self.max_match_len).max(end);
+        while self.end() <= wanted {
+            let read = {
+                let data = self.reader.fill_buf()?;
+                self.buf.extend_from_slice(data);
+                data.len()
+            };
+            if read == 0 {
+                self.eof = true;
+                self.at_end = true;
+                break;
+            }
+            self.reader.consume(read);
+        }
+        Ok(())
+    }
+
+    /// Replace the window with the next line.
+    fn next_line(&mut self) -> std::io::Result<()> {
+        if self.line > 0 {
+            // Step over the last line and its terminator.
+            self.offset += self.buf.len() as u64 + 1;
+        }
+        self.buf.clear();
+        let read = self.reader.read_until(b'\n', &mut self.buf)?;
+        if read == 0 {
+            self.done = true;
+            return Ok(());
+        }
+        if self.buf.last() == Some(&b'\n') {
+            self.buf.pop();
+        }
+        self.line += 1;
+        self.pos = 0;
+        self.last_match_end = None;
+        self.at_end = true;
+        Ok(())
+    }
+}
+
+/// Count the newlines in some bytes.
+fn newlines(bytes: &[u8]) -> u64 {
+    bytes.iter().filter(|&&b| b == b'\n').count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, BufReader, Read};
+
+    /// Reads a few bytes at a time, to exercise the window edges.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn find_all(pattern: &str, text: &str, max_match_len: usize) -> Vec<(u64, u64, u64)> {
+        let regex = Regex::new(pattern).unwrap();
+        StreamSearcher::new(
+            &regex,
+            BufReader::with_capacity(4, Trickle(text.as_bytes())),
+        )
+        .max_match_len(max_match_len)
+        .find_iter()
+        .map(|m| m.unwrap())
+        .map(|m| (m.start(), m.end(), m.line()))
+        .collect()
+    }
+
+    #[test]
+    fn test_stream_matches_find_iter() {
+        let text = "the cat sat on\nthe mat with the hat\n\nthat's all";
+        for pattern in [r"\w+at", r"^the", r"at$", r"\bt", r"", r"\Athe", r"all\z"] {
+            let regex = Regex::new(pattern).unwrap();
+            let expected: Vec<_> = regex
+                .find_iter(text)
+                .map(|(s, e)| (s as u64, e as u64))
+                .collect();
+            let found: Vec<_> = find_all(pattern, text, 8)
+                .into_iter()
+                .map(|(s, e, _)| (s, e))
+                .collect();
+            assert_eq!(found, expected, "pattern {:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_stream_keeps_to_utf16_characters() {
+        let text: Vec<u8> = "xyzab,"
+            .repeat(5)
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let pattern = EncodedBytes::from_parts(b"ab", crate::Encoding::UTF8);
+        let regex = Regex::with_target_encoding(
+            pattern,
+            crate::Encoding::UTF16_LE,
+            crate::RegexOptions::REGEX_OPTION_NONE,
+            crate::Syntax::default(),
+        )
+        .unwrap();
+        for max_match_len in [1, 4, 7] {
+            let found: Vec<_> =
+                StreamSearcher::new(&regex, BufReader::with_capacity(4, Trickle(&text)))
+                    .max_match_len(max_match_len)
+                    .find_iter()
+                    .map(|m| m.unwrap().start())
+                    .collect();
+            assert_eq!(found, vec![6, 18, 30, 42, 54], "{}", max_match_len);
+        }
+
+        let empty = Regex::with_target_encoding(
+            EncodedBytes::from_parts(b"", crate::Encoding::UTF8),
+            crate::Encoding::UTF16_LE,
+            crate::RegexOptions::REGEX_OPTION_NONE,
+            crate::Syntax::default(),
+        )
+        .unwrap();
+        let found: Vec<_> =
+            StreamSearcher::new(&empty, BufReader::with_capacity(4, Trickle(&text[..8])))
+                .max_match_len(2)
+                .find_iter()
+                .map(|m| m.unwrap().start())
+                .collect();
+        assert_eq!(found, vec![0, 2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn test_stream_misses_long_matches() {
+        // Read a byte at a time, so the window is as small as it can be.
+        let find = |pattern, text: &str, max_match_len| -> Vec<(u64, u64)> {
+            let regex = Regex::new(pattern).unwrap();
+            StreamSearcher::new(&regex, BufReader::with_capacity(1, text.as_bytes()))
+                .max_match_len(max_match_len)
+                .find_iter()
+                .map(|m| m.unwrap())
+                .map(|m| (m.start(), m.end()))
+                .collect()
+        };
+        let text = "abcdef abcdef abcdef";
+        assert_eq!(find("abcdef", text, 6).len(), 3);
+        assert_eq!(find("abcdef", text, 4), vec![]);
+        assert_eq!(find("a+", "aaaaaaaaaa", 4), vec![(0, 5), (5, 10)]);
+    }
+
+    #[test]
+    fn test_stream_line_numbers() {
+        let text = "a\nbb a\n\na a";
+        assert_eq!(
+            find_all("a", text, 2),
+            vec![(0, 1, 1), (5, 6, 2), (8, 9, 4), (10, 11, 4)]
+        );
+    }
+
+    #[test]
+    fn test_stream_captures() {
+        let regex = Regex::new(r"(\d+)-(x)?(\d+)").unwrap();
+        let caps: Vec<_> = StreamSearcher::new(&regex, "id 12-34\n5-6".as_bytes())
+            .captures_iter()
+            .map(|c| c.unwrap())
+            .collect();
+        assert_eq!(caps.len(), 2);
+        assert_eq!(caps[0].get(0).unwrap().as_bytes(), b"12-34");
+        assert_eq!(caps[0].get(1).unwrap().start(), 3);
+        assert!(caps[0].get(2).is_none());
+        assert_eq!(caps[0].get(3).unwrap().as_bytes(), b"34");
+        assert_eq!(caps[1].line(), 2);
+        assert_eq!(caps[1].get(3).unwrap().end(), 12);
+    }
+
+    #[test]
+    fn test_stream_line_oriented() {
+        let regex = Regex::new(r"^\w+$").unwrap();
+        let text = "one\ntwo words\nthree\n";
+        let found: Vec<_> = StreamSearcher::new(&regex, text.as_bytes())
+            .line_oriented(true)
+            .find_iter()
+            .map(|m| m.unwrap())
+            .map(|m| (m.start(), m.line(), m.into_bytes()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(0, 1, b"one".to_vec()), (14, 3, b"three".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_stream_search_options() {
+        let regex = Regex::new(r"^a|a$").unwrap();
+        let found: Vec<_> = StreamSearcher::new(&regex, "a a".as_bytes())
+            .search_options(SearchOptions::SEARCH_OPTION_NOTBOL)
+            .find_iter()
+            .map(|m| m.unwrap().start())
+            .collect();
+        assert_eq!(found, vec![2]);
+    }
+
+    #[test]
+    fn test_stream_io_error() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "broken"))
+            }
+        }
+        let regex = Regex::new("a").unwrap();
+        let mut matches = StreamSearcher::new(&regex, BufReader::new(Broken)).find_iter();
+        let err = matches.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::Io);
+        assert!(matches.next().is_none());
+    }
+}
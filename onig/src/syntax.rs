@@ -202,6 +202,35 @@ impl Syntax {
         }
     }
 
+    /// Toggle Capture History
+    ///
+    /// Returns `None` if `(?@...)` capture history groups are already
+    /// enabled or disabled as requested. Otherwise returns a copy of
+    /// this syntax with the operator toggled.
+    pub(crate) fn with_capture_history(&self, yes: bool) -> Option<Syntax> {
+        let op = SyntaxOperator::SYNTAX_OPERATOR_ATMARK_CAPTURE_HISTORY;
+        if self.operators().contains(op) == yes {
+            return None;
+        }
+        let mut toggled = *self;
+        if yes {
+            toggled.enable_operators(op);
+        } else {
+            toggled.disable_operators(op);
+        }
+        Some(toggled)
+    }
+
+    /// Do the two syntaxes have the same settings?
+    pub(crate) fn same_settings(&self, other: &Syntax) -> bool {
+        self.raw == other.raw
+    }
+
+    /// The meta characters of this syntax.
+    pub(crate) fn meta_chars(&self) -> &onig_sys::OnigMetaCharTableType {
+        &self.raw.meta_char_table
+    }
+
     fn raw_mut(&self) -> *mut onig_sys::OnigSyntaxType {
         &self.raw as *const onig_sys::OnigSyntaxType as *mut onig_sys::OnigSyntaxType
     }
@@ -219,4 +248,216 @@ mod test {
         syn.disable_operators(SyntaxOperator::SYNTAX_OPERATOR_ESC_X_BRACE_HEX8);
         assert_eq!(Syntax::python().raw, syn.raw);
     }
+
+    fn find(syntax: &Syntax, pattern: &str, text: &str) -> Result<Option<(usize, usize)>, ()> {
+        crate::Regex::with_options(pattern, RegexOptions::REGEX_OPTION_NONE, syntax)
+            .map(|r| r.find(text))
+            .map_err(|_| ())
+    }
+
+    #[test]
+    fn toggle_operators() {
+        use SyntaxOperator as Op;
+        let none = Ok(None);
+        let cases = [
+            (
+                Syntax::grep(),
+                Op::SYNTAX_OPERATOR_ESC_ASTERISK_ZERO_INF,
+                r"ba\*",
+                "baa",
+                Ok(Some((0, 3))),
+                none,
+            ),
+            (
+                Syntax::grep(),
+                Op::SYNTAX_OPERATOR_ESC_PLUS_ONE_INF,
+                r"ba\+",
+                "baa",
+                Ok(Some((0, 3))),
+                none,
+            ),
+            (
+                Syntax::grep(),
+                Op::SYNTAX_OPERATOR_ESC_QMARK_ZERO_ONE,
+                r"ba\?",
+                "baa",
+                Ok(Some((0, 2))),
+                none,
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_ESC_O_BRACE_OCTAL,
+                r"\o{101}",
+                "xA",
+                Ok(Some((1, 2))),
+                none,
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_QMARK_LPAREN_IF_ELSE,
+                r"(a)?(?(1)b|c)",
+                "ab",
+                Ok(Some((0, 2))),
+                Err(()),
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_K_KEEP,
+                r"a\Kb",
+                "ab",
+                Ok(Some((1, 2))),
+                none,
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_R_GENERAL_NEWLINE,
+                r"\R",
+                "a\r\n",
+                Ok(Some((1, 3))),
+                none,
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_ESC_CAPITAL_N_O_SUPER_DOT,
+                r"\N",
+                "\na",
+                Ok(Some((1, 2))),
+                none,
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_QMARK_TILDE_ABSENT_GROUP,
+                r"/\*(?~\*/)\*/",
+                "/* a */",
+                Ok(Some((0, 7))),
+                Err(()),
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_ESC_X_Y_TEXT_SEGMENT,
+                r"\X",
+                "e\u{301}",
+                Ok(Some((0, 3))),
+                none,
+            ),
+            (
+                Syntax::perl(),
+                Op::SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL,
+                r"a(?R)?b",
+                "ab",
+                Ok(Some((0, 2))),
+                Err(()),
+            ),
+            (
+                Syntax::perl(),
+                Op::SYNTAX_OPERATOR_QMARK_BRACE_CALLOUT_CONTENTS,
+                r"a(?{x})b",
+                "ab",
+                Ok(Some((0, 2))),
+                Err(()),
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_ASTERISK_CALLOUT_NAME,
+                r"a(*FAIL)|b",
+                "ab",
+                Ok(Some((1, 2))),
+                Err(()),
+            ),
+            (
+                Syntax::oniguruma(),
+                Op::SYNTAX_OPERATOR_OPTION_ONIGURUMA,
+                r"(?W)\w",
+                "é1",
+                Ok(Some((2, 3))),
+                Err(()),
+            ),
+            (
+                Syntax::python(),
+                Op::SYNTAX_OPERATOR_QMARK_CAPITAL_P_NAME,
+                r"(?P<n>a)(?P=n)",
+                "aa",
+                Ok(Some((0, 2))),
+                Err(()),
+            ),
+        ];
+        for (base, op, pattern, text, on, off) in cases {
+            let mut syntax = *base;
+            syntax.enable_operators(op);
+            assert!(syntax.operators().contains(op));
+            assert_eq!(find(&syntax, pattern, text), on, "{:?} on", op);
+            syntax.disable_operators(op);
+            assert!(!syntax.operators().contains(op));
+            assert_eq!(find(&syntax, pattern, text), off, "{:?} off", op);
+        }
+    }
+
+    #[test]
+    fn toggle_behavior() {
+        use SyntaxBehavior as Bv;
+        let cases = [
+            (
+                Syntax::perl(),
+                Bv::SYNTAX_BEHAVIOR_VARIABLE_LEN_LOOK_BEHIND,
+                r"(?<=a|bc)x",
+                "bcx",
+                Ok(Some((2, 3))),
+                Err(()),
+            ),
+            (
+                Syntax::perl(),
+                Bv::SYNTAX_BEHAVIOR_ISOLATED_OPTION_CONTINUE_BRANCH,
+                r"a(?i)b|c",
+                "C",
+                Ok(Some((0, 1))),
+                Ok(None),
+            ),
+            (
+                Syntax::python(),
+                Bv::SYNTAX_BEHAVIOR_PYTHON,
+                r"a\Z",
+                "a\n",
+                Ok(None),
+                Ok(Some((0, 1))),
+            ),
+            (
+                Syntax::oniguruma(),
+                Bv::SYNTAX_BEHAVIOR_WHOLE_OPTIONS,
+                r"(?I)\d",
+                "1",
+                Ok(Some((0, 1))),
+                Err(()),
+            ),
+            (
+                Syntax::oniguruma(),
+                Bv::SYNTAX_BEHAVIOR_ALLOW_EMPTY_RANGE_IN_CC,
+                r"[b-a]",
+                "a",
+                Ok(None),
+                Err(()),
+            ),
+            (
+                Syntax::oniguruma(),
+                Bv::SYNTAX_BEHAVIOR_ALLOW_INVALID_CODE_END_OF_RANGE_IN_CC,
+                r"[a-\x{7fffffff}]",
+                "b",
+                Ok(Some((0, 1))),
+                Err(()),
+            ),
+        ];
+        for (base, behavior, pattern, text, on, off) in cases {
+            let mut syntax = *base;
+            syntax.enable_behavior(behavior);
+            assert_eq!(find(&syntax, pattern, text), on, "{:?} on", behavior);
+            syntax.disable_behavior(behavior);
+            assert_eq!(find(&syntax, pattern, text), off, "{:?} off", behavior);
+        }
+
+        // Oniguruma accepts, but ignores, context independent anchors.
+        let mut syntax = *Syntax::posix_basic();
+        syntax.enable_behavior(Bv::SYNTAX_BEHAVIOR_CONTEXT_INDEP_ANCHORS);
+        assert!(syntax
+            .behavior()
+            .contains(Bv::SYNTAX_BEHAVIOR_CONTEXT_INDEP_ANCHORS));
+    }
 }
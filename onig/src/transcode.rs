@@ -0,0 +1,126 @@
+//! Pattern Transcoding
+//!
+//! Oniguruma only compiles patterns written in the encoding of the
+//! text they will search. This module re-encodes UTF-8 patterns into
+//! the target encoding, so a pattern written as a Rust string literal
+//! can search UTF-16 or UTF-32 text.
+
+use super::{Encoding, Error, ErrorKind};
+
+/// A pattern re-encoded into a target encoding.
+#[derive(Debug)]
+pub(crate) struct Transcoded {
+    /// The pattern in the target encoding.
+    pub(crate) bytes: Vec<u8>,
+    /// The `(target, source)` byte offsets of each character boundary.
+    boundaries: Vec<(usize, usize)>,
+}
+
+impl Transcoded {
+    /// Map a byte offset in the transcoded pattern back to the
+    /// original pattern.
+    pub(crate) fn source_offset(&self, target: usize) -> usize {
+        match self.boundaries.binary_search_by_key(&target, |&(t, _)| t) {
+            Ok(i) => self.boundaries[i].1,
+            Err(i) => self.boundaries[i.saturating_sub(1)].1,
+        }
+    }
+}
+
+/// Transcode a Pattern
+///
+/// UTF-8 and ASCII patterns can be transcoded into UTF-16 or UTF-32
+/// in either byte order. Patterns which are pure ASCII can also be
+/// used with any ASCII compatible encoding.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern bytes, in `from`.
+/// * `from` - The encoding the pattern is written in.
+/// * `to` - The encoding to transcode the pattern into.
+pub(crate) fn transcode(pattern: &[u8], from: Encoding, to: Encoding) -> Result<Transcoded, Error> {
+    let unsupported = || {
+        Error::with_kind(
+            ErrorKind::EncodingMismatch,
+            format!(
+                "Can't transcode a pattern from {} to {}",
+                from.name(),
+                to.name()
+            ),
+        )
+    };
+    if from != Encoding::UTF8 && from != Encoding::ASCII {
+        return Err(unsupported());
+    }
+    let text = std::str::from_utf8(pattern).map_err(|e| {
+        Error::with_kind(ErrorKind::EncodingMismatch, "Pattern is not valid UTF-8").with_source(e)
+    })?;
+
+    let encode: fn(char, &mut Vec<u8>) = if to == Encoding::UTF16_LE {
+        |c, buf| {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                buf.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+    } else if to == Encoding::UTF16_BE {
+        |c, buf| {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                buf.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+    } else if to == Encoding::UTF32_LE {
+        |c, buf| buf.extend_from_slice(&(c as u32).to_le_bytes())
+    } else if to == Encoding::UTF32_BE {
+        |c, buf| buf.extend_from_slice(&(c as u32).to_be_bytes())
+    } else if text.is_ascii() && unsafe { (*to.as_raw()).min_enc_len } == 1 {
+        |c, buf| buf.push(c as u8)
+    } else {
+        return Err(unsupported());
+    };
+
+    let mut transcoded = Transcoded {
+        bytes: Vec::with_capacity(pattern.len() * 4),
+        boundaries: Vec::with_capacity(pattern.len() + 1),
+    };
+    for (pos, c) in text.char_indices() {
+        transcoded.boundaries.push((transcoded.bytes.len(), pos));
+        encode(c, &mut transcoded.bytes);
+    }
+    transcoded
+        .boundaries
+        .push((transcoded.bytes.len(), text.len()));
+    Ok(transcoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcode_utf16() {
+        let t = transcode("aé😀".as_bytes(), Encoding::UTF8, Encoding::UTF16_BE).unwrap();
+        assert_eq!(t.bytes, [0, 0x61, 0, 0xe9, 0xd8, 0x3d, 0xde, 0x00]);
+        assert_eq!(t.source_offset(2), 1);
+        assert_eq!(t.source_offset(4), 3);
+        assert_eq!(t.source_offset(5), 3);
+        assert_eq!(t.source_offset(8), 7);
+    }
+
+    #[test]
+    fn test_transcode_utf32() {
+        let t = transcode(b"ab", Encoding::ASCII, Encoding::UTF32_LE).unwrap();
+        assert_eq!(t.bytes, [0x61, 0, 0, 0, 0x62, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_transcode_unsupported() {
+        let t = transcode(b"abc", Encoding::UTF8, Encoding::SJIS).unwrap();
+        assert_eq!(t.bytes, b"abc");
+        let err = transcode("é".as_bytes(), Encoding::UTF8, Encoding::SJIS).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingMismatch);
+        let err = transcode(b"abc", Encoding::SJIS, Encoding::UTF16_LE).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingMismatch);
+        let err = transcode(b"a\xc3", Encoding::UTF8, Encoding::UTF16_LE).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingMismatch);
+    }
+}
@@ -0,0 +1,2009 @@
+//! Pattern Translation
+//!
+//! This module rewrites a pattern written for one [`Syntax`] as a
+//! pattern for another, e.g. to keep patterns written for Perl, Python
+//! or POSIX tools in one canonical form. The pattern is parsed into an
+//! [`ast`](crate::ast) with the source syntax and printed back with the
+//! operators, options and meta characters of the target.
+//!
+//! What the syntaxes' options mean is translated too, not just how
+//! they're spelled. In `Syntax::perl()` `^` and `$` only match at the
+//! ends of the string unless `(?m)` is set, while in `Syntax::ruby()`
+//! they always match at newlines, so they become `\A` and `\Z`. The
+//! same goes for `.` in the POSIX syntaxes, which matches newlines, and
+//! for negated bracket expressions in `Syntax::grep()`, which don't.
+
+use std::slice;
+
+use super::ast::{
+    self, Absent, Anchor, Backref, Call, Callout, CalloutDirection, Class, ClassEscape,
+    ClassEscapeKind, ClassItem, ClassItemKind, Condition, Conditional, Flag, Greed, Group,
+    GroupKind, Node, NodeKind, Options, Property, Reference, Repeat, Span, Special,
+};
+use super::{Error, ErrorKind, RegexOptions, Syntax, SyntaxBehavior, SyntaxOperator};
+
+/// Translate a Pattern Between Syntaxes
+///
+/// Parses `pattern` as written for `from` and returns a pattern which
+/// matches the same text when compiled with `to`. Both syntaxes are
+/// used with their own options, as
+/// [`Regex::with_options`](crate::Regex::with_options) uses them with
+/// `REGEX_OPTION_NONE`. Escapes, groups, options and meta characters
+/// are rewritten in the target's spelling, and group numbers are kept.
+///
+/// # Errors
+///
+/// Returns the error from parsing `pattern` if it isn't valid in
+/// `from`. If the target syntax can't express part of the pattern, e.g.
+/// a look-ahead in `Syntax::grep()`, returns an error of kind
+/// [`ErrorKind::Untranslatable`] with [`Error::pattern_span`] set to
+/// that part.
+///
+/// # Examples
+///
+/// ```
+/// use onig::Syntax;
+///
+/// let ruby = onig::translate(r"(?P<word>\w+) (?P=word)", Syntax::python(), Syntax::ruby());
+/// assert_eq!(ruby.unwrap(), r"(?<word>\w+) \k<word>");
+///
+/// let grep = onig::translate("(a|b)+c?", Syntax::posix_extended(), Syntax::grep());
+/// assert_eq!(grep.unwrap(), r"\(a\|b\)\+c\?");
+///
+/// let perl = onig::translate("^a$", Syntax::perl(), Syntax::ruby());
+/// assert_eq!(perl.unwrap(), r"\Aa\Z");
+/// ```
+pub fn translate(pattern: &str, from: &Syntax, to: &Syntax) -> Result<String, Error> {
+    let ast = ast::parse(pattern, RegexOptions::REGEX_OPTION_NONE, from)?;
+    let mut printer = Printer::new(&ast.names, from, to);
+    printer.base_options(&ast.root, from)?;
+    printer.expr(&ast.root)?;
+    Ok(printer.out)
+}
+
+/// What `^`, `$` and `.` Match
+#[derive(Clone, Copy)]
+struct Mode {
+    /// `^` and `$` only match at the ends of the string.
+    single_line: bool,
+    /// `.` matches newlines.
+    dot_all: bool,
+}
+
+impl Mode {
+    fn new(options: RegexOptions) -> Self {
+        Mode {
+            single_line: options.contains(RegexOptions::REGEX_OPTION_SINGLELINE),
+            dot_all: options.contains(RegexOptions::REGEX_OPTION_MULTILINE),
+        }
+    }
+
+    fn apply(&mut self, options: &Options) {
+        for (flags, on) in [(&options.on, true), (&options.off, false)] {
+            for flag in flags {
+                match flag {
+                    Flag::DotAll => self.dot_all = on,
+                    Flag::MultiLine => self.single_line = !on,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+struct Printer<'a> {
+    names: &'a [(String, Vec<usize>)],
+    syntax: &'a Syntax,
+    ops: SyntaxOperator,
+    escape: Option<char>,
+    /// The variable meta characters: any char, zero or more, zero or
+    /// one and one or more times.
+    meta: [Option<char>; 4],
+    /// The meaning of the anchors and `.` at this point of the pattern.
+    source: Mode,
+    /// Their meaning at this point of the translation.
+    target: Mode,
+    /// Do negated bracket expressions leave out newlines in the source?
+    source_cc_newline: bool,
+    /// Can the target only capture with named groups?
+    only_named: bool,
+    /// The number of capture groups written so far.
+    opened: usize,
+    /// Was the last thing written a back-reference which a following
+    /// digit would be read as part of?
+    open_number: bool,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn new(names: &'a [(String, Vec<usize>)], from: &Syntax, to: &'a Syntax) -> Self {
+        let ops = to.operators();
+        let table = to.meta_chars();
+        let meta_char = |c: u32| char::from_u32(c).filter(|&c| c != '\0');
+        let meta = if ops.contains(SyntaxOperator::SYNTAX_OPERATOR_VARIABLE_META_CHARACTERS) {
+            [
+                meta_char(table.anychar),
+                meta_char(table.anytime),
+                meta_char(table.zero_or_one_time),
+                meta_char(table.one_or_more_time),
+            ]
+        } else {
+            [None; 4]
+        };
+        let options = to.options();
+        let only_named = (!names.is_empty()
+            && to
+                .behavior()
+                .contains(SyntaxBehavior::SYNTAX_BEHAVIOR_CAPTURE_ONLY_NAMED_GROUP)
+            && !options.contains(RegexOptions::REGEX_OPTION_CAPTURE_GROUP))
+            || options.contains(RegexOptions::REGEX_OPTION_DONT_CAPTURE_GROUP);
+        Printer {
+            names,
+            syntax: to,
+            ops,
+            escape: to.escape_char(),
+            meta,
+            source: Mode::new(from.options()),
+            target: Mode::new(options),
+            source_cc_newline: from
+                .behavior()
+                .contains(SyntaxBehavior::SYNTAX_BEHAVIOR_NOT_NEWLINE_IN_NEGATIVE_CC),
+            only_named,
+            opened: 0,
+            open_number: false,
+            out: String::new(),
+        }
+    }
+
+    fn has(&self, op: SyntaxOperator) -> bool {
+        self.ops.contains(op)
+    }
+
+    fn allows(&self, behavior: SyntaxBehavior) -> bool {
+        self.syntax.behavior().contains(behavior)
+    }
+
+    fn untranslatable(&self, what: &str, span: &Span) -> Error {
+        Error::with_kind(
+            ErrorKind::Untranslatable,
+            format!("{} can't be expressed in the target syntax", what),
+        )
+        .with_span(span.clone())
+    }
+
+    fn push(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.open_number = false;
+    }
+
+    /// `text` after the escape character.
+    fn escaped(&self, text: &str) -> Option<String> {
+        self.escape.map(|escape| format!("{}{}", escape, text))
+    }
+
+    /// `text` after the escape character, if the syntax has `op`.
+    fn escaped_op(&self, op: SyntaxOperator, text: &str) -> Option<String> {
+        self.escaped(text).filter(|_| self.has(op))
+    }
+
+    // Groups and alternatives
+
+    fn open(&mut self, what: &str, span: &Span) -> Result<(), Error> {
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_LPAREN_SUBEXP) {
+            self.push("(");
+        } else if let Some(open) =
+            self.escaped_op(SyntaxOperator::SYNTAX_OPERATOR_ESC_LPAREN_SUBEXP, "(")
+        {
+            self.push(&open);
+        } else {
+            return Err(self.untranslatable(what, span));
+        }
+        Ok(())
+    }
+
+    /// Close a group opened with [`Printer::open`].
+    fn close(&mut self) {
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_LPAREN_SUBEXP) {
+            self.push(")");
+        } else {
+            let close = self.escaped(")").unwrap_or_default();
+            self.push(&close);
+        }
+    }
+
+    /// Open a `(?...` group.
+    fn open_extension(&mut self, text: &str, what: &str, span: &Span) -> Result<(), Error> {
+        if !self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT) {
+            return Err(self.untranslatable(what, span));
+        }
+        self.open(what, span)?;
+        self.push("?");
+        self.push(text);
+        Ok(())
+    }
+
+    fn alternation_operator(&mut self, span: &Span) -> Result<(), Error> {
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_VBAR_ALT) {
+            self.push("|");
+        } else if let Some(vbar) =
+            self.escaped_op(SyntaxOperator::SYNTAX_OPERATOR_ESC_VBAR_ALT, "|")
+        {
+            self.push(&vbar);
+        } else {
+            return Err(self.untranslatable("alternation", span));
+        }
+        Ok(())
+    }
+
+    /// Write a whole group body or pattern.
+    fn expr(&mut self, node: &Node) -> Result<(), Error> {
+        match node.kind {
+            NodeKind::Alternation(ref branches) => {
+                self.alternatives(&branches.iter().collect::<Vec<_>>())
+            }
+            _ => self.alternatives(&[node]),
+        }
+    }
+
+    /// Write the alternatives of a group body or pattern.
+    fn alternatives(&mut self, branches: &[&Node]) -> Result<(), Error> {
+        // Isolated options the target wouldn't carry into the later
+        // alternatives itself.
+        let mut carried = Options::default();
+        for (i, branch) in branches.iter().enumerate() {
+            if i > 0 {
+                self.alternation_operator(&branch.span)?;
+            }
+            let items = match branch.kind {
+                NodeKind::Concat(ref nodes) => &nodes[..],
+                _ => slice::from_ref(*branch),
+            };
+            let later = i + 1 < branches.len();
+            if is_empty(&carried) {
+                self.branch(items, later, !later, &mut carried)?;
+            } else {
+                let target = self.target;
+                let wrapper = carried.clone();
+                self.options_group(&wrapper, false, &branch.span)?;
+                self.branch(items, later, true, &mut carried)?;
+                self.close();
+                self.target = target;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the items of one alternative. `later` says whether more
+    /// alternatives follow, and `isolate` whether an options group
+    /// ending the alternative can be written as isolated options.
+    fn branch(
+        &mut self,
+        items: &[Node],
+        later: bool,
+        isolate: bool,
+        carried: &mut Options,
+    ) -> Result<(), Error> {
+        for (k, item) in items.iter().enumerate() {
+            match item.kind {
+                NodeKind::Options(ref options) => {
+                    self.source.apply(options);
+                    let options = self.target_options(options, &item.span)?;
+                    if is_empty(&options) {
+                        continue;
+                    }
+                    if later
+                        && !self
+                            .allows(SyntaxBehavior::SYNTAX_BEHAVIOR_ISOLATED_OPTION_CONTINUE_BRANCH)
+                    {
+                        // The target would take the later alternatives
+                        // into the options' scope, so scope them to the
+                        // rest of this one and repeat them in the others.
+                        let target = self.target;
+                        self.options_group(&options, false, &item.span)?;
+                        merge(carried, &options);
+                        self.branch(&items[k + 1..], later, true, carried)?;
+                        self.close();
+                        self.target = target;
+                        return Ok(());
+                    }
+                    self.options_group(&options, true, &item.span)?;
+                }
+                NodeKind::Group(ref group)
+                    if matches!(group.kind, GroupKind::Options(_))
+                        && isolate
+                        && k + 1 == items.len()
+                        && (k == 0 || !matches!(group.body.kind, NodeKind::Alternation(_))) =>
+                {
+                    self.group(group, &item.span, true, false)?
+                }
+                _ => self.node(item, false)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a group. An options group is written as isolated options
+    /// followed by its body when `isolated` is set. `repeated` says
+    /// whether a repeat operator follows.
+    fn group(
+        &mut self,
+        group: &Group,
+        span: &Span,
+        isolated: bool,
+        repeated: bool,
+    ) -> Result<(), Error> {
+        let modes = (self.source, self.target);
+        let extension = match group.kind {
+            GroupKind::Capture { index: None, .. } | GroupKind::NonCapture => {
+                return self.non_capture(&group.body, span, repeated)
+            }
+            GroupKind::Capture {
+                index: Some(_),
+                ref name,
+                history,
+            } => {
+                self.open_capture(name.as_deref(), history, span)?;
+                self.opened += 1;
+                self.expr(&group.body)?;
+                self.close();
+                return Ok(());
+            }
+            GroupKind::Atomic => (">", "an atomic group"),
+            GroupKind::LookAhead => ("=", "a look-ahead"),
+            GroupKind::NegativeLookAhead => ("!", "a negative look-ahead"),
+            GroupKind::LookBehind => ("<=", "a look-behind"),
+            GroupKind::NegativeLookBehind => ("<!", "a negative look-behind"),
+            GroupKind::Options(ref options) => {
+                self.source.apply(options);
+                let options = self.target_options(options, span)?;
+                if is_empty(&options) && isolated {
+                    self.expr(&group.body)?;
+                } else if is_empty(&options) {
+                    self.non_capture(&group.body, span, repeated)?;
+                } else {
+                    self.options_group(&options, isolated, span)?;
+                    self.expr(&group.body)?;
+                    if !isolated {
+                        self.close();
+                    }
+                }
+                (self.source, self.target) = modes;
+                return Ok(());
+            }
+        };
+        self.open_extension(extension.0, extension.1, span)?;
+        self.expr(&group.body)?;
+        self.close();
+        (self.source, self.target) = modes;
+        Ok(())
+    }
+
+    fn open_capture(
+        &mut self,
+        name: Option<&str>,
+        history: bool,
+        span: &Span,
+    ) -> Result<(), Error> {
+        use SyntaxOperator as Op;
+        let name = match name {
+            Some(name) => name,
+            None if self.only_named => {
+                return Err(self.untranslatable("an unnamed capture group", span))
+            }
+            None if history => return self.open_extension("@", "a capture history group", span),
+            None => return self.open("a group", span),
+        };
+        let multiplex = self
+            .names
+            .iter()
+            .any(|(n, groups)| n == name && groups.len() > 1);
+        if multiplex
+            && !self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_ALLOW_MULTIPLEX_DEFINITION_NAME)
+        {
+            return Err(self.untranslatable("a name defined more than once", span));
+        }
+        let text = if history {
+            if !self.has(Op::SYNTAX_OPERATOR_ATMARK_CAPTURE_HISTORY)
+                || !self.has(Op::SYNTAX_OPERATOR_QMARK_LT_NAMED_GROUP)
+            {
+                return Err(self.untranslatable("a capture history group", span));
+            }
+            format!("@<{}>", name)
+        } else if self.has(Op::SYNTAX_OPERATOR_QMARK_LT_NAMED_GROUP) {
+            format!("<{}>", name)
+        } else if self.has(Op::SYNTAX_OPERATOR_QMARK_CAPITAL_P_NAME) {
+            format!("P<{}>", name)
+        } else {
+            return Err(self.untranslatable("a named group", span));
+        };
+        self.open_extension(&text, "a named group", span)
+    }
+
+    /// Write a non-capturing group, or just its body if the target has
+    /// no such groups and the body doesn't need one.
+    fn non_capture(&mut self, body: &Node, span: &Span, repeated: bool) -> Result<(), Error> {
+        let modes = (self.source, self.target);
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT) {
+            self.open_extension(":", "a non-capturing group", span)?;
+            self.expr(body)?;
+            self.close();
+        } else if is_atom(body) {
+            self.node(body, repeated)?;
+        } else {
+            let items = match body.kind {
+                NodeKind::Concat(ref nodes) if !repeated => &nodes[..],
+                NodeKind::Empty if !repeated => &[],
+                _ => return Err(self.untranslatable("a non-capturing group", span)),
+            };
+            if items
+                .iter()
+                .any(|item| matches!(item.kind, NodeKind::Options(_)))
+            {
+                return Err(self.untranslatable("a non-capturing group", span));
+            }
+            self.branch(items, false, false, &mut Options::default())?;
+        }
+        (self.source, self.target) = modes;
+        Ok(())
+    }
+
+    // Options
+
+    /// The target's letters for `flag`, if it has them.
+    fn flag_letters(&self, flag: Flag) -> Option<&'static str> {
+        use SyntaxOperator as Op;
+        if !self.has(Op::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT) {
+            return None;
+        }
+        let perl = self.has(Op::SYNTAX_OPERATOR_OPTION_PERL);
+        let ruby = self.has(Op::SYNTAX_OPERATOR_OPTION_RUBY);
+        let oniguruma = self.has(Op::SYNTAX_OPERATOR_OPTION_ONIGURUMA);
+        let whole = self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_WHOLE_OPTIONS);
+        let letters = match flag {
+            Flag::IgnoreCase if perl || ruby || oniguruma => "i",
+            Flag::DotAll if perl => "s",
+            Flag::DotAll if ruby || oniguruma => "m",
+            Flag::MultiLine if perl => "m",
+            Flag::Ascii if self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_PYTHON) => "a",
+            Flag::WordAscii if oniguruma => "W",
+            Flag::DigitAscii if oniguruma => "D",
+            Flag::SpaceAscii if oniguruma => "S",
+            Flag::PosixAscii if oniguruma => "P",
+            Flag::GraphemeCluster if oniguruma => "y{g}",
+            Flag::WordSegment if oniguruma => "y{w}",
+            Flag::IgnoreCaseAscii if whole => "I",
+            Flag::FindLongest if whole => "L",
+            Flag::FindNotEmpty if whole => "C",
+            _ => return None,
+        };
+        Some(letters)
+    }
+
+    /// The target's letters for setting `flag` on or off, if it can.
+    fn option_letters(&self, flag: Flag, on: bool) -> Option<&'static str> {
+        // `(?-` is a relative call with Perl's calls.
+        if !on && self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL) {
+            return None;
+        }
+        self.flag_letters(flag)
+    }
+
+    /// The options to write for `options`. Extended mode doesn't matter
+    /// once the pattern is parsed, and what `.`, `^` and `$` match is
+    /// written into them where the target has no option for it.
+    fn target_options(&self, options: &Options, span: &Span) -> Result<Options, Error> {
+        let mut translated = Options::default();
+        for (flags, on, into) in [
+            (&options.on, true, &mut translated.on),
+            (&options.off, false, &mut translated.off),
+        ] {
+            for &flag in flags {
+                match self.option_letters(flag, on) {
+                    Some(_) => into.push(flag),
+                    None if matches!(flag, Flag::Extend | Flag::DotAll | Flag::MultiLine) => {}
+                    None => {
+                        let what = format!("the {} option", flag_name(flag));
+                        return Err(self.untranslatable(&what, span));
+                    }
+                }
+            }
+        }
+        Ok(translated)
+    }
+
+    /// Open an options group, or write isolated options, and apply them
+    /// to the target's mode.
+    fn options_group(
+        &mut self,
+        options: &Options,
+        isolated: bool,
+        span: &Span,
+    ) -> Result<(), Error> {
+        let mut text = String::new();
+        for &flag in &options.on {
+            text.push_str(self.flag_letters(flag).unwrap_or_default());
+        }
+        if !options.off.is_empty() {
+            text.push('-');
+            for &flag in &options.off {
+                text.push_str(self.flag_letters(flag).unwrap_or_default());
+            }
+        }
+        // Isolated options end with a plain parenthesis whichever
+        // parentheses open groups.
+        text.push(if isolated { ')' } else { ':' });
+        self.open_extension(&text, "an option group", span)?;
+        self.target.apply(options);
+        Ok(())
+    }
+
+    /// Write `text` in a group setting `flag` on or off, if the target
+    /// has an option for it.
+    fn flagged(&mut self, flag: Flag, on: bool, text: &str, span: &Span) -> bool {
+        let letters = match self.option_letters(flag, on) {
+            Some(letters) => letters,
+            None => return false,
+        };
+        let options = if on {
+            format!("{}:", letters)
+        } else {
+            format!("-{}:", letters)
+        };
+        if self.open_extension(&options, "", span).is_err() {
+            return false;
+        }
+        self.push(text);
+        self.close();
+        true
+    }
+
+    /// Write options making up for the differences between the two
+    /// syntaxes' own options, where the pattern depends on them.
+    fn base_options(&mut self, root: &Node, from: &Syntax) -> Result<(), Error> {
+        let mut options = Options::default();
+        let ignore_case = |syntax: &Syntax| {
+            syntax
+                .options()
+                .contains(RegexOptions::REGEX_OPTION_IGNORECASE)
+        };
+        let mut set = |flag, on| {
+            if on {
+                options.on.push(flag)
+            } else {
+                options.off.push(flag)
+            }
+        };
+        if ignore_case(from) != ignore_case(self.syntax) {
+            set(Flag::IgnoreCase, ignore_case(from));
+        }
+        if self.source.single_line != self.target.single_line
+            && self
+                .option_letters(Flag::MultiLine, !self.source.single_line)
+                .is_some()
+            && contains(root, &|kind| {
+                matches!(kind, NodeKind::Anchor(Anchor::LineStart | Anchor::LineEnd))
+            })
+        {
+            set(Flag::MultiLine, !self.source.single_line);
+        }
+        if self.source.dot_all != self.target.dot_all
+            && self
+                .option_letters(Flag::DotAll, self.source.dot_all)
+                .is_some()
+            && contains(root, &|kind| matches!(kind, NodeKind::AnyChar))
+        {
+            set(Flag::DotAll, self.source.dot_all);
+        }
+        if is_empty(&options) {
+            return Ok(());
+        }
+        // Whole pattern options must open the pattern, and without these
+        // options the anchors and dots are written out instead.
+        let whole = match first(root).kind {
+            NodeKind::Options(ref options)
+            | NodeKind::Group(Group {
+                kind: GroupKind::Options(ref options),
+                ..
+            }) => options.on.iter().any(|&flag| is_whole(flag)),
+            _ => false,
+        };
+        if whole {
+            return match options
+                .on
+                .iter()
+                .chain(&options.off)
+                .any(|&flag| flag == Flag::IgnoreCase)
+            {
+                true => Err(self.untranslatable("the ignore case option", &root.span)),
+                false => Ok(()),
+            };
+        }
+        let options = self.target_options(&options, &root.span)?;
+        self.options_group(&options, true, &root.span)
+    }
+
+    // Atoms
+
+    /// Write a node inside an alternative. `repeated` says whether a
+    /// repeat operator follows.
+    fn node(&mut self, node: &Node, repeated: bool) -> Result<(), Error> {
+        let span = &node.span;
+        match node.kind {
+            NodeKind::Empty => Ok(()),
+            NodeKind::Literal(c) => self.literal(c, span),
+            NodeKind::Byte(b) => {
+                let text = self
+                    .byte(b)
+                    .ok_or_else(|| self.untranslatable("a byte escape", span))?;
+                self.push(&text);
+                Ok(())
+            }
+            NodeKind::AnyChar => self.any_char(self.source.dot_all, span),
+            NodeKind::Anchor(anchor) => self.anchor(anchor, span),
+            NodeKind::Special(special) => self.special(special, span),
+            NodeKind::Class(ref class) => {
+                let text = self.class(class, self.source_cc_newline, false, span)?;
+                self.push(&text);
+                Ok(())
+            }
+            NodeKind::ClassEscape(escape) => self.class_escape(escape, span),
+            NodeKind::Property(ref property) => {
+                let text = self
+                    .property(property)
+                    .ok_or_else(|| self.untranslatable("a character property", span))?;
+                self.push(&text);
+                Ok(())
+            }
+            NodeKind::Backref(ref backref) => self.backref(backref, span),
+            NodeKind::Call(ref call) => self.call(call, span),
+            NodeKind::Callout(ref callout) => self.callout(callout, span),
+            NodeKind::Group(ref group) => self.group(group, span, false, repeated),
+            NodeKind::Conditional(ref conditional) => self.conditional(conditional, span),
+            NodeKind::Absent(ref absent) => self.absent(absent, span),
+            NodeKind::Repeat(ref repeat) => self.repeat(repeat, span),
+            NodeKind::Options(_) | NodeKind::Concat(_) | NodeKind::Alternation(_) => {
+                self.expr(node)
+            }
+        }
+    }
+
+    fn literal(&mut self, c: char, span: &Span) -> Result<(), Error> {
+        let text = if self.open_number && c.is_ascii_digit() {
+            // Keep the digit out of the back-reference's number.
+            if self.has(SyntaxOperator::SYNTAX_OPERATOR_BRACKET_CC) {
+                Some(format!("[{}]", c))
+            } else {
+                self.code(c)
+            }
+        } else {
+            self.char(c)
+        };
+        let text = text.ok_or_else(|| self.untranslatable("a literal character", span))?;
+        self.push(&text);
+        Ok(())
+    }
+
+    /// A character outside a bracket expression.
+    fn char(&self, c: char) -> Option<String> {
+        if Some(c) == self.escape {
+            return Some(format!("{}{}", c, c));
+        }
+        if c.is_control() {
+            if let Some(text) = self.control(c) {
+                return Some(text);
+            }
+        }
+        if !self.syntax.is_meta(c) {
+            return Some(c.to_string());
+        }
+        if !self.syntax.is_escape_operator(c) {
+            if let Some(text) = self.escaped(&c.to_string()) {
+                return Some(text);
+            }
+        }
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_BRACKET_CC) && c != '^' {
+            return Some(format!("[{}]", c));
+        }
+        None
+    }
+
+    /// The escape for a control character, if there is one.
+    fn control(&self, c: char) -> Option<String> {
+        use SyntaxOperator as Op;
+        let letter = match c {
+            '\t' => "t",
+            '\n' => "n",
+            '\r' => "r",
+            '\x0c' => "f",
+            '\x07' => "a",
+            '\x1b' => "e",
+            '\x0b' if self.has(Op::SYNTAX_OPERATOR_ESC_V_VTAB) => "v",
+            _ => return self.code(c),
+        };
+        self.escaped_op(Op::SYNTAX_OPERATOR_ESC_CONTROL_CHARS, letter)
+            .or_else(|| self.code(c))
+    }
+
+    /// The escape giving the code point of `c`, if there is one.
+    fn code(&self, c: char) -> Option<String> {
+        use SyntaxOperator as Op;
+        let code = c as u32;
+        if code < 0x80 && self.has(Op::SYNTAX_OPERATOR_ESC_X_HEX2) {
+            self.escaped(&format!("x{:02X}", code))
+        } else if self.has(Op::SYNTAX_OPERATOR_ESC_X_BRACE_HEX8) {
+            self.escaped(&format!("x{{{:X}}}", code))
+        } else if code <= 0xffff && self.has(Op::SYNTAX_OPERATOR_ESC_U_HEX4) {
+            self.escaped(&format!("u{:04X}", code))
+        } else if self.has(Op::SYNTAX_OPERATOR_ESC_O_BRACE_OCTAL) {
+            self.escaped(&format!("o{{{:o}}}", code))
+        } else if code < 0o100 && self.has(Op::SYNTAX_OPERATOR_ESC_OCTAL3) {
+            self.escaped(&format!("{:03o}", code))
+        } else {
+            None
+        }
+    }
+
+    /// The escape for a byte which isn't part of a UTF-8 sequence.
+    fn byte(&self, b: u8) -> Option<String> {
+        use SyntaxOperator as Op;
+        if self.has(Op::SYNTAX_OPERATOR_ESC_X_HEX2) {
+            self.escaped(&format!("x{:02X}", b))
+        } else if self.has(Op::SYNTAX_OPERATOR_ESC_OCTAL3)
+            && (usize::from(b) > self.opened || !self.has(Op::SYNTAX_OPERATOR_DECIMAL_BACKREF))
+        {
+            // Numbers up to the number of groups are back-references.
+            self.escaped(&format!("{:03o}", b))
+        } else {
+            None
+        }
+    }
+
+    fn any_char(&mut self, newline: bool, span: &Span) -> Result<(), Error> {
+        let dot = if self.has(SyntaxOperator::SYNTAX_OPERATOR_DOT_ANYCHAR) {
+            Some('.')
+        } else {
+            self.meta[0]
+        };
+        if let Some(dot) = dot {
+            if self.target.dot_all == newline {
+                self.push(&dot.to_string());
+                return Ok(());
+            }
+        }
+        let super_dot = if newline { "O" } else { "N" };
+        if let Some(text) = self.escaped_op(
+            SyntaxOperator::SYNTAX_OPERATOR_ESC_CAPITAL_N_O_SUPER_DOT,
+            super_dot,
+        ) {
+            self.push(&text);
+            return Ok(());
+        }
+        if let Some(dot) = dot {
+            if self.flagged(Flag::DotAll, newline, &dot.to_string(), span) {
+                return Ok(());
+            }
+        }
+        if !newline {
+            let class = Class {
+                negated: true,
+                operands: vec![vec![ClassItem {
+                    kind: ClassItemKind::Literal('\n'),
+                    span: span.clone(),
+                }]],
+            };
+            let text = self.class(&class, false, false, span)?;
+            self.push(&text);
+            return Ok(());
+        }
+        Err(self.untranslatable("any character including newline", span))
+    }
+
+    fn anchor(&mut self, anchor: Anchor, span: &Span) -> Result<(), Error> {
+        use SyntaxOperator as Op;
+        let python = self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_PYTHON);
+        let text = match anchor {
+            Anchor::LineStart if self.source.single_line => return self.string_start(span),
+            Anchor::LineEnd if self.source.single_line => return self.semi_end(span),
+            Anchor::LineStart | Anchor::LineEnd => {
+                let c = if anchor == Anchor::LineStart {
+                    "^"
+                } else {
+                    "$"
+                };
+                let line_anchor = self.has(Op::SYNTAX_OPERATOR_LINE_ANCHOR);
+                if line_anchor && !self.target.single_line {
+                    self.push(c);
+                    return Ok(());
+                }
+                if line_anchor && self.flagged(Flag::MultiLine, true, c, span) {
+                    return Ok(());
+                }
+                return Err(self.untranslatable("a line anchor", span));
+            }
+            Anchor::StartOfString => return self.string_start(span),
+            Anchor::EndOfStringOrNewline => return self.semi_end(span),
+            Anchor::EndOfString => self
+                .escaped_op(
+                    Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR,
+                    if python { "Z" } else { "z" },
+                )
+                .or_else(|| self.escaped_op(Op::SYNTAX_OPERATOR_ESC_GNU_BUF_ANCHOR, "'")),
+            Anchor::SearchStart => {
+                self.escaped_op(Op::SYNTAX_OPERATOR_ESC_CAPITAL_G_BEGIN_ANCHOR, "G")
+            }
+            Anchor::WordBoundary => self.escaped_op(Op::SYNTAX_OPERATOR_ESC_B_WORD_BOUND, "b"),
+            Anchor::NotWordBoundary => self.escaped_op(Op::SYNTAX_OPERATOR_ESC_B_WORD_BOUND, "B"),
+            Anchor::WordStart | Anchor::WordEnd => {
+                let start = anchor == Anchor::WordStart;
+                let ltgt = if start { "<" } else { ">" };
+                self.escaped_op(Op::SYNTAX_OPERATOR_ESC_LTGT_WORD_BEGIN_END, ltgt)
+                    .or_else(|| {
+                        // A word boundary with a word character after
+                        // or before it.
+                        let boundary =
+                            self.escaped_op(Op::SYNTAX_OPERATOR_ESC_B_WORD_BOUND, "b")?;
+                        let word = self.escaped_op(Op::SYNTAX_OPERATOR_ESC_W_WORD, "w")?;
+                        if !self.has(Op::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT)
+                            || !self.has(Op::SYNTAX_OPERATOR_LPAREN_SUBEXP)
+                        {
+                            return None;
+                        }
+                        let look = if start { "=" } else { "<=" };
+                        Some(format!("{}(?{}{})", boundary, look, word))
+                    })
+            }
+            // Oniguruma tests the bit of `\y` and `\Y` against the wrong
+            // set of operators, see `ast`.
+            Anchor::TextSegmentBoundary => {
+                self.escaped_op(Op::SYNTAX_OPERATOR_ESC_CONTROL_CHARS, "y")
+            }
+            Anchor::NotTextSegmentBoundary => {
+                self.escaped_op(Op::SYNTAX_OPERATOR_ESC_CONTROL_CHARS, "Y")
+            }
+        };
+        let text = text.ok_or_else(|| self.untranslatable("an anchor", span))?;
+        self.push(&text);
+        Ok(())
+    }
+
+    /// Write an anchor for the start of the string.
+    fn string_start(&mut self, span: &Span) -> Result<(), Error> {
+        use SyntaxOperator as Op;
+        if let Some(text) = self
+            .escaped_op(Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR, "A")
+            .or_else(|| self.escaped_op(Op::SYNTAX_OPERATOR_ESC_GNU_BUF_ANCHOR, "`"))
+        {
+            self.push(&text);
+            return Ok(());
+        }
+        let line_anchor = self.has(Op::SYNTAX_OPERATOR_LINE_ANCHOR);
+        if line_anchor && self.target.single_line {
+            self.push("^");
+            return Ok(());
+        }
+        if line_anchor && self.flagged(Flag::MultiLine, false, "^", span) {
+            return Ok(());
+        }
+        Err(self.untranslatable("a start of string anchor", span))
+    }
+
+    /// Write an anchor for the end of the string or before a final
+    /// newline.
+    fn semi_end(&mut self, span: &Span) -> Result<(), Error> {
+        use SyntaxOperator as Op;
+        if !self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_PYTHON) {
+            if let Some(text) = self.escaped_op(Op::SYNTAX_OPERATOR_ESC_AZ_BUF_ANCHOR, "Z") {
+                self.push(&text);
+                return Ok(());
+            }
+        }
+        let line_anchor = self.has(Op::SYNTAX_OPERATOR_LINE_ANCHOR);
+        if line_anchor && self.target.single_line {
+            self.push("$");
+            return Ok(());
+        }
+        if line_anchor && self.flagged(Flag::MultiLine, false, "$", span) {
+            return Ok(());
+        }
+        Err(self.untranslatable("an end of string anchor", span))
+    }
+
+    fn special(&mut self, special: Special, span: &Span) -> Result<(), Error> {
+        use SyntaxOperator as Op;
+        let text = match special {
+            Special::Keep => self.escaped_op(Op::SYNTAX_OPERATOR_ESC_CAPITAL_K_KEEP, "K"),
+            Special::GeneralNewline => {
+                self.escaped_op(Op::SYNTAX_OPERATOR_ESC_CAPITAL_R_GENERAL_NEWLINE, "R")
+            }
+            Special::TextSegment => self.escaped_op(Op::SYNTAX_OPERATOR_ESC_X_Y_TEXT_SEGMENT, "X"),
+            Special::NotNewline => return self.any_char(false, span),
+            Special::TrueAnyChar => return self.any_char(true, span),
+        };
+        let text = text.ok_or_else(|| self.untranslatable("a special escape", span))?;
+        self.push(&text);
+        Ok(())
+    }
+
+    fn class_escape(&mut self, escape: ClassEscape, span: &Span) -> Result<(), Error> {
+        let text = match self.class_escape_text(escape) {
+            Some(text) => text,
+            None => {
+                // Fall back on the POSIX bracket of the same class.
+                let class = Class {
+                    negated: escape.negated,
+                    operands: vec![vec![ClassItem {
+                        kind: ClassItemKind::Posix(ast::Posix {
+                            name: posix_name(escape.kind).to_owned(),
+                            negated: false,
+                        }),
+                        span: span.clone(),
+                    }]],
+                };
+                self.class(&class, false, false, span)?
+            }
+        };
+        self.push(&text);
+        Ok(())
+    }
+
+    fn class_escape_text(&self, escape: ClassEscape) -> Option<String> {
+        use SyntaxOperator as Op;
+        let (op, letter) = match escape.kind {
+            ClassEscapeKind::Word => (Op::SYNTAX_OPERATOR_ESC_W_WORD, 'w'),
+            ClassEscapeKind::Digit => (Op::SYNTAX_OPERATOR_ESC_D_DIGIT, 'd'),
+            ClassEscapeKind::Space => (Op::SYNTAX_OPERATOR_ESC_S_WHITE_SPACE, 's'),
+            ClassEscapeKind::HexDigit => (Op::SYNTAX_OPERATOR_ESC_H_XDIGIT, 'h'),
+        };
+        let letter = if escape.negated {
+            letter.to_ascii_uppercase()
+        } else {
+            letter
+        };
+        self.escaped_op(op, &letter.to_string())
+    }
+
+    fn property(&self, property: &Property) -> Option<String> {
+        let p = if property.negated { 'P' } else { 'p' };
+        self.escaped_op(
+            SyntaxOperator::SYNTAX_OPERATOR_ESC_P_BRACE_CHAR_PROPERTY,
+            &format!("{}{{{}}}", p, property.name),
+        )
+    }
+
+    // References
+
+    /// The name group `number` alone has, if any.
+    fn unique_name(&self, number: usize) -> Option<&'a str> {
+        self.names
+            .iter()
+            .find(|(_, groups)| groups[..] == [number])
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// How to refer to the groups of a back-reference in the target:
+    /// by name if the source does or the target needs it, else by
+    /// number.
+    fn reference(
+        &self,
+        target: &Reference,
+        groups: &[usize],
+        span: &Span,
+    ) -> Result<Reference, Error> {
+        if let Reference::Name(ref name) = target {
+            return Ok(Reference::Name(name.clone()));
+        }
+        let number = groups[0];
+        if !self.only_named {
+            return Ok(Reference::Number(number));
+        }
+        match self.unique_name(number) {
+            Some(name) => Ok(Reference::Name(name.to_owned())),
+            None => Err(self.untranslatable("a numbered reference", span)),
+        }
+    }
+
+    /// Can group `number` be referred to as `\n`?
+    fn decimal_backref(&self, number: usize) -> bool {
+        self.has(SyntaxOperator::SYNTAX_OPERATOR_DECIMAL_BACKREF)
+            && self.escape.is_some()
+            && (number <= self.opened || number <= 9)
+    }
+
+    fn backref(&mut self, backref: &Backref, span: &Span) -> Result<(), Error> {
+        let target = self.reference(&backref.target, &backref.groups, span)?;
+        let named = self.escaped_op(SyntaxOperator::SYNTAX_OPERATOR_ESC_K_NAMED_BACKREF, "k");
+        if let Some(level) = backref.level {
+            let k = named.ok_or_else(|| self.untranslatable("a back-reference level", span))?;
+            self.push(&format!("{}<{}{:+}>", k, reference_text(&target), level));
+            return Ok(());
+        }
+        match target {
+            Reference::Number(number) if self.decimal_backref(number) => {
+                let text = self.escaped(&number.to_string()).unwrap_or_default();
+                self.push(&text);
+                self.open_number = true;
+            }
+            _ if named.is_some() => {
+                let k = named.unwrap_or_default();
+                self.push(&format!("{}<{}>", k, reference_text(&target)));
+            }
+            Reference::Name(ref name)
+                if self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_CAPITAL_P_NAME) =>
+            {
+                self.open_extension(&format!("P={})", name), "a back-reference", span)?;
+            }
+            Reference::Name(ref name) => {
+                // Fall back on the number of the group.
+                match self.unique_name_number(name) {
+                    Some(number) if !self.only_named && self.decimal_backref(number) => {
+                        let text = self.escaped(&number.to_string()).unwrap_or_default();
+                        self.push(&text);
+                        self.open_number = true;
+                    }
+                    _ => return Err(self.untranslatable("a named back-reference", span)),
+                }
+            }
+            _ => return Err(self.untranslatable("a back-reference", span)),
+        }
+        Ok(())
+    }
+
+    /// The number of the only group called `name`.
+    fn unique_name_number(&self, name: &str) -> Option<usize> {
+        match self.names.iter().find(|(n, _)| n == name) {
+            Some((_, groups)) if groups.len() == 1 => Some(groups[0]),
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, call: &Call, span: &Span) -> Result<(), Error> {
+        use SyntaxOperator as Op;
+        let target = match call.target {
+            Reference::Name(ref name) => Reference::Name(name.clone()),
+            _ if call.group == 0 => Reference::Number(0),
+            _ => self.reference(&call.target, &[call.group], span)?,
+        };
+        if let Some(g) = self.escaped_op(Op::SYNTAX_OPERATOR_ESC_G_SUBEXP_CALL, "g") {
+            self.push(&format!("{}<{}>", g, reference_text(&target)));
+            return Ok(());
+        }
+        let name = match target {
+            Reference::Number(0) if self.has(Op::SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL) => {
+                return self.open_extension("R)", "a subexpression call", span)
+            }
+            Reference::Number(number) => self.unique_name(number).map(str::to_owned),
+            Reference::Name(name) => Some(name),
+            Reference::Relative(_) => None,
+        };
+        let text = match name {
+            Some(name) if self.has(Op::SYNTAX_OPERATOR_QMARK_PERL_SUBEXP_CALL) => {
+                format!("&{})", name)
+            }
+            Some(name) if self.has(Op::SYNTAX_OPERATOR_QMARK_CAPITAL_P_NAME) => {
+                format!("P>{})", name)
+            }
+            _ => return Err(self.untranslatable("a subexpression call", span)),
+        };
+        self.open_extension(&text, "a subexpression call", span)
+    }
+
+    fn callout(&mut self, callout: &Callout, span: &Span) -> Result<(), Error> {
+        use SyntaxOperator as Op;
+        let (op, text) = match callout {
+            Callout::Contents {
+                contents,
+                tag,
+                direction,
+            } => {
+                let mut braces = 1;
+                while contents.contains(&"}".repeat(braces)) {
+                    braces += 1;
+                }
+                let direction = match direction {
+                    CalloutDirection::Progress => "",
+                    CalloutDirection::Retraction => "<",
+                    CalloutDirection::Both => "X",
+                };
+                let text = format!(
+                    "?{}{}{}{}{})",
+                    "{".repeat(braces),
+                    contents,
+                    "}".repeat(braces),
+                    tag_text(tag),
+                    direction,
+                );
+                (Op::SYNTAX_OPERATOR_QMARK_BRACE_CALLOUT_CONTENTS, text)
+            }
+            Callout::Name { name, tag, args } => {
+                let args = if args.is_empty() {
+                    String::new()
+                } else {
+                    format!("{{{}}}", args.join(","))
+                };
+                let text = format!("*{}{}{})", name, tag_text(tag), args);
+                (Op::SYNTAX_OPERATOR_ASTERISK_CALLOUT_NAME, text)
+            }
+        };
+        let effect = matches!(callout, Callout::Name { .. })
+            || self.has(Op::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT);
+        if !self.has(op) || !effect {
+            return Err(self.untranslatable("a callout", span));
+        }
+        self.open("a callout", span)?;
+        self.push(&text);
+        Ok(())
+    }
+
+    // Compound nodes
+
+    fn conditional(&mut self, conditional: &Conditional, span: &Span) -> Result<(), Error> {
+        if !self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_LPAREN_IF_ELSE) {
+            return Err(self.untranslatable("a conditional", span));
+        }
+        let modes = (self.source, self.target);
+        self.open_extension("(", "a conditional", span)?;
+        match conditional.condition {
+            Condition::Backref(ref backref) => {
+                let target = self.reference(&backref.target, &backref.groups, span)?;
+                let text = match target {
+                    Reference::Name(name) => format!("<{}>)", name),
+                    target => format!("{})", reference_text(&target)),
+                };
+                self.push(&text);
+            }
+            Condition::Expression(ref expression) => {
+                let start = self.out.len();
+                self.expr(expression)?;
+                (self.source, self.target) = modes;
+                // The condition mustn't read as a group number or name.
+                let condition = &self.out[start..];
+                if !condition.is_empty()
+                    && condition
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || "_<>'+-".contains(c))
+                {
+                    self.out.insert_str(start, "(?:");
+                    self.close();
+                }
+                self.close();
+            }
+        }
+        let mut branches = vec![&*conditional.yes];
+        match conditional.no.as_deref() {
+            Some(Node {
+                kind: NodeKind::Alternation(ref no),
+                ..
+            }) => branches.extend(no),
+            Some(no) => branches.push(no),
+            None => {}
+        }
+        self.alternatives(&branches)?;
+        self.close();
+        (self.source, self.target) = modes;
+        Ok(())
+    }
+
+    fn absent(&mut self, absent: &Absent, span: &Span) -> Result<(), Error> {
+        if !self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_TILDE_ABSENT_GROUP) {
+            return Err(self.untranslatable("an absent group", span));
+        }
+        let modes = (self.source, self.target);
+        match absent {
+            Absent::Repeater(body) => {
+                self.open_extension("~", "an absent group", span)?;
+                self.expr(body)?;
+            }
+            Absent::Expression { absent, expr } => {
+                self.open_extension("~|", "an absent group", span)?;
+                let mut branches = vec![&**absent];
+                match expr.kind {
+                    NodeKind::Alternation(ref rest) => branches.extend(rest),
+                    _ => branches.push(expr),
+                }
+                self.alternatives(&branches)?;
+            }
+            Absent::Range(body) => {
+                self.open_extension("~|", "an absent group", span)?;
+                self.expr(body)?;
+            }
+            Absent::Clear => self.open_extension("~|", "an absent group", span)?,
+        }
+        self.close();
+        (self.source, self.target) = modes;
+        Ok(())
+    }
+
+    fn repeat(&mut self, repeat: &Repeat, span: &Span) -> Result<(), Error> {
+        if matches!(repeat.body.kind, NodeKind::Empty) {
+            return Ok(());
+        }
+        let mut operator = self.quantifier(repeat.min, repeat.max, repeat.greed);
+        // A possessive repeat is a greedy one in an atomic group.
+        let atomic = operator.is_none() && repeat.greed == Greed::Possessive;
+        if atomic {
+            operator = self.quantifier(repeat.min, repeat.max, Greed::Greedy);
+            self.open_extension(">", "a possessive repeat", span)?;
+        }
+        let operator = operator.ok_or_else(|| self.untranslatable("a repeat", span))?;
+        if let NodeKind::Repeat(_) = repeat.body.kind {
+            // Repeat operators can't follow one another.
+            self.open_extension(":", "a nested repeat", span)?;
+            self.node(&repeat.body, false)?;
+            self.close();
+        } else {
+            self.node(&repeat.body, true)?;
+        }
+        self.push(&operator);
+        if atomic {
+            self.close();
+        }
+        Ok(())
+    }
+
+    /// Spell a repeat operator, if the target can.
+    fn quantifier(&self, min: u32, max: Option<u32>, greed: Greed) -> Option<String> {
+        use SyntaxOperator as Op;
+        let simple = match (min, max) {
+            (0, None) => self.simple_operator(
+                '*',
+                Op::SYNTAX_OPERATOR_ASTERISK_ZERO_INF,
+                Op::SYNTAX_OPERATOR_ESC_ASTERISK_ZERO_INF,
+                self.meta[1],
+            ),
+            (1, None) => self.simple_operator(
+                '+',
+                Op::SYNTAX_OPERATOR_PLUS_ONE_INF,
+                Op::SYNTAX_OPERATOR_ESC_PLUS_ONE_INF,
+                self.meta[3],
+            ),
+            (0, Some(1)) => self.simple_operator(
+                '?',
+                Op::SYNTAX_OPERATOR_QMARK_ZERO_ONE,
+                Op::SYNTAX_OPERATOR_ESC_QMARK_ZERO_ONE,
+                self.meta[2],
+            ),
+            _ => None,
+        };
+        let lazy = self.has(Op::SYNTAX_OPERATOR_QMARK_NON_GREEDY);
+        if let Some(simple) = simple {
+            match greed {
+                Greed::Greedy => return Some(simple),
+                Greed::Lazy if lazy => return Some(simple + "?"),
+                Greed::Possessive if self.has(Op::SYNTAX_OPERATOR_PLUS_POSSESSIVE_REPEAT) => {
+                    return Some(simple + "+")
+                }
+                _ => {}
+            }
+        }
+        let (open, close) = if self.has(Op::SYNTAX_OPERATOR_BRACE_INTERVAL) {
+            ("{".to_owned(), "}".to_owned())
+        } else {
+            (
+                self.escaped_op(Op::SYNTAX_OPERATOR_ESC_BRACE_INTERVAL, "{")?,
+                self.escaped("}")?,
+            )
+        };
+        let bounds = match max {
+            Some(max) if max == min => min.to_string(),
+            Some(max) => format!("{},{}", min, max),
+            None => format!("{},", min),
+        };
+        match greed {
+            Greed::Greedy => Some(format!("{}{}{}", open, bounds, close)),
+            Greed::Lazy if lazy => {
+                // `{n}?` is an optional `{n}` in some syntaxes.
+                let bounds = match max {
+                    Some(max)
+                        if max == min
+                            && self.allows(
+                                SyntaxBehavior::SYNTAX_BEHAVIOR_FIXED_INTERVAL_IS_GREEDY_ONLY,
+                            ) =>
+                    {
+                        format!("{},{}", min, max)
+                    }
+                    _ => bounds,
+                };
+                Some(format!("{}{}{}?", open, bounds, close))
+            }
+            Greed::Possessive if self.has(Op::SYNTAX_OPERATOR_PLUS_POSSESSIVE_INTERVAL) => {
+                Some(format!("{}{}{}+", open, bounds, close))
+            }
+            // Without `{n,m}+`, `{m,n}` is a possessive `{n,m}`.
+            Greed::Possessive => match max {
+                Some(max) if max > min => Some(format!("{}{},{}{}", open, max, min, close)),
+                _ => None,
+            },
+            Greed::Lazy => None,
+        }
+    }
+
+    fn simple_operator(
+        &self,
+        c: char,
+        op: SyntaxOperator,
+        escaped_op: SyntaxOperator,
+        meta: Option<char>,
+    ) -> Option<String> {
+        if self.has(op) {
+            Some(c.to_string())
+        } else {
+            self.escaped_op(escaped_op, &c.to_string())
+                .or_else(|| meta.map(String::from))
+        }
+    }
+
+    // Bracket expressions
+
+    /// Spell a bracket expression. `newline` says whether the source
+    /// leaves newlines out of it when it's negated, and `nested`
+    /// whether it's inside another.
+    fn class(
+        &self,
+        class: &Class,
+        newline: bool,
+        nested: bool,
+        span: &Span,
+    ) -> Result<String, Error> {
+        if !self.has(SyntaxOperator::SYNTAX_OPERATOR_BRACKET_CC) {
+            return Err(self.untranslatable("a bracket expression", span));
+        }
+        let target = self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_NOT_NEWLINE_IN_NEGATIVE_CC);
+        if !class.negated || newline == target || has_newline(class) {
+            return self.bracket(class, nested, span);
+        }
+        if newline {
+            // Leave the newline out explicitly.
+            let newline = ClassItem {
+                kind: ClassItemKind::Literal('\n'),
+                span: span.clone(),
+            };
+            let items = match class.operands[..] {
+                [ref items] => items.iter().cloned().chain([newline]).collect(),
+                _ => vec![
+                    ClassItem {
+                        kind: ClassItemKind::Class(Class {
+                            negated: false,
+                            operands: class.operands.clone(),
+                        }),
+                        span: span.clone(),
+                    },
+                    newline,
+                ],
+            };
+            let class = Class {
+                negated: true,
+                operands: vec![items],
+            };
+            return self.bracket(&class, nested, span);
+        }
+        // The target leaves newlines out, so match them separately.
+        let alternation = if self.has(SyntaxOperator::SYNTAX_OPERATOR_VBAR_ALT) {
+            Some("|".to_owned())
+        } else {
+            self.escaped_op(SyntaxOperator::SYNTAX_OPERATOR_ESC_VBAR_ALT, "|")
+        };
+        let (alternation, newline) = match (alternation, self.char('\n')) {
+            (Some(alternation), Some(newline))
+                if !nested
+                    && self.has(SyntaxOperator::SYNTAX_OPERATOR_QMARK_GROUP_EFFECT)
+                    && self.has(SyntaxOperator::SYNTAX_OPERATOR_LPAREN_SUBEXP) =>
+            {
+                (alternation, newline)
+            }
+            _ => return Err(self.untranslatable("a negated bracket expression", span)),
+        };
+        let bracket = self.bracket(class, nested, span)?;
+        Ok(format!("(?:{}{}{})", bracket, alternation, newline))
+    }
+
+    fn bracket(&self, class: &Class, nested: bool, span: &Span) -> Result<String, Error> {
+        let escapes = self.allows(SyntaxBehavior::SYNTAX_BEHAVIOR_BACKSLASH_ESCAPE_IN_CC)
+            && self.escape.is_some();
+        if !escapes && !nested && !class.negated {
+            // `[^]` can't be written without escapes.
+            if let [ref items] = class.operands[..] {
+                if let [ClassItem {
+                    kind: ClassItemKind::Literal('^'),
+                    ..
+                }] = items[..]
+                {
+                    return self
+                        .char('^')
+                        .ok_or_else(|| self.untranslatable("a bracket expression", span));
+                }
+            }
+        }
+        let mut text = String::from("[");
+        if class.negated {
+            text.push('^');
+        }
+        for (i, operand) in class.operands.iter().enumerate() {
+            if i > 0 {
+                if !self.has(SyntaxOperator::SYNTAX_OPERATOR_CCLASS_SET_OP) {
+                    return Err(self.untranslatable("a bracket expression intersection", span));
+                }
+                text.push_str("&&");
+            }
+            // Reversed ranges, allowed by `ALLOW_EMPTY_RANGE_IN_CC`,
+            // match nothing, so leave them out.
+            let items = operand
+                .iter()
+                .filter(|item| !matches!(item.kind, ClassItemKind::Range(from, to) if from > to))
+                .map(|item| self.class_item(item, escapes))
+                .collect::<Result<Vec<_>, _>>()?;
+            if items.is_empty() {
+                return Err(self.untranslatable("an empty bracket expression", span));
+            }
+            if escapes {
+                text.extend(items);
+            } else {
+                text.push_str(&self.order_items(items, i == 0 && !class.negated, span)?);
+            }
+        }
+        text.push(']');
+        Ok(text)
+    }
+
+    /// Order the items of a bracket expression without escapes so that
+    /// `]` comes first, `^` doesn't, and `[` and `-` come last.
+    fn order_items(
+        &self,
+        mut items: Vec<String>,
+        first: bool,
+        span: &Span,
+    ) -> Result<String, Error> {
+        let position = |items: &[String], c| items.iter().position(|item| item.starts_with(c));
+        let mut text = String::new();
+        if let Some(i) = position(&items, ']') {
+            if !first && i > 0 {
+                return Err(self.untranslatable("a bracket expression", span));
+            }
+            text.push_str(&items.remove(i));
+            if position(&items, ']').is_some() {
+                return Err(self.untranslatable("a bracket expression", span));
+            }
+        }
+        let mut last = Vec::new();
+        for c in ["[", "-"] {
+            if let Some(i) = items.iter().position(|item| item == c) {
+                last.push(items.remove(i));
+            }
+        }
+        if text.is_empty() && first {
+            if let Some(i) = items.iter().position(|item| !item.starts_with('^')) {
+                let item = items.remove(i);
+                text.push_str(&item);
+            } else if items.is_empty() {
+                if let Some(i) = last.iter().position(|item| item == "-") {
+                    // `-` can come first as well.
+                    text.push_str(&last.remove(i));
+                }
+            }
+        }
+        text.extend(items);
+        text.extend(last);
+        if first && text.starts_with('^') {
+            return Err(self.untranslatable("a bracket expression", span));
+        }
+        Ok(text)
+    }
+
+    fn class_item(&self, item: &ClassItem, escapes: bool) -> Result<String, Error> {
+        let span = &item.span;
+        let text = match item.kind {
+            ClassItemKind::Literal(c) => self.class_char(c, escapes),
+            ClassItemKind::Byte(b) => self.byte(b).filter(|_| escapes),
+            ClassItemKind::Range(from, to) => {
+                let from = self.class_char(from, escapes);
+                let to = self.class_char(to, escapes);
+                match (from, to) {
+                    (Some(from), Some(to))
+                        if escapes || !(from == "-" || to == "-" || to == "]") =>
+                    {
+                        Some(format!("{}-{}", from, to))
+                    }
+                    _ => None,
+                }
+            }
+            ClassItemKind::Escape(escape) => self
+                .class_escape_text(escape)
+                .filter(|_| escapes)
+                .or_else(|| self.posix(posix_name(escape.kind), escape.negated, escapes)),
+            ClassItemKind::Property(ref property) => self.property(property).filter(|_| escapes),
+            ClassItemKind::Posix(ref posix) => self.posix(&posix.name, posix.negated, escapes),
+            ClassItemKind::Class(ref class) => {
+                if !self.has(SyntaxOperator::SYNTAX_OPERATOR_CCLASS_SET_OP) {
+                    return Err(self.untranslatable("a nested bracket expression", span));
+                }
+                return self.class(class, self.source_cc_newline, true, span);
+            }
+        };
+        text.ok_or_else(|| self.untranslatable("a bracket expression item", span))
+    }
+
+    /// A character inside a bracket expression.
+    fn class_char(&self, c: char, escapes: bool) -> Option<String> {
+        if !escapes {
+            return Some(c.to_string());
+        }
+        if Some(c) == self.escape || matches!(c, ']' | '[' | '-' | '^' | '&') {
+            return self.escaped(&c.to_string());
+        }
+        if c.is_control() {
+            if let Some(text) = self.control(c) {
+                return Some(text);
+            }
+        }
+        Some(c.to_string())
+    }
+
+    /// A POSIX bracket, or the property of the same name.
+    fn posix(&self, name: &str, negated: bool, escapes: bool) -> Option<String> {
+        if self.has(SyntaxOperator::SYNTAX_OPERATOR_POSIX_BRACKET) {
+            let not = if negated { "^" } else { "" };
+            return Some(format!("[:{}{}:]", not, name));
+        }
+        let property = Property {
+            name: name.to_owned(),
+            negated,
+        };
+        self.property(&property).filter(|_| escapes)
+    }
+}
+
+fn is_empty(options: &Options) -> bool {
+    options.on.is_empty() && options.off.is_empty()
+}
+
+/// Add `options` to those set by `into`.
+fn merge(into: &mut Options, options: &Options) {
+    for &flag in &options.on {
+        into.off.retain(|&f| f != flag);
+        if !into.on.contains(&flag) {
+            into.on.push(flag);
+        }
+    }
+    for &flag in &options.off {
+        into.on.retain(|&f| f != flag);
+        if !into.off.contains(&flag) {
+            into.off.push(flag);
+        }
+    }
+}
+
+fn is_whole(flag: Flag) -> bool {
+    matches!(
+        flag,
+        Flag::IgnoreCaseAscii | Flag::FindLongest | Flag::FindNotEmpty
+    )
+}
+
+fn flag_name(flag: Flag) -> &'static str {
+    match flag {
+        Flag::IgnoreCase => "ignore case",
+        Flag::Extend => "extended",
+        Flag::DotAll => "dot all",
+        Flag::MultiLine => "multi-line",
+        Flag::Ascii => "ASCII",
+        Flag::WordAscii => "ASCII word",
+        Flag::DigitAscii => "ASCII digit",
+        Flag::SpaceAscii => "ASCII space",
+        Flag::PosixAscii => "ASCII POSIX bracket",
+        Flag::GraphemeCluster => "grapheme cluster",
+        Flag::WordSegment => "word segment",
+        Flag::IgnoreCaseAscii => "ASCII ignore case",
+        Flag::FindLongest => "find longest",
+        Flag::FindNotEmpty => "find not empty",
+    }
+}
+
+fn posix_name(kind: ClassEscapeKind) -> &'static str {
+    match kind {
+        ClassEscapeKind::Word => "word",
+        ClassEscapeKind::Digit => "digit",
+        ClassEscapeKind::Space => "space",
+        ClassEscapeKind::HexDigit => "xdigit",
+    }
+}
+
+fn reference_text(target: &Reference) -> String {
+    match target {
+        Reference::Number(number) => number.to_string(),
+        Reference::Relative(offset) => format!("{:+}", offset),
+        Reference::Name(name) => name.clone(),
+    }
+}
+
+fn tag_text(tag: &Option<String>) -> String {
+    tag.as_ref()
+        .map(|tag| format!("[{}]", tag))
+        .unwrap_or_default()
+}
+
+/// Does `node` match a single thing, so it can be repeated?
+fn is_atom(node: &Node) -> bool {
+    !matches!(
+        node.kind,
+        NodeKind::Empty
+            | NodeKind::Options(_)
+            | NodeKind::Repeat(_)
+            | NodeKind::Concat(_)
+            | NodeKind::Alternation(_)
+    )
+}
+
+/// Does a negated bracket expression leave out newlines itself?
+fn has_newline(class: &Class) -> bool {
+    match class.operands[..] {
+        [ref items] => items.iter().any(|item| match item.kind {
+            ClassItemKind::Literal(c) => c == '\n',
+            ClassItemKind::Range(from, to) => from <= '\n' && '\n' <= to,
+            _ => false,
+        }),
+        _ => false,
+    }
+}
+
+/// The first node of an expression.
+fn first(node: &Node) -> &Node {
+    match node.kind {
+        NodeKind::Concat(ref nodes) | NodeKind::Alternation(ref nodes) => first(&nodes[0]),
+        _ => node,
+    }
+}
+
+/// Does `node`, or any node inside it, satisfy `f`?
+fn contains(node: &Node, f: &dyn Fn(&NodeKind) -> bool) -> bool {
+    if f(&node.kind) {
+        return true;
+    }
+    match node.kind {
+        NodeKind::Group(ref group) => contains(&group.body, f),
+        NodeKind::Repeat(ref repeat) => contains(&repeat.body, f),
+        NodeKind::Concat(ref nodes) | NodeKind::Alternation(ref nodes) => {
+            nodes.iter().any(|node| contains(node, f))
+        }
+        NodeKind::Conditional(ref conditional) => {
+            let condition = match conditional.condition {
+                Condition::Expression(ref expression) => contains(expression, f),
+                Condition::Backref(_) => false,
+            };
+            condition
+                || contains(&conditional.yes, f)
+                || conditional.no.as_ref().is_some_and(|no| contains(no, f))
+        }
+        NodeKind::Absent(Absent::Repeater(ref body) | Absent::Range(ref body)) => contains(body, f),
+        NodeKind::Absent(Absent::Expression {
+            ref absent,
+            ref expr,
+        }) => contains(absent, f) || contains(expr, f),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Regex, SearchOptions};
+
+    const CORPUS: &[&str] = &[
+        "",
+        "abc",
+        "a|b|c",
+        "a*b+c?",
+        "a{2}b{1,3}c{2,}",
+        "a*?b+?c??",
+        "a{1,2}?",
+        "a*+b++",
+        "a{2,3}+",
+        "(a)(b)\\2\\1",
+        "(a|b)+c",
+        "((a)|b)*",
+        "(?:ab)*c",
+        "(?:a)",
+        "x(?:)y",
+        ".",
+        "a.c",
+        "^a",
+        "a$",
+        "^a$",
+        "(?m)^a$",
+        "(?s).",
+        "(?i)abc",
+        "a(?i)b|c",
+        "a(?i:b)c",
+        "a(?-i)b",
+        "(?i)a(?-i)b|c",
+        "\\Aa\\z",
+        "a\\Z",
+        "\\ba\\B",
+        "\\<a\\>",
+        "\\w+\\W\\d\\D\\s\\S",
+        "\\h\\H",
+        "[abc]",
+        "[^abc]",
+        "[a-z0-9]",
+        "[^a-z]+",
+        "[]a]",
+        "[^]a]",
+        "[a-]",
+        "[-a]",
+        "[a^]",
+        "[\\]]",
+        "[\\-]",
+        "[\\^a]",
+        "[[:alpha:]]",
+        "[[:^digit:]x]",
+        "[\\w\\d]",
+        "[^\\n]",
+        "[a-z&&[^c]]",
+        "[\\p{Alpha}]",
+        "\\p{Alpha}\\P{Digit}",
+        "a\\.b\\*c\\+\\?",
+        "\\(\\)\\{\\}\\[\\]",
+        "\\|",
+        "a\\tb\\nc",
+        "\\x41\\x{263A}",
+        "\\u263A",
+        "\\101",
+        "\\\\",
+        "a{,3}",
+        "{",
+        "a{",
+        "a}",
+        "*a",
+        "(?=a)a",
+        "(?!a).",
+        "(?<=a)b",
+        "(?<!a)b",
+        "(?>a+)b",
+        "(?<n>a)\\k<n>",
+        "(?P<n>a)(?P=n)",
+        "(?<n>a)|(?<n>b)",
+        "(a)\\k<1>",
+        "(?<n>a|b\\g<n>)",
+        "(a|b\\g<1>)",
+        "(?<n>a)(?P>n)",
+        "(a)?(?(1)b|c)",
+        "(?<n>a)?(?(<n>)b|c)",
+        "(?~abc)",
+        "(?~|abc|\\d+)",
+        "\\K",
+        "\\R",
+        "\\X",
+        "\\N",
+        "\\O",
+        "\\G",
+        "\\y\\Y",
+        "(a)\\1 0",
+        "(a)\\10",
+        "\\`a\\'",
+        "a\\{2\\}",
+        "\\(a\\)\\1",
+        "a\\|b",
+        "a\\+\\?",
+        "é日😀",
+        "\\xff",
+        "(?a)\\w",
+        "(?W)\\w",
+        "(?x) a b # c",
+        "(?I)a",
+        "(?L)a|ab",
+        "(?i:a|b)c",
+        "(?m:.)",
+        "(?-m)^a",
+        "(?m)a|^b",
+        "^*a",
+        "a**",
+        "(a*)*",
+        "a{2}{3}",
+        "a$b",
+        "^|$",
+        "(?{foo})",
+        "(*FAIL)",
+        "[]@-#]",
+        "[a^-]b-(]",
+    ];
+
+    const HAYSTACKS: &[&str] = &[
+        "",
+        "a",
+        "abc",
+        "ABC",
+        "aab",
+        "bbc",
+        "c",
+        "a\nb",
+        "b\na",
+        "a\n",
+        "\na\n",
+        "x a b",
+        "aaaa",
+        "abab",
+        "12 ab_c",
+        "a.b*c+?",
+        "()[]{}|\\",
+        "é日😀",
+        "a\tb\nc",
+        "a0",
+        "aa",
+        "]a^-",
+        "A☺",
+    ];
+
+    fn syntaxes() -> Vec<(&'static str, &'static Syntax)> {
+        vec![
+            ("ruby", Syntax::ruby()),
+            ("oniguruma", Syntax::oniguruma()),
+            ("perl", Syntax::perl()),
+            ("perl_ng", Syntax::perl_ng()),
+            ("java", Syntax::java()),
+            ("python", Syntax::python()),
+            ("posix_basic", Syntax::posix_basic()),
+            ("posix_extended", Syntax::posix_extended()),
+            ("emacs", Syntax::emacs()),
+            ("grep", Syntax::grep()),
+            ("gnu_regex", Syntax::gnu_regex()),
+            ("asis", Syntax::asis()),
+        ]
+    }
+
+    /// Where `regex` matches in `haystack` and what each group captures.
+    fn matches(regex: &Regex, haystack: &str) -> Vec<Vec<Option<(usize, usize)>>> {
+        let mut region = crate::Region::new();
+        let mut found = Vec::new();
+        let mut at = 0;
+        while at <= haystack.len() {
+            let start = regex.search_with_options(
+                haystack,
+                at,
+                haystack.len(),
+                SearchOptions::SEARCH_OPTION_NONE,
+                Some(&mut region),
+            );
+            if start.is_none() {
+                break;
+            }
+            let groups = (0..region.len()).map(|i| region.pos(i)).collect::<Vec<_>>();
+            let (start, end) = groups[0].unwrap();
+            found.push(groups);
+            at = if end > start { end } else { end + 1 };
+            while !haystack.is_char_boundary(at.min(haystack.len())) {
+                at += 1;
+            }
+        }
+        found
+    }
+
+    fn untranslatable(pattern: &str, from: &Syntax, to: &Syntax) -> Option<Span> {
+        let e = translate(pattern, from, to).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Untranslatable, "{}", e);
+        e.pattern_span()
+    }
+
+    #[test]
+    fn test_translate_examples() {
+        let cases: &[(&str, &Syntax, &Syntax, &str)] = &[
+            ("^a$", Syntax::perl(), Syntax::ruby(), r"\Aa\Z"),
+            ("(?m)^a$", Syntax::perl(), Syntax::ruby(), "^a$"),
+            ("^a$", Syntax::ruby(), Syntax::perl(), "(?m)^a$"),
+            ("a.c", Syntax::posix_extended(), Syntax::ruby(), "(?m)a.c"),
+            (
+                r"(?P<word>\w+) (?P=word)",
+                Syntax::python(),
+                Syntax::ruby(),
+                r"(?<word>\w+) \k<word>",
+            ),
+            (
+                "(a|b)+c?",
+                Syntax::posix_extended(),
+                Syntax::grep(),
+                r"\(a\|b\)\+c\?",
+            ),
+            (
+                r"\(a\|b\)*",
+                Syntax::grep(),
+                Syntax::posix_extended(),
+                "(a|b)*",
+            ),
+            ("a(?i)b|c", Syntax::ruby(), Syntax::perl(), "a(?i:b|c)"),
+            ("a(?i)b|c", Syntax::perl(), Syntax::ruby(), "a(?i:b)|(?i:c)"),
+            ("a*+", Syntax::ruby(), Syntax::java(), "a*+"),
+            ("a*+", Syntax::java(), Syntax::python(), "(?>a*)"),
+            ("a{2,3}+", Syntax::java(), Syntax::ruby(), "a{3,2}"),
+            ("(a)\\1 0", Syntax::ruby(), Syntax::ruby(), "(a)\\1 0"),
+            ("(a)\\1[0]", Syntax::ruby(), Syntax::ruby(), "(a)\\1[0]"),
+            ("[^a]", Syntax::grep(), Syntax::ruby(), "[^a\\n]"),
+            (
+                "[]a-]",
+                Syntax::posix_extended(),
+                Syntax::ruby(),
+                "[\\]a\\-]",
+            ),
+            ("[\\]a^-]", Syntax::ruby(), Syntax::grep(), "[]a^-]"),
+            ("\\d", Syntax::ruby(), Syntax::posix_basic(), "[[:digit:]]"),
+            ("ikR[]@-#]", Syntax::emacs(), Syntax::java(), "ikR[\\]]"),
+            (".[\\E-$]", Syntax::grep(), Syntax::python(), ".[\\\\]"),
+        ];
+        for &(pattern, from, to, expected) in cases {
+            assert_eq!(
+                translate(pattern, from, to).unwrap(),
+                expected,
+                "{:?}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_translate_errors() {
+        assert_eq!(
+            untranslatable("a(?=b)", Syntax::ruby(), Syntax::grep()),
+            Some(1..6)
+        );
+        assert_eq!(
+            untranslatable("a|b", Syntax::ruby(), Syntax::posix_basic()),
+            Some(2..3)
+        );
+        assert_eq!(
+            untranslatable(r"\p{Alpha}", Syntax::ruby(), Syntax::grep()),
+            Some(0..9)
+        );
+        assert_eq!(
+            untranslatable("(a)(?P<n>b)", Syntax::python(), Syntax::ruby()),
+            Some(0..3)
+        );
+        assert_eq!(
+            untranslatable(".Ek&[b-(]", Syntax::emacs(), Syntax::ruby()),
+            Some(4..9)
+        );
+        let invalid = translate("(", Syntax::ruby(), Syntax::perl()).unwrap_err();
+        assert_ne!(invalid.kind(), ErrorKind::Untranslatable);
+    }
+
+    #[test]
+    fn test_translate_matches_the_same() {
+        let mut failures = Vec::new();
+        let mut translated = 0;
+        for (from_name, from) in syntaxes() {
+            for pattern in CORPUS {
+                let source =
+                    match Regex::with_options(pattern, RegexOptions::REGEX_OPTION_NONE, from) {
+                        Ok(source) => source,
+                        Err(_) => continue,
+                    };
+                for (to_name, to) in syntaxes() {
+                    let context = format!("{} -> {}: {:?}", from_name, to_name, pattern);
+                    let output = match translate(pattern, from, to) {
+                        Ok(output) => output,
+                        Err(e) if e.kind() == ErrorKind::Untranslatable => continue,
+                        Err(e) => {
+                            failures.push(format!("{} failed: {}", context, e));
+                            continue;
+                        }
+                    };
+                    translated += 1;
+                    let target =
+                        match Regex::with_options(&output, RegexOptions::REGEX_OPTION_NONE, to) {
+                            Ok(target) => target,
+                            Err(e) => {
+                                failures.push(format!("{} gave {:?}: {}", context, output, e));
+                                continue;
+                            }
+                        };
+                    for haystack in HAYSTACKS {
+                        if matches(&source, haystack) != matches(&target, haystack) {
+                            failures.push(format!(
+                                "{} gave {:?}, which differs on {:?}",
+                                context, output, haystack
+                            ));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+        assert!(translated > 1000, "only {} translations", translated);
+    }
+}
@@ -1,5 +1,6 @@
 #![allow(clippy::transmute_ptr_to_ref)]
 
+use std::collections::VecDeque;
 use std::iter::FusedIterator;
 use std::mem::transmute;
 use std::ops::Index;
@@ -86,6 +87,196 @@ impl<'t> FusedIterator for CaptureTreeNodeIter<'t> {}
 
 impl<'t> ExactSizeIterator for CaptureTreeNodeIter<'t> {}
 
+/// Capture History
+///
+/// The tree of every capture made by the `(?@...)` groups in a match,
+/// returned by [`Captures::history`](crate::Captures::history). Unlike
+/// the capture groups, which only hold the last iteration of a
+/// repeated group, the history has a node for each iteration.
+///
+/// The root node is the whole match, group `0`. Oniguruma records the
+/// history of groups `1` to `31` only.
+///
+/// # Examples
+///
+/// ```
+/// use onig::RegexBuilder;
+///
+/// let r = RegexBuilder::new(r"(?:(?@<key>\w+)=(?@<value>\w+);)+")
+///     .capture_history(true)
+///     .build()
+///     .unwrap();
+/// let caps = r.captures("a=1;b=2;").unwrap();
+/// let history = caps.history().unwrap();
+/// let keys: Vec<_> = history.find_all(1).map(|n| n.as_str()).collect();
+/// assert_eq!(keys, ["a", "b"]);
+/// assert_eq!(caps.name("key"), Some("b"));
+/// ```
+#[derive(Debug)]
+pub struct CaptureHistory<'c, 't> {
+    root: &'c CaptureTreeNode,
+    text: &'t str,
+    /// The name of each group, by group number.
+    names: Vec<Option<&'c str>>,
+}
+
+impl<'c, 't> CaptureHistory<'c, 't> {
+    pub(crate) fn new(
+        root: &'c CaptureTreeNode,
+        text: &'t str,
+        names: Vec<Option<&'c str>>,
+    ) -> Self {
+        CaptureHistory { root, text, names }
+    }
+
+    /// The node for the whole match.
+    pub fn root(&self) -> CaptureHistoryNode<'_, 't> {
+        CaptureHistoryNode {
+            node: self.root,
+            history: self,
+        }
+    }
+
+    /// Iterate over every node, parents before their children.
+    pub fn depth_first(&self) -> CaptureHistoryDepthFirst<'_, 't> {
+        self.root().depth_first()
+    }
+
+    /// Iterate over every node, level by level.
+    pub fn breadth_first(&self) -> CaptureHistoryBreadthFirst<'_, 't> {
+        self.root().breadth_first()
+    }
+
+    /// Iterate over every capture made by group `group`, in the order
+    /// they appear in the text.
+    pub fn find_all(&self, group: usize) -> impl Iterator<Item = CaptureHistoryNode<'_, 't>> {
+        self.depth_first().filter(move |node| node.group() == group)
+    }
+
+    /// Iterate over every capture made by groups named `name`, in the
+    /// order they appear in the text.
+    pub fn find_all_named<'h>(
+        &'h self,
+        name: &'h str,
+    ) -> impl Iterator<Item = CaptureHistoryNode<'h, 't>> {
+        self.depth_first()
+            .filter(move |node| node.name() == Some(name))
+    }
+}
+
+/// Capture History Node
+///
+/// A single capture in a [`CaptureHistory`], with the text it matched
+/// and the captures made within it.
+#[derive(Debug, Clone, Copy)]
+pub struct CaptureHistoryNode<'h, 't> {
+    node: &'h CaptureTreeNode,
+    history: &'h CaptureHistory<'h, 't>,
+}
+
+impl<'h, 't> CaptureHistoryNode<'h, 't> {
+    /// The capture group number for this capture
+    pub fn group(&self) -> usize {
+        self.node.group()
+    }
+
+    /// The name of the capture group, if it has one
+    pub fn name(&self) -> Option<&'h str> {
+        self.history.names.get(self.group()).copied().flatten()
+    }
+
+    /// The extent of this capture
+    pub fn pos(&self) -> (usize, usize) {
+        self.node.pos()
+    }
+
+    /// The text matched by this capture
+    pub fn as_str(&self) -> &'t str {
+        let (beg, end) = self.pos();
+        &self.history.text[beg..end]
+    }
+
+    /// The number of child captures this capture contains
+    pub fn len(&self) -> usize {
+        self.node.len()
+    }
+
+    /// Does the node have any child captures?
+    pub fn is_empty(&self) -> bool {
+        self.node.is_empty()
+    }
+
+    /// An iterator over the children of this capture
+    pub fn children(&self) -> impl ExactSizeIterator<Item = CaptureHistoryNode<'h, 't>> {
+        let history = self.history;
+        self.node
+            .children()
+            .map(move |node| CaptureHistoryNode { node, history })
+    }
+
+    /// Iterate over this node and its descendants, parents before
+    /// their children.
+    pub fn depth_first(&self) -> CaptureHistoryDepthFirst<'h, 't> {
+        CaptureHistoryDepthFirst {
+            stack: vec![self.node],
+            history: self.history,
+        }
+    }
+
+    /// Iterate over this node and its descendants, level by level.
+    pub fn breadth_first(&self) -> CaptureHistoryBreadthFirst<'h, 't> {
+        CaptureHistoryBreadthFirst {
+            queue: vec![self.node].into(),
+            history: self.history,
+        }
+    }
+}
+
+/// Depth-first iterator over a capture history
+#[derive(Debug)]
+pub struct CaptureHistoryDepthFirst<'h, 't> {
+    stack: Vec<&'h CaptureTreeNode>,
+    history: &'h CaptureHistory<'h, 't>,
+}
+
+impl<'h, 't> Iterator for CaptureHistoryDepthFirst<'h, 't> {
+    type Item = CaptureHistoryNode<'h, 't>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack
+            .extend((0..node.len()).rev().map(|index| &node[index]));
+        Some(CaptureHistoryNode {
+            node,
+            history: self.history,
+        })
+    }
+}
+
+impl<'h, 't> FusedIterator for CaptureHistoryDepthFirst<'h, 't> {}
+
+/// Breadth-first iterator over a capture history
+#[derive(Debug)]
+pub struct CaptureHistoryBreadthFirst<'h, 't> {
+    queue: VecDeque<&'h CaptureTreeNode>,
+    history: &'h CaptureHistory<'h, 't>,
+}
+
+impl<'h, 't> Iterator for CaptureHistoryBreadthFirst<'h, 't> {
+    type Item = CaptureHistoryNode<'h, 't>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.children());
+        Some(CaptureHistoryNode {
+            node,
+            history: self.history,
+        })
+    }
+}
+
+impl<'h, 't> FusedIterator for CaptureHistoryBreadthFirst<'h, 't> {}
+
 #[cfg(test)]
 mod tests {
     use super::super::*;
@@ -128,4 +319,67 @@ mod tests {
         assert_eq!(tree[0][0].group(), 4);
         assert_eq!(tree[0][0].pos(), (3, 4));
     }
+
+    #[test]
+    fn test_captures_history() {
+        let regex = RegexBuilder::new(r"(?:(?@<pair>(?@<key>\w+)=(?@<value>\d+));?)+")
+            .capture_history(true)
+            .build()
+            .unwrap();
+        let caps = regex.captures("x a=1;b=22;c=3").unwrap();
+        let history = caps.history().unwrap();
+
+        let root = history.root();
+        assert_eq!(root.group(), 0);
+        assert_eq!(root.name(), None);
+        assert_eq!(root.as_str(), "a=1;b=22;c=3");
+        assert_eq!(root.len(), 3);
+
+        let pairs: Vec<_> = root.children().map(|n| n.as_str()).collect();
+        assert_eq!(pairs, ["a=1", "b=22", "c=3"]);
+        let values: Vec<_> = history
+            .find_all_named("value")
+            .map(|n| n.as_str())
+            .collect();
+        assert_eq!(values, ["1", "22", "3"]);
+        assert_eq!(history.find_all(2).count(), 3);
+
+        let depth_first: Vec<_> = history
+            .depth_first()
+            .map(|n| (n.name(), n.as_str()))
+            .take(4)
+            .collect();
+        assert_eq!(
+            depth_first,
+            [
+                (None, "a=1;b=22;c=3"),
+                (Some("pair"), "a=1"),
+                (Some("key"), "a"),
+                (Some("value"), "1")
+            ]
+        );
+        let breadth_first: Vec<_> = history.breadth_first().map(|n| n.group()).collect();
+        assert_eq!(breadth_first, [0, 1, 1, 1, 2, 3, 2, 3, 2, 3]);
+    }
+
+    #[test]
+    fn test_capture_history_toggle() {
+        let builder = RegexBuilder::new("(?@a)+");
+        assert!(builder.build().is_err());
+        let regex = builder.clone().capture_history(true).build().unwrap();
+        let caps = regex.captures("aa").unwrap();
+        assert_eq!(caps.history().unwrap().find_all(1).count(), 2);
+        // Each regex owns its copy of the toggled syntax.
+        let again = builder.capture_history(true).build().unwrap();
+        assert!(!std::ptr::eq(regex.syntax(), again.syntax()));
+        assert_eq!(regex, again);
+        drop(again);
+        let clone = regex.clone();
+        drop(regex);
+        let caps = clone.captures("aaa").unwrap();
+        assert_eq!(caps.history().unwrap().find_all(1).count(), 3);
+
+        let regex = Regex::new("(a)+").unwrap();
+        assert!(regex.captures("aa").unwrap().history().is_none());
+    }
 }
@@ -0,0 +1,44 @@
+//! The global default case fold is process wide, so the tests which
+//! change it run in their own binary, one at a time.
+
+use std::sync::{Arc, Mutex};
+
+use onig::cache::RegexCache;
+use onig::{CaseFold, Regex};
+
+/// Serialises the tests which change the global default.
+static GLOBAL_DEFAULT: Mutex<()> = Mutex::new(());
+
+#[test]
+fn test_case_fold_global_default() {
+    let _guard = GLOBAL_DEFAULT.lock().unwrap();
+    let previous = CaseFold::global_default();
+    assert!(previous.contains(CaseFold::CASE_FOLD_MULTI_CHAR));
+    let turkish = previous | CaseFold::CASE_FOLD_TURKISH_AZERI;
+    CaseFold::set_global_default(turkish).unwrap();
+    assert_eq!(CaseFold::global_default(), turkish);
+    let regex = Regex::new("a").unwrap();
+    CaseFold::set_global_default(previous).unwrap();
+    assert_eq!(regex.case_fold_flag(), turkish);
+    assert_eq!(CaseFold::global_default(), previous);
+    assert_ne!(regex, Regex::new("a").unwrap());
+}
+
+#[test]
+fn test_cache_follows_global_default() {
+    let _guard = GLOBAL_DEFAULT.lock().unwrap();
+    let cache = RegexCache::new(4);
+    let previous = CaseFold::global_default();
+    let first = cache.get("b").unwrap();
+    let turkish = previous | CaseFold::CASE_FOLD_TURKISH_AZERI;
+    CaseFold::set_global_default(turkish).unwrap();
+    let folded = cache.get("b");
+    let cached = Regex::cached("b");
+    CaseFold::set_global_default(previous).unwrap();
+    let folded = folded.unwrap();
+    assert!(!Arc::ptr_eq(&first, &folded));
+    assert_eq!(folded.case_fold_flag(), turkish);
+    assert_eq!(cached.unwrap().case_fold_flag(), turkish);
+    assert!(Arc::ptr_eq(&first, &cache.get("b").unwrap()));
+    assert_eq!(Regex::cached("b").unwrap().case_fold_flag(), previous);
+}
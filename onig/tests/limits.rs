@@ -0,0 +1,35 @@
+//! Oniguruma's limits are process wide, so the tests which change
+//! them run in their own binary, one at a time.
+
+use std::sync::Mutex;
+
+use onig::limits::*;
+use onig::Regex;
+
+/// Serialises the tests which change the limits.
+static LIMITS: Mutex<()> = Mutex::new(());
+
+#[test]
+fn test_capture_num_limit() {
+    let _guard = LIMITS.lock().unwrap();
+    let previous = capture_num_limit();
+    set_capture_num_limit(1000).unwrap();
+    assert_eq!(capture_num_limit(), 1000);
+    assert!(Regex::new(&"(a)".repeat(1001)).is_err());
+    assert!(Regex::new(&"(a)".repeat(10)).is_ok());
+    set_capture_num_limit(previous).unwrap();
+}
+
+#[test]
+fn test_search_limit_round_trip() {
+    let _guard = LIMITS.lock().unwrap();
+    let previous = subexp_call_limit_in_search();
+    set_subexp_call_limit_in_search(1_000_000).unwrap();
+    assert_eq!(subexp_call_limit_in_search(), 1_000_000);
+    set_subexp_call_limit_in_search(previous).unwrap();
+
+    let previous = subexp_call_max_nest_level();
+    set_subexp_call_max_nest_level(previous + 1).unwrap();
+    assert_eq!(subexp_call_max_nest_level(), previous + 1);
+    set_subexp_call_max_nest_level(previous).unwrap();
+}